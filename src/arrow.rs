@@ -0,0 +1,533 @@
+//! Query results in the Arrow columnar format. [`RecordBatch::into_ffi`] hands them over through
+//! the Arrow C data interface, which polars, DataFusion and pyarrow import without copying.
+
+use std::ffi::{c_char, c_void, CString};
+use std::ptr;
+
+use crate::backend::row::SQLType;
+use crate::virtual_machine::result_writer::column_kind::{integer, ColumnKind};
+
+/// Type of the values of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Int16,
+    Int32,
+    Int64,
+    UInt64,
+    /// Binary values of the given number of bytes, which UUIDs are stored as
+    FixedSizeBinary(i32),
+    Utf8,
+}
+
+impl DataType {
+    fn of_kind(kind: ColumnKind) -> Self {
+        match kind {
+            ColumnKind::Int(16) => DataType::Int16,
+            ColumnKind::Int(32) => DataType::Int32,
+            ColumnKind::Int(_) => DataType::Int64,
+            ColumnKind::UInt64 => DataType::UInt64,
+            ColumnKind::Uuid => DataType::FixedSizeBinary(16),
+            ColumnKind::Text => DataType::Utf8,
+        }
+    }
+
+    // How the C data interface writes the type
+    fn format(&self) -> String {
+        match self {
+            DataType::Int16 => "s".to_string(),
+            DataType::Int32 => "i".to_string(),
+            DataType::Int64 => "l".to_string(),
+            DataType::UInt64 => "L".to_string(),
+            DataType::FixedSizeBinary(size) => format!("w:{}", size),
+            DataType::Utf8 => "u".to_string(),
+        }
+    }
+}
+
+/// Name and type of a column. Every column may hold nulls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+/// Memory holding one part of a column, aligned to 8 bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buffer {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Buffer {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let words = bytes
+            .chunks(8)
+            .map(|chunk| {
+                let mut word = [0; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u64::from_ne_bytes(word)
+            })
+            .collect();
+        Self {
+            words,
+            len: bytes.len(),
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        // The words hold at least `len` initialized bytes
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr().cast(), self.len) }
+    }
+}
+
+/// A column laid out as Arrow does. The first buffer is the validity bitmap, with the bit of
+/// every non-null value set starting from the lowest bit. For Utf8 columns the offsets of the
+/// strings follow, as `i32`s, and then their bytes. For other types the values follow in
+/// little-endian order, with zeros in place of nulls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Array {
+    data_type: DataType,
+    len: usize,
+    null_count: usize,
+    buffers: Vec<Buffer>,
+}
+
+impl Array {
+    fn from_values(data_type: DataType, values: &[&SQLType]) -> Self {
+        let mut validity = vec![0u8; values.len().div_ceil(8)];
+        let mut offsets = 0i32.to_le_bytes().to_vec();
+        let mut data = Vec::new();
+        for (idx, value) in values.iter().enumerate() {
+            let is_null = **value == SQLType::Null;
+            if !is_null {
+                validity[idx / 8] |= 1 << (idx % 8);
+            }
+            let number = integer(value).unwrap_or_default();
+            match (data_type, value) {
+                (DataType::Int16, _) => data.extend((number as i16).to_le_bytes()),
+                (DataType::Int32, _) => data.extend((number as i32).to_le_bytes()),
+                (DataType::Int64, _) => data.extend((number as i64).to_le_bytes()),
+                (DataType::UInt64, _) => data.extend((number as u64).to_le_bytes()),
+                (DataType::FixedSizeBinary(_), SQLType::Uuid(uuid)) => data.extend(uuid.as_bytes()),
+                (DataType::FixedSizeBinary(size), _) => {
+                    data.extend(std::iter::repeat_n(0, size as usize))
+                }
+                (DataType::Utf8, value) => {
+                    if !is_null {
+                        data.extend(value.to_string().as_bytes());
+                    }
+                    offsets.extend((data.len() as i32).to_le_bytes());
+                }
+            }
+        }
+
+        let mut buffers = vec![Buffer::from_bytes(&validity)];
+        if data_type == DataType::Utf8 {
+            buffers.push(Buffer::from_bytes(&offsets));
+        }
+        buffers.push(Buffer::from_bytes(&data));
+        Self {
+            data_type,
+            len: values.len(),
+            null_count: values
+                .iter()
+                .filter(|value| ***value == SQLType::Null)
+                .count(),
+            buffers,
+        }
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.null_count
+    }
+
+    pub fn is_null(&self, idx: usize) -> bool {
+        let validity = self.buffers[0].as_slice();
+        validity
+            .get(idx / 8)
+            .is_none_or(|byte| byte & (1 << (idx % 8)) == 0)
+    }
+
+    pub fn buffers(&self) -> &[Buffer] {
+        &self.buffers
+    }
+
+    fn into_ffi(self) -> ArrowArray {
+        let buffer_ptrs = self
+            .buffers
+            .iter()
+            .map(|buffer| buffer.words.as_ptr().cast())
+            .collect();
+        ArrowArray::new(
+            self.len,
+            self.null_count,
+            self.buffers,
+            buffer_ptrs,
+            Vec::new(),
+        )
+    }
+}
+
+/// Columns of a query result, all with a value for every row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordBatch {
+    schema: Vec<Field>,
+    columns: Vec<Array>,
+    num_rows: usize,
+}
+
+impl RecordBatch {
+    // The type of each column is the narrowest one holding all its values. Integers keep their
+    // width, UUIDs become 16-byte binaries and columns mixing types or holding only nulls become
+    // text
+    pub(crate) fn from_rows(column_names: Vec<String>, rows: Vec<Vec<SQLType>>) -> Self {
+        let (schema, columns) = column_names
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let values: Vec<&SQLType> = rows
+                    .iter()
+                    .map(|row| row.get(idx).unwrap_or(&SQLType::Null))
+                    .collect();
+                let data_type = DataType::of_kind(ColumnKind::of_column(&values));
+                let field = Field { name, data_type };
+                (field, Array::from_values(data_type, &values))
+            })
+            .unzip();
+        Self {
+            schema,
+            columns,
+            num_rows: rows.len(),
+        }
+    }
+
+    pub fn schema(&self) -> &[Field] {
+        &self.schema
+    }
+
+    pub fn columns(&self) -> &[Array] {
+        &self.columns
+    }
+
+    /// The column with the given name, if the result has one
+    pub fn column_by_name(&self, name: &str) -> Option<&Array> {
+        let idx = self.schema.iter().position(|field| field.name == name)?;
+        self.columns.get(idx)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Exports the batch through the Arrow C data interface, as a struct array with a child
+    /// array per column. The buffers are handed over without copying them, and are freed once
+    /// the receiver calls the `release` callbacks of both structs, or when they are dropped
+    /// before being handed to anyone. Both structs must be moved to the receiver rather than
+    /// copied, see [`ArrowArray`]
+    pub fn into_ffi(self) -> (ArrowArray, ArrowSchema) {
+        let children = self
+            .schema
+            .iter()
+            .map(|field| ArrowSchema::new(&field.data_type.format(), &field.name, Vec::new()))
+            .collect();
+        let schema = ArrowSchema::new("+s", "", children);
+
+        let children = self.columns.into_iter().map(Array::into_ffi).collect();
+        // A struct array only has a validity buffer, which may be null when no row is null
+        let array = ArrowArray::new(self.num_rows, 0, Vec::new(), vec![ptr::null()], children);
+        (array, schema)
+    }
+}
+
+// Set on fields that may hold nulls
+const ARROW_FLAG_NULLABLE: i64 = 2;
+
+/// `struct ArrowSchema` of the Arrow C data interface. It owns what it points to just as an
+/// [`ArrowArray`] does, and must be moved the same way
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArrowSchema {
+    pub format: *const c_char,
+    pub name: *const c_char,
+    pub metadata: *const c_char,
+    pub flags: i64,
+    pub n_children: i64,
+    pub children: *mut *mut ArrowSchema,
+    pub dictionary: *mut ArrowSchema,
+    pub release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    pub private_data: *mut c_void,
+}
+
+// What a schema points to, kept alive until it is released
+struct SchemaData {
+    format: CString,
+    name: CString,
+    children: Box<[*mut ArrowSchema]>,
+}
+
+impl ArrowSchema {
+    fn new(format: &str, name: &str, children: Vec<ArrowSchema>) -> Self {
+        let mut data = Box::new(SchemaData {
+            format: CString::new(format).unwrap_or_default(),
+            name: CString::new(name).unwrap_or_default(),
+            children: children
+                .into_iter()
+                .map(|child| Box::into_raw(Box::new(child)))
+                .collect(),
+        });
+        Self {
+            format: data.format.as_ptr(),
+            name: data.name.as_ptr(),
+            metadata: ptr::null(),
+            flags: ARROW_FLAG_NULLABLE,
+            n_children: data.children.len() as i64,
+            children: data.children.as_mut_ptr(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_schema),
+            private_data: Box::into_raw(data).cast(),
+        }
+    }
+}
+
+impl Drop for ArrowSchema {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) };
+        }
+    }
+}
+
+unsafe extern "C" fn release_schema(schema: *mut ArrowSchema) {
+    let schema = unsafe { &mut *schema };
+    let data = unsafe { Box::from_raw(schema.private_data.cast::<SchemaData>()) };
+    // Dropping the children releases them, unless the receiver moved them out already
+    for &child in data.children.iter() {
+        drop(unsafe { Box::from_raw(child) });
+    }
+    schema.release = None;
+}
+
+/// `struct ArrowArray` of the Arrow C data interface.
+///
+/// The struct owns its buffers and children. They are freed by its `release` callback, which
+/// dropping the struct calls unless the receiver already did. Hand it over by moving it, e.g.
+/// with `ptr::write` into a struct the receiver allocated. A bitwise copy made with `ptr::read`
+/// or `memcpy` leaves two structs that both free the same buffers, a double free. After copying
+/// one, `mem::forget` the original or set its `release` to `None`, as the C data interface asks
+/// receivers that move an array to do
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArrowArray {
+    pub length: i64,
+    pub null_count: i64,
+    pub offset: i64,
+    pub n_buffers: i64,
+    pub n_children: i64,
+    pub buffers: *mut *const c_void,
+    pub children: *mut *mut ArrowArray,
+    pub dictionary: *mut ArrowArray,
+    pub release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    pub private_data: *mut c_void,
+}
+
+// What an array points to, kept alive until it is released
+struct ArrayData {
+    _buffers: Vec<Buffer>,
+    buffer_ptrs: Box<[*const c_void]>,
+    children: Box<[*mut ArrowArray]>,
+}
+
+impl ArrowArray {
+    fn new(
+        len: usize,
+        null_count: usize,
+        buffers: Vec<Buffer>,
+        buffer_ptrs: Vec<*const c_void>,
+        children: Vec<ArrowArray>,
+    ) -> Self {
+        let mut data = Box::new(ArrayData {
+            _buffers: buffers,
+            buffer_ptrs: buffer_ptrs.into_boxed_slice(),
+            children: children
+                .into_iter()
+                .map(|child| Box::into_raw(Box::new(child)))
+                .collect(),
+        });
+        Self {
+            length: len as i64,
+            null_count: null_count as i64,
+            offset: 0,
+            n_buffers: data.buffer_ptrs.len() as i64,
+            n_children: data.children.len() as i64,
+            buffers: data.buffer_ptrs.as_mut_ptr(),
+            children: data.children.as_mut_ptr(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_array),
+            private_data: Box::into_raw(data).cast(),
+        }
+    }
+}
+
+impl Drop for ArrowArray {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) };
+        }
+    }
+}
+
+unsafe extern "C" fn release_array(array: *mut ArrowArray) {
+    let array = unsafe { &mut *array };
+    let data = unsafe { Box::from_raw(array.private_data.cast::<ArrayData>()) };
+    for &child in data.children.iter() {
+        drop(unsafe { Box::from_raw(child) });
+    }
+    array.release = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_char, CStr};
+    use std::mem::MaybeUninit;
+    use std::ptr;
+    use std::slice;
+
+    use super::{ArrowArray, ArrowSchema, DataType, RecordBatch};
+    use crate::backend::row::SQLType;
+
+    fn batch() -> RecordBatch {
+        RecordBatch::from_rows(
+            vec!["id".to_string(), "name".to_string()],
+            vec![
+                vec![SQLType::Integer(1), SQLType::Text("one".to_string())],
+                vec![SQLType::Integer(2), SQLType::Null],
+                vec![SQLType::Integer(3), SQLType::Text("three".to_string())],
+            ],
+        )
+    }
+
+    // Contents of the `idx`th buffer of an exported array, `len` bytes long
+    fn ffi_buffer(array: &ArrowArray, idx: usize, len: usize) -> &[u8] {
+        assert!(idx < array.n_buffers as usize);
+        unsafe { slice::from_raw_parts((*array.buffers.add(idx)).cast(), len) }
+    }
+
+    fn array_child(array: &ArrowArray, idx: usize) -> &ArrowArray {
+        assert!(idx < array.n_children as usize);
+        unsafe { &**array.children.add(idx) }
+    }
+
+    fn schema_child(schema: &ArrowSchema, idx: usize) -> &ArrowSchema {
+        assert!(idx < schema.n_children as usize);
+        unsafe { &**schema.children.add(idx) }
+    }
+
+    fn c_str(chars: *const c_char) -> String {
+        unsafe { CStr::from_ptr(chars) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn builds_columns_in_arrow_layout() {
+        let batch = batch();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema()[0].data_type, DataType::Int32);
+        assert_eq!(batch.schema()[1].data_type, DataType::Utf8);
+
+        let ids = batch.column_by_name("id").unwrap();
+        assert_eq!(ids.null_count(), 0);
+        assert_eq!(ids.buffers()[0].as_slice(), [0b111]);
+        let values: Vec<u8> = [1i32, 2, 3]
+            .iter()
+            .flat_map(|id| id.to_le_bytes())
+            .collect();
+        assert_eq!(ids.buffers()[1].as_slice(), values);
+
+        let names = batch.column_by_name("name").unwrap();
+        assert_eq!(names.null_count(), 1);
+        assert!(names.is_null(1));
+        assert!(!names.is_null(2));
+        let offsets: Vec<u8> = [0i32, 3, 3, 8]
+            .iter()
+            .flat_map(|offset| offset.to_le_bytes())
+            .collect();
+        assert_eq!(names.buffers()[1].as_slice(), offsets);
+        assert_eq!(names.buffers()[2].as_slice(), b"onethree");
+    }
+
+    #[test]
+    fn exports_and_releases_a_batch() {
+        let (mut array, mut schema) = batch().into_ffi();
+
+        assert_eq!(c_str(schema.format), "+s");
+        assert_eq!(schema.n_children, 2);
+        let name = schema_child(&schema, 1);
+        assert_eq!(c_str(name.format), "u");
+        assert_eq!(c_str(name.name), "name");
+
+        assert_eq!((array.length, array.n_buffers, array.n_children), (3, 1, 2));
+        let names = array_child(&array, 1);
+        assert_eq!((names.length, names.null_count, names.n_buffers), (3, 1, 3));
+        assert_eq!(ffi_buffer(names, 2, 8), b"onethree");
+
+        // As a receiver would, through the callbacks. Dropping the structs afterwards frees
+        // nothing again
+        unsafe {
+            (array.release.unwrap())(&mut array);
+            (schema.release.unwrap())(&mut schema);
+        }
+        assert!(array.release.is_none());
+        assert!(schema.release.is_none());
+    }
+
+    #[test]
+    fn receiver_may_move_out_a_child() {
+        let (mut array, schema) = batch().into_ffi();
+        let ids = unsafe { *array.children };
+
+        // A moved child is marked released in place, so releasing the parent skips it
+        let mut moved = unsafe { ptr::read(ids) };
+        unsafe { (*ids).release = None };
+        unsafe { (array.release.unwrap())(&mut array) };
+        assert_eq!(ffi_buffer(&moved, 1, 4), 1i32.to_le_bytes());
+        unsafe { (moved.release.unwrap())(&mut moved) };
+        drop(schema);
+    }
+
+    #[test]
+    fn moves_into_memory_of_the_receiver() {
+        let (array, schema) = batch().into_ffi();
+        let mut out_array = MaybeUninit::<ArrowArray>::uninit();
+        let mut out_schema = MaybeUninit::<ArrowSchema>::uninit();
+
+        // Writing moves the structs, so only the copies of the receiver release the batch
+        let (out_array, out_schema) = unsafe {
+            out_array.as_mut_ptr().write(array);
+            out_schema.as_mut_ptr().write(schema);
+            (&mut *out_array.as_mut_ptr(), &mut *out_schema.as_mut_ptr())
+        };
+        assert_eq!(out_array.length, 3);
+        unsafe {
+            (out_array.release.unwrap())(out_array);
+            (out_schema.release.unwrap())(out_schema);
+        }
+    }
+}
@@ -1,8 +1,21 @@
+mod bloom;
+pub mod catalog;
 pub mod columns;
 pub mod cursor;
 pub mod database;
 mod db_cell;
+mod header;
+pub mod key;
 mod page;
 mod pager;
+pub mod recover;
 pub mod row;
+pub mod sha3;
+mod shared_cache;
+pub mod storage;
 pub mod table;
+pub mod uuid;
+
+pub use header::HeaderError;
+pub use page::PageError;
+pub use pager::{CacheSize, PagerError, PagerMetrics, Quotas, WriteLock};
@@ -0,0 +1,171 @@
+use std::mem;
+
+// Bits and hash functions per key that keep false positives around 1%
+const BITS_PER_KEY: usize = 10;
+const NUM_HASHES: u64 = 7;
+
+// Set of keys that may report keys it never saw as present, but never misses one it did see
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    len: usize,
+    // Number of keys the filter was sized for. It keeps working past it, with more false positives
+    capacity: usize,
+}
+
+impl BloomFilter {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let num_words = (capacity * BITS_PER_KEY)
+            .div_ceil(u64::BITS as usize)
+            .max(1);
+        Self {
+            bits: vec![0; num_words],
+            len: 0,
+            capacity,
+        }
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        for bit in self.bit_positions(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn may_contain(&self, key: u64) -> bool {
+        self.bit_positions(key)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.bits.len() * mem::size_of::<u64>()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Derives every hash from two independent ones, as in Kirsch and Mitzenmacher
+    fn bit_positions(&self, key: u64) -> impl Iterator<Item = usize> {
+        let num_bits = (self.bits.len() * 64) as u64;
+        let first = mix(key);
+        let second = mix(first) | 1;
+        (0..NUM_HASHES)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % num_bits) as usize)
+    }
+}
+
+// Finalizer of SplitMix64, which spreads sequential rowids over the whole range
+fn mix(key: u64) -> u64 {
+    let mut z = key.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Connection, SQLType};
+
+    #[test]
+    fn inserted_keys_are_always_found() {
+        let mut filter = BloomFilter::with_capacity(1000);
+        for key in (0..1000).map(|i| i * 7919) {
+            filter.insert(key);
+        }
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000).all(|i| filter.may_contain(i * 7919)));
+
+        // Past its capacity it still finds every key, just with more false positives
+        for key in 1_000_000..1_005_000 {
+            filter.insert(key);
+        }
+        assert!((1_000_000..1_005_000).all(|key| filter.may_contain(key)));
+        assert!((0..1000).all(|i| filter.may_contain(i * 7919)));
+    }
+
+    #[test]
+    fn false_positives_stay_rare() {
+        let mut filter = BloomFilter::with_capacity(10_000);
+        for key in 0..10_000 {
+            filter.insert(key);
+        }
+        let false_positives = (10_000..110_000)
+            .filter(|&key| filter.may_contain(key))
+            .count();
+        assert!(false_positives < 2_000, "{false_positives} false positives");
+        assert_eq!(
+            filter.memory_used(),
+            (10_000 * BITS_PER_KEY).div_ceil(64) * 8
+        );
+    }
+
+    #[test]
+    fn empty_filter_contains_nothing() {
+        let filter = BloomFilter::with_capacity(0);
+        assert!((0..100).all(|key| !filter.may_contain(key)));
+        assert_eq!(filter.memory_used(), 8);
+    }
+
+    #[test]
+    fn lookups_of_missing_rows_skip_the_tree() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        conn.execute("PRAGMA bloom_filter = on;").unwrap();
+        for id in 0..500 {
+            conn.execute(&format!("INSERT INTO t VALUES ({}, 'n{}');", id * 2, id))
+                .unwrap();
+        }
+        let mut names = |sql: &str| -> Vec<SQLType> {
+            conn.query(sql)
+                .unwrap()
+                .map(|row| row.into_values().remove(0))
+                .collect()
+        };
+        assert_eq!(
+            names("SELECT name FROM t WHERE id = 998;"),
+            [SQLType::Text("n499".to_string())]
+        );
+        assert!(names("SELECT name FROM t WHERE id = 999;").is_empty());
+
+        let page_accesses = |conn: &Connection| {
+            let metrics = conn.database().pager_metrics();
+            metrics.cache_hits + metrics.cache_misses
+        };
+        let before = page_accesses(&conn);
+        let found = (0..200)
+            .filter(|id| {
+                conn.query(&format!("SELECT name FROM t WHERE id = {};", id * 2 + 1))
+                    .unwrap()
+                    .next()
+                    .is_some()
+            })
+            .count();
+        assert_eq!(found, 0);
+        // Only the few false positives read the tree
+        assert!(page_accesses(&conn) - before < 50);
+
+        // Rows inserted after the filter was built are found, and so are rows of a rebuilt filter
+        for id in 500..2000 {
+            conn.execute(&format!("INSERT INTO t VALUES ({}, 'n{}');", id * 2, id))
+                .unwrap();
+        }
+        for id in [500, 1000, 1999] {
+            let sql = format!("SELECT name FROM t WHERE id = {};", id * 2);
+            assert_eq!(conn.query(&sql).unwrap().count(), 1, "{sql}");
+        }
+        conn.execute("PRAGMA bloom_filter = off;").unwrap();
+        assert_eq!(
+            conn.query("SELECT name FROM t WHERE id = 3998;")
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+}
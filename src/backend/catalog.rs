@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use bincode;
+use serde::{Deserialize, Serialize};
+
+use super::columns::{ColumnConstraints, Columns};
+use super::pager::TreeGrowth;
+
+// Size and shape of a table's B-tree, kept up to date on every insert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableStats {
+    pub num_rows: u64,
+    pub leaf_pages: u32,
+    pub interior_pages: u32,
+    pub depth: u32,
+}
+
+impl TableStats {
+    // A freshly created table is a single empty leaf
+    pub fn new() -> Self {
+        Self {
+            num_rows: 0,
+            leaf_pages: 1,
+            interior_pages: 0,
+            depth: 1,
+        }
+    }
+
+    pub fn record_insert(&mut self, growth: TreeGrowth) {
+        self.num_rows += 1;
+        self.leaf_pages += growth.leaf_pages;
+        self.interior_pages += growth.interior_pages;
+        self.depth += growth.depth;
+    }
+
+    // Pages emptied by deletes stay in the tree, so only the rows are counted down
+    pub fn record_delete(&mut self) {
+        self.num_rows = self.num_rows.saturating_sub(1);
+    }
+}
+
+impl Default for TableStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Descriptions given with COMMENT to a table and to its columns
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Comments {
+    pub table: Option<String>,
+    pub columns: BTreeMap<String, String>,
+}
+
+// A secondary index of a table. Its B-tree holds a key for every row, made by the `key` module of
+// the values of the indexed columns followed by the rowid, which tells apart rows sharing values
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub root_page: u32,
+}
+
+// One record of the catalog B-tree, keyed by table id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub table_id: u64,
+    pub name: String,
+    pub columns: Columns,
+    pub root_page: u32,
+    pub stats: TableStats,
+    // Column whose values are the keys of the table's B-tree
+    pub primary_key: Option<String>,
+    // Constraints of the columns that have any, loaded back into `columns` when the table is
+    // opened
+    pub constraints: BTreeMap<String, ColumnConstraints>,
+    pub comments: Comments,
+    // Names of the columns in the order they were declared in
+    pub declared_order: Vec<String>,
+    pub indexes: Vec<IndexEntry>,
+}
+
+impl CatalogEntry {
+    const BINCODE_CONFIG: bincode::config::Configuration<bincode::config::BigEndian> =
+        bincode::config::standard().with_big_endian();
+}
+
+impl TryInto<Box<[u8]>> for CatalogEntry {
+    type Error = ();
+
+    fn try_into(self) -> Result<Box<[u8]>, Self::Error> {
+        let entry_encoded =
+            bincode::serde::encode_to_vec::<CatalogEntry, _>(self, Self::BINCODE_CONFIG)
+                .map_err(|_| ())?;
+
+        Ok(entry_encoded.into())
+    }
+}
+
+impl TryFrom<&[u8]> for CatalogEntry {
+    type Error = ();
+
+    // Files with other layouts of the record are refused by their format version, so only the
+    // current one is read. A record with bytes left over is not one
+    fn try_from(bytes: &[u8]) -> Result<CatalogEntry, Self::Error> {
+        match bincode::serde::borrow_decode_from_slice::<CatalogEntry, _>(
+            bytes,
+            Self::BINCODE_CONFIG,
+        ) {
+            Ok((entry, read)) if read == bytes.len() => Ok(entry),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> CatalogEntry {
+        CatalogEntry {
+            table_id: 7,
+            name: "t".to_string(),
+            columns: Columns::new(),
+            root_page: 3,
+            stats: TableStats::new(),
+            primary_key: Some("id".to_string()),
+            constraints: BTreeMap::new(),
+            comments: Comments::default(),
+            declared_order: vec!["id".to_string()],
+            indexes: vec![IndexEntry {
+                name: "t_idx".to_string(),
+                columns: vec!["id".to_string()],
+                root_page: 4,
+            }],
+        }
+    }
+
+    #[test]
+    fn entries_are_read_back() {
+        let bytes: Box<[u8]> = entry().try_into().unwrap();
+        let read = CatalogEntry::try_from(&*bytes).unwrap();
+        assert_eq!((read.table_id, read.name.as_str()), (7, "t"));
+        assert_eq!(read.primary_key.as_deref(), Some("id"));
+        assert_eq!(read.declared_order, ["id"]);
+        assert_eq!(read.indexes, entry().indexes);
+    }
+
+    #[test]
+    fn truncated_or_longer_records_are_refused() {
+        let bytes: Box<[u8]> = entry().try_into().unwrap();
+        assert!(CatalogEntry::try_from(&bytes[..bytes.len() - 1]).is_err());
+        let mut longer = bytes.to_vec();
+        longer.push(0);
+        assert!(CatalogEntry::try_from(longer.as_slice()).is_err());
+    }
+}
@@ -1,11 +1,17 @@
-#![allow(dead_code)]
-use super::row::SQLType;
+use super::row::{Row, SQLType};
 use std::collections::BTreeMap;
+use std::fmt;
+use std::num::IntErrorKind;
 use std::ops::{Deref, DerefMut};
 
-use bincode;
 use serde::{Deserialize, Serialize};
 
+// Column used as the B-tree key of tables that do not declare a PRIMARY KEY
+pub const ROWID_COLUMN: &str = "id";
+
+// Hidden column holding the key of each row. Tables with a PRIMARY KEY use it as an alias of it
+pub const ROWID: &str = "rowid";
+
 pub trait ColumnType {
     fn validate(&self, input: &str) -> Option<SQLType>;
 }
@@ -14,20 +20,106 @@ pub trait ColumnType {
 pub enum IntegerType {
     Int,
     UBigInt,
+    BigInt,
+    SmallInt,
 }
 
-impl ColumnType for IntegerType {
-    fn validate(&self, input: &str) -> Option<SQLType> {
+// Why a value does not fit an integer column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerParseError {
+    // A number too large or too small for the type
+    OutOfRange,
+    // Not a number at all
+    Malformed,
+}
+
+impl IntegerType {
+    // Like `validate`, but telling numbers the type cannot hold apart from values that are not
+    // numbers
+    pub fn parse(&self, input: &str) -> Result<SQLType, IntegerParseError> {
+        let value = match input.parse::<i128>() {
+            Ok(value) => value,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+                ) =>
+            {
+                return Err(IntegerParseError::OutOfRange)
+            }
+            Err(_) => return Err(IntegerParseError::Malformed),
+        };
+        let (min, max) = self.range();
+        if !(min..=max).contains(&value) {
+            return Err(IntegerParseError::OutOfRange);
+        }
+
+        Ok(self.to_value(value))
+    }
+
+    // Value of a column of this type, from a number within its range
+    pub fn to_value(&self, value: i128) -> SQLType {
+        match self {
+            IntegerType::Int => SQLType::Integer(value as i32),
+            IntegerType::UBigInt => SQLType::UBigInt(value as u64),
+            IntegerType::BigInt => SQLType::BigInt(value as i64),
+            IntegerType::SmallInt => SQLType::SmallInt(value as i16),
+        }
+    }
+
+    // Smallest and largest values a column of this type holds
+    pub fn range(&self) -> (i128, i128) {
+        match self {
+            IntegerType::Int => (i32::MIN as i128, i32::MAX as i128),
+            IntegerType::UBigInt => (0, u64::MAX as i128),
+            IntegerType::BigInt => (i64::MIN as i128, i64::MAX as i128),
+            IntegerType::SmallInt => (i16::MIN as i128, i16::MAX as i128),
+        }
+    }
+
+    // Maps a value within range to a B-tree key. Signed values get their sign bit flipped so
+    // negative values sort before positive ones.
+    pub fn to_key(&self, value: i128) -> u64 {
+        match self {
+            IntegerType::UBigInt => value as u64,
+            _ => (value as i64 as u64) ^ (1 << 63),
+        }
+    }
+
+    pub fn from_key(&self, key: u64) -> i128 {
         match self {
-            IntegerType::Int => Some(SQLType::Integer(input.parse::<i32>().ok()?)),
-            IntegerType::UBigInt => Some(SQLType::UBigInt(input.parse::<u64>().ok()?)),
+            IntegerType::UBigInt => key as i128,
+            _ => (key ^ (1 << 63)) as i64 as i128,
         }
     }
 }
 
+impl ColumnType for IntegerType {
+    fn validate(&self, input: &str) -> Option<SQLType> {
+        self.parse(input).ok()
+    }
+}
+
+impl fmt::Display for IntegerType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IntegerType::Int => "INT",
+            IntegerType::UBigInt => "UNSIGNED BIG INT",
+            IntegerType::BigInt => "BIGINT",
+            IntegerType::SmallInt => "SMALLINT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TextType {
-    Varchar(u8),
+    // At most this many bytes
+    Varchar(u32),
+    // Exactly this many characters. Shorter values are padded with spaces, and longer ones are
+    // truncated as long as only spaces are cut off
+    Char(u8),
+    Text,
 }
 
 impl ColumnType for TextType {
@@ -40,18 +132,161 @@ impl ColumnType for TextType {
                     None
                 }
             }
+            TextType::Char(size) => {
+                let size = *size as usize;
+                let (kept, cut) = match input.char_indices().nth(size) {
+                    Some((end, _)) => input.split_at(end),
+                    None => (input, ""),
+                };
+                if !cut.chars().all(|c| c == ' ') {
+                    return None;
+                }
+                Some(SQLType::Text(format!("{:<size$}", kept)))
+            }
+            TextType::Text => Some(SQLType::Text(input.to_owned())),
         }
     }
 }
 
+impl TextType {
+    // How long values of the type can be, as told when one is too long
+    pub fn limit(&self) -> String {
+        match self {
+            TextType::Varchar(max_size) => format!("{} bytes", max_size),
+            TextType::Char(size) => format!("{} characters, not counting trailing spaces", size),
+            TextType::Text => "any length".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TextType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextType::Varchar(max_size) => write!(f, "VARCHAR({})", max_size),
+            TextType::Char(size) => write!(f, "CHAR({})", size),
+            TextType::Text => write!(f, "TEXT"),
+        }
+    }
+}
+
+// One of a fixed list of labels, stored as its position in the list
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnumType {
+    pub labels: Vec<String>,
+}
+
+impl EnumType {
+    // Turns a stored position back into its label
+    pub fn label(&self, value: &SQLType) -> Option<SQLType> {
+        let SQLType::SmallInt(idx) = value else {
+            return None;
+        };
+        let label = self.labels.get(usize::try_from(*idx).ok()?)?;
+        Some(SQLType::Text(label.clone()))
+    }
+}
+
+impl ColumnType for EnumType {
+    fn validate(&self, input: &str) -> Option<SQLType> {
+        let idx = self.labels.iter().position(|label| label == input)?;
+        Some(SQLType::SmallInt(idx.try_into().ok()?))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ColumnItemType {
     Integer(IntegerType),
     Text(TextType),
+    Enum(EnumType),
+    Uuid,
+    // Text holding a JSON document
+    Json,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Columns(pub BTreeMap<String, ColumnItemType>);
+impl ColumnItemType {
+    // Quotes a value written as in an INSERT, unless the column holds numbers
+    pub fn literal(&self, value: &str) -> String {
+        match self {
+            ColumnItemType::Integer(_) => value.to_string(),
+            _ => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+}
+
+impl fmt::Display for ColumnItemType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnItemType::Integer(int_type) => write!(f, "{}", int_type),
+            ColumnItemType::Text(text_type) => write!(f, "{}", text_type),
+            ColumnItemType::Enum(enum_type) => {
+                let labels: Vec<String> = enum_type
+                    .labels
+                    .iter()
+                    .map(|label| format!("'{}'", label))
+                    .collect();
+                write!(f, "ENUM({})", labels.join(", "))
+            }
+            ColumnItemType::Uuid => write!(f, "UUID"),
+            ColumnItemType::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+// Rules the values of a column must follow besides fitting its type
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ColumnConstraints {
+    pub not_null: bool,
+    pub unique: bool,
+    // Value used when an INSERT leaves the column out
+    pub default: Option<ColumnDefault>,
+    // Expression every inserted row must not make false, kept as its SQL text
+    pub check: Option<String>,
+    // Table and column every non-NULL value must be found in
+    pub references: Option<(String, String)>,
+}
+
+// Value a column takes when an INSERT leaves it out
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ColumnDefault {
+    // Written as it would be in the INSERT
+    Literal(String),
+    // Evaluated anew for every row inserted, e.g. random(), and kept as its SQL text
+    Expr(String),
+}
+
+impl ColumnDefault {
+    // The value as written in an INSERT, or the SQL text of the expression
+    pub fn as_str(&self) -> &str {
+        match self {
+            ColumnDefault::Literal(text) | ColumnDefault::Expr(text) => text,
+        }
+    }
+
+    // Literals are quoted unless the column holds numbers, and expressions are parenthesized
+    pub fn to_sql(&self, column_type: &ColumnItemType) -> String {
+        match self {
+            ColumnDefault::Literal(value) => column_type.literal(value),
+            ColumnDefault::Expr(expr) => format!("({})", expr),
+        }
+    }
+}
+
+impl ColumnConstraints {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// Columns of a table by name, along with the constraints of those that have any and the order
+// they were declared in. Only the types are written where the columns are, the catalog keeps the
+// rest in fields of their own
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+pub struct Columns(
+    pub BTreeMap<String, ColumnItemType>,
+    #[serde(skip)] pub BTreeMap<String, ColumnConstraints>,
+    #[serde(skip)] pub Vec<String>,
+);
 
 impl From<Vec<(&str, ColumnItemType)>> for Columns {
     fn from(columns_vec: Vec<(&str, ColumnItemType)>) -> Self {
@@ -59,7 +294,7 @@ impl From<Vec<(&str, ColumnItemType)>> for Columns {
             .into_iter()
             .map(|column| (column.0.to_owned(), column.1))
             .collect();
-        Columns(columns_map)
+        Columns(columns_map, BTreeMap::new(), Vec::new())
     }
 }
 
@@ -78,14 +313,51 @@ impl DerefMut for Columns {
 }
 
 impl Columns {
-    const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard();
     pub fn new() -> Self {
-        Self(BTreeMap::<String, ColumnItemType>::new())
+        Self(
+            BTreeMap::<String, ColumnItemType>::new(),
+            BTreeMap::new(),
+            Vec::new(),
+        )
+    }
+
+    // Names in the order CREATE TABLE declared them, which is the order of the values of an
+    // INSERT without column names. Tables created before the order was recorded use the order
+    // of the names instead
+    pub fn declared_order(&self) -> Vec<&str> {
+        match self.2.is_empty() {
+            true => self.keys().map(String::as_str).collect(),
+            false => self.2.iter().map(String::as_str).collect(),
+        }
+    }
+
+    pub fn constraints(&self, column: &str) -> Option<&ColumnConstraints> {
+        self.1.get(column)
     }
 
     pub fn to_printable(&self) -> Vec<String> {
         self.0.keys().map(|key| key.to_owned()).collect()
     }
-}
-
 
+    // Replaces the stored form of values with the one shown to users, such as the labels of
+    // enum columns
+    pub fn decode_row(&self, row: Row) -> Row {
+        if !self
+            .values()
+            .any(|column| matches!(column, ColumnItemType::Enum(_)))
+        {
+            return row;
+        }
+        let attributes = self
+            .values()
+            .zip(row.attributes())
+            .map(|(column, value)| match column {
+                ColumnItemType::Enum(enum_type) => {
+                    enum_type.label(value).unwrap_or_else(|| value.clone())
+                }
+                _ => value.clone(),
+            })
+            .collect();
+        Row::new(row.rowid(), attributes)
+    }
+}
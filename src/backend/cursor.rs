@@ -1,21 +1,149 @@
-use crate::backend::table::Table;
+use std::ops::Bound;
+
+use crate::backend::db_cell::DBCell;
+use crate::backend::pager::{Pager, PagerError};
+
+// Place of a cell in the order of its tree: its key, then in index trees its value
+type SortKey = (u64, Box<[u8]>);
 
 #[derive(Debug, Clone)]
-pub struct DBCursor<'a> {
-    table: &'a Table,
-    pub page_num: u32,
-    pub cell_ptr_pos: usize,
+pub struct DBCursor {
+    root_page_num: u32,
+    // Path from the root to the current leaf as (page number, child or cell index) pairs
+    stack: Vec<(u32, usize)>,
+    lower_bound: Bound<SortKey>,
+    upper_bound: Bound<SortKey>,
+    positioned: bool,
 }
 
-impl<'a, 'b> DBCursor<'b>
-where
-    'a: 'b,
-{
-    pub fn new(table: &'a Table) -> Self {
+impl DBCursor {
+    pub fn new(root_page_num: u32) -> Self {
+        Self::with_bounds(root_page_num, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub fn with_bounds(
+        root_page_num: u32,
+        lower_bound: Bound<u64>,
+        upper_bound: Bound<u64>,
+    ) -> Self {
+        let sort_key = |key| (key, Box::default());
+        Self::with_sort_key_bounds(
+            root_page_num,
+            lower_bound.map(sort_key),
+            upper_bound.map(sort_key),
+        )
+    }
+
+    // Cursor over the keys of an index tree made by the `key` module, such as those starting with
+    // a prefix when bounded by it and `key::prefix_end`
+    pub fn with_key_bounds(
+        root_page_num: u32,
+        lower_bound: Bound<&[u8]>,
+        upper_bound: Bound<&[u8]>,
+    ) -> Self {
+        let sort_key = |key: &[u8]| {
+            let cell = DBCell::new_keyed(key);
+            (cell.id, cell.value)
+        };
+        Self::with_sort_key_bounds(
+            root_page_num,
+            lower_bound.map(sort_key),
+            upper_bound.map(sort_key),
+        )
+    }
+
+    fn with_sort_key_bounds(
+        root_page_num: u32,
+        lower_bound: Bound<SortKey>,
+        upper_bound: Bound<SortKey>,
+    ) -> Self {
         DBCursor {
-            table,
-            page_num: 0,
-            cell_ptr_pos: 0,
+            root_page_num,
+            stack: Vec::new(),
+            lower_bound,
+            upper_bound,
+            positioned: false,
         }
     }
+
+    // Descends from the root to the first cell not ordered before the lower bound. Cells equal to
+    // an excluded bound are skipped by `next_cell`
+    fn seek(&mut self, pager: &mut Pager) -> Result<(), PagerError> {
+        self.stack.clear();
+        self.positioned = true;
+
+        let mut target = DBCell::new_interior(0, 0);
+        if let Bound::Included((key, value)) | Bound::Excluded((key, value)) = &self.lower_bound {
+            target.id = *key;
+            target.value = value.clone();
+        }
+
+        let mut page_num = self.root_page_num;
+        loop {
+            let page = pager.page(page_num)?;
+            let idx = page.find_position(&target)?;
+            self.stack.push((page_num, idx));
+            if page.is_leaf() {
+                return Ok(());
+            }
+            page_num = page.child_at(idx)?;
+        }
+    }
+
+    fn before_lower_bound(&self, sort_key: (u64, &[u8])) -> bool {
+        match &self.lower_bound {
+            Bound::Excluded((key, value)) => sort_key <= (*key, value),
+            _ => false,
+        }
+    }
+
+    fn past_upper_bound(&self, sort_key: (u64, &[u8])) -> bool {
+        match &self.upper_bound {
+            Bound::Unbounded => false,
+            Bound::Included((key, value)) => sort_key > (*key, value),
+            Bound::Excluded((key, value)) => sort_key >= (*key, value),
+        }
+    }
+
+    // Next key of an index tree read through a cursor made by `with_key_bounds`
+    pub fn next_key(&mut self, pager: &mut Pager) -> Result<Option<Box<[u8]>>, PagerError> {
+        Ok(self.next_cell(pager)?.map(|cell| cell.value))
+    }
+
+    pub fn next_cell(&mut self, pager: &mut Pager) -> Result<Option<DBCell>, PagerError> {
+        if !self.positioned {
+            self.seek(pager)?;
+        }
+
+        while let Some(&(page_num, idx)) = self.stack.last() {
+            let page = pager.page(page_num)?;
+
+            if page.is_leaf() && idx < page.num_cells() {
+                let cell = page.cell_at(idx)?;
+                let sort_key = page.page_type().sort_key(&cell);
+                if self.past_upper_bound(sort_key) {
+                    self.stack.clear();
+                    return Ok(None);
+                }
+                self.stack.last_mut().unwrap().1 += 1;
+                if self.before_lower_bound(sort_key) {
+                    continue;
+                }
+                return Ok(Some(cell));
+            }
+
+            if !page.is_leaf() && idx <= page.num_cells() {
+                self.stack.push((page.child_at(idx)?, 0));
+                continue;
+            }
+
+            // Current page exhausted, move on to the parent's next child
+            self.stack.pop();
+            if let Some(parent) = self.stack.last_mut() {
+                parent.1 += 1;
+            }
+        }
+
+        Ok(None)
+    }
 }
@@ -1,18 +1,77 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io;
-use std::path::Path;
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 
 use thiserror::Error;
 
+use super::catalog::{CatalogEntry, Comments, IndexEntry, TableStats};
 use super::columns::Columns;
-use super::table::Table;
+use super::cursor::DBCursor;
+use super::page::{PageType, PAGE_SIZE};
+use super::pager::{CacheSize, IntegrityCheck, Pager, PagerError, PagerMetrics, Quotas, WriteLock};
+use super::row::Row;
+use super::shared_cache::SharedPageCache;
+use super::storage::{MemoryStorage, Storage};
+use super::table::{Table, TableError};
+use crate::metrics::{Histogram, Metrics};
+
+// Alias under which the tables of the database that was opened directly can be qualified
+pub const MAIN_DATABASE: &str = "main";
+// Path that opens a database held in memory instead of a file
+pub const MEMORY_PATH: &str = ":memory:";
+
+// Page caches of the files opened read-only, so that every read-only connection to a file shares
+// one, whichever thread it is on
+static SHARED_CACHES: Mutex<BTreeMap<PathBuf, Weak<SharedPageCache>>> = Mutex::new(BTreeMap::new());
 
 pub struct Database {
-    file: Rc<RefCell<File>>,
+    path: String,
+    pager: Rc<RefCell<Pager>>,
     tables: HashMap<String, Table>,
+    // Other database files whose tables are reachable as `alias.table`
+    attached: BTreeMap<String, Database>,
+    // Rows changed by the last statement that modifies rows, and by every statement since opening
+    changes: u64,
+    total_changes: u64,
+    // Time taken by every statement run since opening, and how many of them failed
+    statement_latency: Cell<Histogram>,
+    statements_failed: Cell<u64>,
+    read_only: bool,
+    // Whether changes are held back until COMMIT
+    in_transaction: bool,
+    // Stats of every table when the statement being run began, and whether they needed writing
+    statement_stats: HashMap<String, (TableStats, bool)>,
+    // Change counter of the file when the tables were last loaded from the catalog
+    loaded_counter: Cell<u32>,
+    // Whether tables keep bloom filters over their rowids
+    bloom_filter: bool,
+    // Whether the database lives in a file that other handles can open
+    file_backed: bool,
+    // Threads that large table scans are split across. 1 scans on the calling thread only
+    threads: usize,
+    // Whether inserted values must be found in the columns their REFERENCES clause names
+    foreign_keys: bool,
+}
+
+// Size of a database file and how its pages are used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseStats {
+    pub file_size: u64,
+    pub page_size: usize,
+    pub page_count: u32,
+    // Pages no tree uses, which are left over in the file
+    pub freelist_count: u32,
+    pub catalog_pages: u32,
+    pub memory_used: usize,
+    // Statistics kept in the catalog for every table, sorted by name
+    pub tables: Vec<(String, TableStats)>,
 }
 
 #[derive(Error, Debug)]
@@ -23,48 +82,915 @@ pub enum DatabaseError {
     DuplicateTable,
     #[error("Table does not exist in database.")]
     TableDoesNotExist,
+    #[error("No database attached as {0}")]
+    UnknownDatabase(String),
+    #[error("A database is already attached as {0}")]
+    DuplicateDatabase(String),
+    #[error("A transaction is already open")]
+    TransactionAlreadyOpen,
+    #[error("No transaction is open")]
+    NoTransaction,
+    #[error("Index {0} already exists in database.")]
+    DuplicateIndex(String),
+    #[error("Index {0} does not exist in database.")]
+    IndexDoesNotExist(String),
+    #[error("The catalog entry for table id {0} is corrupt")]
+    CorruptCatalog(u64),
+    #[error(transparent)]
+    PagerError(#[from] PagerError),
+    #[error(transparent)]
+    TableError(#[from] TableError),
 }
 
 impl Database {
     pub fn close(&mut self) {
-        for table in self.tables.values_mut() {
-            if let Err(err) = table.flush() {
-                eprintln!("Error flushing table {} to disk: {}", table.name, err);
+        // An unfinished transaction is abandoned, as if it never started
+        if self.in_transaction {
+            if let Err(err) = self.rollback() {
+                eprintln!("Error rolling back open transaction: {}", err);
             }
         }
+        // Read-only databases have nothing to write back
+        if !self.read_only {
+            if let Err(err) = self.flush() {
+                eprintln!("Error flushing database to disk: {}", err);
+            } else if let Err(err) = self.truncate_free_pages() {
+                eprintln!("Error truncating free pages: {}", err);
+            }
+        }
+        for attached_db in self.attached.values_mut() {
+            attached_db.close();
+        }
     }
 
+    // Gives the pages at the end of the file that no tree uses back to the file system
+    fn truncate_free_pages(&mut self) -> Result<(), DatabaseError> {
+        // Another process may have added tables since they were loaded
+        self.refresh()?;
+        let mut pager = self.pager.borrow_mut();
+        let catalog_root = pager.catalog_root();
+        let mut last_used_page = 0;
+        let roots = self.tables.values().flat_map(|table| {
+            iter::once(table.root_page_num())
+                .chain(table.indexes.iter().map(|index| index.root_page))
+        });
+        for root in iter::once(catalog_root).chain(roots) {
+            let last_page = pager.tree_pages(root)?.into_iter().max().unwrap_or(root);
+            last_used_page = last_used_page.max(last_page);
+        }
+        Ok(pager.truncate(last_used_page + 1)?)
+    }
+
+    // Opens the database file at the given path, creating it if missing. The path `:memory:`
+    // opens a new database that only lives in memory
     pub fn open(path_str: &str) -> Result<Self, DatabaseError> {
+        if path_str == MEMORY_PATH {
+            return Self::open_with_storage(path_str, Box::new(MemoryStorage::new()));
+        }
+
         let path = Path::new(path_str);
-        let file = RefCell::new(
-            File::options()
-                .create(true)
-                .append(true)
-                .open(path)?,
-        )
-        .into();
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut db = Self::open_with_storage(path_str, Box::new(file))?;
+        db.file_backed = true;
+        Ok(db)
+    }
+
+    // Opens a database kept in any storage, such as a browser store on targets without a file
+    // system. `path_str` only names the database
+    pub fn open_with_storage(
+        path_str: &str,
+        storage: Box<dyn Storage>,
+    ) -> Result<Self, DatabaseError> {
+        let pager = Rc::new(RefCell::new(Pager::open(storage, false)?));
+        Self::from_pager(path_str, pager, false)
+    }
+
+    // Opens an existing database file that only accepts reads. Read-only databases opened on the
+    // same file share their page cache, even when they are used from different threads
+    pub fn open_read_only(path_str: &str) -> Result<Self, DatabaseError> {
+        let path = Path::new(path_str).canonicalize()?;
+        let file = File::options().read(true).open(&path)?;
+        let mut pager = Pager::open(Box::new(file), true)?;
+
+        let shared_cache = {
+            let mut caches = SHARED_CACHES.lock().unwrap_or_else(|err| err.into_inner());
+            match caches.get(&path).and_then(Weak::upgrade) {
+                Some(shared_cache) => shared_cache,
+                None => {
+                    let shared_cache = Arc::new(SharedPageCache::default());
+                    caches.retain(|_, shared_cache| shared_cache.strong_count() > 0);
+                    caches.insert(path, Arc::downgrade(&shared_cache));
+                    shared_cache
+                }
+            }
+        };
+        pager.share_cache(shared_cache);
+
+        let mut db = Self::from_pager(path_str, Rc::new(RefCell::new(pager)), true)?;
+        db.file_backed = true;
+        Ok(db)
+    }
+
+    fn from_pager(
+        path_str: &str,
+        pager: Rc<RefCell<Pager>>,
+        read_only: bool,
+    ) -> Result<Self, DatabaseError> {
+        let tables = load_tables(&pager)?;
+        let loaded_counter = Cell::new(pager.borrow().change_counter());
+
+        Ok(Self {
+            path: path_str.to_string(),
+            pager,
+            tables,
+            attached: BTreeMap::new(),
+            changes: 0,
+            total_changes: 0,
+            statement_latency: Cell::new(Histogram::default()),
+            statements_failed: Cell::new(0),
+            read_only,
+            in_transaction: false,
+            statement_stats: HashMap::new(),
+            loaded_counter,
+            bloom_filter: false,
+            file_backed: false,
+            threads: 1,
+            foreign_keys: true,
+        })
+    }
+
+    // Catches up with changes other processes made to the file since the last statement, dropping
+    // stale cached pages and reloading the tables if the catalog may have changed
+    pub fn refresh(&mut self) -> Result<(), DatabaseError> {
+        self.pager.borrow_mut().refresh()?;
+        let counter = self.pager.borrow().change_counter();
+        if counter != self.loaded_counter.get() {
+            self.reload_tables()?;
+            self.loaded_counter.set(counter);
+        }
+
+        for attached_db in self.attached.values_mut() {
+            attached_db.refresh()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Writes changed table statistics to the catalog and every modified page to disk
+    pub fn flush(&self) -> Result<(), DatabaseError> {
+        let mut pager = self.pager.borrow_mut();
+        let catalog_root = pager.catalog_root();
+
+        for table in self.tables.values() {
+            if table.take_stats_dirty() {
+                pager.delete(catalog_root, table.table_id())?;
+                pager.insert(catalog_root, table.table_id(), &table.catalog_entry())?;
+            }
+        }
+
+        pager.flush()?;
+        // The tables already hold what was just written
+        self.loaded_counter.set(pager.change_counter());
+
+        for attached_db in self.attached.values() {
+            attached_db.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
+    // Starts holding back changes in memory, so the statements until COMMIT are written together
+    pub fn begin(&mut self) -> Result<(), DatabaseError> {
+        if self.in_transaction {
+            return Err(DatabaseError::TransactionAlreadyOpen);
+        }
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<(), DatabaseError> {
+        if !self.in_transaction {
+            return Err(DatabaseError::NoTransaction);
+        }
+        // The transaction stays open if the changes cannot be written, so COMMIT can be retried
+        self.flush()?;
+        self.in_transaction = false;
+        Ok(())
+    }
+
+    // Discards every change made since BEGIN
+    pub fn rollback(&mut self) -> Result<(), DatabaseError> {
+        if !self.in_transaction {
+            return Err(DatabaseError::NoTransaction);
+        }
+        self.in_transaction = false;
+        self.discard_changes()
+    }
+
+    // Marks the start of a statement that writes, so that `rollback_statement` can undo it
+    // without the statements run before it in the transaction
+    pub fn begin_statement(&mut self) {
+        self.pager.borrow_mut().begin_statement();
+        self.statement_stats = self
+            .tables
+            .iter()
+            .map(|(name, table)| (name.clone(), (table.stats(), table.stats_dirty())))
+            .collect();
+        for attached_db in self.attached.values_mut() {
+            attached_db.begin_statement();
+        }
+    }
+
+    pub fn end_statement(&mut self) {
+        self.pager.borrow_mut().end_statement();
+        self.statement_stats.clear();
+        for attached_db in self.attached.values_mut() {
+            attached_db.end_statement();
+        }
+    }
+
+    // Undoes what the statement being run changed, leaving the changes of earlier statements of
+    // the transaction in place
+    pub fn rollback_statement(&mut self) -> Result<(), DatabaseError> {
+        self.pager.borrow_mut().rollback_statement();
+        // Tables created, dropped or renamed by the statement are back as they were in the
+        // catalog, and their stats as they were before it
+        self.reload_tables()?;
+        for (name, (stats, dirty)) in mem::take(&mut self.statement_stats) {
+            if let Some(table) = self.tables.get(&name) {
+                table.restore_stats(stats, dirty);
+            }
+        }
+        for attached_db in self.attached.values_mut() {
+            attached_db.rollback_statement()?;
+        }
+        Ok(())
+    }
+
+    // Discards everything not written to disk yet, which is the whole transaction if one is open.
+    // Used when a statement could not complete and left the changes in an unknown state
+    pub fn abort(&mut self) -> Result<(), DatabaseError> {
+        self.in_transaction = false;
+        self.discard_changes()
+    }
+
+    fn discard_changes(&mut self) -> Result<(), DatabaseError> {
+        self.pager.borrow_mut().rollback()?;
+        // Table statistics and bloom filters count the discarded rows
+        self.reload_tables()?;
+        for attached_db in self.attached.values_mut() {
+            attached_db.discard_changes()?;
+        }
+        Ok(())
+    }
+
+    // Inserts the rows into a table and writes them to disk with a single flush, unless a
+    // transaction holds them back. Returns the number of rows inserted
+    pub fn insert_batch(
+        &mut self,
+        table_name: &str,
+        rows: impl IntoIterator<Item = Row>,
+    ) -> Result<u64, DatabaseError> {
+        let inserted = self.table(table_name)?.insert_batch(rows)?;
+        if !self.in_transaction {
+            self.flush()?;
+        }
+        Ok(inserted)
+    }
+
+    // Prepares this and every attached database for a statement that writes
+    pub fn begin_write(&self) -> Result<(), DatabaseError> {
+        self.pager.borrow_mut().begin_write()?;
+        for attached_db in self.attached.values() {
+            attached_db.begin_write()?;
+        }
+        Ok(())
+    }
+
+    // Gives up the locks taken by `begin_write` after a statement that failed
+    pub fn end_write(&self) -> Result<(), DatabaseError> {
+        self.pager.borrow_mut().end_write()?;
+        for attached_db in self.attached.values() {
+            attached_db.end_write()?;
+        }
+        Ok(())
+    }
+
+    pub fn write_lock(&self) -> WriteLock {
+        self.pager.borrow().write_lock()
+    }
+
+    pub fn set_write_lock(&mut self, write_lock: WriteLock) {
+        self.pager.borrow_mut().set_write_lock(write_lock);
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_write_lock(write_lock);
+        }
+    }
+
+    pub fn cache_size(&self) -> CacheSize {
+        self.pager.borrow().cache_size()
+    }
+
+    pub fn set_cache_size(&mut self, cache_size: CacheSize) {
+        self.pager.borrow_mut().set_cache_size(cache_size);
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_cache_size(cache_size);
+        }
+    }
+
+    pub fn paranoid(&self) -> bool {
+        self.pager.borrow().paranoid()
+    }
+
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.pager.borrow_mut().set_paranoid(paranoid);
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_paranoid(paranoid);
+        }
+    }
+
+    pub fn soft_heap_limit(&self) -> Option<usize> {
+        self.pager.borrow().soft_heap_limit()
+    }
+
+    // Applies to the page cache of each database file on its own, this one and those attached
+    pub fn set_soft_heap_limit(&mut self, soft_heap_limit: Option<usize>) {
+        self.pager.borrow_mut().set_soft_heap_limit(soft_heap_limit);
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_soft_heap_limit(soft_heap_limit);
+        }
+    }
+
+    // Approximate bytes taken up by the cached pages and bloom filters of this database and those
+    // attached to it
+    pub fn memory_used(&self) -> usize {
+        let tables: usize = self.tables.values().map(Table::memory_used).sum();
+        let attached: usize = self.attached.values().map(Database::memory_used).sum();
+        self.pager.borrow().memory_used() + tables + attached
+    }
+
+    pub fn read_uncommitted(&self) -> bool {
+        self.pager.borrow().read_uncommitted()
+    }
+
+    pub fn set_read_uncommitted(&mut self, read_uncommitted: bool) {
+        self.pager
+            .borrow_mut()
+            .set_read_uncommitted(read_uncommitted);
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_read_uncommitted(read_uncommitted);
+        }
+    }
+
+    // Limits apply to this database only, not to those attached to it
+    pub fn quotas(&self) -> Quotas {
+        self.pager.borrow().quotas()
+    }
+
+    pub fn set_quotas(&mut self, quotas: Quotas) {
+        self.pager.borrow_mut().set_quotas(quotas);
+    }
+
+    pub fn bloom_filter(&self) -> bool {
+        self.bloom_filter
+    }
+
+    // Keeps a bloom filter over the rowids of every table, so point lookups of missing rows do
+    // not read the tree
+    pub fn set_bloom_filter(&mut self, enabled: bool) {
+        self.bloom_filter = enabled;
+        for table in self.tables.values() {
+            table.set_bloom_filter_enabled(enabled);
+        }
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_bloom_filter(enabled);
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn foreign_keys(&self) -> bool {
+        self.foreign_keys
+    }
+
+    pub fn set_foreign_keys(&mut self, enabled: bool) {
+        self.foreign_keys = enabled;
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_foreign_keys(enabled);
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+        for attached_db in self.attached.values_mut() {
+            attached_db.set_threads(threads);
+        }
+    }
+
+    // File other threads can read a table from with their own handle. Only given when the file
+    // holds every change made to the database, so they see the same rows as this one
+    pub fn snapshot_path(&self, table_name: &str) -> Option<&Path> {
+        let (db, _) = self.resolve(table_name).ok()?;
+        let unsaved_changes = self.in_transaction || db.pager.borrow().has_unsaved_changes();
+        (db.file_backed && !unsaved_changes).then(|| Path::new(&db.path))
+    }
+
+    fn reload_tables(&mut self) -> Result<(), DatabaseError> {
+        self.tables = load_tables(&self.pager)?;
+        for table in self.tables.values() {
+            table.set_bloom_filter_enabled(self.bloom_filter);
+        }
+        Ok(())
+    }
+
+    // Checks the catalog and the B-tree of every table and index, that the row count kept for each
+    // table matches its tree and its indexes hold a key per row, and that every page belongs to
+    // exactly one tree or is free. Returns a description of each problem found, prefixed by the
+    // alias of attached databases
+    pub fn integrity_check(&self) -> Vec<String> {
+        let mut pager = self.pager.borrow_mut();
+        let mut check = IntegrityCheck::default();
+        // Page 0 holds the header
+        check.pages_seen.insert(0);
+
+        let catalog_root = pager.catalog_root();
+        pager.check_tree(catalog_root, &mut check);
+        for table_name in self.table_names() {
+            let table = &self.tables[table_name];
+            let rows = pager.check_tree(table.root_page_num(), &mut check);
+            let counted_rows = table.stats().num_rows;
+            if rows != counted_rows {
+                check.problems.push(format!(
+                    "table {}: holds {} rows but {} are counted in its statistics",
+                    table_name, rows, counted_rows
+                ));
+            }
+            for index in &table.indexes {
+                let keys = pager.check_tree(index.root_page, &mut check);
+                if keys != rows {
+                    check.problems.push(format!(
+                        "index {}: holds {} keys but table {} holds {} rows",
+                        index.name, keys, table_name, rows
+                    ));
+                }
+            }
+        }
+        pager.check_free_pages(&mut check);
+        for page_num in 0..pager.page_count() {
+            if !check.pages_seen.contains(&page_num) {
+                check
+                    .problems
+                    .push(format!("page {} does not belong to any table", page_num));
+            }
+        }
+
+        for (alias, attached_db) in &self.attached {
+            let problems = attached_db.integrity_check();
+            check.problems.extend(
+                problems
+                    .into_iter()
+                    .map(|problem| format!("{}: {}", alias, problem)),
+            );
+        }
+        check.problems
+    }
+
+    pub fn attach(&mut self, path_str: &str, alias: &str) -> Result<(), DatabaseError> {
+        if alias == MAIN_DATABASE || self.attached.contains_key(alias) {
+            return Err(DatabaseError::DuplicateDatabase(alias.to_string()));
+        }
+        let mut attached_db = Database::open(path_str)?;
+        attached_db.set_write_lock(self.write_lock());
+        attached_db.set_read_uncommitted(self.read_uncommitted());
+        attached_db.set_bloom_filter(self.bloom_filter);
+        attached_db.set_threads(self.threads);
+        attached_db.set_cache_size(self.cache_size());
+        attached_db.set_soft_heap_limit(self.soft_heap_limit());
+        attached_db.set_paranoid(self.paranoid());
+        attached_db.set_foreign_keys(self.foreign_keys);
+        self.attached.insert(alias.to_string(), attached_db);
+        Ok(())
+    }
+
+    pub fn detach(&mut self, alias: &str) -> Result<(), DatabaseError> {
+        let mut attached_db = self
+            .attached
+            .remove(alias)
+            .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string()))?;
+        attached_db.close();
+        Ok(())
+    }
 
-        let tables = HashMap::new();
+    // File name of the database without its directory or extension
+    pub fn name(&self) -> &str {
+        Path::new(&self.path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(&self.path)
+    }
 
-        Ok(Self { file, tables })
+    // The alias and file of this database followed by those of every attached database
+    pub fn databases(&self) -> Vec<(&str, &str)> {
+        let mut databases = vec![(MAIN_DATABASE, self.path.as_str())];
+        databases.extend(
+            self.attached
+                .iter()
+                .map(|(alias, attached_db)| (alias.as_str(), attached_db.path.as_str())),
+        );
+        databases
     }
 
-    pub fn add_table(&mut self, table_name: &str, columns: Columns) -> Result<(), DatabaseError> {
+    // Finds the database a possibly qualified table name refers to, along with the unqualified
+    // table name
+    pub fn resolve<'a>(&self, table_name: &'a str) -> Result<(&Database, &'a str), DatabaseError> {
+        match table_name.split_once('.') {
+            None => Ok((self, table_name)),
+            Some((MAIN_DATABASE, table_name)) => Ok((self, table_name)),
+            Some((alias, table_name)) => self
+                .attached
+                .get(alias)
+                .map(|attached_db| (attached_db, table_name))
+                .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string())),
+        }
+    }
+
+    pub fn add_table(
+        &mut self,
+        table_name: &str,
+        columns: Columns,
+        primary_key: Option<String>,
+        comments: Comments,
+    ) -> Result<(), DatabaseError> {
+        if let Some((alias, table_name)) = table_name.split_once('.') {
+            return match alias {
+                MAIN_DATABASE => self.add_table(table_name, columns, primary_key, comments),
+                alias => self
+                    .attached
+                    .get_mut(alias)
+                    .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string()))?
+                    .add_table(table_name, columns, primary_key, comments),
+            };
+        }
+
         if self.tables.contains_key(table_name) {
             return Err(DatabaseError::DuplicateTable);
         }
 
-        let my_table = Table::new(table_name, columns, self.file.clone());
+        let entry = {
+            let mut pager = self.pager.borrow_mut();
+            // The root page of the table comes on top of any pages the catalog grows by
+            let catalog_root = pager.catalog_root();
+            pager.check_file_size(catalog_root, u64::MAX, 1)?;
+            let entry = CatalogEntry {
+                table_id: pager.next_table_id()?,
+                name: table_name.to_string(),
+                constraints: columns.1.clone(),
+                declared_order: columns.2.clone(),
+                columns,
+                root_page: pager.allocate_page(PageType::Leaf)?,
+                stats: TableStats::new(),
+                primary_key,
+                comments,
+                indexes: Vec::new(),
+            };
+            pager.insert(catalog_root, entry.table_id, &entry)?;
+            pager.bump_schema_cookie()?;
+            entry
+        };
+
+        let my_table = Table::from_catalog(entry, self.pager.clone());
+        my_table.set_bloom_filter_enabled(self.bloom_filter);
         self.tables.insert(table_name.to_string(), my_table);
 
         Ok(())
     }
 
-    pub fn get_table(&mut self, table_name: &str) -> Result<&mut Table, DatabaseError> {
-        if let Some(table) = self.tables.get_mut(table_name) {
-            Ok(table)
-        } else {
-            Err(DatabaseError::TableDoesNotExist)
+    // Removes a table along with its rows and indexes. Its pages are freed for new pages to reuse,
+    // and the file only shrinks when the free pages end up at its end
+    pub fn drop_table(&mut self, table_name: &str) -> Result<(), DatabaseError> {
+        if let Some((alias, table_name)) = table_name.split_once('.') {
+            return match alias {
+                MAIN_DATABASE => self.drop_table(table_name),
+                alias => self
+                    .attached
+                    .get_mut(alias)
+                    .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string()))?
+                    .drop_table(table_name),
+            };
+        }
+
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or(DatabaseError::TableDoesNotExist)?;
+        {
+            let mut pager = self.pager.borrow_mut();
+            let catalog_root = pager.catalog_root();
+            pager.delete(catalog_root, table.table_id())?;
+            pager.free_tree(table.root_page_num())?;
+            for index in &table.indexes {
+                pager.free_tree(index.root_page)?;
+            }
+            pager.bump_schema_cookie()?;
+        }
+        self.tables.remove(table_name);
+
+        Ok(())
+    }
+
+    // Creates an index on columns of a table, filled with the key of every row it holds. The
+    // index is named like tables are, and goes in the database its name is qualified with
+    pub fn add_index(
+        &mut self,
+        index_name: &str,
+        table_name: &str,
+        columns: Vec<String>,
+    ) -> Result<(), DatabaseError> {
+        if let Some((alias, index_name)) = index_name.split_once('.') {
+            return match alias {
+                MAIN_DATABASE => self.add_index(index_name, table_name, columns),
+                alias => self
+                    .attached
+                    .get_mut(alias)
+                    .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string()))?
+                    .add_index(index_name, table_name, columns),
+            };
+        }
+
+        if self.index_table(index_name).is_some() {
+            return Err(DatabaseError::DuplicateIndex(index_name.to_string()));
         }
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or(DatabaseError::TableDoesNotExist)?;
+        let index = IndexEntry {
+            name: index_name.to_string(),
+            columns,
+            root_page: self.pager.borrow_mut().allocate_page(PageType::IndexLeaf)?,
+        };
+        table.build_index(&index)?;
+        table.indexes.push(index);
+
+        let mut pager = self.pager.borrow_mut();
+        let catalog_root = pager.catalog_root();
+        pager.delete(catalog_root, table.table_id())?;
+        pager.insert(catalog_root, table.table_id(), &table.catalog_entry())?;
+        pager.bump_schema_cookie()?;
+        Ok(())
+    }
+
+    // Removes an index, freeing its pages
+    pub fn drop_index(&mut self, index_name: &str) -> Result<(), DatabaseError> {
+        if let Some((alias, index_name)) = index_name.split_once('.') {
+            return match alias {
+                MAIN_DATABASE => self.drop_index(index_name),
+                alias => self
+                    .attached
+                    .get_mut(alias)
+                    .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string()))?
+                    .drop_index(index_name),
+            };
+        }
+
+        let table_name = self
+            .index_table(index_name)
+            .ok_or_else(|| DatabaseError::IndexDoesNotExist(index_name.to_string()))?
+            .to_string();
+        let table = self.tables.get_mut(&table_name).unwrap();
+        let idx = table
+            .indexes
+            .iter()
+            .position(|index| index.name == index_name)
+            .unwrap();
+        let index = table.indexes.remove(idx);
+
+        let mut pager = self.pager.borrow_mut();
+        let catalog_root = pager.catalog_root();
+        pager.delete(catalog_root, table.table_id())?;
+        pager.insert(catalog_root, table.table_id(), &table.catalog_entry())?;
+        pager.free_tree(index.root_page)?;
+        pager.bump_schema_cookie()?;
+        Ok(())
     }
+
+    // Name of the table an index of this database belongs to
+    pub fn index_table(&self, index_name: &str) -> Option<&str> {
+        self.tables
+            .iter()
+            .find(|(_, table)| table.index(index_name).is_some())
+            .map(|(table_name, _)| table_name.as_str())
+    }
+
+    // Replaces the catalog entry of a table, which may rename it. Rows are kept as they are, so a
+    // new entry ordering the columns differently leaves the caller to rewrite them
+    pub fn redefine_table(
+        &mut self,
+        table_name: &str,
+        entry: CatalogEntry,
+    ) -> Result<(), DatabaseError> {
+        if let Some((alias, table_name)) = table_name.split_once('.') {
+            return match alias {
+                MAIN_DATABASE => self.redefine_table(table_name, entry),
+                alias => self
+                    .attached
+                    .get_mut(alias)
+                    .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string()))?
+                    .redefine_table(table_name, entry),
+            };
+        }
+
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or(DatabaseError::TableDoesNotExist)?;
+        if entry.name != table_name && self.tables.contains_key(&entry.name) {
+            return Err(DatabaseError::DuplicateTable);
+        }
+        {
+            let mut pager = self.pager.borrow_mut();
+            let catalog_root = pager.catalog_root();
+            pager.delete(catalog_root, table.table_id())?;
+            pager.insert(catalog_root, entry.table_id, &entry)?;
+            pager.bump_schema_cookie()?;
+        }
+        self.tables.remove(table_name);
+
+        let my_table = Table::from_catalog(entry, self.pager.clone());
+        my_table.set_bloom_filter_enabled(self.bloom_filter);
+        self.tables.insert(my_table.name.clone(), my_table);
+
+        Ok(())
+    }
+
+    // Copies the schema, rows and indexes of every table into a new database at the given path
+    pub fn clone_to(&self, path_str: &str) -> Result<(), DatabaseError> {
+        let mut clone = Database::open(path_str)?;
+
+        for table_name in self.table_names() {
+            let table = self.table(table_name)?;
+            clone.add_table(
+                table_name,
+                table.columns.clone(),
+                table.primary_key.clone(),
+                table.comments.clone(),
+            )?;
+            let cloned_table = clone.table(table_name)?;
+
+            let mut cursor = DBCursor::new(table.root_page_num());
+            while let Some(row) = table.next_row(&mut cursor)? {
+                cloned_table.insert(row)?;
+            }
+            for index in &table.indexes {
+                clone.add_index(&index.name, table_name, index.columns.clone())?;
+            }
+        }
+
+        clone.flush()
+    }
+
+    pub fn record_changes(&mut self, changes: u64) {
+        self.changes = changes;
+        self.total_changes += changes;
+    }
+
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    pub fn total_changes(&self) -> u64 {
+        self.total_changes
+    }
+
+    // Counts a statement run. Its latency is only recorded when the platform could measure it
+    pub fn record_statement(&self, elapsed: Option<Duration>, succeeded: bool) {
+        if let Some(elapsed) = elapsed {
+            let mut statement_latency = self.statement_latency.get();
+            statement_latency.record(elapsed);
+            self.statement_latency.set(statement_latency);
+        }
+        if !succeeded {
+            self.statements_failed.set(self.statements_failed.get() + 1);
+        }
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        Metrics::new(
+            self.pager_metrics(),
+            self.statement_latency.get(),
+            self.statements_failed.get(),
+        )
+    }
+
+    pub fn pager_metrics(&self) -> PagerMetrics {
+        self.pager.borrow().metrics()
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.pager.borrow().page_count()
+    }
+
+    // Walks the interior pages of every tree to count the pages that are in use. Attached
+    // databases are left out
+    pub fn stats(&self) -> Result<DatabaseStats, DatabaseError> {
+        let memory_used = self.memory_used();
+        let mut pager = self.pager.borrow_mut();
+        let catalog_root = pager.catalog_root();
+        let catalog_pages = pager.tree_pages(catalog_root)?.len() as u32;
+        // Page 0 holds the header
+        let mut used_pages = 1 + catalog_pages;
+        let mut tables = Vec::new();
+        for table_name in self.table_names() {
+            let table = &self.tables[table_name];
+            used_pages += pager.tree_pages(table.root_page_num())?.len() as u32;
+            tables.push((table_name.to_string(), table.stats()));
+        }
+
+        Ok(DatabaseStats {
+            file_size: pager.file_size()?,
+            page_size: PAGE_SIZE,
+            page_count: pager.page_count(),
+            freelist_count: pager.page_count().saturating_sub(used_pages),
+            catalog_pages,
+            memory_used,
+            tables,
+        })
+    }
+
+    pub fn user_version(&self) -> i32 {
+        self.pager.borrow().user_version()
+    }
+
+    pub fn set_user_version(&self, user_version: i32) -> Result<(), DatabaseError> {
+        Ok(self.pager.borrow_mut().set_user_version(user_version)?)
+    }
+
+    // Changes whenever a table is created or dropped, so anything derived from the schema can tell
+    // it is stale
+    pub fn schema_version(&self) -> u32 {
+        self.pager.borrow().schema_cookie()
+    }
+
+    pub fn table_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tables.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // Names of the tables of the database attached under the alias, or of this one for main
+    pub fn table_names_in(&self, alias: &str) -> Result<Vec<&str>, DatabaseError> {
+        match alias {
+            MAIN_DATABASE => Ok(self.table_names()),
+            alias => self
+                .attached
+                .get(alias)
+                .map(Database::table_names)
+                .ok_or_else(|| DatabaseError::UnknownDatabase(alias.to_string())),
+        }
+    }
+
+    pub fn table(&self, table_name: &str) -> Result<&Table, DatabaseError> {
+        let (db, table_name) = self.resolve(table_name)?;
+        db.tables
+            .get(table_name)
+            .ok_or(DatabaseError::TableDoesNotExist)
+    }
+}
+
+// Every table is described by one record of the catalog B-tree
+fn load_tables(pager: &Rc<RefCell<Pager>>) -> Result<HashMap<String, Table>, DatabaseError> {
+    let mut entries = Vec::new();
+    {
+        let mut pager = pager.borrow_mut();
+        let mut cursor = DBCursor::new(pager.catalog_root());
+        while let Some(cell) = cursor.next_cell(&mut pager)? {
+            let entry = CatalogEntry::try_from(&*cell.value)
+                .map_err(|_| DatabaseError::CorruptCatalog(cell.id))?;
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.name.clone(),
+                Table::from_catalog(entry, pager.clone()),
+            )
+        })
+        .collect())
 }
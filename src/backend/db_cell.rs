@@ -1,6 +1,5 @@
 use std::io::Cursor;
 use std::mem;
-use std::rc::Rc;
 
 use thiserror::Error;
 
@@ -8,6 +7,7 @@ use serde::{Deserialize, Serialize};
 
 const PAYLOAD_SIZE_SIZE: usize = mem::size_of::<u16>();
 const ID_SIZE: usize = mem::size_of::<u64>();
+const VALUE_LEN_SIZE: usize = mem::size_of::<u64>();
 const LEFT_CHILD_SIZE: usize = mem::size_of::<u32>();
 
 #[derive(Error, Debug)]
@@ -21,7 +21,7 @@ pub struct DBCell {
     payload_size_slot: u16,
     pub id: u64,
     pub value: Box<[u8]>,
-    left_child: u32,
+    pub left_child: u32,
 }
 
 impl DBCell {
@@ -32,6 +32,37 @@ impl DBCell {
         .with_big_endian()
         .with_fixed_int_encoding();
 
+    pub const LEFT_CHILD_SIZE: usize = LEFT_CHILD_SIZE;
+
+    pub fn new_interior(id: u64, left_child: u32) -> Self {
+        Self {
+            payload_size_slot: 0,
+            id,
+            value: Box::new([]),
+            left_child,
+        }
+    }
+
+    // Cell of an index tree holding a byte key. Its id is made of the first bytes of the key,
+    // padded with zeros, and its value is the whole key. Index trees order cells by id and then
+    // by value, which is the order of the keys
+    pub fn new_keyed(key: &[u8]) -> Self {
+        let mut prefix = [0; ID_SIZE];
+        let prefix_len = key.len().min(ID_SIZE);
+        prefix[..prefix_len].copy_from_slice(&key[..prefix_len]);
+        Self {
+            payload_size_slot: 0,
+            id: u64::from_be_bytes(prefix),
+            value: key.into(),
+            left_child: 0,
+        }
+    }
+
+    // Number of bytes the cell takes once serialized into a page
+    pub fn encoded_size(&self) -> usize {
+        PAYLOAD_SIZE_SIZE + ID_SIZE + VALUE_LEN_SIZE + self.value.len() + LEFT_CHILD_SIZE
+    }
+
     pub fn new<T>(id: u64, serializable_data: T) -> Result<Self, CellError>
     where
         T: TryInto<Box<[u8]>, Error = ()>,
@@ -42,11 +73,42 @@ impl DBCell {
         Ok(Self {
             payload_size_slot: 0,
             id,
-            value: data.into(),
+            value: data,
             left_child: 0,
         })
     }
 
+    // Serializes the cell at the start of `buf`, which must hold at least `encoded_size` bytes.
+    // Returns the number of bytes written
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        let written =
+            bincode::serde::encode_into_slice(self, buf, Self::BINCODE_CONFIG).map_err(|_| ())?;
+        let payload_size: u16 = (written - PAYLOAD_SIZE_SIZE) as u16;
+
+        buf[..PAYLOAD_SIZE_SIZE].copy_from_slice(&payload_size.to_be_bytes());
+
+        Ok(written)
+    }
+
+    // Number of bytes taken by the cell at the start of `bytes`, provided its payload fits in them
+    // and its size agrees with the length of the value it holds
+    pub fn size_from_slice(bytes: &[u8]) -> Option<usize> {
+        let payload_size = u16::from_be_bytes(bytes.get(..PAYLOAD_SIZE_SIZE)?.try_into().ok()?);
+        let value_len_start = PAYLOAD_SIZE_SIZE + ID_SIZE;
+        let value_len = u64::from_be_bytes(
+            bytes
+                .get(value_len_start..value_len_start + VALUE_LEN_SIZE)?
+                .try_into()
+                .ok()?,
+        );
+        let expected_payload_size = usize::try_from(value_len)
+            .ok()?
+            .checked_add(ID_SIZE + VALUE_LEN_SIZE + LEFT_CHILD_SIZE)?;
+
+        let size = PAYLOAD_SIZE_SIZE + payload_size as usize;
+        (payload_size as usize == expected_payload_size && size <= bytes.len()).then_some(size)
+    }
+
     pub fn id_from_slice(bytes: &[u8]) -> Result<u64, ()> {
         if bytes.len() < PAYLOAD_SIZE_SIZE + ID_SIZE {
             return Err(());
@@ -63,21 +125,6 @@ impl DBCell {
     }
 }
 
-impl TryInto<Rc<[u8]>> for DBCell {
-    type Error = ();
-
-    fn try_into(self) -> Result<Rc<[u8]>, Self::Error> {
-        let mut cell_encoded =
-            bincode::serde::encode_to_vec::<DBCell, _>(self, Self::BINCODE_CONFIG)
-                .map_err(|_| ())?;
-        let payload_size: u16 = (cell_encoded.len() - PAYLOAD_SIZE_SIZE) as u16;
-
-        cell_encoded[..PAYLOAD_SIZE_SIZE].copy_from_slice(&payload_size.to_be_bytes());
-
-        Ok(cell_encoded.into())
-    }
-}
-
 impl TryFrom<&[u8]> for DBCell {
     type Error = ();
 
@@ -86,8 +133,8 @@ impl TryFrom<&[u8]> for DBCell {
             return Err(());
         }
 
-        let db_cell =
-            bincode::serde::decode_borrowed_from_slice::<DBCell, _>(&bytes, Self::BINCODE_CONFIG)
+        let (db_cell, _) =
+            bincode::serde::borrow_decode_from_slice::<DBCell, _>(bytes, Self::BINCODE_CONFIG)
                 .map_err(|_| ())?;
         Ok(db_cell)
     }
@@ -0,0 +1,208 @@
+use thiserror::Error;
+
+use super::page::PAGE_SIZE;
+
+// Identifies sql_rs database files. Stored at the very beginning of page 0
+const MAGIC: &[u8; 16] = b"sql_rs format 1\0";
+
+// Version of the layout of pages and catalog records, raised whenever either changes so that
+// files in another layout are refused instead of misread. Catalog records are not
+// self-describing, e.g. the width VARCHAR lengths are stored with is only known from the version.
+// Files written before the version was stored read as 0
+pub const FORMAT_VERSION: u32 = 3;
+
+#[derive(Error, Debug)]
+pub enum HeaderError {
+    #[error("File is not a sql_rs database")]
+    NotADatabase,
+    #[error("Database was written with a page size of {0} bytes, expected {1}")]
+    PageSizeMismatch(usize, usize),
+    #[error(
+        "Database was written in format version {0}, but this version of sql_rs only reads \
+         version {1}. Use .dump with the version of sql_rs that wrote it and load the SQL into a \
+         new database"
+    )]
+    UnsupportedFormat(u32, u32),
+}
+
+// Page 0 of the file holds this header instead of B-tree data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseHeader {
+    pub page_count: u32,
+    pub catalog_root: u32,
+    pub next_table_id: u64,
+    // Incremented by every flush, so other processes can tell their cached pages are stale
+    pub change_counter: u32,
+    // Set by applications through PRAGMA user_version, never touched by the database itself
+    pub user_version: i32,
+    // Incremented by every schema change, so statements prepared against an older schema can be
+    // detected
+    pub schema_cookie: u32,
+    // First page of the chain of pages freed by dropped tables, or 0 when there is none
+    pub first_free_page: u32,
+}
+
+impl DatabaseHeader {
+    const PAGE_SIZE_OFFSET: usize = 16;
+    const PAGE_COUNT_OFFSET: usize = 18;
+    const CATALOG_ROOT_OFFSET: usize = 22;
+    const NEXT_TABLE_ID_OFFSET: usize = 26;
+    const CHANGE_COUNTER_OFFSET: usize = 34;
+    const USER_VERSION_OFFSET: usize = 38;
+    const SCHEMA_COOKIE_OFFSET: usize = 42;
+    const FIRST_FREE_PAGE_OFFSET: usize = 46;
+    const FORMAT_VERSION_OFFSET: usize = 50;
+    const HEADER_END: usize = 54;
+
+    pub fn read_from_slice(bytes: &[u8]) -> Result<Self, HeaderError> {
+        if bytes.len() < Self::HEADER_END || &bytes[..Self::PAGE_SIZE_OFFSET] != MAGIC {
+            return Err(HeaderError::NotADatabase);
+        }
+
+        let page_size = u16::from_be_bytes(
+            bytes[Self::PAGE_SIZE_OFFSET..Self::PAGE_COUNT_OFFSET]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        if page_size != PAGE_SIZE {
+            return Err(HeaderError::PageSizeMismatch(page_size, PAGE_SIZE));
+        }
+        let format_version = u32::from_be_bytes(
+            bytes[Self::FORMAT_VERSION_OFFSET..Self::HEADER_END]
+                .try_into()
+                .unwrap(),
+        );
+        if format_version != FORMAT_VERSION {
+            return Err(HeaderError::UnsupportedFormat(
+                format_version,
+                FORMAT_VERSION,
+            ));
+        }
+
+        Ok(Self {
+            page_count: u32::from_be_bytes(
+                bytes[Self::PAGE_COUNT_OFFSET..Self::CATALOG_ROOT_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+            catalog_root: u32::from_be_bytes(
+                bytes[Self::CATALOG_ROOT_OFFSET..Self::NEXT_TABLE_ID_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+            next_table_id: u64::from_be_bytes(
+                bytes[Self::NEXT_TABLE_ID_OFFSET..Self::CHANGE_COUNTER_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+            change_counter: u32::from_be_bytes(
+                bytes[Self::CHANGE_COUNTER_OFFSET..Self::USER_VERSION_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+            user_version: i32::from_be_bytes(
+                bytes[Self::USER_VERSION_OFFSET..Self::SCHEMA_COOKIE_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+            schema_cookie: u32::from_be_bytes(
+                bytes[Self::SCHEMA_COOKIE_OFFSET..Self::FIRST_FREE_PAGE_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+            first_free_page: u32::from_be_bytes(
+                bytes[Self::FIRST_FREE_PAGE_OFFSET..Self::FORMAT_VERSION_OFFSET]
+                    .try_into()
+                    .unwrap(),
+            ),
+        })
+    }
+}
+
+impl From<&DatabaseHeader> for [u8; PAGE_SIZE] {
+    fn from(header: &DatabaseHeader) -> Self {
+        let mut bytes = [0; PAGE_SIZE];
+        bytes[..DatabaseHeader::PAGE_SIZE_OFFSET].copy_from_slice(MAGIC);
+        bytes[DatabaseHeader::PAGE_SIZE_OFFSET..DatabaseHeader::PAGE_COUNT_OFFSET]
+            .copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+        bytes[DatabaseHeader::PAGE_COUNT_OFFSET..DatabaseHeader::CATALOG_ROOT_OFFSET]
+            .copy_from_slice(&header.page_count.to_be_bytes());
+        bytes[DatabaseHeader::CATALOG_ROOT_OFFSET..DatabaseHeader::NEXT_TABLE_ID_OFFSET]
+            .copy_from_slice(&header.catalog_root.to_be_bytes());
+        bytes[DatabaseHeader::NEXT_TABLE_ID_OFFSET..DatabaseHeader::CHANGE_COUNTER_OFFSET]
+            .copy_from_slice(&header.next_table_id.to_be_bytes());
+        bytes[DatabaseHeader::CHANGE_COUNTER_OFFSET..DatabaseHeader::USER_VERSION_OFFSET]
+            .copy_from_slice(&header.change_counter.to_be_bytes());
+        bytes[DatabaseHeader::USER_VERSION_OFFSET..DatabaseHeader::SCHEMA_COOKIE_OFFSET]
+            .copy_from_slice(&header.user_version.to_be_bytes());
+        bytes[DatabaseHeader::SCHEMA_COOKIE_OFFSET..DatabaseHeader::FIRST_FREE_PAGE_OFFSET]
+            .copy_from_slice(&header.schema_cookie.to_be_bytes());
+        bytes[DatabaseHeader::FIRST_FREE_PAGE_OFFSET..DatabaseHeader::FORMAT_VERSION_OFFSET]
+            .copy_from_slice(&header.first_free_page.to_be_bytes());
+        bytes[DatabaseHeader::FORMAT_VERSION_OFFSET..DatabaseHeader::HEADER_END]
+            .copy_from_slice(&FORMAT_VERSION.to_be_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::{DatabaseHeader, HeaderError, FORMAT_VERSION};
+    use crate::backend::page::PAGE_SIZE;
+    use crate::Connection;
+
+    fn header_bytes(format_version: u32) -> [u8; PAGE_SIZE] {
+        let header = DatabaseHeader {
+            page_count: 2,
+            catalog_root: 1,
+            next_table_id: 1,
+            change_counter: 0,
+            user_version: 0,
+            schema_cookie: 0,
+            first_free_page: 0,
+        };
+        let mut bytes = <[u8; PAGE_SIZE]>::from(&header);
+        bytes[DatabaseHeader::FORMAT_VERSION_OFFSET..DatabaseHeader::HEADER_END]
+            .copy_from_slice(&format_version.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn reads_the_current_format() {
+        let header = DatabaseHeader::read_from_slice(&header_bytes(FORMAT_VERSION)).unwrap();
+        assert_eq!(header.catalog_root, 1);
+    }
+
+    #[test]
+    fn refuses_other_formats() {
+        for version in [0, FORMAT_VERSION + 1] {
+            let err = DatabaseHeader::read_from_slice(&header_bytes(version)).unwrap_err();
+            assert!(matches!(
+                err,
+                HeaderError::UnsupportedFormat(found, FORMAT_VERSION) if found == version
+            ));
+        }
+    }
+
+    #[test]
+    fn refuses_to_open_a_file_written_before_the_version_was_stored() {
+        let path = env::temp_dir().join(format!("sql_rs_format_{}.db", process::id()));
+        let path_str = path.to_str().unwrap();
+        let _ = fs::remove_file(&path);
+        Connection::open(path_str)
+            .unwrap()
+            .execute("CREATE TABLE t (name VARCHAR(300));")
+            .unwrap();
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[DatabaseHeader::FORMAT_VERSION_OFFSET..DatabaseHeader::HEADER_END].fill(0);
+        fs::write(&path, bytes).unwrap();
+        let err = Connection::open(path_str).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("format version 0"), "{}", err);
+    }
+}
@@ -0,0 +1,260 @@
+// Encoding of values into keys whose bytes compare in the same order as the values, so that
+// B-trees can order text and composite keys without decoding them. Every value starts with a tag
+// giving its kind, which orders NULL before numbers, numbers before text and text before UUIDs.
+// Values are self-delimiting, so a composite key is the concatenation of its values and sorts by
+// the first one, then by the second one and so on
+use thiserror::Error;
+
+use super::row::SQLType;
+use super::uuid::Uuid;
+
+const NULL_TAG: u8 = 0x05;
+const NEGATIVE_TAG: u8 = 0x10;
+const INTEGER_TAG: u8 = 0x11;
+const TEXT_TAG: u8 = 0x20;
+const UUID_TAG: u8 = 0x30;
+
+// Zero bytes within text are followed by ESCAPE, and text ends with a zero byte followed by
+// TERMINATOR, which sorts shorter text before longer text it is a prefix of
+const ESCAPE: u8 = 0xff;
+const TERMINATOR: u8 = 0x01;
+
+#[derive(Error, Debug)]
+pub enum KeyError {
+    #[error("Key ends in the middle of a value")]
+    Truncated,
+    #[error("Key holds a value of unknown kind {0}")]
+    UnknownTag(u8),
+    #[error("Key holds text that is not valid UTF-8")]
+    InvalidText,
+}
+
+pub fn encode_value(value: &SQLType, key: &mut Vec<u8>) {
+    let integer = match value {
+        SQLType::Null => {
+            key.push(NULL_TAG);
+            return;
+        }
+        SQLType::Text(text) => {
+            key.push(TEXT_TAG);
+            for &byte in text.as_bytes() {
+                key.push(byte);
+                if byte == 0 {
+                    key.push(ESCAPE);
+                }
+            }
+            key.extend([0, TERMINATOR]);
+            return;
+        }
+        SQLType::Uuid(uuid) => {
+            key.push(UUID_TAG);
+            key.extend(uuid.as_bytes());
+            return;
+        }
+        SQLType::UBigInt(num) => *num as i128,
+        SQLType::Integer(num) => *num as i128,
+        SQLType::BigInt(num) => *num as i128,
+        SQLType::SmallInt(num) => *num as i128,
+    };
+
+    // Negative numbers keep their two's complement bytes, which compare in order among them
+    match integer < 0 {
+        true => key.push(NEGATIVE_TAG),
+        false => key.push(INTEGER_TAG),
+    }
+    key.extend((integer as u64).to_be_bytes());
+}
+
+// Key made of the given values, in order
+pub fn encode_key(values: &[SQLType]) -> Box<[u8]> {
+    let mut key = Vec::new();
+    for value in values {
+        encode_value(value, &mut key);
+    }
+    key.into()
+}
+
+// Values a key was made of. Integers of every type come back as BigInt when negative and as
+// UBigInt otherwise
+pub fn decode_key(mut key: &[u8]) -> Result<Vec<SQLType>, KeyError> {
+    let mut values = Vec::new();
+    while let Some((&tag, rest)) = key.split_first() {
+        let (value, len) = match tag {
+            NULL_TAG => (SQLType::Null, 0),
+            NEGATIVE_TAG | INTEGER_TAG => {
+                let bytes = rest.get(..8).ok_or(KeyError::Truncated)?;
+                let num = u64::from_be_bytes(bytes.try_into().unwrap());
+                match tag {
+                    NEGATIVE_TAG => (SQLType::BigInt(num as i64), 8),
+                    _ => (SQLType::UBigInt(num), 8),
+                }
+            }
+            TEXT_TAG => decode_text(rest)?,
+            UUID_TAG => {
+                let bytes = rest.get(..16).ok_or(KeyError::Truncated)?;
+                let uuid = Uuid::from_bytes(bytes.try_into().unwrap());
+                (SQLType::Uuid(uuid), 16)
+            }
+            tag => return Err(KeyError::UnknownTag(tag)),
+        };
+        values.push(value);
+        key = &rest[len..];
+    }
+    Ok(values)
+}
+
+// Text at the start of `bytes` along with the number of bytes it took
+fn decode_text(bytes: &[u8]) -> Result<(SQLType, usize), KeyError> {
+    let mut text = Vec::new();
+    let mut idx = 0;
+    loop {
+        match (bytes.get(idx), bytes.get(idx + 1)) {
+            (Some(0), Some(&ESCAPE)) => text.push(0),
+            (Some(0), Some(&TERMINATOR)) => break,
+            (Some(0), Some(_)) => return Err(KeyError::InvalidText),
+            (Some(0), None) | (None, _) => return Err(KeyError::Truncated),
+            (Some(&byte), _) => {
+                text.push(byte);
+                idx += 1;
+                continue;
+            }
+        }
+        idx += 2;
+    }
+    let text = String::from_utf8(text).map_err(|_| KeyError::InvalidText)?;
+    Ok((SQLType::Text(text), idx + 2))
+}
+
+// Smallest key greater than every key starting with `prefix`, which bounds a scan over them. There
+// is none when the prefix is empty or made only of 0xff bytes
+pub fn prefix_end(prefix: &[u8]) -> Option<Box<[u8]>> {
+    let last_idx = prefix.iter().rposition(|&byte| byte != 0xff)?;
+    let mut end = prefix[..=last_idx].to_vec();
+    end[last_idx] += 1;
+    Some(end.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use super::{decode_key, encode_key, prefix_end, KeyError, TEXT_TAG};
+    use crate::backend::row::SQLType;
+    use crate::backend::uuid::Uuid;
+
+    fn key(value: &SQLType) -> Box<[u8]> {
+        encode_key(slice::from_ref(value))
+    }
+
+    #[test]
+    fn values_come_back_from_their_keys() {
+        let values = [
+            SQLType::Null,
+            SQLType::BigInt(i64::MIN),
+            SQLType::BigInt(-1),
+            SQLType::UBigInt(0),
+            SQLType::UBigInt(i64::MAX as u64),
+            SQLType::UBigInt(u64::MAX),
+            SQLType::Text(String::new()),
+            SQLType::Text("a\0b\0".to_string()),
+            SQLType::Text("ünïcode \u{ff}".to_string()),
+            SQLType::Uuid(Uuid::from_bytes([0xab; 16])),
+        ];
+        for value in &values {
+            assert_eq!(decode_key(&key(value)).unwrap(), slice::from_ref(value));
+        }
+        assert_eq!(decode_key(&encode_key(&values)).unwrap(), values);
+
+        // Integers of every width come back at their widest
+        assert_eq!(
+            decode_key(&encode_key(&[SQLType::SmallInt(-3), SQLType::Integer(7)])).unwrap(),
+            [SQLType::BigInt(-3), SQLType::UBigInt(7)]
+        );
+    }
+
+    #[test]
+    fn keys_sort_as_their_values() {
+        let ordered = [
+            SQLType::Null,
+            SQLType::BigInt(i64::MIN),
+            SQLType::Integer(-256),
+            SQLType::SmallInt(-1),
+            SQLType::UBigInt(0),
+            SQLType::SmallInt(1),
+            SQLType::Integer(256),
+            SQLType::BigInt(i64::MAX),
+            SQLType::UBigInt(u64::MAX),
+            SQLType::Text(String::new()),
+            SQLType::Text("a".to_string()),
+            SQLType::Text("a\0".to_string()),
+            SQLType::Text("a\0b".to_string()),
+            SQLType::Text("ab".to_string()),
+            SQLType::Text("b".to_string()),
+            SQLType::Uuid(Uuid::from_bytes([0; 16])),
+        ];
+        for pair in ordered.windows(2) {
+            assert!(
+                key(&pair[0]) < key(&pair[1]),
+                "{:?} does not sort before {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+        // Integers of different widths holding the same number make the same key
+        assert_eq!(key(&SQLType::SmallInt(-5)), key(&SQLType::BigInt(-5)));
+        assert_eq!(key(&SQLType::Integer(5)), key(&SQLType::UBigInt(5)));
+    }
+
+    #[test]
+    fn composite_keys_sort_by_each_value_in_turn() {
+        let composite = |text: &str, num: u64| {
+            encode_key(&[SQLType::Text(text.to_string()), SQLType::UBigInt(num)])
+        };
+        // A shorter first value sorts first, whatever follows it
+        assert!(composite("a", u64::MAX) < composite("a\0", 0));
+        assert!(composite("a", u64::MAX) < composite("ab", 0));
+        assert!(composite("ab", 1) < composite("ab", 2));
+
+        // Every key starting with a value lies between it and its prefix end
+        let prefix = key(&SQLType::Text("a".to_string()));
+        let end = prefix_end(&prefix).unwrap();
+        assert!(composite("a", 0) >= prefix && composite("a", u64::MAX) < end);
+        assert!(composite("a\0", 0) >= end);
+        assert!(composite("ab", 0) >= end);
+    }
+
+    #[test]
+    fn prefix_end_skips_trailing_0xff() {
+        assert_eq!(prefix_end(&[1, 2]).as_deref(), Some(&[1, 3][..]));
+        assert_eq!(prefix_end(&[1, 0xff, 0xff]).as_deref(), Some(&[2][..]));
+        assert_eq!(prefix_end(&[0xff]), None);
+        assert_eq!(prefix_end(&[]), None);
+    }
+
+    #[test]
+    fn malformed_keys_are_refused() {
+        let text = key(&SQLType::Text("a\0b".to_string()));
+        assert!(matches!(
+            decode_key(&text[..text.len() - 1]),
+            Err(KeyError::Truncated)
+        ));
+        let integer = key(&SQLType::UBigInt(1));
+        assert!(matches!(
+            decode_key(&integer[..5]),
+            Err(KeyError::Truncated)
+        ));
+        assert!(matches!(
+            decode_key(&[0x42]),
+            Err(KeyError::UnknownTag(0x42))
+        ));
+        // A zero byte in text followed by neither the escape nor the terminator
+        assert!(matches!(
+            decode_key(&[TEXT_TAG, b'a', 0, 7]),
+            Err(KeyError::InvalidText)
+        ));
+        assert!(matches!(
+            decode_key(&[TEXT_TAG, 0xc3, 0, 1]),
+            Err(KeyError::InvalidText)
+        ));
+    }
+}
@@ -1,18 +1,63 @@
-#![allow(dead_code)]
-use std::mem::{self, MaybeUninit};
+use std::mem;
 use std::ops::{Deref, DerefMut};
-use std::rc::Rc;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use super::cursor::DBCursor;
 use super::db_cell::DBCell;
 use super::row::Row;
 
 pub const PAGE_SIZE: usize = 4096;
 const PAGE_HEADER_SIZE: usize = mem::size_of::<PageHeader>();
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageType {
+    // Interior pages only hold keys and child pointers, rows live in leaf pages
+    Interior = 5,
+    Leaf = 13,
+    // Pages of index trees, where several cells may share a key. Cells are ordered by key and
+    // then by value, and interior cells keep the value of their separator too
+    IndexInterior = 2,
+    IndexLeaf = 10,
+}
+
+impl PageType {
+    fn from_u8(page_type: u8) -> Option<Self> {
+        [
+            PageType::Interior,
+            PageType::Leaf,
+            PageType::IndexInterior,
+            PageType::IndexLeaf,
+        ]
+        .into_iter()
+        .find(|&known| known as u8 == page_type)
+    }
+
+    pub fn is_leaf(self) -> bool {
+        matches!(self, PageType::Leaf | PageType::IndexLeaf)
+    }
+
+    pub fn is_index(self) -> bool {
+        matches!(self, PageType::IndexInterior | PageType::IndexLeaf)
+    }
+
+    // Type of the interior pages of a tree with pages of this type
+    pub fn interior(self) -> Self {
+        match self.is_index() {
+            true => PageType::IndexInterior,
+            false => PageType::Interior,
+        }
+    }
+
+    // What the cells of a tree with pages of this type are ordered by
+    pub fn sort_key(self, cell: &DBCell) -> (u64, &[u8]) {
+        match self.is_index() {
+            true => (cell.id, &cell.value),
+            false => (cell.id, &[]),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
 struct PageHeader {
     page_type: u8,
@@ -29,11 +74,6 @@ impl PageHeader {
         header_slice[0] = val;
     }
 
-    fn set_first_free_block(&mut self, val: u16, header_slice: &mut [u8]) {
-        self.first_free_block = val;
-        header_slice[1..3].copy_from_slice(&val.to_be_bytes());
-    }
-
     fn set_num_cells(&mut self, val: u16, header_slice: &mut [u8]) {
         self.num_cells = val;
         header_slice[3..5].copy_from_slice(&val.to_be_bytes());
@@ -44,11 +84,6 @@ impl PageHeader {
         header_slice[5..7].copy_from_slice(&val.to_be_bytes());
     }
 
-    fn set_fragmented_free_bytes(&mut self, val: u8, header_slice: &mut [u8]) {
-        self.fragmented_free_bytes = val;
-        header_slice[7] = val;
-    }
-
     fn set_right_pointer(&mut self, val: u32, header_slice: &mut [u8]) {
         self.right_pointer = val;
         header_slice[8..12].copy_from_slice(&val.to_be_bytes());
@@ -76,17 +111,15 @@ impl CellPtrArray {
     fn update_pointer_array_after_insert(
         &mut self,
         cell_ptr_array_start: &mut [u8],
+        insert_pos: usize,
         new_cell_ptr: u16,
         new_cell_byte_size: u16,
     ) {
-        let insert_pos = self.partition_point(|&cell_ptr| cell_ptr <= new_cell_ptr);
-        self.insert(insert_pos, new_cell_ptr);
-        println!("INSERT POS {:?}", insert_pos);
-        println!("NEPE {:?}", self);
+        // Cells stored before the insertion point were shifted down to make room for the new one
         for elem in self[..insert_pos].iter_mut() {
             *elem -= new_cell_byte_size;
         }
-        println!("NEPE {:?}", self);
+        self.insert(insert_pos, new_cell_ptr);
         self.write_pointer_array(cell_ptr_array_start);
     }
 
@@ -98,14 +131,12 @@ impl CellPtrArray {
 
     fn write_u16_in_bytes(bytes: &mut [u8], index: usize, value: u16) {
         let start = index * 2;
-        let new_bytes = value.to_le_bytes();
-        bytes[start] = new_bytes[0];
-        bytes[start + 1] = new_bytes[1];
+        bytes[start..start + 2].copy_from_slice(&value.to_be_bytes());
     }
 }
 
 impl DerefMut for CellPtrArray {
-    fn deref_mut<'a>(&'a mut self) -> &'a mut Vec<u16> {
+    fn deref_mut(&mut self) -> &mut Vec<u16> {
         &mut self.0
     }
 }
@@ -113,7 +144,7 @@ impl DerefMut for CellPtrArray {
 impl Deref for CellPtrArray {
     type Target = Vec<u16>;
 
-    fn deref<'a>(&'a self) -> &'a Vec<u16> {
+    fn deref(&self) -> &Vec<u16> {
         &self.0
     }
 }
@@ -133,99 +164,298 @@ pub enum PageError {
     PageFull,
     #[error("Could not insert")]
     InsertError,
+    #[error("A row with key {0} already exists")]
+    DuplicateKey(u64),
     #[error(
         "The slice being deserialized does not correspond to a valid page. End of the slice reached during deserialization"
     )]
     EndOfSliceWhileDeserializing,
+    #[error("Page type {0} is not valid")]
+    InvalidPageType(u8),
+    #[error("Pointers to {0} cells do not fit before the cell content area")]
+    CellPointerArrayOverflow(u16),
+    #[error("Cell content area starts at {0}, past the end of the page")]
+    CellsStartOutOfRange(u16),
+    #[error("Cell {0} points outside the cell content area")]
+    CellOutOfBounds(usize),
+    #[error("Payload of cell {0} runs past the end of the page or disagrees with its size")]
+    PayloadOverflow(usize),
+    #[error("Cell {0} overlaps another cell")]
+    OverlappingCells(usize),
+    #[error("Key of cell {0} is not greater than the key before it")]
+    KeysOutOfOrder(usize),
 }
 
 impl Page {
-    const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard();
     const OFFSET_BYTE_SIZE: usize = 2;
+    // Largest cell for which splitting a full page always yields two halves that fit in a page
+    pub const MAX_CELL_SIZE: usize = (PAGE_SIZE - PAGE_HEADER_SIZE) / 4 - Self::OFFSET_BYTE_SIZE;
 
     pub fn new() -> Self {
-        /* For performance reasons, a page is initialized as an empty array.
-        It is the responsibility of the implementation to read and write the data properly.
-        */
-        let uninitialized_array = {
-            let uninitialized_array: [MaybeUninit<u8>; PAGE_SIZE] =
-                unsafe { MaybeUninit::uninit().assume_init() };
-
-            unsafe { mem::transmute::<_, [u8; PAGE_SIZE]>(uninitialized_array) }
-        };
+        Self::new_typed(PageType::Leaf)
+    }
 
-        Self {
-            header: PageHeader {
-                cells_start: PAGE_SIZE as u16 - 1,
-                ..Default::default()
-            },
-            data: uninitialized_array,
+    pub fn new_typed(page_type: PageType) -> Self {
+        let mut page = Self {
+            header: PageHeader::default(),
+            data: [0; PAGE_SIZE],
             cell_pointer_array: CellPtrArray::default(),
+        };
+        let header_slice = &mut page.data[..PAGE_HEADER_SIZE];
+        page.header.set_page_type(page_type as u8, header_slice);
+        page.header.set_cells_start(PAGE_SIZE as u16, header_slice);
+        page
+    }
+
+    // Rebuilds a page from the bytes it was written to disk as. A paranoid read also checks every
+    // cell, so that a damaged file gives a precise error instead of a panic on a later access
+    pub fn new_from_read(bytes: &[u8; PAGE_SIZE], paranoid: bool) -> Result<Self, PageError> {
+        let header = PageHeader {
+            page_type: bytes[0],
+            first_free_block: u16::from_be_bytes([bytes[1], bytes[2]]),
+            num_cells: u16::from_be_bytes([bytes[3], bytes[4]]),
+            cells_start: u16::from_be_bytes([bytes[5], bytes[6]]),
+            fragmented_free_bytes: bytes[7],
+            right_pointer: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        };
+
+        let end_of_ptr_array =
+            PAGE_HEADER_SIZE + header.num_cells as usize * Self::OFFSET_BYTE_SIZE;
+        if PageType::from_u8(header.page_type).is_none() {
+            return Err(PageError::InvalidPageType(header.page_type));
+        }
+        if header.cells_start as usize > PAGE_SIZE {
+            return Err(PageError::CellsStartOutOfRange(header.cells_start));
+        }
+        if end_of_ptr_array > header.cells_start as usize {
+            return Err(PageError::CellPointerArrayOverflow(header.num_cells));
         }
+
+        let cell_pointer_array =
+            CellPtrArray::read_from_slice(header.num_cells as usize, &bytes[PAGE_HEADER_SIZE..]);
+        if let Some(cell_idx) = cell_pointer_array.iter().position(|&ptr| {
+            (ptr as usize) < header.cells_start as usize || ptr as usize >= PAGE_SIZE
+        }) {
+            return Err(PageError::CellOutOfBounds(cell_idx));
+        }
+
+        let page = Self {
+            header,
+            data: *bytes,
+            cell_pointer_array,
+        };
+        if paranoid {
+            page.check_cells()?;
+        }
+        Ok(page)
     }
 
-    pub fn get_keys(&self) -> Result<Vec<u64>, PageError> {
-        let keys_res_iter = self
+    // Checks that every cell fits in the page without overlapping another, and that cells are in
+    // increasing order
+    fn check_cells(&self) -> Result<(), PageError> {
+        let page_type = self.page_type();
+        let mut extents = Vec::with_capacity(self.num_cells());
+        let mut previous_cell: Option<DBCell> = None;
+        for (cell_idx, &pointer) in self.cell_pointer_array.iter().enumerate() {
+            let pointer = pointer as usize;
+            let size = DBCell::size_from_slice(&self.data[pointer..])
+                .ok_or(PageError::PayloadOverflow(cell_idx))?;
+            extents.push((pointer, pointer + size, cell_idx));
+
+            let cell = self.cell_at(cell_idx)?;
+            if previous_cell.as_ref().is_some_and(|previous_cell| {
+                page_type.sort_key(previous_cell) >= page_type.sort_key(&cell)
+            }) {
+                return Err(PageError::KeysOutOfOrder(cell_idx));
+            }
+            previous_cell = Some(cell);
+        }
+
+        extents.sort_unstable();
+        for pair in extents.windows(2) {
+            let ((_, previous_end, _), (start, _, cell_idx)) = (pair[0], pair[1]);
+            if start < previous_end {
+                return Err(PageError::OverlappingCells(cell_idx));
+            }
+        }
+        Ok(())
+    }
+
+    // Builds a page holding the given cells, which must be sorted by key
+    pub fn from_cells(
+        page_type: PageType,
+        cells: Vec<DBCell>,
+        right_pointer: u32,
+    ) -> Result<Self, PageError> {
+        let mut page = Self::new_typed(page_type);
+        page.set_right_pointer(right_pointer);
+        for cell in cells {
+            page.insert_cell(cell)?;
+        }
+        Ok(page)
+    }
+
+    pub fn page_type(&self) -> PageType {
+        PageType::from_u8(self.header.page_type).unwrap_or(PageType::Leaf)
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.page_type().is_leaf()
+    }
+
+    pub fn is_index(&self) -> bool {
+        self.page_type().is_index()
+    }
+
+    pub fn right_pointer(&self) -> u32 {
+        self.header.right_pointer
+    }
+
+    pub fn set_right_pointer(&mut self, right_pointer: u32) {
+        self.header
+            .set_right_pointer(right_pointer, &mut self.data[..PAGE_HEADER_SIZE]);
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.cell_pointer_array.len()
+    }
+
+    pub fn free_space(&self) -> usize {
+        let end_of_ptr_array = PAGE_HEADER_SIZE + self.num_cells() * Self::OFFSET_BYTE_SIZE;
+        self.header.cells_start as usize - end_of_ptr_array
+    }
+
+    fn cell_slice(&self, cell_idx: usize) -> Result<&[u8], PageError> {
+        let pointer = *self
             .cell_pointer_array
-            .iter()
-            .map(|&cell_ptr| DBCell::id_from_slice(&self.data[cell_ptr as usize..]));
-        let keys_res: Result<Vec<u64>, ()> = keys_res_iter.collect();
-        keys_res.map_err(|_| PageError::CorruptData)
+            .get(cell_idx)
+            .ok_or(PageError::CorruptData)? as usize;
+        self.data.get(pointer..).ok_or(PageError::CorruptData)
+    }
+
+    pub fn key_at(&self, cell_idx: usize) -> Result<u64, PageError> {
+        DBCell::id_from_slice(self.cell_slice(cell_idx)?).map_err(|_| PageError::CorruptData)
+    }
+
+    pub fn cell_at(&self, cell_idx: usize) -> Result<DBCell, PageError> {
+        self.cell_slice(cell_idx)?
+            .try_into()
+            .map_err(|_| PageError::CorruptData)
     }
 
-    pub fn leaf_find_partition(&self, new_key: u64) -> Result<usize, PageError> {
+    pub fn cells(&self) -> Result<Vec<DBCell>, PageError> {
+        (0..self.num_cells())
+            .map(|cell_idx| self.cell_at(cell_idx))
+            .collect()
+    }
+
+    pub fn get_keys(&self) -> Result<Vec<u64>, PageError> {
+        (0..self.num_cells())
+            .map(|cell_idx| self.key_at(cell_idx))
+            .collect()
+    }
+
+    // Index of the first cell whose key is not smaller than `key`
+    pub fn find_cell(&self, key: u64) -> Result<usize, PageError> {
         let keys = self.get_keys()?;
-        let partition_key = keys.partition_point(|&key| key < new_key);
+        Ok(keys.partition_point(|&cell_key| cell_key < key))
+    }
 
-        if partition_key >= keys.len() {
-            return Ok(PAGE_SIZE);
+    // Index of the first cell that is not ordered before `cell`. The same as `find_cell` except in
+    // index trees, where cells sharing the key are ordered by value
+    pub fn find_position(&self, cell: &DBCell) -> Result<usize, PageError> {
+        if !self.is_index() {
+            return self.find_cell(cell.id);
         }
+        let page_type = self.page_type();
+        let cells = self.cells()?;
+        Ok(cells
+            .partition_point(|page_cell| page_type.sort_key(page_cell) < page_type.sort_key(cell)))
+    }
 
-        let partition_point = self.cell_pointer_array[partition_key];
+    // Child page to descend into when looking for `key` in an interior page. In index trees this
+    // is the leftmost child that may hold the key
+    pub fn child_for_key(&self, key: u64) -> Result<(usize, u32), PageError> {
+        let cell_idx = self.find_cell(key)?;
+        Ok((cell_idx, self.child_at(cell_idx)?))
+    }
 
-        Ok(partition_point as usize)
+    // Child page to descend into when inserting `cell` into an interior page
+    pub fn child_for_cell(&self, cell: &DBCell) -> Result<(usize, u32), PageError> {
+        let cell_idx = self.find_position(cell)?;
+        Ok((cell_idx, self.child_at(cell_idx)?))
     }
 
-    pub fn insert<T>(
-        &mut self,
-        insert_cursor: &mut DBCursor,
-        key: u64,
-        value: &T,
-    ) -> Result<(), PageError>
+    // Child pointers of an interior page, indexed 0..=num_cells where the last one is the right
+    // pointer
+    pub fn child_at(&self, child_idx: usize) -> Result<u32, PageError> {
+        if child_idx == self.num_cells() {
+            Ok(self.right_pointer())
+        } else {
+            Ok(self.cell_at(child_idx)?.left_child)
+        }
+    }
+
+    pub fn set_child_at(&mut self, child_idx: usize, child: u32) -> Result<(), PageError> {
+        if child_idx == self.num_cells() {
+            self.set_right_pointer(child);
+            return Ok(());
+        }
+        let cell = self.cell_at(child_idx)?;
+        let cell_end = self.cell_pointer_array[child_idx] as usize + cell.encoded_size();
+        self.data[cell_end - DBCell::LEFT_CHILD_SIZE..cell_end]
+            .copy_from_slice(&child.to_be_bytes());
+        Ok(())
+    }
+
+    pub fn insert<T>(&mut self, key: u64, value: &T) -> Result<(), PageError>
     where
         T: TryInto<Box<[u8]>, Error = ()> + Clone,
     {
-        if self.header.cells_start == 0 {
-            Err(PageError::PageFull)?
+        let cell = DBCell::new(key, (*value).clone()).map_err(|_| PageError::InsertError)?;
+        self.insert_cell(cell)
+    }
+
+    // Cells of index trees may share a key with others, as long as their values differ
+    pub fn insert_cell(&mut self, cell: DBCell) -> Result<(), PageError> {
+        let key = cell.id;
+        let insert_pos = self.find_position(&cell)?;
+        if insert_pos < self.num_cells() {
+            let duplicate = match self.is_index() {
+                true => self.cell_at(insert_pos)?.value == cell.value,
+                false => true,
+            };
+            if duplicate && self.key_at(insert_pos)? == key {
+                return Err(PageError::DuplicateKey(key));
+            }
         }
 
         // Check if page has enough space
-        let cell_bytes: Rc<[u8]> = DBCell::new(key, (*value).clone())
-            .map_err(|_| PageError::InsertError)?
-            .try_into()
-            .map_err(|_| PageError::InsertError)?;
-        let old_cells_start = self.header.cells_start as usize;
-        let new_cells_start = old_cells_start - cell_bytes.len();
-        let end_of_ptr_array_after_insert =
-            PAGE_HEADER_SIZE + ((self.header.num_cells as usize) + 1) * Self::OFFSET_BYTE_SIZE;
-
-        if new_cells_start <= end_of_ptr_array_after_insert {
-            self.header
-                .set_cells_start(0, &mut self.data[..PAGE_HEADER_SIZE]);
+        let cell_size = cell.encoded_size();
+        if cell_size + Self::OFFSET_BYTE_SIZE > self.free_space() {
             Err(PageError::PageFull)?
         }
+        let new_cells_start = self.header.cells_start as usize - cell_size;
 
         // ------------------ Insert data into slot ------------------
-        let partition_point = self.leaf_find_partition(key)?;
-        insert_cursor.cell_ptr_pos = partition_point - cell_bytes.len();
-        println!("CURSOR {insert_cursor:?}");
-        println!("CELL BYTES {cell_bytes:?}");
-        // let insert_cell_ptr = self.cell_pointer_array[insert_cursor.cell_ptr_pos] as usize;
+        // Cells are kept sorted by key in the content area, so the new cell goes right before the
+        // first cell with a greater key
+        let partition_point = self
+            .cell_pointer_array
+            .get(insert_pos)
+            .map(|&cell_ptr| cell_ptr as usize)
+            .unwrap_or(PAGE_SIZE);
+        let cell_ptr_pos = partition_point - cell_size;
         // Make room for cell content area
-        let _ = &self.data[new_cells_start..partition_point].rotate_left(cell_bytes.len());
-        // Insert cell in slot
-        let _ =
-            &self.data[insert_cursor.cell_ptr_pos..partition_point].copy_from_slice(&cell_bytes);
+        self.data[new_cells_start..partition_point].rotate_left(cell_size);
+        // Serialize the cell straight into its slot
+        if cell
+            .encode_into(&mut self.data[cell_ptr_pos..partition_point])
+            .is_err()
+        {
+            self.data[new_cells_start..partition_point].rotate_right(cell_size);
+            return Err(PageError::InsertError);
+        }
 
         // Update header
         self.header
@@ -237,40 +467,64 @@ impl Page {
         // Update cell pointer array
         self.cell_pointer_array.update_pointer_array_after_insert(
             &mut self.data[PAGE_HEADER_SIZE..],
-            insert_cursor.cell_ptr_pos as u16,
-            cell_bytes.len() as u16,
+            insert_pos,
+            cell_ptr_pos as u16,
+            cell_size as u16,
         );
 
-        println!(
-            "AAAAAAAAA {:?}",
-            self.cell_pointer_array
-                .iter()
-                .map(|elem| DBCell::id_from_slice(&self.data[*elem as usize..]))
-                .collect::<Vec<_>>()
-        );
-        println!("{:?}", &self.data[PAGE_SIZE - 100..]);
-        // println!("CELL POINTER ARRAY: {:?}", self.cell_pointer_array);
-
         Ok(())
     }
 
-    pub fn deserialize_cells(&self) -> Result<Vec<Row>, PageError> {
-        let pointer_bytes = &self.cell_pointer_array;
-
-        let mut rows_vec: Vec<Row> = Vec::new();
-        for pointer in pointer_bytes.iter().map(|x| *x as usize) {
-            let cell: DBCell = self
-                .data
-                .get(pointer..)
-                .ok_or(PageError::CorruptData)?
-                .try_into()
-                .map_err(|_| PageError::CorruptData)?;
-
-            let curr_row = Row::try_from(&*cell.value).unwrap();
-            rows_vec.push(curr_row);
+    // Removes the cell with the given key, returning whether it was present
+    pub fn delete(&mut self, key: u64) -> Result<bool, PageError> {
+        self.delete_cell(&DBCell::new_interior(key, 0))
+    }
+
+    // Removes the cell ordered at the same place as `cell`, which in index trees means one with
+    // the same key and value. Returns whether it was present
+    pub fn delete_cell(&mut self, cell: &DBCell) -> Result<bool, PageError> {
+        let page_type = self.page_type();
+        let cell_idx = self.find_position(cell)?;
+        if cell_idx == self.num_cells()
+            || page_type.sort_key(&self.cell_at(cell_idx)?) != page_type.sort_key(cell)
+        {
+            return Ok(false);
+        }
+
+        let mut cells = self.cells()?;
+        cells.remove(cell_idx);
+        *self = Self::from_cells(self.page_type(), cells, self.right_pointer())?;
+        Ok(true)
+    }
+
+    // Writes `cell` over the one with the same key when both take up the same number of bytes,
+    // which keeps every other cell where it is. Returns whether it did
+    pub fn update_same_size(&mut self, cell: &DBCell) -> Result<bool, PageError> {
+        let cell_idx = self.find_cell(cell.id)?;
+        if cell_idx == self.num_cells() || self.key_at(cell_idx)? != cell.id {
+            return Ok(false);
+        }
+        let cell_start = self.cell_pointer_array[cell_idx] as usize;
+        let cell_size =
+            DBCell::size_from_slice(&self.data[cell_start..]).ok_or(PageError::CorruptData)?;
+        if cell_size != cell.encoded_size() {
+            return Ok(false);
         }
 
-        Ok(rows_vec)
+        cell.encode_into(&mut self.data[cell_start..cell_start + cell_size])
+            .map_err(|_| PageError::InsertError)?;
+        Ok(true)
+    }
+
+    pub fn row_at(&self, cell_idx: usize) -> Result<Row, PageError> {
+        let cell = self.cell_at(cell_idx)?;
+        Row::try_from(&*cell.value).map_err(|_| PageError::CorruptData)
+    }
+
+    pub fn deserialize_cells(&self) -> Result<Vec<Row>, PageError> {
+        (0..self.num_cells())
+            .map(|cell_idx| self.row_at(cell_idx))
+            .collect()
     }
 }
 
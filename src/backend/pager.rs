@@ -1,110 +1,1495 @@
-use std::cell::RefCell;
-use std::fs::File;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::io;
-use std::io::{Seek, SeekFrom, Write};
-use std::rc::Rc;
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use thiserror::Error;
 
-use super::cursor::DBCursor;
-use super::page::{Page, PageError, PAGE_SIZE};
-
-const TABLE_MAX_PAGES: usize = 100;
+use super::db_cell::DBCell;
+use super::header::{DatabaseHeader, HeaderError};
+use super::key;
+use super::page::{Page, PageError, PageType, PAGE_SIZE};
+use super::row::SQLType;
+use super::shared_cache::SharedPageCache;
+use super::storage::{LockKind, Storage};
+use crate::metrics::{Histogram, Stopwatch};
 
 #[derive(Error, Debug)]
 pub enum PagerError {
     #[error("Could not insert row in page. The following error ocurred during insertion: {0}")]
     PageRowInsertError(#[from] PageError),
-    #[error("Cannot insert row. Remaining page capacity is smaller than the row size")]
-    PageFull,
-    #[error("Table full")]
-    TableFull,
     #[error("Page index out of range")]
     PageIdxOutOfRange,
-    #[error("Cache miss")]
-    CacheMiss,
+    #[error("Row of {0} bytes exceeds the maximum cell size of {1} bytes")]
+    RowTooLarge(usize, usize),
+    #[error("Could not access database file: {0}")]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    HeaderError(#[from] HeaderError),
+    #[error("Cannot write to a database opened read-only")]
+    ReadOnly,
+    #[error("database is locked")]
+    Locked,
+    #[error("Database was changed by another process. Unsaved changes were discarded")]
+    ModifiedExternally,
+    #[error("Database file may not grow past its limit of {0} bytes")]
+    FileSizeLimit(u64),
+    #[error("Page {0} is corrupt: {1}")]
+    CorruptPage(u32, PageError),
+    #[error("Tree rooted at page {0} is not an index")]
+    NotAnIndex(u32),
+}
+
+// New pages added to a B-tree by a single insert, used to keep table statistics current
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeGrowth {
+    pub leaf_pages: u32,
+    pub interior_pages: u32,
+    pub depth: u32,
+}
+
+impl TreeGrowth {
+    fn record_page(&mut self, page_type: PageType) {
+        match page_type {
+            page_type if page_type.is_leaf() => self.leaf_pages += 1,
+            _ => self.interior_pages += 1,
+        }
+    }
+}
+
+// Counters of the work done by the pager since the database was opened
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PagerMetrics {
+    pub pages_read: u64,
+    pub pages_written: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub cache_evictions: u64,
+    pub leaf_splits: u64,
+    pub interior_splits: u64,
+    // Rows of tables, leaving out the catalog
+    pub rows_read: u64,
+    pub rows_written: u64,
+    pub flush_latency: Histogram,
 }
 
+// Findings of an integrity check, gathered over every tree it walks
+#[derive(Debug, Default)]
+pub struct IntegrityCheck {
+    pub problems: Vec<String>,
+    pub pages_seen: HashSet<u32>,
+}
+
+// When a statement that writes takes the exclusive lock on the database file. Every statement runs
+// in its own transaction, which ends when its changes are flushed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteLock {
+    // Only while flushing, so other processes can read until then. The statement fails at the
+    // end, with its changes left unwritten, if they still hold a lock
+    #[default]
+    Deferred,
+    // Before the statement runs, so it fails early instead of after making any changes
+    Immediate,
+}
+
+impl FromStr for WriteLock {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "deferred" => Ok(WriteLock::Deferred),
+            "immediate" => Ok(WriteLock::Immediate),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for WriteLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteLock::Deferred => write!(f, "deferred"),
+            WriteLock::Immediate => write!(f, "immediate"),
+        }
+    }
+}
+
+// Limits on how much a database may grow, so that an application embedding it can keep a runaway
+// client from filling the disk. None means no limit. They are checked before a row is inserted or
+// a table created, so a statement breaking them fails without changing anything
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quotas {
+    pub max_file_size: Option<u64>,
+    pub max_table_pages: Option<u32>,
+    pub max_row_size: Option<usize>,
+}
+
+// Most pages the pager keeps in memory, either as a number of pages or as a number of KiB. As in
+// SQLite, a negative number in text means KiB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheSize {
+    Pages(u32),
+    Kibibytes(u32),
+}
+
+impl CacheSize {
+    pub fn pages(self) -> usize {
+        match self {
+            CacheSize::Pages(pages) => pages as usize,
+            CacheSize::Kibibytes(kibibytes) => kibibytes as usize * 1024 / PAGE_SIZE,
+        }
+    }
+}
+
+impl Default for CacheSize {
+    fn default() -> Self {
+        CacheSize::Kibibytes(8192)
+    }
+}
+
+impl FromStr for CacheSize {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('-') {
+            Some(kibibytes) => kibibytes.parse().map(CacheSize::Kibibytes).map_err(|_| ()),
+            None => s.parse().map(CacheSize::Pages).map_err(|_| ()),
+        }
+    }
+}
+
+impl fmt::Display for CacheSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheSize::Pages(pages) => write!(f, "{}", pages),
+            CacheSize::Kibibytes(kibibytes) => write!(f, "-{}", kibibytes),
+        }
+    }
+}
+
+// Owns the database storage. Page 0 holds the database header and every other page belongs to some
+// B-tree. Pages are read lazily and written back on flush
 #[derive(Debug)]
 pub struct Pager {
-    pages_cache: [Option<Page>; TABLE_MAX_PAGES],
-    file_ref: Rc<RefCell<File>>,
+    storage: Box<dyn Storage>,
+    header: DatabaseHeader,
+    header_dirty: bool,
+    // Pages are shared with the cache of other pagers on the same file, and copied when changed
+    pages_cache: HashMap<u32, Arc<Page>>,
+    dirty_pages: BTreeSet<u32>,
+    cache_size: CacheSize,
+    // When each cached page was last used, counted in page accesses
+    last_used: HashMap<u32, u64>,
+    clock: u64,
+    metrics: PagerMetrics,
+    write_lock: WriteLock,
+    // Read pages without taking the shared lock, so reads never wait for writers in other
+    // processes but may see a flush halfway through
+    read_uncommitted: bool,
+    // Whether the exclusive lock has been held since the current statement began
+    write_locked: bool,
+    // Refuse any change to the pages, whatever the statement being run
+    read_only: bool,
+    quotas: Quotas,
+    // Most bytes the cached pages may take up, on top of the cache size. Pages changed by the
+    // transaction stay cached past it, as there is no journal to spill them to
+    soft_heap_limit: Option<usize>,
+    // Check every cell of the pages read from the file, not just their headers
+    paranoid: bool,
+    // What the statement being run changed, so it can be undone on its own inside a transaction
+    savepoint: Option<Savepoint>,
+    // Pages of the file that other read-only pagers on it have loaded
+    shared_cache: Option<Arc<SharedPageCache>>,
+}
+
+// The header as it was when a statement began, and the pages the statement has changed since.
+// Pages an earlier statement of the transaction had changed keep their contents from before, and
+// those that were unchanged have none as they can be read from the file again
+#[derive(Debug)]
+struct Savepoint {
+    header: DatabaseHeader,
+    header_dirty: bool,
+    pages: HashMap<u32, Option<Arc<Page>>>,
 }
 
 impl Pager {
-    pub fn new(file: Rc<RefCell<File>>) -> Pager {
-        const INIT_NONE: Option<Page> = None;
-        let pages_cache: [Option<Page>; TABLE_MAX_PAGES] = [INIT_NONE; TABLE_MAX_PAGES];
+    // Reads the header of an existing database, or lays out an empty one (header page plus an
+    // empty catalog root) when the file is new and may be written
+    pub fn open(mut storage: Box<dyn Storage>, read_only: bool) -> Result<Pager, PagerError> {
+        if storage.size()? == 0 {
+            if read_only {
+                return Err(HeaderError::NotADatabase.into());
+            }
+            let mut pager = Self {
+                storage,
+                header: DatabaseHeader {
+                    page_count: 1,
+                    catalog_root: 0,
+                    next_table_id: 1,
+                    change_counter: 0,
+                    user_version: 0,
+                    schema_cookie: 0,
+                    first_free_page: 0,
+                },
+                header_dirty: true,
+                pages_cache: HashMap::new(),
+                dirty_pages: BTreeSet::new(),
+                cache_size: CacheSize::default(),
+                last_used: HashMap::new(),
+                clock: 0,
+                metrics: PagerMetrics::default(),
+                write_lock: WriteLock::default(),
+                read_uncommitted: false,
+                write_locked: false,
+                read_only,
+                quotas: Quotas::default(),
+                soft_heap_limit: None,
+                paranoid: false,
+                savepoint: None,
+                shared_cache: None,
+            };
+            pager.header.catalog_root = pager.allocate_page(PageType::Leaf)?;
+            pager.flush()?;
+            return Ok(pager);
+        }
+
+        let mut header_bytes = [0; PAGE_SIZE];
+        lock(storage.as_mut(), LockKind::Shared)?;
+        let read = storage.read_exact_at(&mut header_bytes, 0);
+        storage.unlock()?;
+        read.map_err(|_| HeaderError::NotADatabase)?;
+
+        Ok(Self {
+            storage,
+            header: DatabaseHeader::read_from_slice(&header_bytes)?,
+            header_dirty: false,
+            pages_cache: HashMap::new(),
+            dirty_pages: BTreeSet::new(),
+            cache_size: CacheSize::default(),
+            last_used: HashMap::new(),
+            clock: 0,
+            metrics: PagerMetrics::default(),
+            write_lock: WriteLock::default(),
+            read_uncommitted: false,
+            write_locked: false,
+            read_only,
+            quotas: Quotas::default(),
+            soft_heap_limit: None,
+            paranoid: false,
+            savepoint: None,
+            shared_cache: None,
+        })
+    }
+
+    // Looks up pages in a cache shared with other pagers before reading them from the file, and
+    // adds those it reads. Only pagers that never write may share one
+    pub fn share_cache(&mut self, shared_cache: Arc<SharedPageCache>) {
+        self.shared_cache = Some(shared_cache);
+    }
+
+    pub fn catalog_root(&self) -> u32 {
+        self.header.catalog_root
+    }
+
+    pub fn metrics(&self) -> PagerMetrics {
+        self.metrics
+    }
 
-        Self {
-            pages_cache,
-            file_ref: file,
+    pub fn record_rows_read(&mut self, rows: u64) {
+        self.metrics.rows_read += rows;
+    }
+
+    pub fn record_row_written(&mut self) {
+        self.metrics.rows_written += 1;
+    }
+
+    pub fn write_lock(&self) -> WriteLock {
+        self.write_lock
+    }
+
+    pub fn set_write_lock(&mut self, write_lock: WriteLock) {
+        self.write_lock = write_lock;
+    }
+
+    pub fn cache_size(&self) -> CacheSize {
+        self.cache_size
+    }
+
+    // Takes effect right away, dropping clean pages if the cache holds more than the new size
+    pub fn set_cache_size(&mut self, cache_size: CacheSize) {
+        self.cache_size = cache_size;
+        self.evict_clean_pages(self.cache_capacity());
+    }
+
+    pub fn soft_heap_limit(&self) -> Option<usize> {
+        self.soft_heap_limit
+    }
+
+    pub fn set_soft_heap_limit(&mut self, soft_heap_limit: Option<usize>) {
+        self.soft_heap_limit = soft_heap_limit;
+        self.evict_clean_pages(self.cache_capacity());
+    }
+
+    // Approximate bytes taken up by the cached pages
+    pub fn memory_used(&self) -> usize {
+        self.pages_cache.len() * mem::size_of::<Page>()
+    }
+
+    // Most pages kept cached, going by both the cache size and the soft heap limit
+    fn cache_capacity(&self) -> usize {
+        let pages = self.cache_size.pages();
+        match self.soft_heap_limit {
+            Some(limit) => pages.min(limit / mem::size_of::<Page>()),
+            None => pages,
         }
     }
 
-    pub fn insert<T>(&mut self, cursor: &mut DBCursor, key: u64, value: &T) -> Result<(), PagerError>
-    where
-        T: TryInto<Box<[u8]>, Error = ()> + Clone,
-    {
-        let page_option = self
-            .pages_cache
-            .get_mut(cursor.page_num as usize)
-            .ok_or_else(|| PagerError::PageIdxOutOfRange)?;
+    pub fn read_uncommitted(&self) -> bool {
+        self.read_uncommitted
+    }
+
+    pub fn set_read_uncommitted(&mut self, read_uncommitted: bool) {
+        self.read_uncommitted = read_uncommitted;
+    }
 
-        if let Some(page) = page_option.as_mut() {
-            match page.insert(cursor, key, value) {
-                Ok(()) => return Ok(()),
-                Err(err) => panic!("Error while inserting record on page: {err}"),
+    pub fn paranoid(&self) -> bool {
+        self.paranoid
+    }
+
+    // Pages cached before turning it on are dropped, unless changed, to be checked when next read
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+        if paranoid {
+            self.evict_clean_pages(0);
+        }
+    }
+
+    pub fn quotas(&self) -> Quotas {
+        self.quotas
+    }
+
+    pub fn set_quotas(&mut self, quotas: Quotas) {
+        self.quotas = quotas;
+    }
+
+    // Fails when inserting `key` into the tree rooted at `root`, plus allocating `extra_pages`
+    // more, could take the file past its size limit. Every page on the way down to the leaf may
+    // split, and so may the root
+    pub fn check_file_size(
+        &mut self,
+        root: u32,
+        key: u64,
+        extra_pages: u32,
+    ) -> Result<(), PagerError> {
+        let Some(max_file_size) = self.quotas.max_file_size else {
+            return Ok(());
+        };
+        let mut new_pages = extra_pages + 1;
+        let mut page_num = root;
+        loop {
+            new_pages += 1;
+            let page = self.page(page_num)?;
+            if page.is_leaf() {
+                break;
             }
-        } else {
-            self.new_page(cursor.page_num as usize);
-            return self.insert(cursor, key, value);
+            page_num = page.child_for_key(key)?.1;
+        }
+
+        let file_size = (self.header.page_count + new_pages) as u64 * PAGE_SIZE as u64;
+        match file_size > max_file_size {
+            true => Err(PagerError::FileSizeLimit(max_file_size)),
+            false => Ok(()),
+        }
+    }
+
+    // Called before a statement that writes. Takes the exclusive lock right away under the
+    // immediate write lock
+    pub fn begin_write(&mut self) -> Result<(), PagerError> {
+        if self.read_only {
+            return Err(PagerError::ReadOnly);
+        }
+        if self.write_lock == WriteLock::Immediate && !self.write_locked {
+            lock(self.storage.as_mut(), LockKind::Exclusive)?;
+            self.write_locked = true;
         }
+        Ok(())
     }
 
-    pub fn new_page(&mut self, page_idx: usize) -> Result<(), PagerError> {
-        let new_page = Page::new();
-        if page_idx >= TABLE_MAX_PAGES {
-            return Err(PagerError::TableFull);
+    // Releases the lock taken by `begin_write` when the statement fails before flushing
+    pub fn end_write(&mut self) -> Result<(), PagerError> {
+        if mem::take(&mut self.write_locked) {
+            self.storage.unlock()?;
+        }
+        Ok(())
+    }
+
+    // Forgets every change that has not been flushed, going back to the pages and header in the
+    // file
+    pub fn rollback(&mut self) -> Result<(), PagerError> {
+        self.savepoint = None;
+        for page_num in mem::take(&mut self.dirty_pages) {
+            self.pages_cache.remove(&page_num);
+            self.last_used.remove(&page_num);
+        }
+        if mem::take(&mut self.header_dirty) {
+            self.header = self.read_header()?;
+        }
+        self.end_write()
+    }
+
+    // Starts keeping what the statement about to run changes, replacing what the previous one kept
+    pub fn begin_statement(&mut self) {
+        self.savepoint = Some(Savepoint {
+            header: self.header,
+            header_dirty: self.header_dirty,
+            pages: HashMap::new(),
+        });
+    }
+
+    // Stops keeping the changes of a statement that completed
+    pub fn end_statement(&mut self) {
+        self.savepoint = None;
+    }
+
+    // Undoes the changes of the statement being run, leaving those of earlier statements of the
+    // transaction in place
+    pub fn rollback_statement(&mut self) {
+        let Some(savepoint) = self.savepoint.take() else {
+            return;
         };
-        self.pages_cache[page_idx] = Some(new_page);
+        for (page_num, page) in savepoint.pages {
+            match page {
+                Some(page) => {
+                    self.pages_cache.insert(page_num, page);
+                }
+                None => {
+                    self.pages_cache.remove(&page_num);
+                    self.last_used.remove(&page_num);
+                    self.dirty_pages.remove(&page_num);
+                }
+            }
+        }
+        self.header = savepoint.header;
+        self.header_dirty = savepoint.header_dirty;
+    }
+
+    // Keeps the contents a page had before the statement being run first changes it
+    fn save_page(&mut self, page_num: u32) {
+        if let Some(savepoint) = self.savepoint.as_mut() {
+            savepoint.pages.entry(page_num).or_insert_with(|| {
+                self.dirty_pages
+                    .contains(&page_num)
+                    .then(|| self.pages_cache.get(&page_num).cloned())
+                    .flatten()
+            });
+        }
+    }
+
+    pub fn change_counter(&self) -> u32 {
+        self.header.change_counter
+    }
+
+    // Drops the cached pages if another process changed the file since they were read, which is
+    // told by the change counter in its header. Pages changed but not flushed yet are kept
+    pub fn refresh(&mut self) -> Result<(), PagerError> {
+        if self.has_unsaved_changes() {
+            return Ok(());
+        }
+        let header = self.read_header()?;
+        if header.change_counter != self.header.change_counter {
+            self.header = header;
+            self.clear_cache();
+        }
+        Ok(())
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.dirty_pages.is_empty() || self.header_dirty
+    }
+
+    fn read_header(&mut self) -> Result<DatabaseHeader, PagerError> {
+        let mut bytes = [0; PAGE_SIZE];
+        self.read_page_bytes(0, &mut bytes)?;
+        Ok(DatabaseHeader::read_from_slice(&bytes)?)
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.header.page_count
+    }
+
+    pub fn next_table_id(&mut self) -> Result<u64, PagerError> {
+        self.check_writable()?;
+        let table_id = self.header.next_table_id;
+        self.header.next_table_id += 1;
+        self.header_dirty = true;
+        Ok(table_id)
+    }
+
+    pub fn user_version(&self) -> i32 {
+        self.header.user_version
+    }
 
+    pub fn set_user_version(&mut self, user_version: i32) -> Result<(), PagerError> {
+        self.check_writable()?;
+        self.header.user_version = user_version;
+        self.header_dirty = true;
         Ok(())
     }
 
-    pub fn flush(&mut self, page_idx: usize) -> Result<(), PagerError> {
-        if page_idx >= TABLE_MAX_PAGES {
+    pub fn schema_cookie(&self) -> u32 {
+        self.header.schema_cookie
+    }
+
+    pub fn bump_schema_cookie(&mut self) -> Result<(), PagerError> {
+        self.check_writable()?;
+        self.header.schema_cookie = self.header.schema_cookie.wrapping_add(1);
+        self.header_dirty = true;
+        Ok(())
+    }
+
+    fn check_writable(&self) -> Result<(), PagerError> {
+        match self.read_only {
+            true => Err(PagerError::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    fn load_page(&mut self, page_num: u32) -> Result<(), PagerError> {
+        if page_num == 0 || page_num >= self.header.page_count {
             return Err(PagerError::PageIdxOutOfRange);
         }
+        if self.pages_cache.contains_key(&page_num) {
+            self.metrics.cache_hits += 1;
+            self.touch(page_num);
+            return Ok(());
+        }
+        let change_counter = self.header.change_counter;
+        let shared_page = self
+            .shared_cache
+            .as_ref()
+            .and_then(|shared_cache| shared_cache.get(page_num, change_counter));
+        let page = match shared_page {
+            Some(page) => {
+                self.metrics.cache_hits += 1;
+                page
+            }
+            None => {
+                self.metrics.cache_misses += 1;
+                let mut bytes = [0; PAGE_SIZE];
+                self.read_page_bytes(page_num, &mut bytes)?;
+                self.metrics.pages_read += 1;
+                let page = Arc::new(
+                    Page::new_from_read(&bytes, self.paranoid)
+                        .map_err(|err| PagerError::CorruptPage(page_num, err))?,
+                );
+                match &self.shared_cache {
+                    Some(shared_cache) => shared_cache.insert(page_num, change_counter, page),
+                    None => page,
+                }
+            }
+        };
+
+        // A full cache makes room for an eighth of its size at once, so scans bigger than the
+        // cache do not pick a page to evict on every read
+        let capacity = self.cache_capacity();
+        if self.pages_cache.len() >= capacity {
+            self.evict_clean_pages(capacity.saturating_sub(capacity / 8 + 1));
+        }
+        self.pages_cache.insert(page_num, page);
+        self.touch(page_num);
 
-        let mut file = self.file_ref.borrow_mut();
-        let _ = file.seek(SeekFrom::Start((page_idx * PAGE_SIZE) as u64));
-        let page_to_write = self.pages_cache.get(page_idx).unwrap().as_ref().unwrap().clone();
-        let bytes: [u8; PAGE_SIZE] = page_to_write.into();
-        let _ = file.write_all(&bytes);
         Ok(())
     }
 
-    pub fn flush_all(&mut self) -> Result<(), PagerError> {
-        let flush_indices: Vec<usize> = self
+    fn clear_cache(&mut self) {
+        self.pages_cache.clear();
+        self.last_used.clear();
+    }
+
+    fn touch(&mut self, page_num: u32) {
+        self.clock += 1;
+        self.last_used.insert(page_num, self.clock);
+    }
+
+    // Drops the least recently used pages until at most `limit` are cached. Dirty pages stay
+    // until they are flushed, even past the limit
+    fn evict_clean_pages(&mut self, limit: usize) {
+        if self.pages_cache.len() <= limit {
+            return;
+        }
+        let mut clean_pages: Vec<(u64, u32)> = self
             .pages_cache
-            .iter()
-            .enumerate()
-            .filter(|x| x.1.is_some())
-            .map(|x| x.0)
+            .keys()
+            .filter(|page_num| !self.dirty_pages.contains(page_num))
+            .map(|&page_num| {
+                (
+                    self.last_used.get(&page_num).copied().unwrap_or(0),
+                    page_num,
+                )
+            })
             .collect();
+        clean_pages.sort_unstable();
+
+        let excess = self.pages_cache.len() - limit;
+        for (_, page_num) in clean_pages.into_iter().take(excess) {
+            self.pages_cache.remove(&page_num);
+            self.last_used.remove(&page_num);
+            self.metrics.cache_evictions += 1;
+        }
+    }
+
+    fn read_page_bytes(
+        &mut self,
+        page_num: u32,
+        bytes: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), PagerError> {
+        // Converting the lock would give up the exclusive one held for the statement
+        let locked = !self.write_locked && !self.read_uncommitted;
+        if locked {
+            lock(self.storage.as_mut(), LockKind::Shared)?;
+        }
+        let read = self
+            .storage
+            .read_exact_at(bytes, page_num as u64 * PAGE_SIZE as u64);
+        if locked {
+            self.storage.unlock()?;
+        }
+        Ok(read?)
+    }
+
+    pub fn page(&mut self, page_num: u32) -> Result<&Page, PagerError> {
+        self.load_page(page_num)?;
+        Ok(&self.pages_cache[&page_num])
+    }
+
+    fn page_mut(&mut self, page_num: u32) -> Result<&mut Page, PagerError> {
+        self.check_writable()?;
+        self.load_page(page_num)?;
+        self.save_page(page_num);
+        self.dirty_pages.insert(page_num);
+        Ok(Arc::make_mut(self.pages_cache.get_mut(&page_num).unwrap()))
+    }
+
+    // Takes a page off the chain of free pages, or adds one at the end of the file when there is
+    // none
+    pub fn allocate_page(&mut self, page_type: PageType) -> Result<u32, PagerError> {
+        self.check_writable()?;
+        let page_num = match self.header.first_free_page {
+            0 => {
+                self.header.page_count += 1;
+                self.header.page_count - 1
+            }
+            page_num => {
+                self.header.first_free_page = self.page(page_num)?.right_pointer();
+                page_num
+            }
+        };
+        self.header_dirty = true;
+        self.save_page(page_num);
+        self.pages_cache
+            .insert(page_num, Arc::new(Page::new_typed(page_type)));
+        self.dirty_pages.insert(page_num);
+        self.touch(page_num);
+
+        Ok(page_num)
+    }
+
+    // Inserts a row into the B-tree rooted at `root`, splitting pages on the way back up as
+    // needed. The root keeps its page number, so references to the tree stay valid.
+    pub fn insert<T>(&mut self, root: u32, key: u64, value: &T) -> Result<TreeGrowth, PagerError>
+    where
+        T: TryInto<Box<[u8]>, Error = ()> + Clone,
+    {
+        let cell = DBCell::new(key, (*value).clone()).map_err(|_| PageError::InsertError)?;
+        self.insert_cell(root, cell)
+    }
+
+    // Inserts a key made by the `key` module into the index tree rooted at `root`. Keys are
+    // unique, so repeated values must be told apart by the rowid or another column at their end
+    pub fn insert_key(&mut self, root: u32, key: &[u8]) -> Result<TreeGrowth, PagerError> {
+        if !self.page(root)?.is_index() {
+            return Err(PagerError::NotAnIndex(root));
+        }
+        self.insert_cell(root, DBCell::new_keyed(key))
+    }
 
-        for i in flush_indices {
-            match self.flush(i) {
-                Ok(()) => {}
-                Err(PagerError::PageIdxOutOfRange) => {}
-                _ => unreachable!(),
+    fn insert_cell(&mut self, root: u32, cell: DBCell) -> Result<TreeGrowth, PagerError> {
+        if cell.encoded_size() > Page::MAX_CELL_SIZE {
+            return Err(PagerError::RowTooLarge(
+                cell.encoded_size(),
+                Page::MAX_CELL_SIZE,
+            ));
+        }
+
+        let mut growth = TreeGrowth::default();
+        if let Some((mut separator, right_page)) = self.insert_into(root, cell, &mut growth)? {
+            // The old root contents move to a new page of the same type and the root becomes the
+            // interior page above both halves
+            let old_root_type = self.page(root)?.page_type();
+            let left_page = self.allocate_page(old_root_type)?;
+            separator.left_child = left_page;
+            let new_root = Page::from_cells(old_root_type.interior(), vec![separator], right_page)?;
+            let old_root = mem::replace(self.page_mut(root)?, new_root);
+            *self.page_mut(left_page)? = old_root;
+
+            growth.interior_pages += 1;
+            growth.depth += 1;
+        }
+
+        Ok(growth)
+    }
+
+    // Returns the separator and new right sibling when `page_num` had to be split. The separator
+    // is an interior cell whose child is left for the caller to set
+    fn insert_into(
+        &mut self,
+        page_num: u32,
+        cell: DBCell,
+        growth: &mut TreeGrowth,
+    ) -> Result<Option<(DBCell, u32)>, PagerError> {
+        let page = self.page(page_num)?;
+        let page_type = page.page_type();
+
+        if page.is_leaf() {
+            return match self.page_mut(page_num)?.insert_cell(cell.clone()) {
+                Ok(()) => Ok(None),
+                Err(PageError::PageFull) => {
+                    growth.record_page(page_type);
+                    self.split_leaf(page_num, cell).map(Some)
+                }
+                Err(err) => Err(err.into()),
+            };
+        }
+
+        let (child_idx, child) = page.child_for_cell(&cell)?;
+        let Some((mut separator, new_child)) = self.insert_into(child, cell, growth)? else {
+            return Ok(None);
+        };
+
+        // `child` now holds the keys up to the separator and `new_child` the rest of its range
+        let page = self.page_mut(page_num)?;
+        page.set_child_at(child_idx, new_child)?;
+        separator.left_child = child;
+        match page.insert_cell(separator.clone()) {
+            Ok(()) => Ok(None),
+            Err(PageError::PageFull) => {
+                growth.record_page(page_type);
+                self.split_interior(page_num, separator).map(Some)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn split_leaf(&mut self, page_num: u32, new_cell: DBCell) -> Result<(DBCell, u32), PagerError> {
+        self.metrics.leaf_splits += 1;
+        let page = self.page(page_num)?;
+        let page_type = page.page_type();
+        let mut cells = page.cells()?;
+        let insert_pos =
+            cells.partition_point(|cell| page_type.sort_key(cell) < page_type.sort_key(&new_cell));
+        let appending = insert_pos == cells.len();
+        cells.insert(insert_pos, new_cell);
+
+        // Appending past the last key (e.g. increasing rowids) leaves the full page untouched so
+        // sequential inserts produce densely packed leaves
+        let split_at = if appending {
+            cells.len() - 1
+        } else {
+            let total_size: usize = cells.iter().map(DBCell::encoded_size).sum();
+            let mut left_size = 0;
+            cells
+                .iter()
+                .position(|cell| {
+                    left_size += cell.encoded_size();
+                    left_size > total_size / 2
+                })
+                .unwrap_or(cells.len() - 1)
+                .max(1)
+        };
+
+        let right_cells = cells.split_off(split_at);
+        // Index trees keep the value in the separator, as cells sharing its key may end up on
+        // either side
+        let last_cell = cells.last().ok_or(PageError::CorruptData)?;
+        let mut separator = DBCell::new_interior(last_cell.id, 0);
+        if page_type.is_index() {
+            separator.value = last_cell.value.clone();
+        }
+
+        let right_page = self.allocate_page(page_type)?;
+        *self.page_mut(right_page)? = Page::from_cells(page_type, right_cells, 0)?;
+        *self.page_mut(page_num)? = Page::from_cells(page_type, cells, 0)?;
+
+        Ok((separator, right_page))
+    }
+
+    fn split_interior(
+        &mut self,
+        page_num: u32,
+        new_cell: DBCell,
+    ) -> Result<(DBCell, u32), PagerError> {
+        self.metrics.interior_splits += 1;
+        let page = self.page(page_num)?;
+        let page_type = page.page_type();
+        let right_pointer = page.right_pointer();
+        let mut cells = page.cells()?;
+        let insert_pos =
+            cells.partition_point(|cell| page_type.sort_key(cell) < page_type.sort_key(&new_cell));
+        cells.insert(insert_pos, new_cell);
+
+        // The middle cell moves up: its key becomes the separator and its child the right pointer
+        // of the left half
+        let mut right_cells = cells.split_off(cells.len() / 2);
+        let middle = right_cells.remove(0);
+
+        let right_page = self.allocate_page(page_type)?;
+        *self.page_mut(right_page)? = Page::from_cells(page_type, right_cells, right_pointer)?;
+        *self.page_mut(page_num)? = Page::from_cells(page_type, cells, middle.left_child)?;
+
+        Ok((middle, right_page))
+    }
+
+    // Upper bound on the keys of the tree rooted at `root`: the last key of its rightmost leaf, or
+    // the last separator on the way there when deletes have emptied that leaf
+    pub fn max_key(&mut self, root: u32) -> Result<Option<u64>, PagerError> {
+        let mut page_num = root;
+        let mut max_key = None;
+        loop {
+            let page = self.page(page_num)?;
+            if let Some(last_idx) = page.num_cells().checked_sub(1) {
+                max_key = max_key.max(Some(page.key_at(last_idx)?));
+            }
+            if page.is_leaf() {
+                return Ok(max_key);
+            }
+            page_num = page.right_pointer();
+        }
+    }
+
+    // Removes the cell with the given key from the tree. Pages are not merged, so a leaf may be
+    // left empty
+    pub fn delete(&mut self, root: u32, key: u64) -> Result<bool, PagerError> {
+        let mut page_num = root;
+        while !self.page(page_num)?.is_leaf() {
+            page_num = self.page(page_num)?.child_for_key(key)?.1;
+        }
+
+        Ok(self.page_mut(page_num)?.delete(key)?)
+    }
+
+    // Replaces the row with the given key by writing over its cell, returning whether it could.
+    // It cannot when there is no such row or the new one takes up a different number of bytes
+    pub fn update<T>(&mut self, root: u32, key: u64, value: &T) -> Result<bool, PagerError>
+    where
+        T: TryInto<Box<[u8]>, Error = ()> + Clone,
+    {
+        let cell = DBCell::new(key, (*value).clone()).map_err(|_| PageError::InsertError)?;
+        let mut page_num = root;
+        while !self.page(page_num)?.is_leaf() {
+            page_num = self.page(page_num)?.child_for_key(key)?.1;
+        }
+
+        Ok(self.page_mut(page_num)?.update_same_size(&cell)?)
+    }
+
+    // Removes a key from the index tree rooted at `root`, returning whether it was present
+    pub fn delete_key(&mut self, root: u32, key: &[u8]) -> Result<bool, PagerError> {
+        if !self.page(root)?.is_index() {
+            return Err(PagerError::NotAnIndex(root));
+        }
+        let cell = DBCell::new_keyed(key);
+        let mut page_num = root;
+        while !self.page(page_num)?.is_leaf() {
+            page_num = self.page(page_num)?.child_for_cell(&cell)?.1;
+        }
+
+        Ok(self.page_mut(page_num)?.delete_cell(&cell)?)
+    }
+
+    // Gives every page of the tree rooted at `root` back for new pages to reuse. Free pages are
+    // empty leaves chained through their right pointers, starting from the header. The lowest are
+    // reused first, which leaves the rest at the end of the file for `truncate` to cut off
+    pub fn free_tree(&mut self, root: u32) -> Result<(), PagerError> {
+        let mut pages = self.tree_pages(root)?;
+        pages.sort_unstable();
+        for page_num in pages.into_iter().rev() {
+            self.free_page(page_num)?;
+        }
+        Ok(())
+    }
+
+    // Empties the tree rooted at `root`, freeing every page but the root, which becomes an empty
+    // leaf of the same kind of tree
+    pub fn clear_tree(&mut self, root: u32) -> Result<(), PagerError> {
+        let leaf_type = match self.page(root)?.is_index() {
+            true => PageType::IndexLeaf,
+            false => PageType::Leaf,
+        };
+        let mut pages = self.tree_pages(root)?;
+        pages.retain(|&page_num| page_num != root);
+        pages.sort_unstable();
+        for page_num in pages.into_iter().rev() {
+            self.free_page(page_num)?;
+        }
+        *self.page_mut(root)? = Page::new_typed(leaf_type);
+        Ok(())
+    }
+
+    fn free_page(&mut self, page_num: u32) -> Result<(), PagerError> {
+        self.check_writable()?;
+        let mut page = Page::new_typed(PageType::Leaf);
+        page.set_right_pointer(self.header.first_free_page);
+        self.header.first_free_page = page_num;
+        self.header_dirty = true;
+        self.save_page(page_num);
+        self.pages_cache.insert(page_num, Arc::new(page));
+        self.dirty_pages.insert(page_num);
+        self.touch(page_num);
+        Ok(())
+    }
+
+    // Pages on the chain of free pages, in the order they are taken off it. A chain looping back
+    // on itself is cut where it does
+    pub fn free_pages(&mut self) -> Result<Vec<u32>, PagerError> {
+        let mut pages = Vec::new();
+        let mut page_num = self.header.first_free_page;
+        while page_num != 0 && !pages.contains(&page_num) {
+            pages.push(page_num);
+            page_num = self.page(page_num)?.right_pointer();
+        }
+        Ok(pages)
+    }
+
+    // Pages of the tree rooted at `root`. Leaves are all at the same depth, so only interior
+    // pages and the first leaf are read
+    pub fn tree_pages(&mut self, root: u32) -> Result<Vec<u32>, PagerError> {
+        let mut level = vec![root];
+        let mut pages = Vec::new();
+        loop {
+            pages.extend(&level);
+            if self.page(level[0])?.is_leaf() {
+                return Ok(pages);
+            }
+            let mut next_level = Vec::new();
+            for page_num in level {
+                let page = self.page(page_num)?;
+                for child_idx in 0..=page.num_cells() {
+                    next_level.push(page.child_at(child_idx)?);
+                }
+            }
+            level = next_level;
+        }
+    }
+
+    pub fn file_size(&mut self) -> Result<u64, PagerError> {
+        Ok(self.storage.size()?)
+    }
+
+    // Drops the pages from `page_count` on, which must not belong to any tree, and shrinks the
+    // file to match. Free pages among them are taken off the chain of free pages
+    pub fn truncate(&mut self, page_count: u32) -> Result<(), PagerError> {
+        self.check_writable()?;
+        if page_count >= self.header.page_count {
+            return Ok(());
+        }
+        let free_pages = self.free_pages()?;
+        if free_pages.iter().any(|&page_num| page_num >= page_count) {
+            self.header.first_free_page = 0;
+            for &page_num in free_pages.iter().rev() {
+                if page_num < page_count {
+                    self.free_page(page_num)?;
+                }
+            }
+        }
+        self.header.page_count = page_count;
+        self.header_dirty = true;
+        self.pages_cache
+            .retain(|&page_num, _| page_num < page_count);
+        self.last_used.retain(|&page_num, _| page_num < page_count);
+        self.dirty_pages.retain(|&page_num| page_num < page_count);
+
+        if !self.write_locked {
+            lock(self.storage.as_mut(), LockKind::Exclusive)?;
+        }
+        let truncated = self
+            .write_dirty_pages()
+            .and_then(|_| Ok(self.storage.set_len(page_count as u64 * PAGE_SIZE as u64)?));
+        self.write_locked = false;
+        self.storage.unlock()?;
+        truncated
+    }
+
+    // Walks the tree rooted at `root`, noting pages that cannot be decoded or are reached twice,
+    // keys out of order or outside the range allowed by their parent, and leaves at different
+    // depths. Returns the number of rows found in its leaves
+    pub fn check_tree(&mut self, root: u32, check: &mut IntegrityCheck) -> u64 {
+        let mut leaf_depth = None;
+        self.check_subtree(root, (None, None), 0, &mut leaf_depth, check)
+    }
+
+    // Walks the chain of free pages, noting pages that are also reached from a tree or lie past
+    // the end of the file
+    pub fn check_free_pages(&mut self, check: &mut IntegrityCheck) {
+        let mut page_num = self.header.first_free_page;
+        while page_num != 0 {
+            if page_num >= self.header.page_count {
+                check.problems.push(format!(
+                    "free page {} is past the end of the file",
+                    page_num
+                ));
+                return;
+            }
+            if !check.pages_seen.insert(page_num) {
+                check
+                    .problems
+                    .push(format!("page {} is referenced more than once", page_num));
+                return;
+            }
+            match self.page(page_num) {
+                Ok(page) => page_num = page.right_pointer(),
+                Err(err) => {
+                    check.problems.push(format!("page {}: {}", page_num, err));
+                    return;
+                }
+            }
+        }
+    }
+
+    // Keys of the subtree must be greater than the lower bound and at most the upper one. Index
+    // trees may repeat keys, so there they only have to be in order and within both bounds
+    fn check_subtree(
+        &mut self,
+        page_num: u32,
+        (lower, upper): (Option<u64>, Option<u64>),
+        depth: usize,
+        leaf_depth: &mut Option<usize>,
+        check: &mut IntegrityCheck,
+    ) -> u64 {
+        if !check.pages_seen.insert(page_num) {
+            check
+                .problems
+                .push(format!("page {} is referenced more than once", page_num));
+            return 0;
+        }
+        let (keys, children, is_index) = match self.page(page_num).and_then(|page| {
+            let keys = page.get_keys()?;
+            let children = match page.is_leaf() {
+                true => Vec::new(),
+                false => (0..=keys.len())
+                    .map(|child_idx| page.child_at(child_idx))
+                    .collect::<Result<_, _>>()?,
             };
+            Ok((keys, children, page.is_index()))
+        }) {
+            Ok(contents) => contents,
+            Err(err) => {
+                check.problems.push(format!("page {}: {}", page_num, err));
+                return 0;
+            }
+        };
+
+        let out_of_order = |pair: &[u64]| match is_index {
+            true => pair[0] > pair[1],
+            false => pair[0] >= pair[1],
+        };
+        if keys.windows(2).any(out_of_order) {
+            check.problems.push(format!(
+                "page {}: keys are not in increasing order",
+                page_num
+            ));
+        }
+        if let Some(key) = keys.iter().find(|&&key| {
+            lower.is_some_and(|lower| key < lower || (key == lower && !is_index))
+                || upper.is_some_and(|upper| key > upper)
+        }) {
+            check.problems.push(format!(
+                "page {}: key {} is outside the range allowed by its parent",
+                page_num, key
+            ));
+        }
+
+        if children.is_empty() {
+            match *leaf_depth {
+                None => *leaf_depth = Some(depth),
+                Some(expected) if expected != depth => check.problems.push(format!(
+                    "page {}: leaf at depth {} while other leaves are at depth {}",
+                    page_num, depth, expected
+                )),
+                Some(_) => {}
+            }
+            return keys.len() as u64;
+        }
+
+        let mut rows = 0;
+        for (idx, child) in children.into_iter().enumerate() {
+            let child_lower = if idx == 0 { lower } else { Some(keys[idx - 1]) };
+            let child_upper = keys.get(idx).copied().or(upper);
+            rows += self.check_subtree(
+                child,
+                (child_lower, child_upper),
+                depth + 1,
+                leaf_depth,
+                check,
+            );
+        }
+        rows
+    }
+
+    // Renders the tree rooted at `root` one page per line, annotated with the number of cells, free
+    // space and range of keys held by each page. Keys of table trees are shown by `show_key`, and
+    // those of index trees as the values they encode
+    pub fn print_tree(
+        &mut self,
+        root: u32,
+        show_key: &dyn Fn(u64) -> String,
+    ) -> Result<String, PagerError> {
+        let mut output = String::new();
+        self.print_subtree(root, show_key, "", "", &mut output)?;
+        Ok(output)
+    }
+
+    fn print_subtree(
+        &mut self,
+        page_num: u32,
+        show_key: &dyn Fn(u64) -> String,
+        prefix: &str,
+        child_prefix: &str,
+        output: &mut String,
+    ) -> Result<(), PagerError> {
+        let page = self.page(page_num)?;
+        let show_cell = |cell: DBCell| match page.is_index() {
+            true => show_index_key(&cell.value),
+            false => show_key(cell.id),
+        };
+        let key_range = match page.num_cells() {
+            0 => String::new(),
+            num_cells => format!(
+                ", keys {}..={}",
+                show_cell(page.cell_at(0)?),
+                show_cell(page.cell_at(num_cells - 1)?)
+            ),
+        };
+        let kind = if page.is_leaf() { "leaf" } else { "interior" };
+        output.push_str(&format!(
+            "{}page {} ({}): {} cells, {} bytes free{}\n",
+            prefix,
+            page_num,
+            kind,
+            page.num_cells(),
+            page.free_space(),
+            key_range
+        ));
+
+        if page.is_leaf() {
+            return Ok(());
+        }
+        let children: Vec<u32> = (0..=page.num_cells())
+            .map(|child_idx| page.child_at(child_idx))
+            .collect::<Result<_, _>>()?;
+        for (idx, child) in children.iter().enumerate() {
+            let (branch, continuation) = if idx + 1 == children.len() {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            self.print_subtree(
+                *child,
+                show_key,
+                &format!("{}{}", child_prefix, branch),
+                &format!("{}{}", child_prefix, continuation),
+                output,
+            )?;
         }
         Ok(())
     }
 
-    pub fn pages(&self) -> impl Iterator<Item = &Option<Page>> {
-        self.pages_cache.iter()
+    // Writes every modified page, and the header if it changed, back to the file. Holds the
+    // exclusive lock meanwhile so no other process reads the file halfway through
+    pub fn flush(&mut self) -> Result<(), PagerError> {
+        if self.dirty_pages.is_empty() && !self.header_dirty {
+            return self.end_write();
+        }
+        if !self.write_locked {
+            lock(self.storage.as_mut(), LockKind::Exclusive)?;
+        }
+        let stopwatch = Stopwatch::start();
+        let written = self.write_dirty_pages();
+        if let Some(elapsed) = stopwatch.elapsed() {
+            self.metrics.flush_latency.record(elapsed);
+        }
+        self.write_locked = false;
+        self.storage.unlock()?;
+        written
+    }
+
+    fn write_dirty_pages(&mut self) -> Result<(), PagerError> {
+        // The changes were made on pages another process has since rewritten, so writing them would
+        // corrupt the file. A new database has no header to check yet
+        if self.storage.size()? > 0 {
+            let header = self.read_header()?;
+            if header.change_counter != self.header.change_counter {
+                self.header = header;
+                self.header_dirty = false;
+                self.clear_cache();
+                self.dirty_pages.clear();
+                self.savepoint = None;
+                return Err(PagerError::ModifiedExternally);
+            }
+        }
+
+        // Pages stay dirty until the flush succeeds, so one that fails halfway writes them all again
+        // the next time
+        for &page_num in &self.dirty_pages {
+            let bytes: [u8; PAGE_SIZE] = Page::clone(&self.pages_cache[&page_num]).into();
+            self.storage
+                .write_all_at(&bytes, page_num as u64 * PAGE_SIZE as u64)?;
+            self.metrics.pages_written += 1;
+        }
+
+        // The header is written last, and the counter only moves once it is, so a failed flush is
+        // not mistaken for a change by another process
+        let mut header = self.header;
+        header.change_counter = header.change_counter.wrapping_add(1);
+        let bytes: [u8; PAGE_SIZE] = (&header).into();
+        self.storage.write_all_at(&bytes, 0)?;
+        self.header = header;
+
+        self.storage.flush()?;
+        self.dirty_pages.clear();
+        self.header_dirty = false;
+        // What the file holds now cannot be undone
+        self.savepoint = None;
+        Ok(())
+    }
+}
+
+// Fails with `PagerError::Locked` instead of waiting when another process holds a conflicting lock
+// The values an index key encodes, in parentheses, with text quoted so values holding commas stay
+// apart
+fn show_index_key(index_key: &[u8]) -> String {
+    let Ok(values) = key::decode_key(index_key) else {
+        return "(malformed key)".to_string();
+    };
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| match value {
+            SQLType::Text(text) => format!("'{}'", text.replace('\'', "''")),
+            value => value.to_string(),
+        })
+        .collect();
+    format!("({})", values.join(", "))
+}
+
+fn lock(storage: &mut dyn Storage, kind: LockKind) -> Result<(), PagerError> {
+    if storage.try_lock(kind)? {
+        Ok(())
+    } else {
+        Err(PagerError::Locked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::io;
+    use std::ops::Bound;
+    use std::rc::Rc;
+
+    use super::{IntegrityCheck, Pager};
+    use crate::backend::cursor::DBCursor;
+    use crate::backend::key;
+    use crate::backend::page::PageType;
+    use crate::backend::row::SQLType;
+    use crate::backend::storage::{MemoryStorage, Storage};
+    use crate::Connection;
+
+    const NUM_KEYS: u64 = 500;
+
+    // Keys long enough for a few hundred of them to take several levels of pages
+    fn index_key(num: u64) -> Box<[u8]> {
+        key::encode_key(&[
+            SQLType::Text(format!("{:04}{}", num, "x".repeat(400))),
+            SQLType::UBigInt(num),
+        ])
+    }
+
+    // Index tree holding every key, inserted out of order
+    fn filled_index() -> (Pager, u32) {
+        let mut pager = Pager::open(Box::new(MemoryStorage::new()), false).unwrap();
+        let root = pager.allocate_page(PageType::IndexLeaf).unwrap();
+        for num in (0..NUM_KEYS).map(|num| num * 7 % NUM_KEYS) {
+            pager.insert_key(root, &index_key(num)).unwrap();
+        }
+        (pager, root)
+    }
+
+    fn keys(pager: &mut Pager, root: u32) -> Vec<Box<[u8]>> {
+        let mut cursor = DBCursor::with_key_bounds(root, Bound::Unbounded, Bound::Unbounded);
+        let mut keys = Vec::new();
+        while let Some(key) = cursor.next_key(pager).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    fn check(pager: &mut Pager, root: u32) -> u64 {
+        let mut check = IntegrityCheck::default();
+        let num_keys = pager.check_tree(root, &mut check);
+        assert!(check.problems.is_empty(), "{:?}", check.problems);
+        num_keys
+    }
+
+    #[test]
+    fn inserted_keys_split_pages_and_stay_in_order() {
+        let (mut pager, root) = filled_index();
+        assert_eq!(check(&mut pager, root), NUM_KEYS);
+        // The root became an interior page over more than one level of pages
+        assert!(!pager.page(root).unwrap().is_leaf());
+        let pages = pager.tree_pages(root).unwrap();
+        assert!(pages.len() > 10, "only {} pages", pages.len());
+        assert!(!pager.page(pages[1]).unwrap().is_leaf());
+
+        let expected: Vec<Box<[u8]>> = (0..NUM_KEYS).map(index_key).collect();
+        assert_eq!(keys(&mut pager, root), expected);
+    }
+
+    #[test]
+    fn keys_are_found_by_prefix_after_splits() {
+        let (mut pager, root) = filled_index();
+        let prefix = key::encode_key(&[SQLType::Text(format!("{:04}{}", 123, "x".repeat(400)))]);
+        let end = key::prefix_end(&prefix).unwrap();
+        let mut cursor =
+            DBCursor::with_key_bounds(root, Bound::Included(&prefix), Bound::Excluded(&end));
+        assert_eq!(cursor.next_key(&mut pager).unwrap(), Some(index_key(123)));
+        assert_eq!(cursor.next_key(&mut pager).unwrap(), None);
+    }
+
+    #[test]
+    fn deleted_keys_empty_the_leaves() {
+        let (mut pager, root) = filled_index();
+        for num in (0..NUM_KEYS).step_by(2) {
+            assert!(pager.delete_key(root, &index_key(num)).unwrap());
+            assert!(!pager.delete_key(root, &index_key(num)).unwrap());
+        }
+        assert_eq!(check(&mut pager, root), NUM_KEYS / 2);
+        let expected: Vec<Box<[u8]>> = (1..NUM_KEYS).step_by(2).map(index_key).collect();
+        assert_eq!(keys(&mut pager, root), expected);
+
+        // Leaves are not merged, so the tree keeps its pages, empty ones included, until cleared
+        for num in (1..NUM_KEYS).step_by(2) {
+            assert!(pager.delete_key(root, &index_key(num)).unwrap());
+        }
+        assert_eq!(check(&mut pager, root), 0);
+        assert!(keys(&mut pager, root).is_empty());
+        pager.insert_key(root, &index_key(42)).unwrap();
+        assert_eq!(keys(&mut pager, root), vec![index_key(42)]);
+
+        pager.clear_tree(root).unwrap();
+        assert_eq!(pager.tree_pages(root).unwrap(), vec![root]);
+        assert!(pager.page(root).unwrap().is_index());
+        assert_eq!(check(&mut pager, root), 0);
+    }
+
+    #[test]
+    fn keys_are_only_inserted_into_index_trees() {
+        let mut pager = Pager::open(Box::new(MemoryStorage::new()), false).unwrap();
+        let root = pager.allocate_page(PageType::Leaf).unwrap();
+        assert!(pager.insert_key(root, &index_key(1)).is_err());
+        assert!(pager.delete_key(root, &index_key(1)).is_err());
+    }
+
+    // Bytes shared by every handle on them, where a given number of writes fail before the
+    // following ones go through again
+    #[derive(Debug, Default, Clone)]
+    struct FlakyStorage {
+        bytes: Rc<RefCell<Vec<u8>>>,
+        writes_to_skip: Rc<Cell<u32>>,
+        writes_to_fail: Rc<Cell<u32>>,
+    }
+
+    impl Storage for FlakyStorage {
+        fn size(&mut self) -> io::Result<u64> {
+            Ok(self.bytes.borrow().len() as u64)
+        }
+
+        fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+            let bytes = self.bytes.borrow();
+            let offset = offset as usize;
+            buf.copy_from_slice(&bytes[offset..offset + buf.len()]);
+            Ok(())
+        }
+
+        fn write_all_at(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+            match (self.writes_to_skip.get(), self.writes_to_fail.get()) {
+                (0, 0) => {}
+                (0, failures) => {
+                    self.writes_to_fail.set(failures - 1);
+                    return Err(io::Error::other("injected fault"));
+                }
+                (skips, _) => self.writes_to_skip.set(skips - 1),
+            }
+            let mut bytes = self.bytes.borrow_mut();
+            let offset = offset as usize;
+            if bytes.len() < offset + buf.len() {
+                bytes.resize(offset + buf.len(), 0);
+            }
+            bytes[offset..offset + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pages_stay_dirty_until_a_flush_succeeds() {
+        let storage = FlakyStorage::default();
+        let mut pager = Pager::open(Box::new(storage.clone()), false).unwrap();
+        let root = pager.allocate_page(PageType::IndexLeaf).unwrap();
+        pager.flush().unwrap();
+
+        for num in 0..NUM_KEYS {
+            pager.insert_key(root, &index_key(num)).unwrap();
+        }
+        // The first page goes through and the second one fails
+        storage.writes_to_skip.set(1);
+        storage.writes_to_fail.set(1);
+        assert!(pager.flush().is_err());
+        assert!(pager.has_unsaved_changes());
+        pager.flush().unwrap();
+        assert!(!pager.has_unsaved_changes());
+
+        let mut reopened = Pager::open(Box::new(storage), false).unwrap();
+        assert_eq!(check(&mut reopened, root), NUM_KEYS);
+        assert_eq!(keys(&mut reopened, root).len() as u64, NUM_KEYS);
+    }
+
+    #[test]
+    fn printed_trees_show_the_values_of_their_keys() {
+        let mut pager = Pager::open(Box::new(MemoryStorage::new()), false).unwrap();
+        let root = pager.allocate_page(PageType::IndexLeaf).unwrap();
+        for values in [
+            [SQLType::Text("c".to_string()), SQLType::Integer(7)],
+            [SQLType::Text("a, 'b'".to_string()), SQLType::Integer(-3)],
+        ] {
+            pager.insert_key(root, &key::encode_key(&values)).unwrap();
+        }
+        let tree = pager.print_tree(root, &|key| key.to_string()).unwrap();
+        assert!(
+            tree.ends_with(", keys ('a, ''b''', -3)..=('c', 7)\n"),
+            "{tree}"
+        );
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        for id in -2..=2 {
+            conn.execute(&format!("INSERT INTO t VALUES ({id}, 'n');"))
+                .unwrap();
+        }
+        let tree = conn.database().table("t").unwrap().print_tree().unwrap();
+        assert!(tree.ends_with(", keys -2..=2\n"), "{tree}");
     }
 }
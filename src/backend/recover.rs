@@ -0,0 +1,328 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+
+use super::catalog::CatalogEntry;
+use super::columns::Columns;
+use super::database::{Database, DatabaseError};
+use super::header::DatabaseHeader;
+use super::page::{Page, PAGE_SIZE};
+use super::row::Row;
+
+// What was salvaged from a damaged database file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    // Rows written into the new database for every table, in the order the tables were created
+    pub tables: Vec<(String, u64)>,
+    // Description of every part of the file that could not be salvaged
+    pub problems: Vec<String>,
+}
+
+// Salvages what it can from a database file that may be too damaged to open, writing it into a
+// new database at `path_str`. Pages are decoded straight from the file: leaves whose cells are
+// all catalog records give the tables, the trees of the tables give their rows, and leaves no
+// tree reaches any more go to the only table whose rows have as many values as theirs
+pub fn recover(corrupt_path: &str, path_str: &str) -> Result<RecoveryReport, DatabaseError> {
+    let bytes = fs::read(corrupt_path)?;
+    let mut report = RecoveryReport::default();
+
+    if let Err(err) = DatabaseHeader::read_from_slice(&bytes) {
+        report.problems.push(format!("page 0: {}", err));
+    }
+    let mut pages = BTreeMap::new();
+    for (page_num, chunk) in bytes.chunks(PAGE_SIZE).enumerate().skip(1) {
+        let decoded = <&[u8; PAGE_SIZE]>::try_from(chunk)
+            .map_err(|_| "page is cut short".to_string())
+            .and_then(|chunk| Page::new_from_read(chunk, false).map_err(|err| err.to_string()));
+        match decoded {
+            Ok(page) => {
+                pages.insert(page_num as u32, page);
+            }
+            Err(err) => report.problems.push(format!("page {}: {}", page_num, err)),
+        }
+    }
+
+    // Tables are keyed by id, as a damaged catalog may list one in several places
+    let mut entries = BTreeMap::new();
+    let mut catalog_pages = HashSet::new();
+    for (page_num, page) in pages.iter().filter(|(_, page)| page.is_leaf()) {
+        let page_entries: Option<Vec<CatalogEntry>> = page.cells().ok().and_then(|cells| {
+            cells
+                .iter()
+                .map(|cell| CatalogEntry::try_from(&*cell.value).ok())
+                .collect()
+        });
+        let Some(page_entries) = page_entries.filter(|entries| !entries.is_empty()) else {
+            continue;
+        };
+        catalog_pages.insert(*page_num);
+        for entry in page_entries {
+            entries.entry(entry.table_id).or_insert(entry);
+        }
+    }
+
+    let mut db = Database::open(path_str)?;
+    let entries: Vec<CatalogEntry> = entries.into_values().collect();
+    let mut created = Vec::new();
+    for entry in &entries {
+        let columns = Columns(
+            entry.columns.0.clone(),
+            entry.constraints.clone(),
+            entry.declared_order.clone(),
+        );
+        match db.add_table(
+            &entry.name,
+            columns,
+            entry.primary_key.clone(),
+            entry.comments.clone(),
+        ) {
+            Ok(()) => created.push(entry),
+            Err(err) => report
+                .problems
+                .push(format!("table {}: {}", entry.name, err)),
+        }
+    }
+
+    // Pages reached from the root of every table, by position of the table in `created`
+    let mut owners = HashMap::new();
+    for (idx, entry) in created.iter().enumerate() {
+        let mut stack = vec![entry.root_page];
+        while let Some(page_num) = stack.pop() {
+            if owners.contains_key(&page_num) || catalog_pages.contains(&page_num) {
+                continue;
+            }
+            let Some(page) = pages.get(&page_num) else {
+                report.problems.push(format!(
+                    "table {}: page {} is missing",
+                    entry.name, page_num
+                ));
+                continue;
+            };
+            owners.insert(page_num, idx);
+            if !page.is_leaf() {
+                stack.extend((0..=page.num_cells()).filter_map(|idx| page.child_at(idx).ok()));
+            }
+        }
+    }
+
+    let mut salvaged = vec![0; created.len()];
+    // Indexes hold no rows, and are built again from the rows salvaged
+    let leaves = pages.iter().filter(|(page_num, page)| {
+        page.is_leaf() && !page.is_index() && !catalog_pages.contains(page_num)
+    });
+    for (page_num, page) in leaves {
+        let rows: Vec<Option<Row>> = (0..page.num_cells())
+            .map(|cell_idx| page.row_at(cell_idx).ok())
+            .collect();
+        let lost = rows.iter().filter(|row| row.is_none()).count();
+        if lost > 0 {
+            report.problems.push(format!(
+                "page {}: {} of {} rows cannot be decoded",
+                page_num,
+                lost,
+                rows.len()
+            ));
+        }
+        let rows: Vec<Row> = rows.into_iter().flatten().collect();
+
+        let owner = match owners.get(page_num) {
+            Some(owner) => Some(*owner),
+            None if rows.is_empty() => None,
+            None => {
+                let num_values = rows[0].attributes().len();
+                let mut candidates = created.iter().enumerate().filter(|(_, entry)| {
+                    entry.columns.len() == num_values
+                        && rows.iter().all(|row| row.attributes().len() == num_values)
+                });
+                match (candidates.next(), candidates.next()) {
+                    (Some((idx, _)), None) => Some(idx),
+                    _ => {
+                        report.problems.push(format!(
+                            "page {}: {} rows belong to no known table",
+                            page_num,
+                            rows.len()
+                        ));
+                        None
+                    }
+                }
+            }
+        };
+        let Some(idx) = owner else {
+            continue;
+        };
+
+        let table = db.table(&created[idx].name)?;
+        for row in rows {
+            let rowid = row.rowid();
+            match table.insert(row) {
+                Ok(()) => salvaged[idx] += 1,
+                Err(err) => report.problems.push(format!(
+                    "table {}: row {} cannot be written: {}",
+                    created[idx].name, rowid, err
+                )),
+            }
+        }
+    }
+
+    for entry in &created {
+        for index in &entry.indexes {
+            if let Err(err) = db.add_index(&index.name, &entry.name, index.columns.clone()) {
+                report
+                    .problems
+                    .push(format!("index {}: {}", index.name, err));
+            }
+        }
+    }
+
+    db.flush()?;
+    report.tables = created
+        .iter()
+        .zip(salvaged)
+        .map(|(entry, rows)| (entry.name.clone(), rows))
+        .collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::{env, process};
+
+    use super::*;
+    use crate::{Connection, SQLType};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("sql_rs_recover_{}_{}.db", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    // Database with a table spanning several pages, indexed on its names, and a smaller table
+    // whose rows have another number of values. Returns the root page of the large table
+    fn write_database(path: &Path) -> u32 {
+        let mut conn = Connection::open(path.to_str().unwrap()).unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        conn.execute("CREATE TABLE u (id INT PRIMARY KEY, a TEXT, b TEXT);")
+            .unwrap();
+        conn.execute("BEGIN;").unwrap();
+        for id in 0..300 {
+            conn.execute(&format!(
+                "INSERT INTO t VALUES ({id}, 'name {id} {}');",
+                "x".repeat(50)
+            ))
+            .unwrap();
+        }
+        conn.execute("INSERT INTO u VALUES (1, 'a', 'b');").unwrap();
+        conn.execute("COMMIT;").unwrap();
+        conn.execute("CREATE INDEX by_name ON t (name);").unwrap();
+        let root_page = conn.database().table("t").unwrap().root_page_num();
+        root_page
+    }
+
+    fn count(path: &Path, table_name: &str) -> Vec<Vec<SQLType>> {
+        let mut conn = Connection::open(path.to_str().unwrap()).unwrap();
+        assert!(conn.integrity_check().is_empty());
+        conn.query(&format!("SELECT COUNT(*) FROM {table_name};"))
+            .unwrap()
+            .map(|row| row.into_values())
+            .collect()
+    }
+
+    fn recover_into(corrupt: &Path, recovered: &Path) -> RecoveryReport {
+        recover(corrupt.to_str().unwrap(), recovered.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn intact_databases_are_copied_whole() {
+        let (corrupt, recovered) = (temp_path("intact"), temp_path("intact_out"));
+        write_database(&corrupt);
+
+        let report = recover_into(&corrupt, &recovered);
+        assert_eq!(
+            report,
+            RecoveryReport {
+                tables: vec![("t".to_string(), 300), ("u".to_string(), 1)],
+                problems: vec![],
+            }
+        );
+        assert_eq!(count(&recovered, "t"), [[SQLType::Integer(300)]]);
+        let conn = Connection::open(recovered.to_str().unwrap()).unwrap();
+        assert_eq!(conn.database().table("t").unwrap().indexes.len(), 1);
+        drop(conn);
+
+        fs::remove_file(&corrupt).unwrap();
+        fs::remove_file(&recovered).unwrap();
+    }
+
+    #[test]
+    fn leaves_of_a_damaged_tree_go_to_the_table_they_fit() {
+        let (corrupt, recovered) = (temp_path("root"), temp_path("root_out"));
+        let root_page = write_database(&corrupt);
+
+        // The header and the root of the large table are overwritten
+        let mut bytes = fs::read(&corrupt).unwrap();
+        bytes[..16].fill(0xff);
+        let root = root_page as usize * PAGE_SIZE;
+        bytes[root..root + PAGE_SIZE].fill(0xff);
+        fs::write(&corrupt, bytes).unwrap();
+
+        let report = recover_into(&corrupt, &recovered);
+        assert_eq!(
+            report.tables,
+            [("t".to_string(), 300), ("u".to_string(), 1)]
+        );
+        assert_eq!(
+            report.problems,
+            [
+                "page 0: File is not a sql_rs database".to_string(),
+                format!("page {root_page}: Page type 255 is not valid"),
+                format!("table t: page {root_page} is missing"),
+            ]
+        );
+        assert_eq!(count(&recovered, "t"), [[SQLType::Integer(300)]]);
+        assert_eq!(count(&recovered, "u"), [[SQLType::Integer(1)]]);
+
+        fs::remove_file(&corrupt).unwrap();
+        fs::remove_file(&recovered).unwrap();
+    }
+
+    #[test]
+    fn rows_of_a_damaged_leaf_are_lost() {
+        let (corrupt, recovered) = (temp_path("leaf"), temp_path("leaf_out"));
+        let root_page = write_database(&corrupt);
+
+        let mut bytes = fs::read(&corrupt).unwrap();
+        let page = |page_num: u32| {
+            let start = page_num as usize * PAGE_SIZE;
+            let page: &[u8; PAGE_SIZE] = bytes[start..start + PAGE_SIZE].try_into().unwrap();
+            Page::new_from_read(page, false).unwrap()
+        };
+        let root = page(root_page);
+        assert!(!root.is_leaf());
+        let leaf_page = root.child_at(0).unwrap();
+        let lost = page(leaf_page).num_cells() as u64;
+        let leaf = leaf_page as usize * PAGE_SIZE;
+        bytes[leaf..leaf + PAGE_SIZE].fill(0xff);
+        fs::write(&corrupt, bytes).unwrap();
+
+        let report = recover_into(&corrupt, &recovered);
+        assert_eq!(
+            report.tables,
+            [("t".to_string(), 300 - lost), ("u".to_string(), 1)]
+        );
+        assert_eq!(
+            report.problems,
+            [
+                format!("page {leaf_page}: Page type 255 is not valid"),
+                format!("table t: page {leaf_page} is missing"),
+            ]
+        );
+        assert_eq!(
+            count(&recovered, "t"),
+            [[SQLType::Integer(300 - lost as i32)]]
+        );
+
+        fs::remove_file(&corrupt).unwrap();
+        fs::remove_file(&recovered).unwrap();
+    }
+}
@@ -1,22 +1,31 @@
-use std::rc::Rc;
+use std::fmt;
 
 use bincode;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use super::uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd)]
 pub enum SQLType {
     UBigInt(u64),
     Integer(i32),
     Text(String),
+    Null,
+    BigInt(i64),
+    SmallInt(i16),
+    Uuid(Uuid),
 }
 
-impl ToString for SQLType {
-    // Required method
-    fn to_string(&self) -> String {
+impl fmt::Display for SQLType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SQLType::UBigInt(num) => num.to_string(),
-            SQLType::Integer(num) => num.to_string(),
-            SQLType::Text(s) => s.to_owned(),
+            SQLType::UBigInt(num) => write!(f, "{}", num),
+            SQLType::Integer(num) => write!(f, "{}", num),
+            SQLType::Text(s) => write!(f, "{}", s),
+            SQLType::Null => write!(f, "NULL"),
+            SQLType::BigInt(num) => write!(f, "{}", num),
+            SQLType::SmallInt(num) => write!(f, "{}", num),
+            SQLType::Uuid(uuid) => write!(f, "{}", uuid),
         }
     }
 }
@@ -28,11 +37,10 @@ pub struct Row {
 }
 
 impl Row {
-    const BINCODE_CONFIG: bincode::config::Configuration<
-        bincode::config::BigEndian,
-    > = bincode::config::standard().with_big_endian();
+    const BINCODE_CONFIG: bincode::config::Configuration<bincode::config::BigEndian> =
+        bincode::config::standard().with_big_endian();
 
-    pub fn new(rowid:u64, attributes: Vec<SQLType>) -> Self {
+    pub fn new(rowid: u64, attributes: Vec<SQLType>) -> Self {
         Self { rowid, attributes }
     }
 
@@ -40,6 +48,10 @@ impl Row {
         self.rowid
     }
 
+    pub fn attributes(&self) -> &[SQLType] {
+        &self.attributes
+    }
+
     pub fn to_printable(&self) -> Vec<String> {
         self.attributes
             .iter()
@@ -66,10 +78,10 @@ impl TryFrom<&[u8]> for Row {
     type Error = ();
 
     fn try_from(bytes: &[u8]) -> Result<Row, Self::Error> {
-        let (rowid, attributes) = bincode::serde::decode_borrowed_from_slice::<(u64, Vec<SQLType>), _>(
-            &bytes,
-            Self::BINCODE_CONFIG,
-        )
+        let ((rowid, attributes), _) = bincode::serde::borrow_decode_from_slice::<
+            (u64, Vec<SQLType>),
+            _,
+        >(bytes, Self::BINCODE_CONFIG)
         .map_err(|_| ())?;
         Ok(Self { rowid, attributes })
     }
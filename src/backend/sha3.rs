@@ -0,0 +1,214 @@
+// SHA3-256 as specified in FIPS 202, used to fingerprint the content of tables
+
+const RATE: usize = 136;
+const ROUNDS: usize = 24;
+
+const ROUND_CONSTANTS: [u64; ROUNDS] = [
+    0x0000_0000_0000_0001,
+    0x0000_0000_0000_8082,
+    0x8000_0000_0000_808a,
+    0x8000_0000_8000_8000,
+    0x0000_0000_0000_808b,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8009,
+    0x0000_0000_0000_008a,
+    0x0000_0000_0000_0088,
+    0x0000_0000_8000_8009,
+    0x0000_0000_8000_000a,
+    0x0000_0000_8000_808b,
+    0x8000_0000_0000_008b,
+    0x8000_0000_0000_8089,
+    0x8000_0000_0000_8003,
+    0x8000_0000_0000_8002,
+    0x8000_0000_0000_0080,
+    0x0000_0000_0000_800a,
+    0x8000_0000_8000_000a,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8080,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8008,
+];
+
+// Rotation of every lane, and the lane it moves to, in the order the rho and pi steps visit them
+const ROTATIONS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+const LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+fn keccak_f(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // Theta
+        let mut parity = [0; 5];
+        for (x, column) in parity.iter_mut().enumerate() {
+            *column = (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]);
+        }
+        for x in 0..5 {
+            let mix = parity[(x + 4) % 5] ^ parity[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= mix;
+            }
+        }
+
+        // Rho and pi
+        let mut carried = state[1];
+        for (rotation, lane) in ROTATIONS.iter().zip(LANES) {
+            let next = state[lane];
+            state[lane] = carried.rotate_left(*rotation);
+            carried = next;
+        }
+
+        // Chi
+        for y in 0..5 {
+            let row: [u64; 5] = std::array::from_fn(|x| state[x + 5 * y]);
+            for x in 0..5 {
+                state[x + 5 * y] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+
+        // Iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[derive(Clone)]
+pub struct Sha3_256 {
+    state: [u64; 25],
+    // Input not absorbed yet, as it does not fill a whole block
+    buffer: Vec<u8>,
+}
+
+impl Default for Sha3_256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha3_256 {
+    pub fn new() -> Self {
+        Self {
+            state: [0; 25],
+            buffer: Vec::with_capacity(RATE),
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let taken = (RATE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+            if self.buffer.len() == RATE {
+                self.absorb_buffer();
+            }
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        // Domain separation bits of SHA3 followed by pad10*1
+        let len = self.buffer.len();
+        self.buffer.resize(RATE, 0);
+        self.buffer[len] ^= 0x06;
+        self.buffer[RATE - 1] ^= 0x80;
+        self.absorb_buffer();
+
+        let mut digest = [0; 32];
+        for (chunk, lane) in digest.chunks_mut(8).zip(self.state) {
+            chunk.copy_from_slice(&lane.to_le_bytes());
+        }
+        digest
+    }
+
+    fn absorb_buffer(&mut self) {
+        for (lane, bytes) in self.state.iter_mut().zip(self.buffer.chunks(8)) {
+            *lane ^= u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        keccak_f(&mut self.state);
+        self.buffer.clear();
+    }
+}
+
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Connection;
+
+    fn sha3(data: &[u8]) -> String {
+        let mut hasher = Sha3_256::new();
+        hasher.update(data);
+        to_hex(&hasher.finalize())
+    }
+
+    #[test]
+    fn matches_the_fips_202_test_vectors() {
+        assert_eq!(
+            sha3(b""),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+        assert_eq!(
+            sha3(b"abc"),
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+        );
+        assert_eq!(
+            sha3(b"The quick brown fox jumps over the lazy dog"),
+            "69070dda01975c8c120c3aada1b282394e7f032fa9cf32f4cb2259a0897dfc04"
+        );
+        // 200 bytes span more than one block
+        assert_eq!(
+            sha3(&[0xa3; 200]),
+            "79f38adec5c20307a98ef76e8324afbfd46cfd81b22e3973c65fa1bd9de31787"
+        );
+    }
+
+    #[test]
+    fn split_updates_hash_like_a_single_one() {
+        let data: Vec<u8> = (0..=255).cycle().take(3 * RATE + 7).collect();
+        for split in [0, 1, RATE - 1, RATE, RATE + 1, 2 * RATE, data.len()] {
+            let mut hasher = Sha3_256::new();
+            hasher.update(&data[..split]);
+            hasher.update(&data[split..]);
+            assert_eq!(to_hex(&hasher.finalize()), sha3(&data), "split at {split}");
+        }
+    }
+
+    #[test]
+    fn tables_with_the_same_rows_hash_the_same() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE a (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        conn.execute("CREATE TABLE b (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        for id in 0..300 {
+            conn.execute(&format!("INSERT INTO a VALUES ({id}, 'name {id}');"))
+                .unwrap();
+        }
+        // Inserted in another order, which lays out the pages differently
+        for id in (0..300).rev() {
+            conn.execute(&format!("INSERT INTO b VALUES ({id}, 'name {id}');"))
+                .unwrap();
+        }
+        let hash = |conn: &Connection, table_name| {
+            conn.database()
+                .table(table_name)
+                .unwrap()
+                .content_hash()
+                .unwrap()
+        };
+        assert_eq!(hash(&conn, "a"), hash(&conn, "b"));
+
+        conn.execute("UPDATE b SET name = 'renamed' WHERE id = 150;")
+            .unwrap();
+        assert_ne!(hash(&conn, "a"), hash(&conn, "b"));
+        conn.execute("UPDATE b SET name = 'name 150' WHERE id = 150;")
+            .unwrap();
+        assert_eq!(hash(&conn, "a"), hash(&conn, "b"));
+
+        conn.execute("DELETE FROM b WHERE id = 0;").unwrap();
+        assert_ne!(hash(&conn, "a"), hash(&conn, "b"));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, Weak};
+
+use super::page::Page;
+
+// Shards the pages are spread over, so readers of different pages rarely wait on the same latch
+const NUM_SHARDS: usize = 16;
+
+// Pages of a database file shared by the read-only pagers on it, which may live on different
+// threads. Each shard is behind a read/write latch: any number of readers look pages up at once,
+// and one reader adding a page it loaded only holds up readers of the same shard. The cache owns
+// no page itself, pages stay in it while the cache of some pager holds them
+#[derive(Debug)]
+pub struct SharedPageCache {
+    shards: Vec<RwLock<Shard>>,
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    // Change counter of the file when the pages were read, pages read at another one are stale
+    change_counter: u32,
+    pages: HashMap<u32, Weak<Page>>,
+}
+
+impl Default for SharedPageCache {
+    fn default() -> Self {
+        Self {
+            shards: (0..NUM_SHARDS).map(|_| RwLock::default()).collect(),
+        }
+    }
+}
+
+impl SharedPageCache {
+    // The page as read when the file had the given change counter, if some pager still holds it
+    pub fn get(&self, page_num: u32, change_counter: u32) -> Option<Arc<Page>> {
+        let shard = self.shard(page_num).read().ok()?;
+        if shard.change_counter != change_counter {
+            return None;
+        }
+        shard.pages.get(&page_num)?.upgrade()
+    }
+
+    // Adds a page read when the file had the given change counter. Returns the page already
+    // cached if another reader added it first, so both share one copy
+    pub fn insert(&self, page_num: u32, change_counter: u32, page: Arc<Page>) -> Arc<Page> {
+        let Ok(mut shard) = self.shard(page_num).write() else {
+            return page;
+        };
+        if shard.change_counter != change_counter {
+            shard.change_counter = change_counter;
+            shard.pages.clear();
+        }
+        if let Some(cached) = shard.pages.get(&page_num).and_then(Weak::upgrade) {
+            return cached;
+        }
+        // Pages no pager holds any more are dropped before the map grows, which keeps it within
+        // twice the pages held
+        if shard.pages.len() == shard.pages.capacity() {
+            shard.pages.retain(|_, page| page.strong_count() > 0);
+        }
+        shard.pages.insert(page_num, Arc::downgrade(&page));
+        page
+    }
+
+    fn shard(&self, page_num: u32) -> &RwLock<Shard> {
+        &self.shards[page_num as usize % NUM_SHARDS]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process, thread};
+
+    use super::*;
+    use crate::backend::page::PageType;
+    use crate::{Connection, SQLType};
+
+    #[test]
+    fn pages_are_shared_while_some_pager_holds_them() {
+        let cache = SharedPageCache::default();
+        let page = Arc::new(Page::new_typed(PageType::Leaf));
+        let cached = cache.insert(3, 7, Arc::clone(&page));
+        assert!(Arc::ptr_eq(&cached, &page));
+        assert!(Arc::ptr_eq(&cache.get(3, 7).unwrap(), &page));
+
+        // A reader that loaded the page too gets the copy already cached
+        let copy = cache.insert(3, 7, Arc::new(Page::new_typed(PageType::Leaf)));
+        assert!(Arc::ptr_eq(&copy, &page));
+
+        // Pages read before the file changed are not returned
+        assert!(cache.get(3, 8).is_none());
+        assert!(cache.get(19, 7).is_none());
+
+        drop((page, cached, copy));
+        assert!(cache.get(3, 7).is_none());
+    }
+
+    #[test]
+    fn read_only_connections_on_several_threads_share_pages() {
+        let path = env::temp_dir().join(format!("sql_rs_shared_cache_{}.db", process::id()));
+        let _ = fs::remove_file(&path);
+        let path_str = path.to_str().unwrap().to_string();
+
+        let mut conn = Connection::open(&path_str).unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        conn.execute("BEGIN;").unwrap();
+        for id in 0..2000 {
+            conn.execute(&format!("INSERT INTO t VALUES ({id}, 'name {id}');"))
+                .unwrap();
+        }
+        conn.execute("COMMIT;").unwrap();
+        drop(conn);
+
+        let count_rows = |path: &str| {
+            let mut conn = Connection::open_read_only(path).unwrap();
+            let rows = conn.query("SELECT id, name FROM t;").unwrap().count();
+            let pages_read = conn.database().pager_metrics().pages_read;
+            (conn, rows, pages_read)
+        };
+        let spawn_readers = || {
+            (0..4)
+                .map(|_| {
+                    let path_str = path_str.clone();
+                    thread::spawn(move || {
+                        let (_, rows, pages_read) = count_rows(&path_str);
+                        (rows, pages_read)
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|reader| reader.join().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Readers starting together may each read a page before another adds it
+        for (rows, _) in spawn_readers() {
+            assert_eq!(rows, 2000);
+        }
+
+        // Once a connection holds every page the others read none from the file
+        let (mut warm, rows, pages_read) = count_rows(&path_str);
+        assert_eq!(rows, 2000);
+        assert!(pages_read > 0);
+        assert_eq!(spawn_readers(), [(2000, 0); 4]);
+
+        assert!(warm.execute("DELETE FROM t WHERE id = 1;").is_err());
+        assert_eq!(
+            warm.query("SELECT name FROM t WHERE id = 1999;")
+                .unwrap()
+                .map(|row| row.into_values())
+                .collect::<Vec<_>>(),
+            [[SQLType::Text("name 1999".to_string())]]
+        );
+        drop(warm);
+        fs::remove_file(&path).unwrap();
+    }
+}
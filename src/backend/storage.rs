@@ -0,0 +1,231 @@
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{File, TryLockError};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+/// Advisory locks coordinating processes that use the same database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// Held while reading. Any number of processes can hold it at once
+    Shared,
+    /// Held while writing, by a single process
+    Exclusive,
+}
+
+/// The bytes a database lives in. The pager only goes through this trait, so a database can be
+/// kept in memory or any other store, e.g. on wasm32 where there is no file system.
+pub trait Storage: fmt::Debug {
+    fn size(&mut self) -> io::Result<u64>;
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()>;
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> io::Result<()>;
+    /// Makes previous writes durable
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Shrinks the storage to `size` bytes. Storage that cannot shrink may keep its bytes, which
+    /// are never read again
+    fn set_len(&mut self, _size: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Takes or converts the lock held on the storage without waiting. Returns false when another
+    /// process holds a conflicting lock. Storage private to the process needs no locking
+    fn try_lock(&mut self, _kind: LockKind) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    fn unlock(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Storage for File {
+    fn size(&mut self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(buf)
+    }
+
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(buf)
+    }
+
+    // Waits for the data to reach the disk, not only the operating system
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(self)?;
+        self.sync_data()
+    }
+
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        File::set_len(self, size)
+    }
+
+    fn try_lock(&mut self, kind: LockKind) -> io::Result<bool> {
+        let result = match kind {
+            LockKind::Shared => File::try_lock_shared(self),
+            LockKind::Exclusive => File::try_lock(self),
+        };
+        match result {
+            Ok(()) => Ok(true),
+            Err(TryLockError::WouldBlock) => Ok(false),
+            Err(TryLockError::Error(err)) => Err(err),
+        }
+    }
+
+    fn unlock(&mut self) -> io::Result<()> {
+        File::unlock(self)
+    }
+}
+
+/// Keeps the whole database in a byte vector, which is dropped along with it
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    bytes: Vec<u8>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn size(&mut self) -> io::Result<u64> {
+        Ok(self.bytes.len() as u64)
+    }
+
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let start = offset as usize;
+        let source = self
+            .bytes
+            .get(start..start + buf.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        buf.copy_from_slice(source);
+        Ok(())
+    }
+
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        let start = offset as usize;
+        let end = start + buf.len();
+        if self.bytes.len() < end {
+            self.bytes.resize(end, 0);
+        }
+        self.bytes[start..end].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.bytes.truncate(size as usize);
+        Ok(())
+    }
+}
+
+/// How [`FaultyStorage`] fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The operation fails without writing anything
+    Error,
+    /// Only the first half of the bytes is written before the operation fails
+    TornWrite,
+}
+
+/// Wraps another storage and makes one of its writes or flushes go wrong, simulating a crash at
+/// that point. Everything written afterwards is dropped, as it would never reach the disk
+#[derive(Debug)]
+pub struct FaultyStorage {
+    inner: Box<dyn Storage>,
+    fault: Fault,
+    // 1-based count of the write or flush that fails
+    fail_at: u64,
+    // Shared so it can still be read once the storage belongs to a database
+    operations: Rc<Cell<u64>>,
+}
+
+impl FaultyStorage {
+    pub fn new(inner: Box<dyn Storage>, fail_at: u64, fault: Fault) -> Self {
+        Self {
+            inner,
+            fault,
+            fail_at,
+            operations: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Number of writes and flushes attempted so far. The count keeps updating after the storage
+    /// is handed to a database
+    pub fn operations(&self) -> Rc<Cell<u64>> {
+        self.operations.clone()
+    }
+
+    fn crashed(&self) -> bool {
+        self.operations.get() >= self.fail_at
+    }
+
+    // Counts a write or flush, returning whether it is the one that fails
+    fn next_operation(&mut self) -> bool {
+        self.operations.set(self.operations.get() + 1);
+        self.operations.get() == self.fail_at
+    }
+}
+
+impl Storage for FaultyStorage {
+    fn size(&mut self) -> io::Result<u64> {
+        self.inner.size()
+    }
+
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.inner.read_exact_at(buf, offset)
+    }
+
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        let crashed = self.crashed();
+        if self.next_operation() {
+            if self.fault == Fault::TornWrite {
+                self.inner.write_all_at(&buf[..buf.len() / 2], offset)?;
+            }
+            return Err(io::Error::other("injected fault"));
+        }
+        match crashed {
+            true => Ok(()),
+            false => self.inner.write_all_at(buf, offset),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let crashed = self.crashed();
+        if self.next_operation() {
+            return Err(io::Error::other("injected fault"));
+        }
+        match crashed {
+            true => Ok(()),
+            false => self.inner.flush(),
+        }
+    }
+
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        let crashed = self.crashed();
+        if self.next_operation() {
+            return Err(io::Error::other("injected fault"));
+        }
+        match crashed {
+            true => Ok(()),
+            false => self.inner.set_len(size),
+        }
+    }
+
+    fn try_lock(&mut self, kind: LockKind) -> io::Result<bool> {
+        self.inner.try_lock(kind)
+    }
+
+    fn unlock(&mut self) -> io::Result<()> {
+        self.inner.unlock()
+    }
+}
@@ -1,106 +1,723 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::fs::File;
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
 use std::rc::Rc;
 
 use tabled::{builder::Builder, settings::style::Style};
 use thiserror::Error;
 
+use super::bloom::BloomFilter;
+use super::catalog::{CatalogEntry, Comments, IndexEntry, TableStats};
 use super::columns::*;
 use super::cursor::DBCursor;
+use super::db_cell::DBCell;
+use super::key::{self, KeyError};
 use super::page::PageError;
 use super::pager::{Pager, PagerError};
-use super::row::Row;
+use super::row::{Row, SQLType};
+use super::sha3::Sha3_256;
+
+// Rows whose rowid lies between the bounds
+pub type RowidRange = (Bound<u64>, Bound<u64>);
 
 #[derive(Debug)]
 pub struct Table {
     pub name: String,
     pub columns: Columns,
-    num_rows: usize,
-    pager: RefCell<Pager>,
+    // Column whose values are the rowids of the table
+    pub primary_key: Option<String>,
+    pub comments: Comments,
+    pub indexes: Vec<IndexEntry>,
+    table_id: u64,
     root_page_num: u32,
-    curr_page_idx: usize,
+    stats: Cell<TableStats>,
+    // Whether the stats changed since they were last written to the catalog
+    stats_dirty: Cell<bool>,
+    // Answers lookups of rowids that are not in the table without reading its tree. Built the
+    // first time it is needed once enabled
+    bloom_filter_enabled: Cell<bool>,
+    bloom_filter: RefCell<Option<BloomFilter>>,
+    pager: Rc<RefCell<Pager>>,
 }
 
 #[derive(Error, Debug)]
 pub enum TableError {
-    #[error("Cannot insert row. Pages limit was reached.")]
-    TableFull,
-    #[error("Error when opening connection: {0}")]
+    #[error("Error when inserting row: {0}")]
     RowInsertError(PagerError),
-    #[error("Error when flushing table to disk: {0}")]
-    FlushError(PagerError),
+    #[error("Error when reading row: {0}")]
+    ReadError(PagerError),
+    #[error("Error when deleting row: {0}")]
+    RowDeleteError(PagerError),
+    #[error("Error when updating row: {0}")]
+    RowUpdateError(PagerError),
     #[error(transparent)]
-    PageError(#[from] PageError),
+    CorruptPage(#[from] PageError),
+    #[error("No rowids left to assign")]
+    RowidsExhausted,
+    #[error("Row of {0} bytes exceeds the limit of {1} bytes set for the database")]
+    RowSizeLimit(usize, usize),
+    #[error("Table {0} may not grow past its limit of {1} pages")]
+    TablePageLimit(String, u32),
+    #[error("Index {0} holds a corrupt key: {1}")]
+    CorruptIndex(String, KeyError),
 }
 
 impl Table {
-    const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard();
-
-    pub fn new(name: &str, columns: Columns, file: Rc<RefCell<File>>) -> Table {
-        let pager = RefCell::new(Pager::new(file));
-
+    pub fn from_catalog(entry: CatalogEntry, pager: Rc<RefCell<Pager>>) -> Table {
         Table {
-            name: name.to_string(),
+            name: entry.name,
+            columns: Columns(entry.columns.0, entry.constraints, entry.declared_order),
+            primary_key: entry.primary_key,
+            comments: entry.comments,
+            indexes: entry.indexes,
+            table_id: entry.table_id,
+            root_page_num: entry.root_page,
+            stats: Cell::new(entry.stats),
+            stats_dirty: Cell::new(false),
+            bloom_filter_enabled: Cell::new(false),
+            bloom_filter: RefCell::new(None),
             pager,
-            columns,
-            root_page_num: 0,
-            curr_page_idx: 0,
-            num_rows: 0,
         }
     }
 
-    fn new_page_and_insert(&self, row: Row) -> Result<(), TableError> {
-        match self.pager.borrow_mut().new_page(self.curr_page_idx) {
-            Ok(()) => Ok(()),
-            Err(PagerError::TableFull) => Err(TableError::TableFull),
-            Err(_) => unreachable!(),
-        }?;
-        self.insert(row)
+    pub fn catalog_entry(&self) -> CatalogEntry {
+        CatalogEntry {
+            table_id: self.table_id,
+            name: self.name.clone(),
+            columns: self.columns.clone(),
+            root_page: self.root_page_num,
+            stats: self.stats.get(),
+            primary_key: self.primary_key.clone(),
+            constraints: self.columns.1.clone(),
+            comments: self.comments.clone(),
+            declared_order: self.columns.2.clone(),
+            indexes: self.indexes.clone(),
+        }
+    }
+
+    // CREATE TABLE statement that recreates the table without its rows
+    pub fn schema(&self) -> String {
+        let definitions: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(name, column_type)| {
+                let mut definition = format!("{} {}", name, column_type);
+                if self.primary_key.as_ref() == Some(name) {
+                    definition.push_str(" PRIMARY KEY");
+                }
+                if let Some(constraints) = self.columns.constraints(name) {
+                    if constraints.not_null {
+                        definition.push_str(" NOT NULL");
+                    }
+                    if constraints.unique {
+                        definition.push_str(" UNIQUE");
+                    }
+                    if let Some(default) = &constraints.default {
+                        definition += &format!(" DEFAULT {}", default.to_sql(column_type));
+                    }
+                    if let Some(check) = &constraints.check {
+                        definition += &format!(" CHECK ({})", check);
+                    }
+                    if let Some((table, column)) = &constraints.references {
+                        definition += &format!(" REFERENCES {}({})", table, column);
+                    }
+                }
+                if let Some(comment) = self.comments.columns.get(name) {
+                    definition += &format!(" COMMENT '{}'", comment);
+                }
+                definition
+            })
+            .collect();
+
+        let mut schema = format!("CREATE TABLE {} ({})", self.name, definitions.join(", "));
+        if let Some(comment) = &self.comments.table {
+            schema += &format!(" COMMENT '{}'", comment);
+        }
+        schema + ";"
+    }
+
+    // CREATE INDEX statements that recreate the indexes of the table
+    pub fn index_schemas(&self) -> Vec<String> {
+        self.indexes
+            .iter()
+            .map(|index| {
+                format!(
+                    "CREATE INDEX {} ON {} ({});",
+                    index.name,
+                    self.name,
+                    index.columns.join(", ")
+                )
+            })
+            .collect()
+    }
+
+    pub fn index(&self, name: &str) -> Option<&IndexEntry> {
+        self.indexes.iter().find(|index| index.name == name)
+    }
+
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+
+    pub fn root_page_num(&self) -> u32 {
+        self.root_page_num
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats.get()
+    }
+
+    // Returns whether the stats need to be written back to the catalog, clearing the flag
+    pub fn take_stats_dirty(&self) -> bool {
+        self.stats_dirty.replace(false)
+    }
+
+    pub fn stats_dirty(&self) -> bool {
+        self.stats_dirty.get()
+    }
+
+    // Puts back stats kept from earlier, along with whether they needed writing then
+    pub fn restore_stats(&self, stats: TableStats, dirty: bool) {
+        self.stats.set(stats);
+        self.stats_dirty.set(dirty);
     }
 
     pub fn insert(&self, row: Row) -> Result<(), TableError> {
-        let mut cursor = DBCursor::new(self);
-        match self
+        self.check_quotas(&row)?;
+        let keys = self.index_keys(&row);
+        self.store(row)?;
+
+        let mut pager = self.pager.borrow_mut();
+        for (index, key) in self.indexes.iter().zip(keys) {
+            pager
+                .insert_key(index.root_page, &key)
+                .map_err(TableError::RowInsertError)?;
+        }
+        Ok(())
+    }
+
+    // Writes a row into the tree of the table alone, leaving its indexes to the caller
+    fn store(&self, row: Row) -> Result<(), TableError> {
+        let mut pager = self.pager.borrow_mut();
+        let growth = pager
+            .insert(self.root_page_num, row.rowid(), &row)
+            .map_err(TableError::RowInsertError)?;
+        pager.record_row_written();
+
+        let mut stats = self.stats.get();
+        stats.record_insert(growth);
+        self.stats.set(stats);
+        self.stats_dirty.set(true);
+
+        let mut bloom_filter = self.bloom_filter.borrow_mut();
+        if let Some(filter) = bloom_filter.as_mut() {
+            filter.insert(row.rowid());
+            // An overfull filter is rebuilt with room for the new rows on its next use
+            if filter.len() > filter.capacity() {
+                *bloom_filter = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Removes the row with the given rowid, returning whether there was one. The bloom filter
+    // keeps the rowid, which only costs a lookup of the tree should it be asked for again
+    pub fn delete(&self, rowid: u64) -> Result<bool, TableError> {
+        if !self.indexes.is_empty() {
+            let Some(row) = self.row(rowid)? else {
+                return Ok(false);
+            };
+            let mut pager = self.pager.borrow_mut();
+            for (index, key) in self.indexes.iter().zip(self.index_keys(&row)) {
+                pager
+                    .delete_key(index.root_page, &key)
+                    .map_err(TableError::RowDeleteError)?;
+            }
+        }
+        self.remove(rowid)
+    }
+
+    // Removes a row from the tree of the table alone, leaving its indexes to the caller
+    fn remove(&self, rowid: u64) -> Result<bool, TableError> {
+        let mut pager = self.pager.borrow_mut();
+        let deleted = pager
+            .delete(self.root_page_num, rowid)
+            .map_err(TableError::RowDeleteError)?;
+        if deleted {
+            pager.record_row_written();
+            let mut stats = self.stats.get();
+            stats.record_delete();
+            self.stats.set(stats);
+            self.stats_dirty.set(true);
+        }
+        Ok(deleted)
+    }
+
+    // Writes a row over the one holding its rowid, moving its keys in the indexes whose columns
+    // it changes
+    pub fn update(&self, row: Row) -> Result<(), TableError> {
+        if !self.indexes.is_empty() {
+            let old_keys = match self.row(row.rowid())? {
+                Some(old_row) => self.index_keys(&old_row),
+                None => Vec::new(),
+            };
+            let new_keys = self.index_keys(&row);
+            let mut pager = self.pager.borrow_mut();
+            for ((index, old_key), new_key) in self.indexes.iter().zip(old_keys).zip(new_keys) {
+                if old_key != new_key {
+                    pager
+                        .delete_key(index.root_page, &old_key)
+                        .map_err(TableError::RowUpdateError)?;
+                    pager
+                        .insert_key(index.root_page, &new_key)
+                        .map_err(TableError::RowUpdateError)?;
+                }
+            }
+        }
+        self.rewrite(row)
+    }
+
+    // Writes a row over the one holding its rowid, leaving the indexes as they are. It is written
+    // into the same cell when it takes up as many bytes as the old one, and otherwise the old one
+    // is removed and the row stored anew. Meant for rows whose indexed values stay the same, such
+    // as those whose values are only reordered
+    pub fn rewrite(&self, row: Row) -> Result<(), TableError> {
+        let mut pager = self.pager.borrow_mut();
+        let in_place = pager
+            .update(self.root_page_num, row.rowid(), &row)
+            .map_err(TableError::RowUpdateError)?;
+        if in_place {
+            pager.record_row_written();
+            return Ok(());
+        }
+        drop(pager);
+
+        // The row is refused before the old one is gone, so a failed update leaves it as it was
+        self.check_quotas(&row)?;
+        self.remove(row.rowid())?;
+        self.store(row)
+    }
+
+    // Key of the row in every index of the table, in order
+    fn index_keys(&self, row: &Row) -> Vec<Box<[u8]>> {
+        self.indexes
+            .iter()
+            .map(|index| self.index_key(index, row))
+            .collect()
+    }
+
+    fn index_key(&self, index: &IndexEntry, row: &Row) -> Box<[u8]> {
+        let mut values: Vec<SQLType> = index
+            .columns
+            .iter()
+            .map(|column| {
+                self.columns
+                    .keys()
+                    .position(|name| name == column)
+                    .and_then(|idx| row.attributes().get(idx))
+                    .cloned()
+                    .unwrap_or(SQLType::Null)
+            })
+            .collect();
+        values.push(SQLType::UBigInt(row.rowid()));
+        key::encode_key(&values)
+    }
+
+    // Fills the tree of an index, which must be empty, with the key of every row
+    pub fn build_index(&self, index: &IndexEntry) -> Result<(), TableError> {
+        let mut cursor = DBCursor::new(self.root_page_num);
+        while let Some(row) = self.next_row(&mut cursor)? {
+            let key = self.index_key(index, &row);
+            self.pager
+                .borrow_mut()
+                .insert_key(index.root_page, &key)
+                .map_err(TableError::RowInsertError)?;
+        }
+        Ok(())
+    }
+
+    // Rowids of the rows whose first indexed columns hold the given values, in index order
+    pub fn index_rowids(&self, index: &IndexEntry, values: &[SQLType]) -> IndexRowids<'_> {
+        let prefix = key::encode_key(values);
+        let end = key::prefix_end(&prefix);
+        let upper_bound = match &end {
+            Some(end) => Bound::Excluded(&**end),
+            None => Bound::Unbounded,
+        };
+        IndexRowids {
+            table: self,
+            index_name: index.name.clone(),
+            cursor: DBCursor::with_key_bounds(
+                index.root_page,
+                Bound::Included(&prefix),
+                upper_bound,
+            ),
+        }
+    }
+
+    // The row with the given rowid, if there is one
+    pub fn row(&self, rowid: u64) -> Result<Option<Row>, TableError> {
+        let mut cursor = DBCursor::with_bounds(
+            self.root_page_num,
+            Bound::Included(rowid),
+            Bound::Included(rowid),
+        );
+        self.next_row(&mut cursor)
+    }
+
+    // Refuses a row over the size limit, and any row once a split could take the table past its
+    // page limit, as every page on the way down to the leaf may split, and so may the root
+    fn check_quotas(&self, row: &Row) -> Result<(), TableError> {
+        let mut pager = self.pager.borrow_mut();
+        let quotas = pager.quotas();
+        if let Some(max_row_size) = quotas.max_row_size {
+            let encoded: Result<Box<[u8]>, ()> = row.clone().try_into();
+            if let Some(size) = encoded.ok().map(|bytes| bytes.len()) {
+                if size > max_row_size {
+                    return Err(TableError::RowSizeLimit(size, max_row_size));
+                }
+            }
+        }
+        if let Some(max_table_pages) = quotas.max_table_pages {
+            let stats = self.stats.get();
+            if stats.leaf_pages + stats.interior_pages + stats.depth + 1 > max_table_pages {
+                return Err(TableError::TablePageLimit(
+                    self.name.clone(),
+                    max_table_pages,
+                ));
+            }
+        }
+        pager
+            .check_file_size(self.root_page_num, row.rowid(), 0)
+            .map_err(TableError::RowInsertError)
+    }
+
+    // Rowid for a new row, one past the largest in use. With a PRIMARY KEY that is the rowid of
+    // the value one past the largest, or of 1 in an empty table
+    pub fn next_rowid(&self) -> Result<u64, TableError> {
+        let max_key = self
             .pager
             .borrow_mut()
-            .insert(&mut cursor, row.rowid(), &row)
+            .max_key(self.root_page_num)
+            .map_err(TableError::ReadError)?;
+        let Some(ColumnItemType::Integer(int_type)) = self.primary_key_type() else {
+            return match max_key {
+                Some(max_key) => max_key.checked_add(1).ok_or(TableError::RowidsExhausted),
+                None => Ok(1),
+            };
+        };
+        let value = max_key.map_or(1, |max_key| int_type.from_key(max_key) + 1);
+        match value <= int_type.range().1 {
+            true => Ok(int_type.to_key(value)),
+            false => Err(TableError::RowidsExhausted),
+        }
+    }
+
+    // Type of the PRIMARY KEY column, if the table has one
+    pub fn primary_key_type(&self) -> Option<&ColumnItemType> {
+        self.columns.get(self.primary_key.as_deref()?)
+    }
+
+    pub fn bloom_filter_enabled(&self) -> bool {
+        self.bloom_filter_enabled.get()
+    }
+
+    pub fn set_bloom_filter_enabled(&self, enabled: bool) {
+        self.bloom_filter_enabled.set(enabled);
+        if !enabled {
+            self.bloom_filter.replace(None);
+        }
+    }
+
+    // Approximate bytes taken up by the bloom filter, if one was built
+    pub fn memory_used(&self) -> usize {
+        self.bloom_filter
+            .borrow()
+            .as_ref()
+            .map_or(0, BloomFilter::memory_used)
+    }
+
+    // Whether a row with the given rowid may exist. Only false when the bloom filter rules it out
+    pub fn may_contain(&self, rowid: u64) -> Result<bool, TableError> {
+        if !self.bloom_filter_enabled.get() {
+            return Ok(true);
+        }
+        if self.bloom_filter.borrow().is_none() {
+            let filter = self.build_bloom_filter()?;
+            self.bloom_filter.replace(Some(filter));
+        }
+        Ok(self
+            .bloom_filter
+            .borrow()
+            .as_ref()
+            .is_none_or(|filter| filter.may_contain(rowid)))
+    }
+
+    // Whether a row with the given rowid exists, skipping the tree if the bloom filter rules it out
+    pub fn contains(&self, rowid: u64) -> Result<bool, TableError> {
+        if !self.may_contain(rowid)? {
+            return Ok(false);
+        }
+        let mut cursor = DBCursor::with_bounds(
+            self.root_page_num,
+            Bound::Included(rowid),
+            Bound::Included(rowid),
+        );
+        let cell = cursor
+            .next_cell(&mut self.pager.borrow_mut())
+            .map_err(TableError::ReadError)?;
+        Ok(cell.is_some())
+    }
+
+    // Counts the statistics again from the tree, builds every index again from the rows, and
+    // drops the bloom filter so that it is built again from the rows on its next use
+    pub fn reindex(&self) -> Result<(), TableError> {
+        for index in &self.indexes {
+            self.pager
+                .borrow_mut()
+                .clear_tree(index.root_page)
+                .map_err(TableError::RowDeleteError)?;
+            self.build_index(index)?;
+        }
+
+        let mut pager = self.pager.borrow_mut();
+        let mut stats = TableStats {
+            num_rows: 0,
+            leaf_pages: 0,
+            interior_pages: 0,
+            depth: 1,
+        };
+        let pages = pager
+            .tree_pages(self.root_page_num)
+            .map_err(TableError::ReadError)?;
+        for page_num in pages {
+            let page = pager.page(page_num).map_err(TableError::ReadError)?;
+            match page.is_leaf() {
+                true => {
+                    stats.leaf_pages += 1;
+                    stats.num_rows += page.num_cells() as u64;
+                }
+                false => stats.interior_pages += 1,
+            }
+        }
+        // Every leaf is at the same depth
+        let mut page = pager
+            .page(self.root_page_num)
+            .map_err(TableError::ReadError)?;
+        while !page.is_leaf() {
+            let child = page.child_at(0)?;
+            page = pager.page(child).map_err(TableError::ReadError)?;
+            stats.depth += 1;
+        }
+
+        self.stats.set(stats);
+        self.stats_dirty.set(true);
+        self.bloom_filter.replace(None);
+        Ok(())
+    }
+
+    // SHA3-256 of the rows in key order, each written as its length followed by its stored bytes,
+    // so that copies of a table hash the same however their pages are laid out
+    pub fn content_hash(&self) -> Result<[u8; 32], TableError> {
+        let mut hasher = Sha3_256::new();
+        let mut cursor = DBCursor::new(self.root_page_num);
+        let mut pager = self.pager.borrow_mut();
+        while let Some(cell) = cursor
+            .next_cell(&mut pager)
+            .map_err(TableError::ReadError)?
         {
-            Ok(()) => Ok(()),
-            Err(PagerError::PageFull) => {
-                todo!()
+            hasher.update(&(cell.value.len() as u64).to_be_bytes());
+            hasher.update(&cell.value);
+        }
+        Ok(hasher.finalize())
+    }
+
+    // Sized for twice the current rows, so the table can grow before the filter is rebuilt
+    fn build_bloom_filter(&self) -> Result<BloomFilter, TableError> {
+        let num_rows = self.stats.get().num_rows as usize;
+        let mut filter = BloomFilter::with_capacity((num_rows * 2).max(1024));
+        let mut cursor = DBCursor::new(self.root_page_num);
+        let mut pager = self.pager.borrow_mut();
+        while let Some(cell) = cursor
+            .next_cell(&mut pager)
+            .map_err(TableError::ReadError)?
+        {
+            filter.insert(cell.id);
+        }
+        Ok(filter)
+    }
+
+    // Inserts every row, leaving the pages they change to be flushed together afterwards. Returns
+    // the number of rows inserted
+    pub fn insert_batch(&self, rows: impl IntoIterator<Item = Row>) -> Result<u64, TableError> {
+        let mut inserted = 0;
+        for row in rows {
+            self.insert(row)?;
+            inserted += 1;
+        }
+        Ok(inserted)
+    }
+
+    pub fn next_row(&self, cursor: &mut DBCursor) -> Result<Option<Row>, TableError> {
+        let mut pager = self.pager.borrow_mut();
+        let cell = cursor
+            .next_cell(&mut pager)
+            .map_err(TableError::ReadError)?;
+        if cell.is_some() {
+            pager.record_rows_read(1);
+        }
+        cell.map(row_from_cell).transpose()
+    }
+
+    // Counts rows read through handles of their own, such as those of a parallel scan
+    pub fn record_rows_read(&self, rows: u64) {
+        self.pager.borrow_mut().record_rows_read(rows);
+    }
+
+    // Splits a rowid range into up to `parts` ranges covering about as many leaf pages each, along
+    // the keys that separate the children of the root page
+    pub fn split_rowid_range(
+        &self,
+        range: RowidRange,
+        parts: usize,
+    ) -> Result<Vec<RowidRange>, TableError> {
+        let separators: Vec<u64> = {
+            let mut pager = self.pager.borrow_mut();
+            let root = pager
+                .page(self.root_page_num)
+                .map_err(TableError::ReadError)?;
+            match root.is_leaf() {
+                true => Vec::new(),
+                false => root.get_keys()?,
             }
-            Err(PagerError::CacheMiss) => self.new_page_and_insert(row),
-            Err(PagerError::TableFull) => Err(TableError::TableFull),
-            Err(other_err) => Err(TableError::RowInsertError(other_err)),
+        };
+
+        // The i-th separator is the largest key under the i-th child
+        let num_children = separators.len() + 1;
+        let mut chosen: Vec<u64> = (1..parts.max(1))
+            .map(|part| part * num_children / parts)
+            .filter(|&child| child > 0)
+            .map(|child| separators[child - 1])
+            .filter(|separator| range.contains(separator))
+            .collect();
+        chosen.dedup();
+
+        let mut ranges = Vec::new();
+        let mut lower = range.0;
+        for separator in chosen {
+            ranges.push((lower, Bound::Included(separator)));
+            lower = Bound::Excluded(separator);
         }
+        ranges.push((lower, range.1));
+        Ok(ranges)
+    }
+
+    // Keys are shown as the value of the primary key they are the rowid for
+    pub fn print_tree(&self) -> Result<String, TableError> {
+        let key_type = match self.primary_key_type() {
+            Some(ColumnItemType::Integer(int_type)) => int_type,
+            _ => &IntegerType::UBigInt,
+        };
+        self.pager
+            .borrow_mut()
+            .print_tree(self.root_page_num, &|key| {
+                key_type.from_key(key).to_string()
+            })
+            .map_err(TableError::ReadError)
+    }
+
+    pub fn print_index_tree(&self, index: &IndexEntry) -> Result<String, TableError> {
+        self.pager
+            .borrow_mut()
+            .print_tree(index.root_page, &|key| key.to_string())
+            .map_err(TableError::ReadError)
     }
 
     pub fn deserialize_rows(&self) -> Result<Vec<Row>, TableError> {
+        let mut cursor = DBCursor::new(self.root_page_num);
         let mut rows: Vec<Row> = Vec::new();
-        for page in self.pager.borrow_mut().pages().filter_map(|p| p.as_ref()) {
-            let deserialized_rows: Result<Vec<Row>, PageError> = page.deserialize_cells();
-            rows.append(&mut deserialized_rows?);
+        while let Some(row) = self.next_row(&mut cursor)? {
+            rows.push(row);
         }
         Ok(rows)
     }
+}
 
-    pub fn flush(&mut self) -> Result<(), TableError> {
-        self.pager
-            .borrow_mut()
-            .flush_all()
-            .map_err(TableError::FlushError)?;
+// Walks the keys of an index that start with some values, giving the rowid each ends with
+pub struct IndexRowids<'a> {
+    table: &'a Table,
+    index_name: String,
+    cursor: DBCursor,
+}
 
-        Ok(())
+impl IndexRowids<'_> {
+    pub fn next_rowid(&mut self) -> Result<Option<u64>, TableError> {
+        let key = self
+            .cursor
+            .next_key(&mut self.table.pager.borrow_mut())
+            .map_err(TableError::ReadError)?;
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        let corrupt = |err| TableError::CorruptIndex(self.index_name.clone(), err);
+        match key::decode_key(&key).map_err(corrupt)?.pop() {
+            Some(SQLType::UBigInt(rowid)) => Ok(Some(rowid)),
+            _ => Err(corrupt(KeyError::Truncated)),
+        }
     }
 }
 
+// Reads the rows of a table within a rowid range through its own read-only handle on the database
+// file, so that several threads can scan parts of one table at once
+pub struct TableReader {
+    pager: Pager,
+    cursor: DBCursor,
+}
+
+impl TableReader {
+    pub fn open(
+        path: &Path,
+        root_page_num: u32,
+        (lower_bound, upper_bound): RowidRange,
+        read_uncommitted: bool,
+        paranoid: bool,
+    ) -> Result<Self, TableError> {
+        let file = File::open(path).map_err(|err| TableError::ReadError(err.into()))?;
+        let mut pager = Pager::open(Box::new(file), true).map_err(TableError::ReadError)?;
+        pager.set_read_uncommitted(read_uncommitted);
+        pager.set_paranoid(paranoid);
+
+        Ok(Self {
+            pager,
+            cursor: DBCursor::with_bounds(root_page_num, lower_bound, upper_bound),
+        })
+    }
+
+    pub fn next_row(&mut self) -> Result<Option<Row>, TableError> {
+        let cell = self
+            .cursor
+            .next_cell(&mut self.pager)
+            .map_err(TableError::ReadError)?;
+        cell.map(row_from_cell).transpose()
+    }
+}
+
+fn row_from_cell(cell: DBCell) -> Result<Row, TableError> {
+    Ok(Row::try_from(&*cell.value).map_err(|_| PageError::CorruptData)?)
+}
+
 impl fmt::Display for Table {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let columns = &self.columns;
         let rows = self.deserialize_rows().unwrap();
 
-        let rows_strings: Vec<_> = rows.iter().map(|row| row.to_printable()).collect();
+        let rows_strings: Vec<_> = rows
+            .into_iter()
+            .map(|row| columns.decode_row(row).to_printable())
+            .collect();
 
         let mut pretty_table_builder = Builder::from(rows_strings);
         pretty_table_builder.insert_record(0, columns.to_printable());
@@ -0,0 +1,119 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::wall_clock;
+
+// 128-bit identifier, written as 32 hex digits grouped as 8-4-4-4-12
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid([u8; 16]);
+
+impl Uuid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    // Random (version 4) UUID. The randomness comes from the standard library's hash keys mixed
+    // with the time, where there is a clock, and a counter, which makes collisions unlikely but is
+    // not cryptographically secure
+    pub fn new_v4() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = wall_clock().map_or(0, |elapsed| elapsed.as_nanos());
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let mut bytes = [0; 16];
+        for half in bytes.chunks_exact_mut(8) {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            hasher.write_u64(count);
+            half.copy_from_slice(&hasher.finish().to_be_bytes());
+        }
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Self(bytes)
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = ();
+
+    // Accepts the hyphenated form in either case. Groups are hex digits only, as
+    // `u8::from_str_radix` would also take a sign
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const GROUP_LENGTHS: [usize; 5] = [8, 4, 4, 4, 12];
+
+        let groups: Vec<&str> = s.split('-').collect();
+        let well_formed = groups.len() == GROUP_LENGTHS.len()
+            && groups.iter().zip(GROUP_LENGTHS).all(|(group, len)| {
+                group.len() == len && group.bytes().all(|byte| byte.is_ascii_hexdigit())
+            });
+        if !well_formed {
+            return Err(());
+        }
+
+        let digits = groups.concat();
+        let mut bytes = [0; 16];
+        for (byte, pair) in bytes.iter_mut().zip(digits.as_bytes().chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| ())?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| ())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, byte) in self.0.iter().enumerate() {
+            if matches!(idx, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Uuid;
+
+    #[test]
+    fn parses_the_hyphenated_form_in_either_case() {
+        let text = "0123abcd-4567-89ef-0123-456789ABCDEF";
+        let uuid: Uuid = text.parse().unwrap();
+        assert_eq!(uuid.to_string(), text.to_lowercase());
+        assert!("0123abcd456789ef0123456789abcdef".parse::<Uuid>().is_err());
+        assert!("0123abcd-4567-89ef-0123-456789abcde"
+            .parse::<Uuid>()
+            .is_err());
+    }
+
+    #[test]
+    fn signs_are_not_hex_digits() {
+        assert!("+123abcd-4567-89ef-0123-456789abcdef"
+            .parse::<Uuid>()
+            .is_err());
+        assert!("0123abcd-+567-89ef-0123-456789abcdef"
+            .parse::<Uuid>()
+            .is_err());
+        assert!("0123abcd-4567-89ef-0123-456789abcd-f"
+            .parse::<Uuid>()
+            .is_err());
+    }
+
+    #[test]
+    fn new_v4_sets_version_and_variant() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_ne!(first, second);
+        assert_eq!(first.as_bytes()[6] >> 4, 4);
+        assert_eq!(first.as_bytes()[8] >> 6, 0b10);
+    }
+}
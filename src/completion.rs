@@ -0,0 +1,77 @@
+use sql_rs::backend::database::Database;
+use sql_rs::sql_compiler::KEYWORDS;
+use sql_rs::virtual_machine::PRAGMA_NAMES;
+
+use crate::line_editor::Completer;
+use crate::metacommand_processor::METACOMMAND_NAMES;
+
+// Completes metacommands at the start of the line, and SQL keywords, pragma names plus the table
+// and column names of the open database everywhere else
+pub struct SqlCompleter<'a> {
+    db: Option<&'a Database>,
+}
+
+impl<'a> SqlCompleter<'a> {
+    pub fn new(db: Option<&'a Database>) -> Self {
+        Self { db }
+    }
+
+    fn names(&self) -> Vec<String> {
+        let Some(db) = self.db else {
+            return Vec::new();
+        };
+
+        let mut names = Vec::new();
+        for table_name in db.table_names() {
+            names.push(table_name.to_string());
+            if let Ok(table) = db.table(table_name) {
+                names.extend(table.columns.keys().cloned());
+            }
+        }
+        names
+    }
+}
+
+impl Completer for SqlCompleter<'_> {
+    fn complete(&self, line: &str, position: usize) -> (usize, Vec<String>) {
+        let start = line[..position]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .map_or(0, |idx| idx + 1);
+        let word = &line[start..position];
+
+        if line[..start].trim().is_empty() && word.starts_with('.') {
+            let candidates = METACOMMAND_NAMES
+                .iter()
+                .filter(|name| name.starts_with(word))
+                .map(|name| name.to_string())
+                .collect();
+            return (start, candidates);
+        }
+        if word.is_empty() {
+            return (start, Vec::new());
+        }
+
+        // Keywords follow the case the user started typing them in
+        let uppercase = word.chars().all(|c| !c.is_lowercase());
+        let lowercase_word = word.to_lowercase();
+        let keywords = KEYWORDS
+            .iter()
+            .chain(&PRAGMA_NAMES)
+            .filter(|keyword| keyword.starts_with(&lowercase_word))
+            .map(|keyword| match uppercase {
+                true => keyword.to_uppercase(),
+                false => keyword.to_string(),
+            });
+
+        let mut candidates: Vec<String> = self
+            .names()
+            .into_iter()
+            .filter(|name| name.starts_with(word))
+            .chain(keywords)
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        (start, candidates)
+    }
+}
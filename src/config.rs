@@ -0,0 +1,52 @@
+use std::env;
+use std::path::PathBuf;
+
+use sql_rs::backend::CacheSize;
+use sql_rs::virtual_machine::result_writer::OutputSettings;
+use sql_rs::Connection;
+
+use crate::metacommand_processor::run_script;
+
+const RC_FILE: &str = ".sql_rsrc";
+
+// Runs the startup script, ~/.sql_rsrc unless SQL_RS_RC names another, so that it can set anything
+// a metacommand or statement can. SQL_RS_MODE, SQL_RS_PROMPT and SQL_RS_CACHE_SIZE then override
+// what it set
+pub fn load_config(connection: &mut Option<Connection>, output_settings: &mut OutputSettings) {
+    if let Some(path) = rc_path().filter(|path| path.is_file()) {
+        if let Err(err) = run_script(&path.to_string_lossy(), connection, output_settings) {
+            eprintln!("{}", err);
+        }
+    }
+
+    if let Ok(mode) = env::var("SQL_RS_MODE") {
+        match mode.parse() {
+            Ok(mode) => output_settings.mode = mode,
+            Err(_) => eprintln!("SQL_RS_MODE: unknown output mode {}", mode),
+        }
+    }
+    if let Ok(prompt) = env::var("SQL_RS_PROMPT") {
+        output_settings.prompt = Some(prompt);
+    }
+    if let Ok(cache_size) = env::var("SQL_RS_CACHE_SIZE") {
+        match cache_size.parse::<CacheSize>() {
+            Ok(cache_size) => {
+                if let Some(connection) = connection.as_mut() {
+                    connection.set_cache_size(cache_size);
+                }
+            }
+            Err(_) => {
+                eprintln!("SQL_RS_CACHE_SIZE expects a number of pages, or of KiB when negative")
+            }
+        }
+    }
+}
+
+// An empty SQL_RS_RC skips the startup script
+fn rc_path() -> Option<PathBuf> {
+    match env::var_os("SQL_RS_RC") {
+        Some(path) if path.is_empty() => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(RC_FILE)),
+    }
+}
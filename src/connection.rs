@@ -0,0 +1,409 @@
+use std::borrow::Cow;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::fs::File;
+use std::io::BufWriter;
+use std::rc::Rc;
+
+#[cfg(feature = "arrow")]
+use crate::arrow::RecordBatch;
+use crate::backend::database::{Database, MEMORY_PATH};
+use crate::backend::storage::Storage;
+use crate::backend::{CacheSize, Quotas};
+use crate::error::SqlRsError;
+use crate::json::JsonValue;
+use crate::metrics::Metrics;
+use crate::sql_compiler::{split_statements, ParseCache, Statement};
+use crate::virtual_machine as VM;
+use crate::virtual_machine::interrupt::InterruptHandle;
+use crate::virtual_machine::progress::{self, ProgressHandler, SharedProgressHandler};
+use crate::virtual_machine::result_writer::{CsvOptions, OutputSettings};
+
+mod rows;
+
+pub use rows::{ColumnMetadata, FromSql, Row, Rows};
+
+/// An open database that SQL can be run against
+pub struct Connection {
+    // Connections handed out by a pool share the database, and with it the page cache
+    db: Rc<RefCell<Database>>,
+    // Whether the open transaction of the database was begun by this connection. The others
+    // cannot run statements until it ends
+    in_transaction: Cell<bool>,
+    changes: Cell<u64>,
+    total_changes: Cell<u64>,
+    parse_cache: ParseCache,
+    interrupt_handle: InterruptHandle,
+    progress_handler: Option<SharedProgressHandler>,
+}
+
+// Statements of `sql` in order, each ending with its `;`. The one after the last statement may be
+// left out, and is added here for the parser
+fn terminated_statements(sql: &str) -> Vec<Cow<'_, str>> {
+    split_statements(sql)
+        .into_iter()
+        .map(|(_, statement)| match statement.ends_with(';') {
+            true => Cow::Borrowed(statement),
+            false => Cow::Owned(format!("{};", statement)),
+        })
+        .collect()
+}
+
+impl Connection {
+    /// Opens the database stored at `path`, creating the file if it does not exist
+    pub fn open(path: &str) -> Result<Self, SqlRsError> {
+        Ok(Self::from_shared(Rc::new(RefCell::new(Database::open(
+            path,
+        )?))))
+    }
+
+    /// Opens an existing database that only accepts queries. Read-only connections to the same
+    /// file share one page cache instead of each loading their own copies of its pages
+    pub fn open_read_only(path: &str) -> Result<Self, SqlRsError> {
+        Ok(Self::from_shared(Rc::new(RefCell::new(
+            Database::open_read_only(path)?,
+        ))))
+    }
+
+    /// Opens a new empty database that is kept in memory and discarded when closed
+    pub fn open_in_memory() -> Result<Self, SqlRsError> {
+        Self::open(MEMORY_PATH)
+    }
+
+    /// Opens a database kept in a custom storage, e.g. a browser store when running on wasm32.
+    /// `name` is only used to refer to the database
+    pub fn open_with_storage(name: &str, storage: Box<dyn Storage>) -> Result<Self, SqlRsError> {
+        Ok(Self::from_shared(Rc::new(RefCell::new(
+            Database::open_with_storage(name, storage)?,
+        ))))
+    }
+
+    pub(crate) fn from_shared(db: Rc<RefCell<Database>>) -> Self {
+        Self {
+            db,
+            in_transaction: Cell::new(false),
+            changes: Cell::new(0),
+            total_changes: Cell::new(0),
+            parse_cache: ParseCache::default(),
+            interrupt_handle: InterruptHandle::default(),
+            progress_handler: None,
+        }
+    }
+
+    /// Runs every statement in `sql` in order, stopping at the first one that fails. Rows
+    /// produced by queries are discarded. Returns the number of rows changed. The `;` after the
+    /// last statement may be left out
+    pub fn execute(&mut self, sql: &str) -> Result<u64, SqlRsError> {
+        self.run(sql, None)
+    }
+
+    /// Runs every statement in `sql` like [`Connection::execute`], but writes the rows produced
+    /// by queries, and whatever else statements print, as `output_settings` says, e.g. to a
+    /// shell's terminal
+    pub fn execute_with_output(
+        &mut self,
+        sql: &str,
+        output_settings: &OutputSettings,
+    ) -> Result<u64, SqlRsError> {
+        self.run(sql, Some(output_settings))
+    }
+
+    fn run(
+        &mut self,
+        sql: &str,
+        output_settings: Option<&OutputSettings>,
+    ) -> Result<u64, SqlRsError> {
+        let mut db = self.db.borrow_mut();
+        self.check_transaction(&db)?;
+        let result = self.interrupt_handle.run(|| {
+            progress::watch(self.progress_handler.as_ref(), || {
+                let mut changes = 0;
+                for statement in terminated_statements(sql) {
+                    changes += match (self.parse_cache.parse(&statement)?, output_settings) {
+                        (statement @ Statement::Select(_), None) => {
+                            db.refresh()?;
+                            VM::query_statement(statement, &db)?;
+                            0
+                        }
+                        (statement, output_settings) => {
+                            let changes_rows = statement.changes_rows();
+                            let changes = VM::execute_statement(
+                                statement,
+                                Some(&mut db),
+                                output_settings.unwrap_or(&OutputSettings::default()),
+                            )?;
+                            if changes_rows {
+                                self.changes.set(changes);
+                                self.total_changes.set(self.total_changes.get() + changes);
+                            }
+                            changes
+                        }
+                    };
+                }
+                Ok(changes)
+            })
+        });
+        // The statements may have begun or ended a transaction. One that failed was undone on its
+        // own, leaving the transaction it ran in open
+        self.in_transaction.set(db.in_transaction());
+        result
+    }
+
+    /// Runs a single SELECT and returns an iterator over its rows
+    pub fn query(&mut self, sql: &str) -> Result<Rows, SqlRsError> {
+        let statements = terminated_statements(sql);
+        let [statement] = statements.as_slice() else {
+            return Err(SqlRsError::NotASingleStatement(statements.len()));
+        };
+
+        let mut db = self.db.borrow_mut();
+        self.check_transaction(&db)?;
+        db.refresh()?;
+        let statement = self.parse_cache.parse(statement)?;
+        let (columns, rows) = self.interrupt_handle.run(|| {
+            progress::watch(self.progress_handler.as_ref(), || {
+                VM::query_statement(statement, &db)
+            })
+        })?;
+        Ok(Rows::new(columns, rows))
+    }
+
+    /// Describes the result columns of a single SELECT without running it, e.g. to announce them
+    /// to a client before sending any row. The statement stays parsed for when it runs
+    pub fn describe(&mut self, sql: &str) -> Result<Vec<ColumnMetadata>, SqlRsError> {
+        let statements = terminated_statements(sql);
+        let [statement] = statements.as_slice() else {
+            return Err(SqlRsError::NotASingleStatement(statements.len()));
+        };
+
+        let mut db = self.db.borrow_mut();
+        self.check_transaction(&db)?;
+        db.refresh()?;
+        let columns = VM::describe_statement(self.parse_cache.parse(statement)?, &db)?;
+        Ok(columns
+            .into_iter()
+            .map(|(name, origin)| ColumnMetadata {
+                name,
+                declared_type: origin.as_ref().map(|origin| origin.column_type.to_string()),
+                table_name: origin.as_ref().map(|origin| origin.table.clone()),
+                origin_name: origin.map(|origin| origin.column),
+            })
+            .collect())
+    }
+
+    /// Runs a single SELECT and returns its rows as a JSON array holding one object per row,
+    /// keyed by column name
+    pub fn query_json(&mut self, sql: &str) -> Result<JsonValue, SqlRsError> {
+        let rows = self.query(sql)?;
+        let columns = rows.columns().to_vec();
+        Ok(JsonValue::Array(
+            rows.map(|row| JsonValue::from_row(&columns, row.into_values()))
+                .collect(),
+        ))
+    }
+
+    /// Runs a single SELECT and returns its result as an Arrow record batch. Each column gets the
+    /// narrowest type holding all its values
+    #[cfg(feature = "arrow")]
+    pub fn query_arrow(&mut self, sql: &str) -> Result<RecordBatch, SqlRsError> {
+        let rows = self.query(sql)?;
+        let columns = rows.columns().to_vec();
+        Ok(RecordBatch::from_rows(
+            columns,
+            rows.map(Row::into_values).collect(),
+        ))
+    }
+
+    /// Runs a single SELECT and writes its rows to a CSV file at `path`, replacing any existing
+    /// file. Rows are streamed to the file as they are produced. Returns the number of rows
+    /// written.
+    pub fn export_csv(
+        &mut self,
+        sql: &str,
+        path: &str,
+        options: CsvOptions,
+    ) -> Result<u64, SqlRsError> {
+        let statements = terminated_statements(sql);
+        let [statement] = statements.as_slice() else {
+            return Err(SqlRsError::NotASingleStatement(statements.len()));
+        };
+        let statement = self.parse_cache.parse(statement)?;
+
+        let mut db = self.db.borrow_mut();
+        self.check_transaction(&db)?;
+        db.refresh()?;
+        let out = Box::new(BufWriter::new(File::create(path)?));
+        Ok(self.interrupt_handle.run(|| {
+            progress::watch(self.progress_handler.as_ref(), || {
+                VM::export_statement(statement, &db, out, options)
+            })
+        })?)
+    }
+
+    /// Stops the statement the connection is running at the next row it reads, making it fail
+    /// with an Interrupted error. Use [`Connection::interrupt_handle`] to do so from another
+    /// thread
+    pub fn interrupt(&self) {
+        self.interrupt_handle.interrupt();
+    }
+
+    /// A handle that interrupts the statements of this connection from any thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt_handle.clone()
+    }
+
+    /// Calls `callback` every `n_ops` steps of the virtual machine while statements run, e.g. to
+    /// update a progress bar. When it returns true the statement is interrupted. Passing `None`,
+    /// or 0 steps, removes the handler
+    pub fn progress_handler<F>(&mut self, n_ops: u64, callback: Option<F>)
+    where
+        F: FnMut() -> bool + 'static,
+    {
+        self.progress_handler = callback.filter(|_| n_ops > 0).map(|callback| {
+            Rc::new(RefCell::new(ProgressHandler::new(
+                n_ops,
+                Box::new(callback),
+            )))
+        });
+    }
+
+    /// Checks the structure of the database file. Returns a description of every problem found,
+    /// so an empty list means the database is sound
+    pub fn integrity_check(&self) -> Vec<String> {
+        self.db.borrow().integrity_check()
+    }
+
+    /// Most pages of the database kept in memory
+    pub fn cache_size(&self) -> CacheSize {
+        self.db.borrow().cache_size()
+    }
+
+    /// Changes how many pages are kept in memory, dropping pages that were not modified right away
+    /// if the cache holds more than the new size
+    pub fn set_cache_size(&mut self, cache_size: CacheSize) {
+        self.db.borrow_mut().set_cache_size(cache_size);
+    }
+
+    /// Limits on how much the database may grow
+    pub fn quotas(&self) -> Quotas {
+        self.db.borrow().quotas()
+    }
+
+    /// Limits the size of the database file, the pages of each table and the size of each row,
+    /// so that a runaway client cannot fill the disk. A statement that would break a limit fails
+    /// with [`ErrorCode::Full`](crate::ErrorCode::Full) before changing anything. Attached
+    /// databases are not limited
+    pub fn set_quotas(&mut self, quotas: Quotas) {
+        self.db.borrow_mut().set_quotas(quotas);
+    }
+
+    /// Approximate bytes of memory taken up by the page caches and bloom filters of the database,
+    /// and by the statements this connection keeps parsed
+    pub fn memory_used(&self) -> usize {
+        self.db.borrow().memory_used() + self.parse_cache.memory_used()
+    }
+
+    /// Most bytes of memory the page cache of each database file may use, on top of its cache size.
+    /// Going over it evicts the least recently used pages, except those changed by an open
+    /// transaction, which stay in memory until it ends. `None` removes the limit
+    pub fn soft_heap_limit(&self) -> Option<usize> {
+        self.db.borrow().soft_heap_limit()
+    }
+
+    /// Changes the soft heap limit, evicting pages right away if the caches are over it
+    pub fn set_soft_heap_limit(&mut self, soft_heap_limit: Option<usize>) {
+        self.db.borrow_mut().set_soft_heap_limit(soft_heap_limit);
+    }
+
+    /// Counters and latency histograms of the work done on the database since it was opened,
+    /// shared by every connection of a pool
+    pub fn metrics(&self) -> Metrics {
+        self.db.borrow().metrics()
+    }
+
+    /// Statements parsed by this connection, which are reused when the same SQL runs again
+    pub fn parse_cache(&self) -> &ParseCache {
+        &self.parse_cache
+    }
+
+    /// Changes how many parsed statements are kept. 0 disables the cache
+    pub fn set_parse_cache_capacity(&mut self, capacity: usize) {
+        self.parse_cache.set_capacity(capacity);
+    }
+
+    /// The underlying database, for access to its tables and statistics
+    pub fn database(&self) -> Ref<'_, Database> {
+        self.db.borrow()
+    }
+
+    /// The underlying database, for changes made other than through SQL, e.g. importing a file
+    pub fn database_mut(&self) -> RefMut<'_, Database> {
+        self.db.borrow_mut()
+    }
+
+    /// Rows changed by the most recent INSERT, UPDATE or DELETE of this connection
+    pub fn changes(&self) -> u64 {
+        self.changes.get()
+    }
+
+    /// Rows changed by every statement of this connection since it was opened
+    pub fn total_changes(&self) -> u64 {
+        self.total_changes.get()
+    }
+
+    // Statements cannot run while another connection of a pool has a transaction open, so they
+    // neither see its changes nor become part of it
+    fn check_transaction(&self, db: &Database) -> Result<(), SqlRsError> {
+        match db.in_transaction() && !self.in_transaction.get() {
+            true => Err(SqlRsError::TransactionInOtherConnection),
+            false => Ok(()),
+        }
+    }
+
+    // Rolls back the transaction the connection left open, before a pool hands it out again
+    pub(crate) fn rollback_transaction(&mut self) -> Result<(), SqlRsError> {
+        if self.in_transaction.replace(false) {
+            self.db.borrow_mut().rollback()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // The last connection to a database closes it
+        if Rc::strong_count(&self.db) == 1 {
+            self.db.borrow_mut().close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Connection, SQLType, SqlRsError};
+
+    #[test]
+    fn the_last_semicolon_may_be_left_out() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+            .unwrap();
+        let changes = conn
+            .execute("INSERT INTO t VALUES (1, 'a;b'); INSERT INTO t VALUES (2, 'c')\n")
+            .unwrap();
+        assert_eq!(changes, 2);
+
+        let names: Vec<SQLType> = conn
+            .query("SELECT name FROM t WHERE id = 1")
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect();
+        assert_eq!(names, [SQLType::Text("a;b".to_string())]);
+        let columns = conn.describe("SELECT id, name FROM t").unwrap();
+        assert_eq!(columns.len(), 2);
+
+        // Still a single statement, terminated or not
+        assert!(matches!(
+            conn.query("SELECT id FROM t; SELECT name FROM t"),
+            Err(SqlRsError::NotASingleStatement(2))
+        ));
+    }
+}
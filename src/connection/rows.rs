@@ -0,0 +1,160 @@
+use std::any::type_name;
+use std::rc::Rc;
+use std::vec;
+
+use crate::backend::row::SQLType;
+use crate::error::SqlRsError;
+
+/// Conversion from a column value to a Rust type, used by [`Row::get`]
+pub trait FromSql: Sized {
+    /// Returns `None` when the value cannot be represented as `Self`
+    fn from_sql(value: &SQLType) -> Option<Self>;
+}
+
+impl FromSql for i64 {
+    fn from_sql(value: &SQLType) -> Option<Self> {
+        match value {
+            SQLType::Integer(num) => Some(*num as i64),
+            SQLType::UBigInt(num) => (*num).try_into().ok(),
+            SQLType::BigInt(num) => Some(*num),
+            SQLType::SmallInt(num) => Some(*num as i64),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for i32 {
+    fn from_sql(value: &SQLType) -> Option<Self> {
+        match value {
+            SQLType::Integer(num) => Some(*num),
+            SQLType::UBigInt(num) => (*num).try_into().ok(),
+            SQLType::BigInt(num) => (*num).try_into().ok(),
+            SQLType::SmallInt(num) => Some(*num as i32),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for u64 {
+    fn from_sql(value: &SQLType) -> Option<Self> {
+        match value {
+            SQLType::Integer(num) => (*num).try_into().ok(),
+            SQLType::UBigInt(num) => Some(*num),
+            SQLType::BigInt(num) => (*num).try_into().ok(),
+            SQLType::SmallInt(num) => (*num).try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for String {
+    fn from_sql(value: &SQLType) -> Option<Self> {
+        match value {
+            SQLType::Text(text) => Some(text.clone()),
+            SQLType::Uuid(uuid) => Some(uuid.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for SQLType {
+    fn from_sql(value: &SQLType) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+// NULL becomes None, any other value has to convert to T
+impl<T: FromSql> FromSql for Option<T> {
+    fn from_sql(value: &SQLType) -> Option<Self> {
+        match value {
+            SQLType::Null => Some(None),
+            value => T::from_sql(value).map(Some),
+        }
+    }
+}
+
+/// One row of a query result
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    columns: Rc<[String]>,
+    values: Vec<SQLType>,
+}
+
+impl Row {
+    /// Reads the value of a result column, converted to `T`
+    pub fn get<T: FromSql>(&self, column: &str) -> Result<T, SqlRsError> {
+        let value = self
+            .columns
+            .iter()
+            .position(|name| name == column)
+            .and_then(|idx| self.values.get(idx))
+            .ok_or_else(|| SqlRsError::NoSuchColumn(column.to_string()))?;
+
+        T::from_sql(value).ok_or_else(|| {
+            SqlRsError::InvalidColumnType(
+                column.to_string(),
+                value.to_string(),
+                type_name::<T>().to_string(),
+            )
+        })
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[SQLType] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<SQLType> {
+        self.values
+    }
+}
+
+/// Description of a result column, known before the query runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMetadata {
+    pub name: String,
+    /// Type of the table column the result column returns as it is. Computed columns have none
+    pub declared_type: Option<String>,
+    /// Table of that column, as the query names it
+    pub table_name: Option<String>,
+    /// Name of that column in its table, which an alias may hide
+    pub origin_name: Option<String>,
+}
+
+/// The rows produced by a query, in order
+#[derive(Debug)]
+pub struct Rows {
+    columns: Rc<[String]>,
+    rows: vec::IntoIter<Vec<SQLType>>,
+}
+
+impl Rows {
+    pub(super) fn new(columns: Vec<String>, rows: Vec<Vec<SQLType>>) -> Self {
+        Self {
+            columns: columns.into(),
+            rows: rows.into_iter(),
+        }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next().map(|values| Row {
+            columns: self.columns.clone(),
+            values,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
@@ -0,0 +1,229 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::backend::database::DatabaseError;
+use crate::backend::table::TableError;
+use crate::backend::{HeaderError, PageError, PagerError};
+use crate::sql_compiler::ParseError;
+use crate::virtual_machine::expr::ExprError;
+use crate::virtual_machine::VMError;
+
+/// Broad category of an error. The numeric value of each code is stable across releases, so it
+/// can be stored or sent to other programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The SQL could not be parsed
+    Syntax = 1,
+    /// The API was used incorrectly, e.g. running a statement with no database open
+    Misuse = 2,
+    /// A table, column or attached database does not exist
+    NotFound = 3,
+    /// A name or key is already taken
+    Constraint = 4,
+    /// A value has the wrong type or is out of range
+    TypeMismatch = 5,
+    /// A value does not fit in a page
+    TooBig = 6,
+    /// The database file is damaged or not a sql_rs database
+    Corrupt = 7,
+    /// Reading or writing a file failed
+    Io = 8,
+    /// The statement was interrupted
+    Interrupted = 9,
+    /// The engine reached a state it should never be in
+    Internal = 10,
+    /// Every connection of a pool is in use, or another process has locked the database
+    Busy = 11,
+    /// A write was attempted on a database opened read-only
+    ReadOnly = 12,
+    /// A limit set on the size of the database, its tables or its rows was reached
+    Full = 13,
+}
+
+impl ErrorCode {
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Every error the library can return
+#[derive(Error, Debug)]
+pub enum SqlRsError {
+    #[error(transparent)]
+    DatabaseError(#[from] DatabaseError),
+    #[error(transparent)]
+    ExprError(#[from] ExprError),
+    #[error(transparent)]
+    HeaderError(#[from] HeaderError),
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    PageError(#[from] PageError),
+    #[error(transparent)]
+    PagerError(#[from] PagerError),
+    #[error(transparent)]
+    ParseError(#[from] ParseError),
+    #[error(transparent)]
+    TableError(#[from] TableError),
+    #[error(transparent)]
+    VMError(#[from] VMError),
+    #[error("Expected a single statement, found {0}")]
+    NotASingleStatement(usize),
+    #[error("No column named {0} in the result")]
+    NoSuchColumn(String),
+    #[error("Value {1} of column {0} cannot be read as {2}")]
+    InvalidColumnType(String, String, String),
+    #[error("All {0} connections of the pool are in use")]
+    PoolExhausted(usize),
+    #[error("Another connection has a transaction open")]
+    TransactionInOtherConnection,
+}
+
+impl SqlRsError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            SqlRsError::DatabaseError(err) => database_error_code(err),
+            SqlRsError::ExprError(err) => expr_error_code(err),
+            SqlRsError::HeaderError(_) => ErrorCode::Corrupt,
+            SqlRsError::IoError(_) => ErrorCode::Io,
+            SqlRsError::PageError(err) => page_error_code(err),
+            SqlRsError::PagerError(err) => pager_error_code(err),
+            SqlRsError::ParseError(_) => ErrorCode::Syntax,
+            SqlRsError::TableError(err) => table_error_code(err),
+            SqlRsError::VMError(err) => vm_error_code(err),
+            SqlRsError::NotASingleStatement(_) => ErrorCode::Misuse,
+            SqlRsError::NoSuchColumn(_) => ErrorCode::NotFound,
+            SqlRsError::InvalidColumnType(..) => ErrorCode::TypeMismatch,
+            SqlRsError::PoolExhausted(_) | SqlRsError::TransactionInOtherConnection => {
+                ErrorCode::Busy
+            }
+        }
+    }
+}
+
+fn page_error_code(err: &PageError) -> ErrorCode {
+    match err {
+        PageError::DuplicateKey(_) => ErrorCode::Constraint,
+        PageError::PageFull => ErrorCode::TooBig,
+        PageError::InsertError => ErrorCode::Internal,
+        PageError::CorruptData
+        | PageError::EndOfSliceWhileDeserializing
+        | PageError::InvalidPageType(_)
+        | PageError::CellPointerArrayOverflow(_)
+        | PageError::CellsStartOutOfRange(_)
+        | PageError::CellOutOfBounds(_)
+        | PageError::PayloadOverflow(_)
+        | PageError::OverlappingCells(_)
+        | PageError::KeysOutOfOrder(_) => ErrorCode::Corrupt,
+    }
+}
+
+fn pager_error_code(err: &PagerError) -> ErrorCode {
+    match err {
+        PagerError::PageRowInsertError(err) => page_error_code(err),
+        PagerError::PageIdxOutOfRange | PagerError::CorruptPage(..) => ErrorCode::Corrupt,
+        PagerError::RowTooLarge(..) => ErrorCode::TooBig,
+        PagerError::IoError(_) => ErrorCode::Io,
+        PagerError::HeaderError(_) => ErrorCode::Corrupt,
+        PagerError::Locked | PagerError::ModifiedExternally => ErrorCode::Busy,
+        PagerError::ReadOnly => ErrorCode::ReadOnly,
+        PagerError::FileSizeLimit(_) => ErrorCode::Full,
+        PagerError::NotAnIndex(_) => ErrorCode::Misuse,
+    }
+}
+
+fn table_error_code(err: &TableError) -> ErrorCode {
+    match err {
+        TableError::RowInsertError(err)
+        | TableError::ReadError(err)
+        | TableError::RowDeleteError(err)
+        | TableError::RowUpdateError(err) => pager_error_code(err),
+        TableError::CorruptPage(err) => page_error_code(err),
+        TableError::RowidsExhausted => ErrorCode::TooBig,
+        TableError::RowSizeLimit(..) | TableError::TablePageLimit(..) => ErrorCode::Full,
+        TableError::CorruptIndex(..) => ErrorCode::Corrupt,
+    }
+}
+
+fn database_error_code(err: &DatabaseError) -> ErrorCode {
+    match err {
+        DatabaseError::ReadFromDiskError(_) => ErrorCode::Io,
+        DatabaseError::DuplicateTable
+        | DatabaseError::DuplicateDatabase(_)
+        | DatabaseError::DuplicateIndex(_) => ErrorCode::Constraint,
+        DatabaseError::TableDoesNotExist
+        | DatabaseError::UnknownDatabase(_)
+        | DatabaseError::IndexDoesNotExist(_) => ErrorCode::NotFound,
+        DatabaseError::CorruptCatalog(_) => ErrorCode::Corrupt,
+        DatabaseError::TransactionAlreadyOpen | DatabaseError::NoTransaction => ErrorCode::Misuse,
+        DatabaseError::PagerError(err) => pager_error_code(err),
+        DatabaseError::TableError(err) => table_error_code(err),
+    }
+}
+
+fn expr_error_code(err: &ExprError) -> ErrorCode {
+    match err {
+        ExprError::UnknownColumn(_) | ExprError::UnknownFunction(_) => ErrorCode::NotFound,
+        ExprError::WrongNumberOfArguments(..) => ErrorCode::Syntax,
+        ExprError::MisusedAggregate(_) | ExprError::UnsupportedSubquery => ErrorCode::Misuse,
+        ExprError::TypeMismatch(..) | ExprError::IntegerOverflow | ExprError::MalformedJson(_) => {
+            ErrorCode::TypeMismatch
+        }
+        ExprError::InvalidJsonPath(_) | ExprError::InvalidRegex(..) => ErrorCode::Syntax,
+    }
+}
+
+fn vm_error_code(err: &VMError) -> ErrorCode {
+    match err {
+        VMError::DBClosed
+        | VMError::NotExplainable
+        | VMError::NotAnalyzable
+        | VMError::NotAQuery
+        | VMError::NoTableForWildcard
+        | VMError::MixedAggregate
+        | VMError::SubqueryColumns(_) => ErrorCode::Misuse,
+        VMError::DuplicatedTableName(_)
+        | VMError::DuplicatedColumnName(_)
+        | VMError::ColumnAlreadyExists(_)
+        | VMError::MultiplePrimaryKeys(_)
+        | VMError::MissingPrimaryKey(_)
+        | VMError::NotNullViolation(_)
+        | VMError::UniqueViolation(_)
+        | VMError::PrimaryKeyViolation(_, _)
+        | VMError::CheckViolation(_)
+        | VMError::ForeignKeyViolation(_)
+        | VMError::DuplicateColumns => ErrorCode::Constraint,
+        VMError::TableWriteError(_, err)
+        | VMError::TableReadError(_, err)
+        | VMError::IndexWriteError(_, err) => database_error_code(err),
+        VMError::AttachError(_, err)
+        | VMError::DetachError(err)
+        | VMError::RefreshError(err)
+        | VMError::StatsError(err)
+        | VMError::TransactionError(err)
+        | VMError::CommitError(err) => database_error_code(err),
+        VMError::ColumnNamesValuesMismatch(..) | VMError::AmbiguousColumn(_) => ErrorCode::Syntax,
+        VMError::ColumnNotInTable(_)
+        | VMError::UnknownPragma(_)
+        | VMError::UnknownReferencedColumn(_) => ErrorCode::NotFound,
+        VMError::InvalidPragmaValue(..) => ErrorCode::Misuse,
+        VMError::ItemParsingError(_)
+        | VMError::ValueOutOfRange(..)
+        | VMError::MalformedInteger(..)
+        | VMError::ValueTooLong(..)
+        | VMError::NoIdParsed
+        | VMError::NonIntegerPrimaryKey(_) => ErrorCode::TypeMismatch,
+        VMError::ItemInsertingError(err) => table_error_code(err),
+        VMError::ExpressionError(err) => expr_error_code(err),
+        VMError::Interrupted => ErrorCode::Interrupted,
+        VMError::ReadOnly => ErrorCode::ReadOnly,
+        VMError::OutputError(_) | VMError::OutfileError(..) | VMError::CsvReadError(..) => {
+            ErrorCode::Io
+        }
+        VMError::CursorNotOpen
+        | VMError::CursorNotPositioned
+        | VMError::UninitializedRegister(_)
+        | VMError::ProgramCounterOutOfRange(_) => ErrorCode::Internal,
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+
+use crate::backend::storage::{Fault, FaultyStorage};
+use crate::connection::Connection;
+use crate::error::SqlRsError;
+
+/// The state a database was left in by a crash partway through some SQL
+#[derive(Debug)]
+pub struct CrashReport {
+    /// Whether the fault was reached. Once it is not, raising `fail_at` any further exercises
+    /// nothing new
+    pub crashed: bool,
+    /// Error returned by the SQL, normally the injected fault
+    pub error: Option<SqlRsError>,
+    /// Problems found by an integrity check of the database reopened after the crash
+    pub problems: Vec<String>,
+}
+
+/// Copies the database at `path` to `scratch_path` and runs `sql` on the copy, making its
+/// `fail_at`th write or flush fail with `fault`. The copy is then reopened as the crash left it
+/// and checked for integrity. It is kept at `scratch_path` for inspection.
+pub fn simulate_crash(
+    path: &str,
+    scratch_path: &str,
+    sql: &str,
+    fail_at: u64,
+    fault: Fault,
+) -> Result<CrashReport, SqlRsError> {
+    fs::copy(path, scratch_path)?;
+    let file = File::options().read(true).write(true).open(scratch_path)?;
+    let storage = FaultyStorage::new(Box::new(file), fail_at, fault);
+    let operations = storage.operations();
+
+    // Nothing reaches the file after the fault, including the flush when the connection closes
+    let error = Connection::open_with_storage(scratch_path, Box::new(storage))?
+        .execute(sql)
+        .err();
+
+    let problems = match Connection::open(scratch_path) {
+        Ok(connection) => connection.integrity_check(),
+        Err(err) => vec![format!("cannot reopen database: {}", err)],
+    };
+    Ok(CrashReport {
+        crashed: operations.get() >= fail_at,
+        error,
+        problems,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::{simulate_crash, CrashReport};
+    use crate::backend::storage::Fault;
+    use crate::connection::Connection;
+    use crate::error::ErrorCode;
+
+    // Runs a few INSERTs on a fresh database with a fault at every write and flush they make in
+    // turn, up to the first position they no longer reach. The last report is of that clean run
+    fn sweep(name: &str, fault: Fault) -> Vec<CrashReport> {
+        let dir = env::temp_dir();
+        let path = dir.join(format!("sql_rs_{}_{}.db", name, process::id()));
+        let scratch = dir.join(format!("sql_rs_{}_{}_scratch.db", name, process::id()));
+        let (path, scratch) = (path.to_str().unwrap(), scratch.to_str().unwrap());
+        let _ = fs::remove_file(path);
+        Connection::open(path)
+            .unwrap()
+            .execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        let sql: String = (0..10)
+            .map(|id| format!("INSERT INTO t VALUES ({}, '{}');", id, "x".repeat(500)))
+            .collect();
+
+        let mut reports = Vec::new();
+        for fail_at in 1..1000 {
+            let report = simulate_crash(path, scratch, &sql, fail_at, fault).unwrap();
+            let crashed = report.crashed;
+            reports.push(report);
+            if !crashed {
+                break;
+            }
+        }
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(scratch);
+        reports
+    }
+
+    fn check_sweep(reports: &[CrashReport]) {
+        let (clean, crashes) = reports.split_last().unwrap();
+        assert!(!clean.crashed, "the sweep never got past the workload");
+        assert!(clean.error.is_none(), "{:?}", clean.error);
+        assert!(clean.problems.is_empty(), "{:?}", clean.problems);
+        assert!(crashes.len() >= 10, "expected a write per INSERT at least");
+        for report in crashes {
+            let error = report.error.as_ref().expect("the fault was not reported");
+            assert_eq!(error.code(), ErrorCode::Io, "{}", error);
+        }
+    }
+
+    #[test]
+    fn failed_writes_leave_partial_statements() {
+        let reports = sweep("crash_error", Fault::Error);
+        check_sweep(&reports);
+
+        // With no journal, the pages a statement changes are not written all or nothing. The
+        // statistics of a table can reach the file without its rows, and once a page splits the
+        // parent can point to a page that never got there
+        let problems: Vec<&String> = reports.iter().flat_map(|report| &report.problems).collect();
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("counted in its statistics")));
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("Page index out of range")));
+    }
+
+    #[test]
+    fn torn_writes_are_found_by_the_integrity_check() {
+        let reports = sweep("crash_torn", Fault::TornWrite);
+        check_sweep(&reports);
+
+        // Half a page can reach the file, which leaves its keys out of order
+        assert!(reports
+            .iter()
+            .flat_map(|report| &report.problems)
+            .any(|problem| problem.contains("keys are not in increasing order")));
+    }
+}
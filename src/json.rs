@@ -0,0 +1,393 @@
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while, take_while1, take_while_m_n},
+    character::complete::{anychar, char, digit0, digit1, multispace0, one_of},
+    combinator::{all_consuming, map, map_opt, map_res, opt, recognize, value, verify},
+    multi::separated_list0,
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
+    IResult,
+};
+
+use crate::backend::row::SQLType;
+
+/// A JSON document. Objects keep their members in insertion order, which for query results is
+/// the order of the result columns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(i128),
+    // Numbers with a fraction or exponent
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    // An object with one member per column
+    pub(crate) fn from_row(columns: &[String], values: Vec<SQLType>) -> Self {
+        JsonValue::Object(
+            columns
+                .iter()
+                .cloned()
+                .zip(values.into_iter().map(JsonValue::from))
+                .collect(),
+        )
+    }
+
+    // Follows a path such as `$.items[0].name` into the document. Returns None if nothing is
+    // found there, or an error if the path is malformed
+    pub(crate) fn extract(&self, path: &str) -> Result<Option<&JsonValue>, ()> {
+        let mut rest = path.strip_prefix('$').ok_or(())?;
+        let mut current = self;
+        while !rest.is_empty() {
+            let (remainder, step) = parse_path_step(rest).map_err(|_| ())?;
+            rest = remainder;
+            let next = match (step, current) {
+                (PathStep::Member(key), JsonValue::Object(members)) => members
+                    .iter()
+                    .find(|(member, _)| *member == key)
+                    .map(|(_, value)| value),
+                (PathStep::Index(idx), JsonValue::Array(values)) => values.get(idx),
+                _ => None,
+            };
+            match next {
+                Some(next) => current = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+}
+
+impl From<SQLType> for JsonValue {
+    fn from(value: SQLType) -> Self {
+        match value {
+            SQLType::UBigInt(num) => JsonValue::Number(num as i128),
+            SQLType::Integer(num) => JsonValue::Number(num as i128),
+            SQLType::Text(text) => JsonValue::String(text),
+            SQLType::Null => JsonValue::Null,
+            SQLType::BigInt(num) => JsonValue::Number(num as i128),
+            SQLType::SmallInt(num) => JsonValue::Number(num as i128),
+            SQLType::Uuid(uuid) => JsonValue::String(uuid.to_string()),
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+// Serializes without any whitespace
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(value) => write!(f, "{}", value),
+            JsonValue::Number(num) => write!(f, "{}", num),
+            JsonValue::Float(num) => write!(f, "{:?}", num),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(values) => {
+                f.write_char('[')?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            JsonValue::Object(members) => {
+                f.write_char('{')?;
+                for (idx, (key, value)) in members.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+enum PathStep<'a> {
+    Member(&'a str),
+    Index(usize),
+}
+
+fn parse_path_step(input: &str) -> IResult<&str, PathStep<'_>> {
+    alt((
+        map(
+            preceded(
+                char('.'),
+                alt((
+                    delimited(char('"'), take_while(|c| c != '"'), char('"')),
+                    take_while1(|c| c != '.' && c != '['),
+                )),
+            ),
+            PathStep::Member,
+        ),
+        map(
+            delimited(char('['), map_res(digit1, str::parse), char(']')),
+            PathStep::Index,
+        ),
+    ))(input)
+}
+
+// Parses a document in the syntax of RFC 8259
+impl FromStr for JsonValue {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        all_consuming(parse_element)(s)
+            .map(|(_, value)| value)
+            .map_err(|_| ())
+    }
+}
+
+fn parse_element(input: &str) -> IResult<&str, JsonValue> {
+    delimited(multispace0, parse_value, multispace0)(input)
+}
+
+fn parse_value(input: &str) -> IResult<&str, JsonValue> {
+    alt((
+        value(JsonValue::Null, tag("null")),
+        value(JsonValue::Bool(true), tag("true")),
+        value(JsonValue::Bool(false), tag("false")),
+        parse_number,
+        map(parse_string, JsonValue::String),
+        map(
+            delimited(
+                pair(char('['), multispace0),
+                separated_list0(char(','), parse_element),
+                char(']'),
+            ),
+            JsonValue::Array,
+        ),
+        map(
+            delimited(
+                pair(char('{'), multispace0),
+                separated_list0(
+                    char(','),
+                    separated_pair(
+                        delimited(multispace0, parse_string, multispace0),
+                        char(':'),
+                        parse_element,
+                    ),
+                ),
+                char('}'),
+            ),
+            JsonValue::Object,
+        ),
+    ))(input)
+}
+
+fn parse_number(input: &str) -> IResult<&str, JsonValue> {
+    let (remainder, number) = recognize(tuple((
+        opt(char('-')),
+        alt((tag("0"), recognize(pair(one_of("123456789"), digit0)))),
+        opt(pair(char('.'), digit1)),
+        opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+    )))(input)?;
+
+    let value = match number.parse::<i128>() {
+        Ok(num) => JsonValue::Number(num),
+        Err(_) => JsonValue::Float(number.parse().unwrap_or(f64::INFINITY)),
+    };
+    Ok((remainder, value))
+}
+
+fn parse_string(input: &str) -> IResult<&str, String> {
+    let (mut rest, _) = char('"')(input)?;
+    let mut parsed = String::new();
+    loop {
+        if let Ok((remainder, _)) = char::<_, nom::error::Error<&str>>('"')(rest) {
+            return Ok((remainder, parsed));
+        }
+        let (remainder, c) = alt((
+            verify(anychar, |&c| c != '"' && c != '\\' && c >= ' '),
+            preceded(char('\\'), parse_escape),
+        ))(rest)?;
+        parsed.push(c);
+        rest = remainder;
+    }
+}
+
+fn parse_escape(input: &str) -> IResult<&str, char> {
+    alt((
+        value('"', char('"')),
+        value('\\', char('\\')),
+        value('/', char('/')),
+        value('\u{8}', char('b')),
+        value('\u{c}', char('f')),
+        value('\n', char('n')),
+        value('\r', char('r')),
+        value('\t', char('t')),
+        // Characters outside the Basic Multilingual Plane are written as a surrogate pair
+        map_opt(
+            separated_pair(
+                preceded(
+                    char('u'),
+                    verify(parse_hex4, |c| (0xd800..0xdc00).contains(c)),
+                ),
+                tag("\\u"),
+                verify(parse_hex4, |c| (0xdc00..0xe000).contains(c)),
+            ),
+            |(high, low)| char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)),
+        ),
+        map_opt(preceded(char('u'), parse_hex4), char::from_u32),
+    ))(input)
+}
+
+fn parse_hex4(input: &str) -> IResult<&str, u32> {
+    map_res(
+        take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+        |hex| u32::from_str_radix(hex, 16),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Connection;
+
+    fn parse(input: &str) -> JsonValue {
+        input.parse().unwrap()
+    }
+
+    fn string(s: &str) -> JsonValue {
+        JsonValue::String(s.to_string())
+    }
+
+    #[test]
+    fn parses_every_kind_of_value() {
+        assert_eq!(
+            parse(r#" { "a" : [1, -2.5, 3e2, null, true, false], "b" : {} , "c": [ ] } "#),
+            JsonValue::Object(vec![
+                (
+                    "a".to_string(),
+                    JsonValue::Array(vec![
+                        JsonValue::Number(1),
+                        JsonValue::Float(-2.5),
+                        JsonValue::Float(300.0),
+                        JsonValue::Null,
+                        JsonValue::Bool(true),
+                        JsonValue::Bool(false),
+                    ])
+                ),
+                ("b".to_string(), JsonValue::Object(vec![])),
+                ("c".to_string(), JsonValue::Array(vec![])),
+            ])
+        );
+        assert_eq!(parse("-0"), JsonValue::Number(0));
+        // Integers too large for i128 are kept as floats
+        assert_eq!(parse(&"9".repeat(40)), JsonValue::Float(1e40));
+    }
+
+    #[test]
+    fn parses_string_escapes() {
+        assert_eq!(
+            parse(r#""a\"b\\c\/d\b\f\n\r\t""#),
+            string("a\"b\\c/d\u{8}\u{c}\n\r\t")
+        );
+        assert_eq!(parse(r#""\u00e9\u4E2D""#), string("é中"));
+        assert_eq!(parse(r#""\ud83d\ude00""#), string("😀"));
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        for input in [
+            "",
+            "[1,]",
+            "{\"a\":1,}",
+            "{a:1}",
+            "01",
+            "1.",
+            ".5",
+            "+1",
+            "'a'",
+            "\"unterminated",
+            "\"tab\there\"",
+            r#""\x""#,
+            r#""\ud83d""#,
+            "[1] [2]",
+            "nul",
+        ] {
+            assert!(input.parse::<JsonValue>().is_err(), "{input:?} parsed");
+        }
+    }
+
+    #[test]
+    fn serializes_to_what_it_parses() {
+        let document = JsonValue::Object(vec![
+            ("quote \"\\".to_string(), string("line\nbreak\u{1}")),
+            (
+                "values".to_string(),
+                JsonValue::Array(vec![
+                    JsonValue::Number(-12),
+                    JsonValue::Float(0.5),
+                    JsonValue::Null,
+                ]),
+            ),
+        ]);
+        let serialized = document.to_string();
+        assert_eq!(
+            serialized,
+            r#"{"quote \"\\":"line\nbreak\u0001","values":[-12,0.5,null]}"#
+        );
+        assert_eq!(parse(&serialized), document);
+    }
+
+    #[test]
+    fn extracts_values_along_a_path() {
+        let document = parse(r#"{"items": [{"name": "a"}, {"name": "b"}], "a.b": 1}"#);
+        assert_eq!(document.extract("$"), Ok(Some(&document)));
+        assert_eq!(document.extract("$.items[1].name"), Ok(Some(&string("b"))));
+        assert_eq!(
+            document.extract(r#"$."a.b""#),
+            Ok(Some(&JsonValue::Number(1)))
+        );
+        assert_eq!(document.extract("$.items[2]"), Ok(None));
+        assert_eq!(document.extract("$.items.name"), Ok(None));
+        assert_eq!(document.extract("items"), Err(()));
+        assert_eq!(document.extract("$.items[x]"), Err(()));
+    }
+
+    #[test]
+    fn query_results_become_an_array_of_objects() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT, note TEXT);")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (2, 'say \"hi\"', NULL);")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (1, 'a', 'b');").unwrap();
+
+        let result = conn.query_json("SELECT note, id, name FROM t;").unwrap();
+        assert_eq!(
+            result.to_string(),
+            r#"[{"note":"b","id":1,"name":"a"},{"note":null,"id":2,"name":"say \"hi\""}]"#
+        );
+        assert_eq!(
+            conn.query_json("SELECT id FROM t WHERE id > 5;").unwrap(),
+            JsonValue::Array(vec![])
+        );
+    }
+}
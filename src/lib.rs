@@ -0,0 +1,24 @@
+//! sql_rs is a small SQLite-like database engine. [`Connection`] opens a database file and runs
+//! SQL against it; the `sql_rs` binary is a REPL built on top of it.
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod backend;
+mod connection;
+mod error;
+pub mod fault_injection;
+mod json;
+mod metrics;
+mod pool;
+pub mod sql_compiler;
+pub mod virtual_machine;
+
+pub use backend::row::SQLType;
+pub use backend::storage::{Fault, FaultyStorage, MemoryStorage, Storage};
+pub use connection::{ColumnMetadata, Connection, FromSql, Row, Rows};
+pub use error::{ErrorCode, SqlRsError};
+pub use json::JsonValue;
+pub use metrics::{Histogram, Metrics};
+pub use pool::{Pool, PooledConnection};
+pub use virtual_machine::interrupt::InterruptHandle;
+pub use virtual_machine::result_writer::CsvOptions;
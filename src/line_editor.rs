@@ -0,0 +1,220 @@
+use std::io::{self, Write};
+
+use console::{measure_text_width, Key, Term};
+use dialoguer::{BasicHistory, History};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReadLineError {
+    #[error("End of input")]
+    Eof,
+    #[error("Input interrupted")]
+    Interrupted,
+    #[error("Could not read input: {0}")]
+    IoError(#[from] io::Error),
+}
+
+// Provides the candidates for Tab completion of the word ending at the cursor
+pub trait Completer {
+    // Returns where the word being completed starts and the possible replacements for it
+    fn complete(&self, line: &str, position: usize) -> (usize, Vec<String>);
+}
+
+// Minimal line editor for the REPL prompt. Supports cursor movement, history and reports Ctrl-D
+// on an empty line as end of input, which the dialoguer prompts swallow.
+pub struct LineEditor {
+    term: Term,
+    history: BasicHistory,
+    // Decorates the buffer with terminal styles when it is drawn
+    highlighter: Option<fn(&str) -> String>,
+    // Terminal rows between the start of the prompt and the cursor in the last render
+    cursor_row: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            term: Term::stderr(),
+            history: BasicHistory::new().max_entries(100).no_duplicates(true),
+            highlighter: None,
+            cursor_row: 0,
+        }
+    }
+
+    pub fn set_highlighter(&mut self, highlighter: fn(&str) -> String) {
+        self.highlighter = Some(highlighter);
+    }
+
+    pub fn add_history(&mut self, entry: &str) {
+        self.history.write(&entry);
+    }
+
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        completer: &dyn Completer,
+    ) -> Result<String, ReadLineError> {
+        let mut buffer: Vec<char> = Vec::new();
+        let mut position = 0;
+        // Whether the previous key was a Tab that could not complete anything by itself
+        let mut listing_ready = false;
+        // Index of the history entry being shown, if any
+        let mut history_position: Option<usize> = None;
+        self.cursor_row = 0;
+
+        loop {
+            self.render(prompt, &buffer, position)?;
+
+            let key = match self.term.read_key() {
+                Ok(key) => key,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(ReadLineError::Eof)
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                    self.finish_line(prompt, &buffer)?;
+                    return Err(ReadLineError::Interrupted);
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            if key != Key::Tab {
+                listing_ready = false;
+            }
+
+            match key {
+                Key::Tab => {
+                    let line: String = buffer.iter().collect();
+                    let byte_position = line
+                        .char_indices()
+                        .nth(position)
+                        .map_or(line.len(), |(idx, _)| idx);
+                    let (start, candidates) = completer.complete(&line, byte_position);
+                    let word = &line[start..byte_position];
+
+                    let common = common_prefix(&candidates);
+                    if common.chars().count() > word.chars().count() {
+                        let word_start = line[..start].chars().count();
+                        let completion: Vec<char> = common.chars().collect();
+                        let inserted = completion.len() - (position - word_start);
+                        buffer.splice(word_start..position, completion);
+                        position += inserted;
+                        if candidates.len() == 1 {
+                            buffer.insert(position, ' ');
+                            position += 1;
+                        }
+                    } else if listing_ready && !candidates.is_empty() {
+                        // A second Tab shows every candidate below the input
+                        self.finish_line(prompt, &buffer)?;
+                        writeln!(self.term, "{}", candidates.join("  "))?;
+                    } else {
+                        listing_ready = true;
+                    }
+                }
+                Key::Enter => {
+                    self.finish_line(prompt, &buffer)?;
+                    return Ok(buffer.into_iter().collect());
+                }
+                Key::CtrlC => {
+                    self.finish_line(prompt, &buffer)?;
+                    return Err(ReadLineError::Interrupted);
+                }
+                Key::Char('\x04') if buffer.is_empty() => {
+                    self.finish_line(prompt, &buffer)?;
+                    return Err(ReadLineError::Eof);
+                }
+                Key::Char('\x04') | Key::Del if position < buffer.len() => {
+                    buffer.remove(position);
+                }
+                Key::Backspace if position > 0 => {
+                    position -= 1;
+                    buffer.remove(position);
+                }
+                Key::ArrowLeft if position > 0 => position -= 1,
+                Key::ArrowRight if position < buffer.len() => position += 1,
+                Key::Home => position = 0,
+                Key::End => position = buffer.len(),
+                Key::ArrowUp | Key::ArrowDown => {
+                    let next_position = match (key, history_position) {
+                        (Key::ArrowUp, None) => Some(0),
+                        (Key::ArrowUp, Some(pos)) => Some(pos + 1),
+                        (_, Some(0)) | (_, None) => None,
+                        (_, Some(pos)) => Some(pos - 1),
+                    };
+                    let entry = match next_position {
+                        Some(pos) => match History::<String>::read(&self.history, pos) {
+                            Some(entry) => entry,
+                            None => continue,
+                        },
+                        None => String::new(),
+                    };
+                    history_position = next_position;
+                    buffer = entry.chars().collect();
+                    position = buffer.len();
+                }
+                Key::Char(c) if !c.is_control() => {
+                    buffer.insert(position, c);
+                    position += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Redraws the prompt and buffer, which may span several terminal rows, and places the cursor
+    fn render(&mut self, prompt: &str, buffer: &[char], position: usize) -> io::Result<()> {
+        let width = (self.term.size().1 as usize).max(1);
+
+        self.term.move_cursor_up(self.cursor_row)?;
+        write!(self.term, "\r")?;
+        self.term.clear_to_end_of_screen()?;
+
+        let mut text: String = buffer.iter().collect();
+        if let Some(highlight) = self.highlighter {
+            text = highlight(&text);
+        }
+        write!(self.term, "{}{}", prompt, text)?;
+
+        let prompt_width = measure_text_width(prompt);
+        let end = prompt_width + buffer.len();
+        // Terminals keep the cursor on the last column after filling a row, so wrap it explicitly
+        if end > 0 && end.is_multiple_of(width) {
+            write!(self.term, "\r\n")?;
+        }
+
+        let cursor = prompt_width + position;
+        self.term.move_cursor_up(end / width - cursor / width)?;
+        write!(self.term, "\r")?;
+        self.term.move_cursor_right(cursor % width)?;
+        self.cursor_row = cursor / width;
+        self.term.flush()
+    }
+
+    // Leaves the cursor on a fresh line below the input
+    fn finish_line(&mut self, prompt: &str, buffer: &[char]) -> io::Result<()> {
+        self.render(prompt, buffer, buffer.len())?;
+        self.cursor_row = 0;
+
+        // A buffer ending exactly at the edge of the terminal already moved to the next row
+        let end = measure_text_width(prompt) + buffer.len();
+        if end.is_multiple_of((self.term.size().1 as usize).max(1)) {
+            return Ok(());
+        }
+        writeln!(self.term)
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix: &str = first;
+    for candidate in rest {
+        let common_len = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.len()), |((idx, _), _)| idx);
+        prefix = &prefix[..common_len];
+    }
+    prefix.to_string()
+}
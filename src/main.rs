@@ -1,56 +1,346 @@
 use std::env;
 use std::error::Error;
+use std::io::{self, BufRead, IsTerminal};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::time::Instant;
 
-use dialoguer::{theme::ColorfulTheme, BasicHistory, Input};
+use console::style;
 
-mod backend;
+mod completion;
+mod config;
+mod line_editor;
 mod metacommand_processor;
-mod sql_compiler;
-mod virtual_machine;
 
-use backend::database::Database;
+use completion::SqlCompleter;
+use config::load_config;
+use line_editor::{LineEditor, ReadLineError};
 use metacommand_processor::{open_metacommand, process_metacommand};
-use sql_compiler::parse_statement;
-use virtual_machine as VM;
+use sql_rs::backend::CacheSize;
+use sql_rs::sql_compiler::{self, annotate, find_word, is_complete, split_statements, ParseError};
+use sql_rs::virtual_machine as VM;
+use sql_rs::{Connection, SqlRsError};
+use VM::result_writer::OutputSettings;
+use VM::VMError;
 
-fn process_input(input_str: &str, db_instance: &mut Option<Database>) {
+// The prompt set with .prompt, or the name of the open database. The latter is marked with a `*`
+// while a transaction is open, as its changes are not yet committed
+fn prompt(connection: &Option<Connection>, output_settings: &OutputSettings) -> String {
+    let db = connection.as_ref().map(Connection::database);
+    let prompt = match (&output_settings.prompt, db) {
+        (Some(prompt), _) => prompt.to_string(),
+        (None, Some(db)) if db.in_transaction() => format!("{}*>", db.name()),
+        (None, Some(db)) => format!("{}>", db.name()),
+        (None, None) => "db>".to_string(),
+    };
+    format!("{} ", style(prompt).bold())
+}
+
+fn continuation_prompt(output_settings: &OutputSettings) -> String {
+    let prompt = output_settings
+        .continuation_prompt
+        .as_deref()
+        .unwrap_or("...>");
+    format!("{} ", style(prompt).bold())
+}
+
+// Ctrl-C while a statement runs cancels it instead of killing the shell. At the prompt the line
+// editor reads Ctrl-C as a key, so the signal only arrives while something is executing.
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        VM::interrupt::interrupt();
+    }
+
+    let handler: extern "C" fn(libc::c_int) = on_sigint;
+    // SAFETY: the handler only performs an atomic store, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
+
+// Runs a single metacommand or SQL statement. A panic while it runs is reported as an error, after
+// throwing away whatever the statement changed, so that the session carries on
+fn run_input(
+    input_str: &str,
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+) -> Result<(), Box<dyn Error>> {
+    let run = AssertUnwindSafe(|| run_statement(input_str, connection, output_settings));
+    let payload = match panic::catch_unwind(run) {
+        Ok(result) => return result,
+        Err(payload) => payload,
+    };
+    let cause = match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(cause), _) => cause.to_string(),
+        (_, Some(cause)) => cause.clone(),
+        _ => "unknown cause".to_string(),
+    };
+
+    if output_settings.once {
+        output_settings.reset_output();
+    }
+    output_settings.expert = false;
+    let Some(connection) = connection.as_mut() else {
+        return Err(format!("Internal error: {}, nothing was changed", cause).into());
+    };
+    let mut db = connection.database_mut();
+    let discarded = match db.in_transaction() {
+        true => "the open transaction was rolled back",
+        false => "changes made by the statement were discarded",
+    };
+    db.abort()?;
+    Err(format!("Internal error: {}, {}", cause, discarded).into())
+}
+
+fn run_statement(
+    input_str: &str,
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+) -> Result<(), Box<dyn Error>> {
     if input_str.starts_with('.') {
-        if let Err(metacommand_err) = process_metacommand(input_str, db_instance) {
-            eprintln!("{}", metacommand_err)
+        return Ok(process_metacommand(input_str, connection, output_settings)?);
+    }
+
+    let Some(connection) = connection.as_mut() else {
+        // Malformed SQL is reported as such even with no database open
+        sql_compiler::parse_statement(input_str)?;
+        return Err(VMError::DBClosed.into());
+    };
+    let pages_read_before = connection.database().pager_metrics().pages_read;
+    let start = Instant::now();
+
+    let result = connection.execute_with_output(input_str, output_settings);
+    if output_settings.once {
+        output_settings.reset_output();
+    }
+    output_settings.expert = false;
+
+    if output_settings.timer {
+        let pages_read = connection.database().pager_metrics().pages_read - pages_read_before;
+        println!(
+            "Run Time: real {:.6} s, pages read {}",
+            start.elapsed().as_secs_f64(),
+            pages_read
+        );
+    }
+
+    result?;
+    let changes_rows =
+        || sql_compiler::parse_statement(input_str).is_ok_and(|statement| statement.changes_rows());
+    if output_settings.changes && changes_rows() {
+        let changes = connection.changes();
+        let rows = if changes == 1 { "row" } else { "rows" };
+        println!(
+            "{} {} affected, {} total changes",
+            changes,
+            rows,
+            connection.total_changes()
+        );
+    }
+
+    Ok(())
+}
+
+// Prints the error in red, followed by the line of the statement it was found in with the spot
+// underlined, when the error tells where that is
+fn report_error(prefix: &str, statement: &str, err: &(dyn Error + 'static)) {
+    eprintln!("{}{}", prefix, style(err).red().for_stderr());
+    let (parse_error, vm_error) = match err.downcast_ref::<SqlRsError>() {
+        Some(SqlRsError::ParseError(err)) => (Some(err), None),
+        Some(SqlRsError::VMError(err)) => (None, Some(err)),
+        _ => (
+            err.downcast_ref::<ParseError>(),
+            err.downcast_ref::<VMError>(),
+        ),
+    };
+    let offset = match (parse_error, vm_error) {
+        (Some(ParseError::MalformedStatement(offset, _)), _) => Some(*offset),
+        (Some(ParseError::UnknownStatement), _) => {
+            Some(statement.len() - statement.trim_start().len())
         }
-        return;
+        (_, Some(err)) => err
+            .subject()
+            .and_then(|subject| find_word(statement, subject)),
+        _ => None,
+    };
+    if let Some(offset) = offset {
+        eprintln!("{}", annotate(statement, offset));
+    }
+}
+
+fn process_input(
+    input_str: &str,
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+) {
+    if let Err(err) = run_input(input_str, connection, output_settings) {
+        report_error("", input_str, err.as_ref());
     }
-    match parse_statement(input_str) {
-        Ok(parsed_statement) => {
-            let _ = VM::execute_statement(parsed_statement, db_instance.as_mut())
-                .inspect_err(|err| eprintln!("{}", err));
+}
+
+// Runs statements piped through stdin as soon as each one is complete. Returns whether all of
+// them succeeded. With bail set, nothing runs after the first failure, and otherwise the number of
+// failures is reported once the input ends.
+fn run_non_interactive(
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+) -> bool {
+    let mut statements = 0;
+    let mut failures = 0;
+    let mut buffer = String::new();
+    let mut buffer_start_line = 1;
+    let mut lines_read = 0;
+
+    // Returns whether to go on running the input
+    let mut run_buffer = |buffer: &str, buffer_start_line: usize| {
+        for (line, statement) in split_statements(buffer) {
+            statements += 1;
+            if let Err(err) = run_input(statement, connection, output_settings) {
+                let prefix = format!("Error near line {}: ", buffer_start_line + line - 1);
+                report_error(&prefix, statement, err.as_ref());
+                failures += 1;
+                if output_settings.bail {
+                    return false;
+                }
+            }
+        }
+        true
+    };
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        lines_read += 1;
+        buffer.push_str(&line);
+        buffer.push('\n');
+
+        if is_complete(&buffer) {
+            if !run_buffer(&buffer, buffer_start_line) {
+                return false;
+            }
+            buffer.clear();
+            buffer_start_line = lines_read + 1;
         }
-        Err(parse_error) => eprintln!("{}", parse_error),
     }
+    if run_buffer(&buffer, buffer_start_line) && failures > 0 {
+        eprintln!("{} of {} statements failed", failures, statements);
+    }
+
+    failures == 0
 }
 
-fn parse_args(db_instance: &mut Option<Database>, args: Vec<String>) {
-    if args.len() > 1 {
-        let _ = open_metacommand(db_instance, args[1..].to_vec())
-            .inspect_err(|err| eprintln!("{}", err));
+// Opens the database named on the command line and loads the startup settings. `--cache-size N`
+// sets its page cache size, in pages or in KiB when negative, and `--bail` stops scripts at their
+// first error. Both override the startup settings
+fn parse_args(
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) {
+    let mut args = args.into_iter().skip(1);
+    let mut cache_size = None;
+    let mut bail = false;
+    let mut open_args = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cache-size" => match args.next().map(|size| size.parse::<CacheSize>()) {
+                Some(Ok(size)) => cache_size = Some(size),
+                _ => eprintln!("--cache-size expects a number of pages, or of KiB when negative"),
+            },
+            "--bail" => bail = true,
+            _ => open_args.push(arg),
+        }
+    }
+
+    if !open_args.is_empty() {
+        let _ = open_metacommand(connection, open_args).inspect_err(|err| eprintln!("{}", err));
+    }
+    load_config(connection, output_settings);
+    output_settings.bail |= bail;
+    if let (Some(connection), Some(cache_size)) = (connection.as_mut(), cache_size) {
+        connection.set_cache_size(cache_size);
     }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
-    let mut db_instance = None;
+    let mut connection = None;
+    let mut output_settings = OutputSettings::default();
+
+    parse_args(&mut connection, &mut output_settings, args);
 
-    parse_args(&mut db_instance, args);
+    if !io::stdin().is_terminal() {
+        let success = run_non_interactive(&mut connection, &mut output_settings);
+        if let Some(connection) = connection.as_mut() {
+            connection.database_mut().close();
+        }
+        process::exit(if success { 0 } else { 1 });
+    }
 
-    let mut prompt_history = BasicHistory::new().max_entries(8).no_duplicates(true);
+    install_interrupt_handler();
+    let mut line_editor = LineEditor::new();
+    line_editor.set_highlighter(sql_compiler::highlight);
+    // Lines of a statement that has not been terminated yet
+    let mut buffer = String::new();
 
     loop {
-        if let Ok(input) = Input::<String>::with_theme(&ColorfulTheme::default())
-            .with_prompt("db")
-            .history_with(&mut prompt_history)
-            .interact_text()
-        {
-            process_input(input.trim(), &mut db_instance);
+        let current_prompt = match buffer.is_empty() {
+            true => prompt(&connection, &output_settings),
+            false => continuation_prompt(&output_settings),
+        };
+
+        let line = {
+            let db = connection.as_ref().map(Connection::database);
+            let completer = SqlCompleter::new(db.as_deref());
+            line_editor.read_line(&current_prompt, &completer)
+        };
+        match line {
+            Ok(input) => {
+                if buffer.is_empty() && input.trim().is_empty() {
+                    continue;
+                }
+                VM::interrupt::clear_interrupt();
+                // Metacommands always fit in a single line
+                if buffer.is_empty() && input.trim_start().starts_with('.') {
+                    line_editor.add_history(input.trim());
+                    process_input(input.trim(), &mut connection, &mut output_settings);
+                    continue;
+                }
+
+                buffer.push_str(&input);
+                buffer.push('\n');
+                if !is_complete(&buffer) {
+                    continue;
+                }
+
+                // History entries are recalled into a single line
+                line_editor.add_history(&buffer.trim().replace('\n', " "));
+                for (_, statement) in split_statements(&buffer) {
+                    process_input(statement, &mut connection, &mut output_settings);
+                }
+                buffer.clear();
+            }
+            // Ctrl-C drops whatever statement was being typed
+            Err(ReadLineError::Interrupted) => buffer.clear(),
+            // End of input behaves like .exit
+            Err(ReadLineError::Eof) => break,
+            Err(err) => {
+                eprintln!("{}", err);
+                break;
+            }
         }
     }
+
+    if let Some(connection) = connection.as_mut() {
+        connection.database_mut().close();
+    }
+    Ok(())
 }
@@ -1,18 +1,89 @@
-use std::env;
+use std::cell::RefMut;
 use std::fs;
+use std::path::Path;
 use std::str::FromStr;
 
 use thiserror::Error;
 
-use crate::backend::database::Database;
+use sql_rs::backend::database::Database;
+use sql_rs::backend::recover::recover;
+use sql_rs::backend::sha3::to_hex;
+use sql_rs::sql_compiler::{format_sql, split_statements};
+use sql_rs::virtual_machine::interrupt::is_interrupted;
+use sql_rs::virtual_machine::result_writer::{OutputMode, OutputSettings};
+use sql_rs::Connection;
+
+mod bench;
+mod dump;
+mod import;
+mod seed;
+
+use bench::{bench, bench_copy, BenchError};
+use dump::{dump, DumpError};
+use import::{import_csv, ImportError};
+use seed::{seed, SeedError};
 
 const SUCCESS: i32 = 0;
 
+pub const METACOMMAND_NAMES: &[&str] = &[
+    ".bail",
+    ".bench",
+    ".btree",
+    ".changes",
+    ".clone",
+    ".close",
+    ".databases",
+    ".dump",
+    ".exit",
+    ".expert",
+    ".format",
+    ".import",
+    ".mode",
+    ".once",
+    ".open",
+    ".output",
+    ".parsecache",
+    ".prompt",
+    ".quit",
+    ".raw",
+    ".read",
+    ".recover",
+    ".schema",
+    ".seed",
+    ".sha3sum",
+    ".stats",
+    ".timer",
+    ".width",
+];
+
 enum Metacommand {
+    Bail,
+    Bench,
+    Btree,
+    Changes,
+    Clone,
     Close,
     Databases,
+    Dump,
     Exit,
+    Expert,
+    Format,
+    Import,
+    Mode,
+    Once,
     Open,
+    Output,
+    ParseCache,
+    Prompt,
+    Raw,
+    Read,
+    Recover,
+    Schema,
+    Seed,
+    Sha3sum,
+    Stats,
+    Timer,
+    Width,
 }
 
 #[derive(Error, Debug)]
@@ -21,79 +92,745 @@ pub enum MetacommandErr {
     DBClosed,
     #[error("Extra argument: : {0}")]
     ExtraArgument(String),
-    #[error("Error when executing .databases metacommand: {0}")]
-    ListDatabasesError(String),
+    #[error("Error when executing .import metacommand: {0}")]
+    ImportError(#[from] ImportError),
+    #[error("Error when executing .bench metacommand: {0}")]
+    BenchError(#[from] BenchError),
+    #[error("Error when executing .dump metacommand: {0}")]
+    DumpError(#[from] DumpError),
+    #[error("Error when executing .seed metacommand: {0}")]
+    SeedError(#[from] SeedError),
+    #[error("Cannot format statement: {0}")]
+    FormatError(String),
     #[error("Not a metacommand")]
     NotAMetacommand,
+    #[error("Expected \"on\" or \"off\", found {0}")]
+    ExpectedOnOff(String),
+    #[error("Unknown output mode {0}. Valid modes are: {1}")]
+    UnknownMode(String, String),
+    #[error("Invalid exit status: {0}")]
+    InvalidExitStatus(String),
+    #[error("Invalid number of runs: {0}")]
+    InvalidRuns(String),
+    #[error("Invalid number of rows: {0}")]
+    InvalidRowCount(String),
+    #[error("Invalid parse cache size: {0}")]
+    InvalidCacheSize(String),
+    #[error("Invalid column width: {0}")]
+    InvalidWidth(String),
+    #[error("Unterminated quote in arguments: {0}")]
+    UnterminatedQuote(String),
+    #[error("Backslash at the end of arguments: {0}")]
+    UnterminatedEscape(String),
+    #[error("Missing argument: {0}")]
+    MissingArgument(String),
     #[error("Cannot open database {0}. Encountered the following error: {1}")]
     OpenDBError(String, String),
+    #[error("Cannot print schema of table {0}: {1}")]
+    SchemaError(String, String),
+    #[error("Cannot print B-tree of table {0}: {1}")]
+    BtreeError(String, String),
+    #[error("Cannot hash table {0}: {1}")]
+    Sha3sumError(String, String),
+    #[error("Cannot clone database into {0}: {1}")]
+    CloneError(String, String),
+    #[error("Cannot open output file {0}: {1}")]
+    OutputFileError(String, String),
+    #[error("Cannot recover {0}: {1}")]
+    RecoverError(String, String),
+    #[error("Cannot read script {0}: {1}")]
+    ReadScriptError(String, String),
+    #[error("Stopped script {0} at the error on line {1}")]
+    ScriptFailed(String, usize),
+    #[error("Parquet output is not available, as sql_rs was built without the parquet feature")]
+    ParquetUnavailable,
+    #[error("Interrupted")]
+    Interrupted,
     #[error("Unrecognized Metacommand: {0}")]
     UnrecognizedMetacommand(String),
 }
 
-fn list_db_files() -> std::io::Result<()> {
-    let current_dir = env::current_dir()?;
-
-    let extension = "db";
-
-    for entry in fs::read_dir(current_dir)? {
-        let entry = entry?;
-        let path = entry.path();
+// Makes scripts stop at their first failing statement, with the script failing as a whole
+fn bail_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    output_settings.bail = parse_on_off(args)?;
+    Ok(())
+}
 
-        if path.is_file() {
-            if let Some(ext) = path.extension() {
-                if ext == extension {
-                    println!("{}", path.display());
+// Splits arguments as a shell would: on whitespace, except within single or double quotes. Single
+// quotes keep everything up to the closing quote as it is. A backslash within double quotes, or
+// outside of any quotes, escapes the next character
+fn split_arguments(text: &str) -> Result<Vec<String>, MetacommandErr> {
+    let mut args = Vec::new();
+    // Argument being read, if any. Quotes start one even when nothing is written between them
+    let mut arg: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(MetacommandErr::UnterminatedQuote(text.to_string())),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => arg.push(unescape(chars.next(), text)?),
+                        Some(c) => arg.push(c),
+                        None => return Err(MetacommandErr::UnterminatedQuote(text.to_string())),
+                    }
                 }
             }
+            '\\' => match chars.next() {
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => return Err(MetacommandErr::UnterminatedEscape(text.to_string())),
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
         }
     }
+    args.extend(arg);
+    Ok(args)
+}
+
+// Character written by a backslash followed by `c`. `\n` and `\t` stand for a new line and a tab,
+// and any other character for itself
+fn unescape(c: Option<char>, text: &str) -> Result<char, MetacommandErr> {
+    match c {
+        Some('n') => Ok('\n'),
+        Some('t') => Ok('\t'),
+        Some(c) => Ok(c),
+        None => Err(MetacommandErr::UnterminatedEscape(text.to_string())),
+    }
+}
+
+// Splits off the first word of arguments that end in SQL, which is left with its own quoting
+fn split_first_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    text.split_once(char::is_whitespace).unwrap_or((text, ""))
+}
 
+// Database of the open connection, which metacommands work on directly
+fn open_database(connection: &Option<Connection>) -> Result<RefMut<'_, Database>, MetacommandErr> {
+    let connection = connection.as_ref().ok_or(MetacommandErr::DBClosed)?;
+    Ok(connection.database_mut())
+}
+
+// SQL given as the last argument of a metacommand. It is read as written unless it starts with a
+// quote, in which case it is a single argument unquoted as `split_arguments` does
+fn sql_argument(text: &str) -> Result<String, MetacommandErr> {
+    let sql = text.trim();
+    if !sql.starts_with(['\'', '"']) {
+        return match sql.is_empty() {
+            true => Err(MetacommandErr::MissingArgument("SQL".to_string())),
+            false => Ok(sql.to_string()),
+        };
+    }
+    match split_arguments(sql)?.as_slice() {
+        [sql] if !sql.trim().is_empty() => Ok(sql.to_string()),
+        [_, extra, ..] => Err(MetacommandErr::ExtraArgument(extra.to_string())),
+        _ => Err(MetacommandErr::MissingArgument("SQL".to_string())),
+    }
+}
+
+// Runs a statement N times and reports how long the runs took and the pages they touched. With
+// --copy the runs go to a temporary copy of the database. The statement may be quoted
+fn bench_metacommand(
+    connection: &mut Option<Connection>,
+    text: &str,
+) -> Result<(), MetacommandErr> {
+    let mut db = open_database(connection)?;
+
+    let (runs, text) = split_first_word(text);
+    if runs.is_empty() {
+        return Err(MetacommandErr::MissingArgument("N".to_string()));
+    }
+    let runs = runs
+        .parse::<usize>()
+        .ok()
+        .filter(|runs| *runs > 0)
+        .ok_or_else(|| MetacommandErr::InvalidRuns(runs.to_string()))?;
+    let (copy, text) = match split_first_word(text) {
+        ("--copy", text) => (true, text),
+        _ => (false, text),
+    };
+    let sql = sql_argument(text)?;
+
+    let report = match copy {
+        true => bench_copy(&db, runs, &sql)?,
+        false => bench(&mut db, runs, &sql)?,
+    };
+    report.print();
     Ok(())
 }
 
-fn close_metacommand(db_instance: &mut Option<Database>) -> Result<(), MetacommandErr> {
-    match db_instance {
-        Some(db) => {
-            db.close();
-            *db_instance = None;
+// Prints the B-tree of the given table, or of every table when none is given, followed by the
+// trees of its indexes
+fn btree_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+
+    let table_names = match args.as_slice() {
+        [] => db.table_names(),
+        [table_name] => vec![table_name.as_str()],
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    for table_name in table_names {
+        let btree_error = |err: String| MetacommandErr::BtreeError(table_name.to_string(), err);
+        let table = db
+            .table(table_name)
+            .map_err(|err| btree_error(err.to_string()))?;
+        let tree = table
+            .print_tree()
+            .map_err(|err| btree_error(err.to_string()))?;
+        println!("{}", table_name);
+        print!("{}", tree);
+
+        for index in &table.indexes {
+            let tree = table
+                .print_index_tree(index)
+                .map_err(|err| btree_error(err.to_string()))?;
+            println!("{} (index on {})", index.name, table_name);
+            print!("{}", tree);
+        }
+    }
+
+    Ok(())
+}
+
+// Copies the open database into a new file
+fn clone_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+
+    let path = match args.as_slice() {
+        [] => return Err(MetacommandErr::MissingArgument("NEWDB".to_string())),
+        [path] => path,
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    if Path::new(path).exists() {
+        return Err(MetacommandErr::CloneError(
+            path.to_string(),
+            "file already exists".to_string(),
+        ));
+    }
+
+    db.clone_to(path)
+        .map_err(|err| MetacommandErr::CloneError(path.to_string(), err.to_string()))
+}
+
+fn close_metacommand(connection: &mut Option<Connection>) -> Result<(), MetacommandErr> {
+    match connection {
+        Some(open_connection) => {
+            open_connection.database_mut().close();
+            *connection = None;
             Ok(())
         }
         None => Err(MetacommandErr::DBClosed),
     }
 }
 
-fn databases_metacommand() -> Result<(), MetacommandErr> {
-    if let Err(list_db_files_err) = list_db_files() {
-        Err(MetacommandErr::ListDatabasesError(
-            list_db_files_err.to_string(),
-        ))
-    } else {
-        Ok(())
+// Lists the open database and every database attached to it
+fn databases_metacommand(connection: &mut Option<Connection>) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+
+    for (alias, path) in db.databases() {
+        println!("{}: {}", alias, path);
+    }
+
+    Ok(())
+}
+
+// Closes the database and exits with the given status, 0 by default
+// Writes the given table, or every table when none is given, as SQL the sqlite3 shell accepts
+fn dump_metacommand(
+    connection: &mut Option<Connection>,
+    output_settings: &OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+
+    let table_names = match args.as_slice() {
+        [] => db.table_names(),
+        [table_name] => vec![table_name.as_str()],
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    let mut out = output_settings
+        .destination()
+        .map_err(DumpError::WriteError)?;
+    Ok(dump(&db, table_names, &mut out)?)
+}
+
+fn exit_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let status = match args.as_slice() {
+        [] => SUCCESS,
+        [status] => status
+            .parse()
+            .map_err(|_| MetacommandErr::InvalidExitStatus(status.to_string()))?,
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    let _ = close_metacommand(connection);
+    std::process::exit(status)
+}
+
+// Makes the next statement print suggested indexes instead of running
+fn expert_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    if let Some(extra) = args.first() {
+        return Err(MetacommandErr::ExtraArgument(extra.to_string()));
+    }
+    output_settings.expert = true;
+    Ok(())
+}
+
+// Prints the statements given as arguments with canonical indentation and casing. The semicolon
+// after the last one may be left out
+fn format_metacommand(text: &str) -> Result<(), MetacommandErr> {
+    let mut sql = sql_argument(text)?;
+    if !sql.trim_end().ends_with(';') {
+        sql.push(';');
+    }
+    let formatted = format_sql(&sql).map_err(|err| MetacommandErr::FormatError(err.to_string()))?;
+    println!("{}", formatted);
+    Ok(())
+}
+
+fn import_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let mut db = open_database(connection)?;
+
+    match args.as_slice() {
+        [] => Err(MetacommandErr::MissingArgument("FILE".to_string())),
+        [_] => Err(MetacommandErr::MissingArgument("TABLE".to_string())),
+        [file_name, table_name] => Ok(import_csv(&mut db, file_name, table_name)?),
+        [_, _, extra, ..] => Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    }
+}
+
+fn mode_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    match args.as_slice() {
+        [] => {
+            println!("current output mode: {}", output_settings.mode);
+            Ok(())
+        }
+        [mode] => {
+            output_settings.mode = mode.parse().map_err(|_| {
+                MetacommandErr::UnknownMode(mode.to_string(), OutputMode::NAMES.join(", "))
+            })?;
+            Ok(())
+        }
+        [_, extra, ..] => Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    }
+}
+
+// Turned off, results sent to files, or to redirected output, have control characters in text
+// escaped as in the terminal, except in CSV. They are written as they are by default
+fn raw_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    output_settings.escape_redirected = !parse_on_off(args)?;
+    Ok(())
+}
+
+// Runs the script in FILE
+fn read_metacommand(
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let script_path = match args.as_slice() {
+        [] => return Err(MetacommandErr::MissingArgument("FILE".to_string())),
+        [script_path] => script_path,
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    run_script(script_path, connection, output_settings)
+}
+
+// Runs every statement and metacommand in a script, reporting failures without stopping unless
+// bail is on. Otherwise the number of failures is reported at the end
+pub fn run_script(
+    script_path: &str,
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
+) -> Result<(), MetacommandErr> {
+    let script = fs::read_to_string(script_path)
+        .map_err(|err| MetacommandErr::ReadScriptError(script_path.to_string(), err.to_string()))?;
+
+    let statements = split_statements(&script);
+    let mut failures = 0;
+    for (line, statement) in &statements {
+        if is_interrupted() {
+            return Err(MetacommandErr::Interrupted);
+        }
+        if let Err(err) = crate::run_input(statement, connection, output_settings) {
+            eprintln!(
+                "{}:{}: error in \"{}\": {}",
+                script_path, line, statement, err
+            );
+            if output_settings.bail {
+                return Err(MetacommandErr::ScriptFailed(script_path.to_string(), *line));
+            }
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        eprintln!(
+            "{}: {} of {} statements failed",
+            script_path,
+            failures,
+            statements.len()
+        );
+    }
+
+    Ok(())
+}
+
+// Salvages the tables and rows that can still be decoded from a damaged database file into a new
+// one, then lists what was recovered and what was lost
+fn recover_metacommand(args: Vec<String>) -> Result<(), MetacommandErr> {
+    let (corrupt_path, path) = match args.as_slice() {
+        [] => return Err(MetacommandErr::MissingArgument("FILE".to_string())),
+        [_] => return Err(MetacommandErr::MissingArgument("NEWDB".to_string())),
+        [corrupt_path, path] => (corrupt_path, path),
+        [_, _, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    if Path::new(path).exists() {
+        return Err(MetacommandErr::RecoverError(
+            corrupt_path.to_string(),
+            format!("{} already exists", path),
+        ));
+    }
+
+    let report = recover(corrupt_path, path)
+        .map_err(|err| MetacommandErr::RecoverError(corrupt_path.to_string(), err.to_string()))?;
+    for (table_name, rows) in report.tables {
+        println!("table {}: {} rows recovered", table_name, rows);
+    }
+    for problem in report.problems {
+        println!("not recovered: {}", problem);
     }
+
+    Ok(())
 }
 
-fn exit_metacommand(db_instance: &mut Option<Database>) -> ! {
-    let _ = close_metacommand(db_instance);
-    std::process::exit(SUCCESS)
+// Prints the CREATE TABLE statement of the given table, or of every table when none is given.
+// Tables are listed in the order they were created, so the output can be run to recreate them
+fn schema_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+
+    let table_names = match args.as_slice() {
+        [] => db.table_names(),
+        [table_name] => vec![table_name.as_str()],
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    let mut tables = table_names
+        .into_iter()
+        .map(|table_name| {
+            db.table(table_name)
+                .map_err(|err| MetacommandErr::SchemaError(table_name.to_string(), err.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    tables.sort_by_key(|table| table.table_id());
+
+    for table in tables {
+        println!("{}", table.schema());
+        for index_schema in table.index_schemas() {
+            println!("{}", index_schema);
+        }
+    }
+
+    Ok(())
+}
+
+// Fills a table with N rows of made up values matching the types of its columns
+fn seed_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let mut db = open_database(connection)?;
+
+    match args.as_slice() {
+        [] => Err(MetacommandErr::MissingArgument("TABLE".to_string())),
+        [_] => Err(MetacommandErr::MissingArgument("N".to_string())),
+        [table_name, num_rows] => {
+            let num_rows = num_rows
+                .parse::<u64>()
+                .map_err(|_| MetacommandErr::InvalidRowCount(num_rows.to_string()))?;
+            let inserted = seed(&mut db, table_name, num_rows)?;
+            println!("{} rows inserted into {}", inserted, table_name);
+            Ok(())
+        }
+        [_, _, extra, ..] => Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    }
+}
+
+// Prints the SHA3-256 of the rows of the given table, or of every table when none is given, which
+// matches between tables holding the same rows
+fn sha3sum_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+
+    let table_names = match args.as_slice() {
+        [] => db.table_names(),
+        [table_name] => vec![table_name.as_str()],
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    let mut tables = table_names
+        .into_iter()
+        .map(|table_name| {
+            db.table(table_name).map_err(|err| {
+                MetacommandErr::Sha3sumError(table_name.to_string(), err.to_string())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    tables.sort_by_key(|table| table.table_id());
+
+    for table in tables {
+        let digest = table
+            .content_hash()
+            .map_err(|err| MetacommandErr::Sha3sumError(table.name.clone(), err.to_string()))?;
+        println!("{}  {}", to_hex(&digest), table.name);
+    }
+
+    Ok(())
+}
+
+fn stats_metacommand(connection: &mut Option<Connection>) -> Result<(), MetacommandErr> {
+    let db = open_database(connection)?;
+    let metrics = db.metrics();
+
+    println!("pages in file:    {}", db.page_count());
+    if let Ok(stats) = db.stats() {
+        println!("free pages:       {}", stats.freelist_count);
+        println!("file size:        {} bytes", stats.file_size);
+        println!("memory used:      {} bytes", stats.memory_used);
+    }
+    println!(
+        "statements:       {} ({} failed)",
+        metrics.statements_executed, metrics.statements_failed
+    );
+    println!("rows read:        {}", metrics.rows_read);
+    println!("rows written:     {}", metrics.rows_written);
+    println!("pages read:       {}", metrics.pages_read);
+    println!("pages written:    {}", metrics.pages_written);
+    println!("cache hits:       {}", metrics.cache_hits);
+    println!("cache misses:     {}", metrics.cache_misses);
+    println!("cache evictions:  {}", metrics.cache_evictions);
+    println!("cache hit rate:   {:.1}%", metrics.cache_hit_rate() * 100.0);
+    println!("leaf splits:      {}", metrics.leaf_splits);
+    println!("interior splits:  {}", metrics.interior_splits);
+    println!(
+        "flush latency:    avg {:.6} s, p95 {:.6} s over {} flushes",
+        metrics.flush_latency.mean().as_secs_f64(),
+        metrics.flush_latency.percentile(95.0).as_secs_f64(),
+        metrics.flush_latency.count()
+    );
+
+    for table_name in db.table_names() {
+        let Ok(table) = db.table(table_name) else {
+            continue;
+        };
+        let stats = table.stats();
+        println!(
+            "table {}: {} rows, {} leaf pages, {} interior pages, depth {}",
+            table_name, stats.num_rows, stats.leaf_pages, stats.interior_pages, stats.depth
+        );
+    }
+
+    Ok(())
+}
+
+fn parse_on_off(args: Vec<String>) -> Result<bool, MetacommandErr> {
+    match args.as_slice() {
+        [] => Err(MetacommandErr::MissingArgument("on|off".to_string())),
+        [setting] => match setting.to_lowercase().as_str() {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(MetacommandErr::ExpectedOnOff(setting.to_string())),
+        },
+        [_, extra, ..] => Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    }
+}
+
+fn timer_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    output_settings.timer = parse_on_off(args)?;
+    Ok(())
+}
+
+fn changes_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    output_settings.changes = parse_on_off(args)?;
+    Ok(())
+}
+
+// Sets the main prompt and optionally the continuation prompt. Without arguments both go back to
+// their defaults
+fn prompt_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let (prompt, continuation_prompt) = match args.as_slice() {
+        [] => (None, None),
+        [prompt] => (
+            Some(prompt.to_string()),
+            output_settings.continuation_prompt.take(),
+        ),
+        [prompt, continuation_prompt] => (
+            Some(prompt.to_string()),
+            Some(continuation_prompt.to_string()),
+        ),
+        [_, _, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    output_settings.prompt = prompt;
+    output_settings.continuation_prompt = continuation_prompt;
+    Ok(())
+}
+
+// Shows how well the parse cache is doing, or changes how many statements it keeps
+fn parse_cache_metacommand(
+    connection: &mut Option<Connection>,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    let connection = connection.as_mut().ok_or(MetacommandErr::DBClosed)?;
+    match args.as_slice() {
+        [] => {
+            println!(
+                "capacity: {}, cached: {}, hits: {}, misses: {}",
+                connection.parse_cache().capacity(),
+                connection.parse_cache().len(),
+                connection.parse_cache().hits(),
+                connection.parse_cache().misses()
+            );
+            Ok(())
+        }
+        [size] => {
+            let capacity = size
+                .parse()
+                .map_err(|_| MetacommandErr::InvalidCacheSize(size.to_string()))?;
+            connection.set_parse_cache_capacity(capacity);
+            Ok(())
+        }
+        [_, extra, ..] => Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    }
+}
+
+// Sets the width of the first columns of table output in order. Without arguments every column
+// goes back to its natural width
+fn width_metacommand(
+    output_settings: &mut OutputSettings,
+    args: Vec<String>,
+) -> Result<(), MetacommandErr> {
+    output_settings.column_widths = args
+        .iter()
+        .map(|width| {
+            width
+                .parse()
+                .map_err(|_| MetacommandErr::InvalidWidth(width.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+// Redirects results to a file, truncating it first. `stdout` or no argument restores the terminal.
+// `.once --parquet FILE` writes the next result as a Parquet file
+fn output_metacommand(
+    output_settings: &mut OutputSettings,
+    mut args: Vec<String>,
+    once: bool,
+) -> Result<(), MetacommandErr> {
+    let parquet = once && args.first().is_some_and(|arg| arg == "--parquet");
+    if parquet {
+        if !cfg!(feature = "parquet") {
+            return Err(MetacommandErr::ParquetUnavailable);
+        }
+        args.remove(0);
+    }
+
+    let file_name = match args.as_slice() {
+        [] if once => return Err(MetacommandErr::MissingArgument("FILE".to_string())),
+        [] => "stdout",
+        [file_name] => file_name.as_str(),
+        [_, extra, ..] => return Err(MetacommandErr::ExtraArgument(extra.to_string())),
+    };
+
+    if file_name == "stdout" && !once {
+        output_settings.reset_output();
+        return Ok(());
+    }
+
+    let file = fs::File::create(file_name)
+        .map_err(|err| MetacommandErr::OutputFileError(file_name.to_string(), err.to_string()))?;
+    output_settings.output_file = Some(file);
+    output_settings.once = once;
+    output_settings.parquet = parquet;
+    Ok(())
 }
 
 pub fn open_metacommand(
-    db_instance: &mut Option<Database>,
+    connection: &mut Option<Connection>,
     args: Vec<String>,
 ) -> Result<(), MetacommandErr> {
     if args.len() > 1 {
         return Err(MetacommandErr::ExtraArgument(args[1].to_string()));
     }
 
-    let _ = close_metacommand(db_instance);
+    // The new connection keeps as many parsed statements as the one it replaces
+    let parse_cache_capacity = connection
+        .as_ref()
+        .map(|connection| connection.parse_cache().capacity());
+    let _ = close_metacommand(connection);
 
     let db_filename = &args[0];
 
-    *db_instance =
-        Some(Database::open(db_filename).map_err(|err| {
-            MetacommandErr::OpenDBError(db_filename.to_string(), err.to_string())
-        })?);
+    let mut new_connection = Connection::open(db_filename)
+        .map_err(|err| MetacommandErr::OpenDBError(db_filename.to_string(), err.to_string()))?;
+    if let Some(capacity) = parse_cache_capacity {
+        new_connection.set_parse_cache_capacity(capacity);
+    }
+    *connection = Some(new_connection);
 
     Ok(())
 }
@@ -103,10 +840,33 @@ impl FromStr for Metacommand {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.strip_prefix('.').ok_or(MetacommandErr::NotAMetacommand)? {
+            "bail" => Ok(Metacommand::Bail),
+            "bench" => Ok(Metacommand::Bench),
+            "btree" => Ok(Metacommand::Btree),
+            "changes" => Ok(Metacommand::Changes),
+            "clone" => Ok(Metacommand::Clone),
             "close" => Ok(Metacommand::Close),
             "databases" => Ok(Metacommand::Databases),
-            "exit" => Ok(Metacommand::Exit),
+            "dump" => Ok(Metacommand::Dump),
+            "exit" | "quit" => Ok(Metacommand::Exit),
+            "expert" => Ok(Metacommand::Expert),
+            "format" => Ok(Metacommand::Format),
+            "import" => Ok(Metacommand::Import),
+            "mode" => Ok(Metacommand::Mode),
+            "once" => Ok(Metacommand::Once),
             "open" => Ok(Metacommand::Open),
+            "output" => Ok(Metacommand::Output),
+            "parsecache" => Ok(Metacommand::ParseCache),
+            "prompt" => Ok(Metacommand::Prompt),
+            "raw" => Ok(Metacommand::Raw),
+            "read" => Ok(Metacommand::Read),
+            "recover" => Ok(Metacommand::Recover),
+            "schema" => Ok(Metacommand::Schema),
+            "seed" => Ok(Metacommand::Seed),
+            "sha3sum" => Ok(Metacommand::Sha3sum),
+            "stats" => Ok(Metacommand::Stats),
+            "timer" => Ok(Metacommand::Timer),
+            "width" => Ok(Metacommand::Width),
             _ => Err(MetacommandErr::UnrecognizedMetacommand(s.to_string())),
         }
     }
@@ -114,20 +874,74 @@ impl FromStr for Metacommand {
 
 pub fn process_metacommand(
     input_str: &str,
-    db_instance: &mut Option<Database>,
+    connection: &mut Option<Connection>,
+    output_settings: &mut OutputSettings,
 ) -> Result<(), MetacommandErr> {
-    let tokens: Vec<_> = input_str.split(" ").collect();
-
-    // input_str cannot be empty, so unrwap is ok
-    let (metacommand_str, args) = tokens.split_first().unwrap();
+    let (metacommand_str, text) = split_first_word(input_str);
     let metacommand = Metacommand::from_str(metacommand_str)?;
+    // Metacommands that take SQL read it as written
+    let args = match metacommand {
+        Metacommand::Bench | Metacommand::Format => Vec::new(),
+        _ => split_arguments(text)?,
+    };
 
     match metacommand {
-        Metacommand::Close => close_metacommand(db_instance),
-        Metacommand::Databases => databases_metacommand(),
-        Metacommand::Exit => exit_metacommand(db_instance),
-        Metacommand::Open => {
-            open_metacommand(db_instance, args.iter().map(|s| s.to_string()).collect())
-        }
+        Metacommand::Bail => bail_metacommand(output_settings, args),
+        Metacommand::Bench => bench_metacommand(connection, text),
+        Metacommand::Btree => btree_metacommand(connection, args),
+        Metacommand::Changes => changes_metacommand(output_settings, args),
+        Metacommand::Clone => clone_metacommand(connection, args),
+        Metacommand::Close => close_metacommand(connection),
+        Metacommand::Databases => databases_metacommand(connection),
+        Metacommand::Dump => dump_metacommand(connection, output_settings, args),
+        Metacommand::Exit => exit_metacommand(connection, args),
+        Metacommand::Expert => expert_metacommand(output_settings, args),
+        Metacommand::Format => format_metacommand(text),
+        Metacommand::Import => import_metacommand(connection, args),
+        Metacommand::Mode => mode_metacommand(output_settings, args),
+        Metacommand::Once => output_metacommand(output_settings, args, true),
+        Metacommand::Output => output_metacommand(output_settings, args, false),
+        Metacommand::Open => open_metacommand(connection, args),
+        Metacommand::ParseCache => parse_cache_metacommand(connection, args),
+        Metacommand::Prompt => prompt_metacommand(output_settings, args),
+        Metacommand::Raw => raw_metacommand(output_settings, args),
+        Metacommand::Read => read_metacommand(connection, output_settings, args),
+        Metacommand::Recover => recover_metacommand(args),
+        Metacommand::Schema => schema_metacommand(connection, args),
+        Metacommand::Seed => seed_metacommand(connection, args),
+        Metacommand::Sha3sum => sha3sum_metacommand(connection, args),
+        Metacommand::Stats => stats_metacommand(connection),
+        Metacommand::Timer => timer_metacommand(output_settings, args),
+        Metacommand::Width => width_metacommand(output_settings, args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sql_argument, MetacommandErr};
+
+    #[test]
+    fn sql_arguments_are_read_as_written_unless_quoted() {
+        let sql = sql_argument(" select 'a b' from t ").unwrap();
+        assert_eq!(sql, "select 'a b' from t");
+        let sql = sql_argument(r#""select \"a\" from t;""#).unwrap();
+        assert_eq!(sql, r#"select "a" from t;"#);
+        assert_eq!(sql_argument("'select 1'").unwrap(), "select 1");
+    }
+
+    #[test]
+    fn quoted_sql_arguments_are_a_single_argument() {
+        assert!(matches!(
+            sql_argument("'select 1' extra"),
+            Err(MetacommandErr::ExtraArgument(extra)) if extra == "extra"
+        ));
+        assert!(matches!(
+            sql_argument("\"\""),
+            Err(MetacommandErr::MissingArgument(_))
+        ));
+        assert!(matches!(
+            sql_argument("\"select 1"),
+            Err(MetacommandErr::UnterminatedQuote(_))
+        ));
     }
 }
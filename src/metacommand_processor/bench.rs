@@ -0,0 +1,115 @@
+use std::fs;
+use std::io;
+use std::process;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use sql_rs::backend::database::Database;
+use sql_rs::sql_compiler::{parse_statement, Statement};
+use sql_rs::virtual_machine as VM;
+use sql_rs::virtual_machine::interrupt::is_interrupted;
+use sql_rs::virtual_machine::result_writer::{CsvOptions, OutputSettings};
+
+#[derive(Error, Debug)]
+pub enum BenchError {
+    #[error("Cannot parse statement: {0}")]
+    ParseError(String),
+    #[error("Run {0} failed: {1}")]
+    RunError(usize, String),
+    #[error("Cannot copy database: {0}")]
+    CopyError(String),
+    #[error("Interrupted after {0} runs")]
+    Interrupted(usize),
+}
+
+// Latencies of the runs of a statement, sorted from fastest to slowest, and the pager work done
+// across all of them
+pub struct BenchReport {
+    latencies: Vec<Duration>,
+    pages_touched: u64,
+    pages_read: u64,
+    pages_written: u64,
+}
+
+impl BenchReport {
+    fn percentile(&self, percent: usize) -> Duration {
+        let idx = (self.latencies.len() * percent).div_ceil(100).max(1) - 1;
+        self.latencies[idx]
+    }
+
+    pub fn print(&self) {
+        let runs = self.latencies.len();
+        let average = self.latencies.iter().sum::<Duration>() / runs as u32;
+        println!("runs: {}", runs);
+        println!(
+            "latency: min {:.6} s, avg {:.6} s, p95 {:.6} s",
+            self.latencies[0].as_secs_f64(),
+            average.as_secs_f64(),
+            self.percentile(95).as_secs_f64()
+        );
+        println!(
+            "pages per run: touched {:.1}, read {:.1}, written {:.1}",
+            self.pages_touched as f64 / runs as f64,
+            self.pages_read as f64 / runs as f64,
+            self.pages_written as f64 / runs as f64
+        );
+    }
+}
+
+// Runs a statement the given number of times, which must be at least one. Rows returned by
+// queries are formatted and then discarded, so that their cost is measured without printing them
+pub(super) fn bench(db: &mut Database, runs: usize, sql: &str) -> Result<BenchReport, BenchError> {
+    let sql = match sql.trim_end().ends_with(';') {
+        true => sql.to_string(),
+        false => format!("{};", sql),
+    };
+    let statement = parse_statement(&sql).map_err(|err| BenchError::ParseError(err.to_string()))?;
+
+    let metrics_before = db.pager_metrics();
+    let mut latencies = Vec::with_capacity(runs);
+    for run in 1..=runs {
+        if is_interrupted() {
+            return Err(BenchError::Interrupted(run - 1));
+        }
+        let start = Instant::now();
+        let result = match statement.clone() {
+            select @ Statement::Select(_) => {
+                VM::export_statement(select, db, Box::new(io::sink()), CsvOptions::default())
+            }
+            statement => VM::execute_statement(statement, Some(db), &OutputSettings::default()),
+        };
+        latencies.push(start.elapsed());
+        result.map_err(|err| BenchError::RunError(run, err.to_string()))?;
+    }
+    latencies.sort();
+
+    let metrics = db.pager_metrics();
+    Ok(BenchReport {
+        latencies,
+        pages_touched: (metrics.cache_hits + metrics.cache_misses)
+            - (metrics_before.cache_hits + metrics_before.cache_misses),
+        pages_read: metrics.pages_read - metrics_before.pages_read,
+        pages_written: metrics.pages_written - metrics_before.pages_written,
+    })
+}
+
+// Runs the statement against a copy of the database in a temporary file, which is deleted
+// afterwards, so that statements that write leave the database untouched
+pub(super) fn bench_copy(db: &Database, runs: usize, sql: &str) -> Result<BenchReport, BenchError> {
+    let path = std::env::temp_dir().join(format!("sql_rs_bench_{}.db", process::id()));
+    let path = path.to_string_lossy();
+    let _ = fs::remove_file(path.as_ref());
+
+    let report = db
+        .clone_to(&path)
+        .and_then(|_| Database::open(&path))
+        .map_err(|err| BenchError::CopyError(err.to_string()))
+        .and_then(|mut copy| {
+            let report = bench(&mut copy, runs, sql);
+            copy.close();
+            report
+        });
+    let _ = fs::remove_file(path.as_ref());
+    report
+}
@@ -0,0 +1,232 @@
+use std::io::{self, Write};
+
+use thiserror::Error;
+
+use sql_rs::backend::columns::{ColumnItemType, IntegerType, TextType};
+use sql_rs::backend::cursor::DBCursor;
+use sql_rs::backend::database::Database;
+use sql_rs::backend::row::SQLType;
+use sql_rs::backend::table::Table;
+use sql_rs::virtual_machine::interrupt::is_interrupted;
+
+#[derive(Error, Debug)]
+pub enum DumpError {
+    #[error("Cannot read table {0}: {1}")]
+    ReadError(String, String),
+    #[error("Cannot write dump: {0}")]
+    WriteError(#[from] io::Error),
+    #[error("Interrupted")]
+    Interrupted,
+}
+
+// Names are always quoted, as they may be keywords in SQLite
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_text(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn sqlite_literal(value: &SQLType) -> String {
+    match value {
+        SQLType::Null => "NULL".to_string(),
+        SQLType::Text(text) => quote_text(text),
+        SQLType::Uuid(uuid) => quote_text(&uuid.to_string()),
+        number => number.to_string(),
+    }
+}
+
+// SQLite column type with the same affinity. Only INTEGER PRIMARY KEY columns alias the rowid,
+// and enums become text restricted to their labels
+fn sqlite_type(name: &str, column_type: &ColumnItemType, primary_key: bool) -> String {
+    match column_type {
+        ColumnItemType::Integer(_) if primary_key => "INTEGER".to_string(),
+        ColumnItemType::Integer(int_type) => match int_type {
+            IntegerType::Int => "INT",
+            IntegerType::UBigInt => "UNSIGNED BIG INT",
+            IntegerType::BigInt => "BIGINT",
+            IntegerType::SmallInt => "SMALLINT",
+        }
+        .to_string(),
+        ColumnItemType::Text(TextType::Varchar(max_size)) => format!("VARCHAR({})", max_size),
+        ColumnItemType::Text(TextType::Char(size)) => format!("CHAR({})", size),
+        ColumnItemType::Text(TextType::Text) | ColumnItemType::Uuid | ColumnItemType::Json => {
+            "TEXT".to_string()
+        }
+        ColumnItemType::Enum(enum_type) => {
+            let labels: Vec<String> = enum_type.labels.iter().map(|l| quote_text(l)).collect();
+            format!(
+                "TEXT CHECK ({} IN ({}))",
+                quote_identifier(name),
+                labels.join(", ")
+            )
+        }
+    }
+}
+
+// CREATE TABLE statement in SQLite syntax, with the columns in the order they were declared.
+// Comments have no equivalent and are left out
+fn sqlite_schema(table: &Table) -> String {
+    let definitions: Vec<String> = table
+        .columns
+        .declared_order()
+        .into_iter()
+        .map(|name| {
+            let column_type = &table.columns[name];
+            let primary_key = table.primary_key.as_deref() == Some(name);
+            let mut definition = format!(
+                "{} {}",
+                quote_identifier(name),
+                sqlite_type(name, column_type, primary_key)
+            );
+            if primary_key {
+                definition.push_str(" PRIMARY KEY");
+            }
+            if let Some(constraints) = table.columns.constraints(name) {
+                if constraints.not_null {
+                    definition.push_str(" NOT NULL");
+                }
+                if constraints.unique {
+                    definition.push_str(" UNIQUE");
+                }
+                // Expressions are parenthesized, as SQLite requires
+                if let Some(default) = &constraints.default {
+                    definition += &format!(" DEFAULT {}", default.to_sql(column_type));
+                }
+                if let Some(check) = &constraints.check {
+                    definition += &format!(" CHECK ({})", check);
+                }
+                if let Some((table, column)) = &constraints.references {
+                    definition += &format!(
+                        " REFERENCES {}({})",
+                        quote_identifier(table),
+                        quote_identifier(column)
+                    );
+                }
+            }
+            definition
+        })
+        .collect();
+
+    format!(
+        "CREATE TABLE {} ({});",
+        quote_identifier(&table.name),
+        definitions.join(", ")
+    )
+}
+
+// Writes the tables, their rows and their indexes as a script the sqlite3 shell can run to recreate
+// them. Tables are written in the order they were created, so those referenced by foreign keys come
+// first
+pub fn dump(db: &Database, table_names: Vec<&str>, out: &mut dyn Write) -> Result<(), DumpError> {
+    let mut tables = table_names
+        .into_iter()
+        .map(|table_name| {
+            db.table(table_name)
+                .map_err(|err| DumpError::ReadError(table_name.to_string(), err.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    tables.sort_by_key(|table| table.table_id());
+
+    writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    for table in tables {
+        writeln!(out, "{}", sqlite_schema(table))?;
+
+        // Rows hold their values in the order of the column names, and the columns of the CREATE
+        // TABLE are in the order they were declared
+        let stored_order: Vec<&String> = table.columns.keys().collect();
+        let positions: Vec<usize> = table
+            .columns
+            .declared_order()
+            .into_iter()
+            .filter_map(|name| stored_order.iter().position(|stored| *stored == name))
+            .collect();
+
+        let mut cursor = DBCursor::new(table.root_page_num());
+        while let Some(row) = table
+            .next_row(&mut cursor)
+            .map_err(|err| DumpError::ReadError(table.name.to_string(), err.to_string()))?
+        {
+            if is_interrupted() {
+                return Err(DumpError::Interrupted);
+            }
+            let row = table.columns.decode_row(row);
+            let values: Vec<String> = positions
+                .iter()
+                .map(|&position| sqlite_literal(&row.attributes()[position]))
+                .collect();
+            writeln!(
+                out,
+                "INSERT INTO {} VALUES({});",
+                quote_identifier(&table.name),
+                values.join(",")
+            )?;
+        }
+
+        // Indexes come after the rows, so they are filled once rather than row by row
+        for index in &table.indexes {
+            let columns: Vec<String> = index.columns.iter().map(|c| quote_identifier(c)).collect();
+            writeln!(
+                out,
+                "CREATE INDEX {} ON {} ({});",
+                quote_identifier(&index.name),
+                quote_identifier(&table.name),
+                columns.join(", ")
+            )?;
+        }
+    }
+    writeln!(out, "COMMIT;")?;
+    out.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_rs::{Connection, SQLType};
+
+    use super::dump;
+
+    fn dump_script(conn: &Connection) -> String {
+        let mut out = Vec::new();
+        dump(&conn.database(), vec!["my t"], &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn all_rows(conn: &mut Connection) -> Vec<Vec<SQLType>> {
+        conn.query("SELECT * FROM \"my t\";")
+            .unwrap()
+            .map(|row| row.into_values())
+            .collect()
+    }
+
+    #[test]
+    fn dump_keeps_the_declared_column_order_and_reloads() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE \"my t\" (id INTEGER PRIMARY KEY, name VARCHAR(10), big BIGINT, c CHAR(4));",
+        )
+        .unwrap();
+        conn.execute("INSERT INTO \"my t\" VALUES (1, 'it''s', 5, 'abcd');")
+            .unwrap();
+        conn.execute("INSERT INTO \"my t\" VALUES (2, NULL, -7, 'x');")
+            .unwrap();
+        conn.execute("CREATE INDEX \"by name\" ON \"my t\" (name, c);")
+            .unwrap();
+
+        let script = dump_script(&conn);
+        assert!(script.contains(
+            "CREATE TABLE \"my t\" (\"id\" INTEGER PRIMARY KEY, \"name\" VARCHAR(10), \"big\" BIGINT, \"c\" CHAR(4));"
+        ));
+        assert!(script.contains("INSERT INTO \"my t\" VALUES(1,'it''s',5,'abcd');"));
+        assert!(script.contains("CREATE INDEX \"by name\" ON \"my t\" (\"name\", \"c\");"));
+
+        let mut reloaded = Connection::open_in_memory().unwrap();
+        reloaded.execute(&script).unwrap();
+        assert_eq!(all_rows(&mut reloaded), all_rows(&mut conn));
+        assert!(reloaded.integrity_check().is_empty());
+        assert_eq!(dump_script(&reloaded), script);
+    }
+}
@@ -0,0 +1,271 @@
+use std::borrow::Cow;
+use std::fs;
+
+use thiserror::Error;
+
+use sql_rs::backend::columns::{ColumnItemType, Columns};
+use sql_rs::backend::database::Database;
+use sql_rs::sql_compiler::{InsertTokens, InsertValue, Statement};
+use sql_rs::virtual_machine::interrupt::is_interrupted;
+use sql_rs::virtual_machine::{self as VM, result_writer::OutputSettings};
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Cannot read {0}: {1}")]
+    ReadError(String, String),
+    #[error("Table {0} does not exist")]
+    NoSuchTable(String),
+    #[error("Line {0}: unterminated quoted field")]
+    UnterminatedQuote(usize),
+    #[error("Line {0}: expected {1} fields but found {2}")]
+    FieldCountMismatch(usize, usize, usize),
+    #[error("Line {0}: {1}")]
+    RowError(usize, String),
+    #[error("Interrupted at line {0}, rows before it were imported")]
+    Interrupted(usize),
+    #[error("Cannot write imported rows: {0}")]
+    CommitError(String),
+}
+
+// A parsed CSV record along with the line it starts on
+type Record = (usize, Vec<String>);
+
+// Splits CSV text into records following RFC 4180: fields may be quoted, quotes inside a quoted
+// field are doubled and quoted fields may span several lines. Blank lines are skipped.
+fn parse_csv(input: &str) -> Result<Vec<Record>, ImportError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                let quote_line = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => return Err(ImportError::UnterminatedQuote(quote_line)),
+                    }
+                }
+            }
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                if fields.len() > 1 || !fields[0].is_empty() {
+                    records.push((record_line, std::mem::take(&mut fields)));
+                }
+                fields.clear();
+                line += 1;
+                record_line = line;
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !fields.is_empty() || !field.is_empty() {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+
+    Ok(records)
+}
+
+// Imports the rows of a CSV file into an existing table. When every field of the first record
+// names a column of the table it is taken as a header, otherwise fields map to columns in table
+// order.
+pub(super) fn import_csv(
+    db: &mut Database,
+    file_name: &str,
+    table_name: &str,
+) -> Result<(), ImportError> {
+    let contents = fs::read_to_string(file_name)
+        .map_err(|err| ImportError::ReadError(file_name.to_string(), err.to_string()))?;
+    let mut records = parse_csv(&contents)?;
+
+    let columns = db
+        .table(table_name)
+        .map_err(|_| ImportError::NoSuchTable(table_name.to_string()))?
+        .columns
+        .clone();
+
+    let is_header = records.first().is_some_and(|(_, fields)| {
+        fields
+            .iter()
+            .all(|field| columns.contains_key(field.trim()))
+    });
+    let column_names: Vec<String> = if is_header {
+        let (_, header) = records.remove(0);
+        header.iter().map(|name| name.trim().to_string()).collect()
+    } else {
+        columns.keys().cloned().collect()
+    };
+
+    // Rows are written together when the import ends rather than flushed one by one. Those before
+    // a failing line are still kept
+    let own_transaction = !db.in_transaction();
+    if own_transaction {
+        db.begin()
+            .map_err(|err| ImportError::CommitError(err.to_string()))?;
+    }
+    let imported = insert_records(db, table_name, &columns, &column_names, records);
+    if own_transaction {
+        db.commit()
+            .map_err(|err| ImportError::CommitError(err.to_string()))?;
+    }
+    imported
+}
+
+fn insert_records(
+    db: &mut Database,
+    table_name: &str,
+    columns: &Columns,
+    column_names: &[String],
+    records: Vec<Record>,
+) -> Result<(), ImportError> {
+    for (line, fields) in records {
+        if is_interrupted() {
+            return Err(ImportError::Interrupted(line));
+        }
+        if fields.len() != column_names.len() {
+            return Err(ImportError::FieldCountMismatch(
+                line,
+                column_names.len(),
+                fields.len(),
+            ));
+        }
+
+        // Integer columns tolerate padding around the number, text is kept verbatim
+        let column_values = fields
+            .iter()
+            .zip(column_names)
+            .map(|(field, name)| match columns.get(name) {
+                Some(ColumnItemType::Integer(_)) => {
+                    Some(InsertValue::Literal(Cow::Borrowed(field.trim())))
+                }
+                _ => Some(InsertValue::Literal(Cow::Borrowed(field.as_str()))),
+            })
+            .collect();
+
+        let insert_tokens = InsertTokens {
+            table_name: Cow::Borrowed(table_name),
+            column_names: column_names.iter().map(Cow::from).collect(),
+            column_values,
+            select: None,
+        };
+        VM::execute_statement(
+            Statement::Insert(insert_tokens),
+            Some(db),
+            &OutputSettings::default(),
+        )
+        .map_err(|err| ImportError::RowError(line, err.to_string()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use sql_rs::{Connection, SQLType};
+
+    use super::*;
+
+    fn import(conn: &Connection, name: &str, contents: &str) -> Result<(), ImportError> {
+        let path = env::temp_dir().join(format!("sql_rs_import_{}_{}.csv", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        let imported = import_csv(&mut conn.database_mut(), path.to_str().unwrap(), "t");
+        fs::remove_file(&path).unwrap();
+        imported
+    }
+
+    fn rows(conn: &mut Connection) -> Vec<Vec<SQLType>> {
+        conn.query("SELECT * FROM t;")
+            .unwrap()
+            .map(|row| row.into_values())
+            .collect()
+    }
+
+    fn row(id: i32, name: &str) -> Vec<SQLType> {
+        vec![SQLType::Integer(id), SQLType::Text(name.to_string())]
+    }
+
+    #[test]
+    fn splits_records_and_fields() {
+        let input = "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\n\"two\nlines\",,x\nlast,line,";
+        let records = parse_csv(input).unwrap();
+        assert_eq!(
+            records,
+            [
+                (1, vec!["a".into(), "b,c".into(), "say \"hi\"".into()]),
+                (3, vec!["two\nlines".into(), "".into(), "x".into()]),
+                (5, vec!["last".into(), "line".into(), "".into()]),
+            ]
+        );
+        assert!(parse_csv("").unwrap().is_empty());
+        assert!(matches!(
+            parse_csv("a,b\n\"c,d\ne,f\n"),
+            Err(ImportError::UnterminatedQuote(2))
+        ));
+    }
+
+    #[test]
+    fn fields_map_to_the_header_or_to_table_order() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+
+        import(&conn, "header", "name,id\n\"b, c\",2\n a , 1\n").unwrap();
+        assert_eq!(rows(&mut conn), [row(1, " a "), row(2, "b, c")]);
+
+        import(&conn, "no_header", "3,c\n 4 ,d\n").unwrap();
+        assert_eq!(rows(&mut conn).len(), 4);
+        assert_eq!(rows(&mut conn)[3], row(4, "d"));
+    }
+
+    #[test]
+    fn rows_before_a_bad_line_are_kept() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+
+        let result = import(&conn, "short", "1,a\n2,b\n3\n4,d\n");
+        assert!(matches!(
+            result,
+            Err(ImportError::FieldCountMismatch(3, 2, 1))
+        ));
+        assert_eq!(rows(&mut conn), [row(1, "a"), row(2, "b")]);
+
+        let result = import(&conn, "bad_value", "5,e\nsix,f\n");
+        assert!(matches!(result, Err(ImportError::RowError(2, _))));
+        assert_eq!(rows(&mut conn).len(), 3);
+        assert!(conn.integrity_check().is_empty());
+    }
+
+    #[test]
+    fn missing_files_and_tables_are_reported() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(matches!(
+            import(&conn, "no_table", "1,a\n"),
+            Err(ImportError::NoSuchTable(table)) if table == "t"
+        ));
+        assert!(matches!(
+            import_csv(&mut conn.database_mut(), "/nonexistent/file.csv", "t"),
+            Err(ImportError::ReadError(..))
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use thiserror::Error;
+
+use sql_rs::backend::columns::{ColumnItemType, TextType};
+use sql_rs::backend::cursor::DBCursor;
+use sql_rs::backend::database::Database;
+use sql_rs::backend::row::SQLType;
+use sql_rs::backend::table::Table;
+use sql_rs::backend::uuid::Uuid;
+use sql_rs::sql_compiler::{InsertTokens, InsertValue, Statement};
+use sql_rs::virtual_machine::interrupt::is_interrupted;
+use sql_rs::virtual_machine::{self as VM, result_writer::OutputSettings};
+
+// Times a row failing a constraint is generated again before giving up
+const ATTEMPTS_PER_ROW: usize = 10;
+
+const WORDS: &[&str] = &[
+    "amber", "birch", "cobalt", "delta", "ember", "fjord", "granite", "harbor", "indigo",
+    "juniper", "kestrel", "lumen", "meadow", "nimbus", "onyx", "pebble", "quartz", "raven",
+    "sierra", "tundra", "umber", "velvet", "willow", "zephyr",
+];
+
+#[derive(Error, Debug)]
+pub enum SeedError {
+    #[error("Table {0} does not exist")]
+    NoSuchTable(String),
+    #[error("Cannot read table {0}: {1}")]
+    ReadError(String, String),
+    #[error("Column {0} references {1}({2}), which holds no values")]
+    NothingToReference(String, String, String),
+    #[error("Row {0}: {1}")]
+    RowError(u64, String),
+    #[error("Interrupted after {0} rows, which were kept")]
+    Interrupted(u64),
+    #[error("Cannot write generated rows: {0}")]
+    CommitError(String),
+}
+
+// Pseudo-random numbers from splitmix64, seeded differently on every run
+struct Random(u64);
+
+impl Random {
+    fn new() -> Self {
+        Self(RandomState::new().build_hasher().finish())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+// How the values of a column are made up
+enum Generator {
+    // Consecutive numbers from the one following the largest in the column, for keys and unique
+    // columns
+    Sequence(i128),
+    Integer(i128, i128),
+    // Words followed by the number of the row, cut to the longest text the column holds
+    Text(Option<usize>),
+    Labels(Vec<String>),
+    Uuid,
+    Json,
+    // Values already held by the column it references
+    Reference(Vec<String>),
+}
+
+impl Generator {
+    // Value for the row after `inserted` others were seeded, which is row `row_num` of the table
+    fn value(&self, inserted: u64, row_num: u64, random: &mut Random) -> String {
+        match self {
+            Generator::Sequence(start) => (start + inserted as i128).to_string(),
+            Generator::Integer(min, max) => {
+                let span = (*max - *min) as u64 + 1;
+                (*min + (random.next() % span) as i128).to_string()
+            }
+            Generator::Text(max_len) => {
+                let mut text = format!(
+                    "{} {} {}",
+                    WORDS[random.below(WORDS.len())],
+                    WORDS[random.below(WORDS.len())],
+                    row_num
+                );
+                if let Some(max_len) = max_len {
+                    text.truncate(*max_len);
+                }
+                text
+            }
+            Generator::Labels(labels) => labels[random.below(labels.len())].clone(),
+            Generator::Uuid => Uuid::new_v4().to_string(),
+            Generator::Json => format!(
+                "{{\"name\": \"{}\", \"value\": {}}}",
+                WORDS[random.below(WORDS.len())],
+                random.below(1000)
+            ),
+            Generator::Reference(values) => values[random.below(values.len())].clone(),
+        }
+    }
+}
+
+// Values of a column in every row of a table
+fn column_values(table: &Table, column: &str) -> Result<Vec<SQLType>, SeedError> {
+    let read_error = |err: String| SeedError::ReadError(table.name.to_string(), err);
+    let Some(idx) = table.columns.keys().position(|name| name == column) else {
+        return Err(read_error(format!("no column {}", column)));
+    };
+
+    let mut values = Vec::new();
+    let mut cursor = DBCursor::new(table.root_page_num());
+    while let Some(row) = table
+        .next_row(&mut cursor)
+        .map_err(|err| read_error(err.to_string()))?
+    {
+        let row = table.columns.decode_row(row);
+        values.extend(row.attributes().get(idx).cloned());
+    }
+    Ok(values)
+}
+
+fn integer_value(value: &SQLType) -> Option<i128> {
+    match value {
+        SQLType::UBigInt(num) => Some(*num as i128),
+        SQLType::Integer(num) => Some(*num as i128),
+        SQLType::BigInt(num) => Some(*num as i128),
+        SQLType::SmallInt(num) => Some(*num as i128),
+        _ => None,
+    }
+}
+
+fn generator(db: &Database, table: &Table, column: &str) -> Result<Generator, SeedError> {
+    let constraints = table
+        .columns
+        .constraints(column)
+        .cloned()
+        .unwrap_or_default();
+    if let Some((referenced_table, referenced_column)) = &constraints.references {
+        let referenced = db
+            .table(referenced_table)
+            .map_err(|_| SeedError::NoSuchTable(referenced_table.to_string()))?;
+        let values: Vec<String> = column_values(referenced, referenced_column)?
+            .into_iter()
+            .filter(|value| *value != SQLType::Null)
+            .map(|value| value.to_string())
+            .collect();
+        if values.is_empty() {
+            return Err(SeedError::NothingToReference(
+                column.to_string(),
+                referenced_table.to_string(),
+                referenced_column.to_string(),
+            ));
+        }
+        return Ok(Generator::Reference(values));
+    }
+
+    let unique = constraints.unique || table.primary_key.as_deref() == Some(column);
+    Ok(match &table.columns[column] {
+        ColumnItemType::Integer(int_type) if unique => {
+            let largest = column_values(table, column)?
+                .iter()
+                .filter_map(integer_value)
+                .max();
+            Generator::Sequence(largest.map_or(1.max(int_type.range().0), |largest| largest + 1))
+        }
+        // Small numbers, which read better than ones spread over the whole range of the type
+        ColumnItemType::Integer(int_type) => {
+            let (min, max) = int_type.range();
+            Generator::Integer(min.max(0), max.min(9999))
+        }
+        ColumnItemType::Text(TextType::Text) => Generator::Text(None),
+        ColumnItemType::Text(TextType::Varchar(max_len)) => {
+            Generator::Text(Some(*max_len as usize))
+        }
+        ColumnItemType::Text(TextType::Char(max_len)) => Generator::Text(Some(*max_len as usize)),
+        ColumnItemType::Enum(enum_type) => Generator::Labels(enum_type.labels.clone()),
+        ColumnItemType::Uuid => Generator::Uuid,
+        ColumnItemType::Json => Generator::Json,
+    })
+}
+
+// Inserts rows of made up values fitting the types of the columns of a table: keys and unique
+// integers count up from the largest value in use, columns with a REFERENCES clause take values
+// of the referenced column and other values are random. Rows breaking a constraint, such as a
+// CHECK, are made up again. Returns the number of rows inserted
+pub(super) fn seed(db: &mut Database, table_name: &str, num_rows: u64) -> Result<u64, SeedError> {
+    let table = db
+        .table(table_name)
+        .map_err(|_| SeedError::NoSuchTable(table_name.to_string()))?;
+    let column_names: Vec<String> = table.columns.keys().cloned().collect();
+    let generators = column_names
+        .iter()
+        .map(|column| generator(db, table, column))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first_row = table.stats().num_rows + 1;
+
+    // Rows are written together when seeding ends, like those of .import
+    let own_transaction = !db.in_transaction();
+    if own_transaction {
+        db.begin()
+            .map_err(|err| SeedError::CommitError(err.to_string()))?;
+    }
+    let mut random = Random::new();
+    let mut inserted = 0;
+    let mut result = Ok(());
+    'rows: while inserted < num_rows {
+        if is_interrupted() {
+            result = Err(SeedError::Interrupted(inserted));
+            break;
+        }
+        let row_num = first_row + inserted;
+        for attempt in 1..=ATTEMPTS_PER_ROW {
+            let insert_tokens = InsertTokens {
+                table_name: Cow::Borrowed(table_name),
+                column_names: column_names.iter().map(Cow::from).collect(),
+                column_values: generators
+                    .iter()
+                    .map(|generator| generator.value(inserted, row_num, &mut random))
+                    .map(|value| Some(InsertValue::Literal(Cow::Owned(value))))
+                    .collect(),
+                select: None,
+            };
+            match VM::execute_statement(
+                Statement::Insert(insert_tokens),
+                Some(db),
+                &OutputSettings::default(),
+            ) {
+                Ok(_) => {
+                    inserted += 1;
+                    continue 'rows;
+                }
+                Err(err) if attempt == ATTEMPTS_PER_ROW => {
+                    result = Err(SeedError::RowError(row_num, err.to_string()));
+                }
+                Err(_) => {}
+            }
+        }
+        break;
+    }
+    if own_transaction {
+        db.commit()
+            .map_err(|err| SeedError::CommitError(err.to_string()))?;
+    }
+    result.map(|_| inserted)
+}
@@ -0,0 +1,166 @@
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::backend::PagerMetrics;
+
+// Measures how long something takes, for the latency histograms. wasm32-unknown-unknown has no
+// clock and `Instant::now` panics there, so nothing is measured and no latency is recorded
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    start: Instant,
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Stopwatch {
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        Some(self.start.elapsed())
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Stopwatch {}
+    }
+
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// Time since the Unix epoch, e.g. to seed random values. None on wasm32-unknown-unknown, where
+// `SystemTime::now` panics for lack of a clock
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn wall_clock() -> Option<Duration> {
+    SystemTime::now().duration_since(UNIX_EPOCH).ok()
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn wall_clock() -> Option<Duration> {
+    None
+}
+
+// Buckets of a histogram, each twice as wide as the one before. The last one catches everything
+// over about half an hour
+const BUCKETS: usize = 32;
+
+/// Distribution of durations, counted in buckets whose upper bounds are powers of two
+/// microseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub(crate) fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros().max(1);
+        let bucket = (u128::BITS - (micros - 1).leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Number of durations recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sum of every duration recorded
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total / count as u32,
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Upper bound of the bucket holding the given percentile, from 0 to 100, capped at the
+    /// longest duration recorded
+    pub fn percentile(&self, percent: f64) -> Duration {
+        let rank = ((self.count as f64 * percent / 100.0).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (upper_bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return upper_bound.min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Upper bound of every bucket along with the number of durations in it, from the shortest
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| (Duration::from_micros(1 << bucket), *count))
+    }
+}
+
+/// Counters and histograms of the work done on a database since it was opened, for services that
+/// export them to their monitoring. Attached databases are left out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Metrics {
+    pub statements_executed: u64,
+    pub statements_failed: u64,
+    pub rows_read: u64,
+    pub rows_written: u64,
+    pub pages_read: u64,
+    pub pages_written: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub cache_evictions: u64,
+    pub leaf_splits: u64,
+    pub interior_splits: u64,
+    /// Time taken by each statement, failed ones included
+    pub statement_latency: Histogram,
+    /// Time taken to write the changes of each transaction to the database file
+    pub flush_latency: Histogram,
+}
+
+impl Metrics {
+    pub(crate) fn new(pager: PagerMetrics, statements: Histogram, statements_failed: u64) -> Self {
+        Self {
+            statements_executed: statements.count(),
+            statements_failed,
+            rows_read: pager.rows_read,
+            rows_written: pager.rows_written,
+            pages_read: pager.pages_read,
+            pages_written: pager.pages_written,
+            cache_hits: pager.cache_hits,
+            cache_misses: pager.cache_misses,
+            cache_evictions: pager.cache_evictions,
+            leaf_splits: pager.leaf_splits,
+            interior_splits: pager.interior_splits,
+            statement_latency: statements,
+            flush_latency: pager.flush_latency,
+        }
+    }
+
+    /// Share of page reads served from the cache, from 0 to 1
+    pub fn cache_hit_rate(&self) -> f64 {
+        match self.cache_hits + self.cache_misses {
+            0 => 0.0,
+            total => self.cache_hits as f64 / total as f64,
+        }
+    }
+}
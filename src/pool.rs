@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::backend::database::Database;
+use crate::connection::Connection;
+use crate::error::SqlRsError;
+
+/// A fixed number of connections to one database that share its page cache. Connections are
+/// not `Send` yet, so a pool serves a single thread until the database handle is thread-safe.
+///
+/// Only one connection at a time can have a transaction open. Until it commits or rolls back,
+/// statements of the others fail with [`ErrorCode::Busy`](crate::ErrorCode::Busy). A transaction
+/// still open when its connection goes back to the pool is rolled back.
+pub struct Pool {
+    idle: Rc<RefCell<Vec<Connection>>>,
+    size: usize,
+}
+
+impl Pool {
+    /// Opens the database at `path` and creates `size` connections to it
+    pub fn new(path: &str, size: usize) -> Result<Self, SqlRsError> {
+        let db = Rc::new(RefCell::new(Database::open(path)?));
+        let idle = (0..size)
+            .map(|_| Connection::from_shared(db.clone()))
+            .collect();
+
+        Ok(Self {
+            idle: Rc::new(RefCell::new(idle)),
+            size,
+        })
+    }
+
+    /// Takes an idle connection, which goes back to the pool when dropped
+    pub fn get(&self) -> Result<PooledConnection, SqlRsError> {
+        let connection = self
+            .idle
+            .borrow_mut()
+            .pop()
+            .ok_or(SqlRsError::PoolExhausted(self.size))?;
+
+        Ok(PooledConnection {
+            connection: Some(connection),
+            idle: self.idle.clone(),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of connections that are not handed out
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+}
+
+/// A connection borrowed from a [`Pool`]
+pub struct PooledConnection {
+    // Only taken out when the connection is returned to the pool
+    connection: Option<Connection>,
+    idle: Rc<RefCell<Vec<Connection>>>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(mut connection) = self.connection.take() {
+            let _ = connection.rollback_transaction();
+            self.idle.borrow_mut().push(connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::database::MEMORY_PATH;
+    use crate::ErrorCode;
+
+    fn count_rows(pool: &Pool) -> usize {
+        pool.get()
+            .unwrap()
+            .query("SELECT * FROM t;")
+            .unwrap()
+            .count()
+    }
+
+    #[test]
+    fn transaction_belongs_to_its_connection() {
+        let pool = Pool::new(MEMORY_PATH, 2).unwrap();
+        let mut a = pool.get().unwrap();
+        let mut b = pool.get().unwrap();
+        a.execute("CREATE TABLE t (id INT PRIMARY KEY);").unwrap();
+
+        a.execute("BEGIN;").unwrap();
+        a.execute("INSERT INTO t VALUES (1);").unwrap();
+        let err = b.execute("INSERT INTO t VALUES (2);").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Busy);
+        assert_eq!(
+            b.query("SELECT * FROM t;").unwrap_err().code(),
+            ErrorCode::Busy
+        );
+        a.execute("ROLLBACK;").unwrap();
+
+        assert_eq!(b.execute("INSERT INTO t VALUES (2);").unwrap(), 1);
+        assert_eq!((a.changes(), a.total_changes()), (1, 1));
+        assert_eq!((b.changes(), b.total_changes()), (1, 1));
+        drop((a, b));
+        assert_eq!(count_rows(&pool), 1);
+    }
+
+    #[test]
+    fn returned_connection_rolls_back_its_transaction() {
+        let pool = Pool::new(MEMORY_PATH, 2).unwrap();
+        let mut conn = pool.get().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY);")
+            .unwrap();
+        conn.execute("BEGIN; INSERT INTO t VALUES (1);").unwrap();
+        drop(conn);
+
+        assert_eq!(count_rows(&pool), 0);
+    }
+}
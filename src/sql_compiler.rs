@@ -3,37 +3,167 @@ use nom::{
     combinator::map_res, error::VerboseError, IResult,
 };
 
+mod alter;
+mod annotate;
+mod attach;
 mod common_parsers;
 mod create;
+mod delete;
+mod drop;
+mod explain;
+pub mod expression;
+mod format;
+mod highlight;
 mod insert;
+mod ordering;
+mod parse_cache;
+mod pragma;
+mod reindex;
+mod script;
 mod select;
 pub mod statement;
+mod transaction;
+mod update;
 
+pub use alter::*;
+pub use annotate::*;
+pub use attach::*;
 use common_parsers::*;
 pub use create::*;
+pub use delete::*;
+pub use drop::*;
+use explain::*;
+pub use format::*;
+pub use highlight::*;
 pub use insert::*;
+pub use ordering::*;
+pub use parse_cache::*;
+pub use pragma::*;
+pub use reindex::*;
+pub use script::*;
 pub use select::*;
 pub use statement::*;
+use transaction::*;
+pub use update::*;
+
+// Every keyword understood by the parser
+pub const KEYWORDS: &[&str] = &[
+    "alter",
+    "and",
+    "as",
+    "asc",
+    "attach",
+    "begin",
+    "big",
+    "bigint",
+    "by",
+    "char",
+    "check",
+    "column",
+    "comment",
+    "commit",
+    "create",
+    "database",
+    "default",
+    "delete",
+    "desc",
+    "detach",
+    "drop",
+    "enclosed",
+    "enum",
+    "exists",
+    "explain",
+    "false",
+    "fields",
+    "from",
+    "glob",
+    "group",
+    "if",
+    "index",
+    "insert",
+    "int",
+    "integer",
+    "into",
+    "is",
+    "json",
+    "key",
+    "limit",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "optionally",
+    "or",
+    "order",
+    "outfile",
+    "pragma",
+    "primary",
+    "references",
+    "regexp",
+    "reindex",
+    "rename",
+    "rollback",
+    "select",
+    "set",
+    "smallint",
+    "table",
+    "terminated",
+    "text",
+    "to",
+    "transaction",
+    "true",
+    "unique",
+    "unsigned",
+    "update",
+    "uuid",
+    "values",
+    "varchar",
+    "where",
+];
 
 fn parse_statement_type(statement_str: &str) -> IResult<&str, StatementType, VerboseError<&str>> {
     let (statement_str, _) = multispace0(statement_str)?;
 
     map_res(
         alt((
+            tag_no_case("alter"),
+            tag_no_case("attach"),
+            tag_no_case("begin"),
+            tag_no_case("commit"),
             tag_no_case("create"),
+            tag_no_case("delete"),
+            tag_no_case("detach"),
+            tag_no_case("drop"),
+            tag_no_case("explain"),
             tag_no_case("insert"),
+            tag_no_case("pragma"),
+            tag_no_case("reindex"),
+            tag_no_case("rollback"),
             tag_no_case("select"),
+            tag_no_case("update"),
         )),
         |s: &str| StatementType::try_from(s),
     )(statement_str)
 }
 
-pub fn parse_statement(statement_str: &str) -> Result<Statement, ParseError> {
+pub fn parse_statement(statement_str: &str) -> Result<Statement<'_>, ParseError> {
     if let Ok((_, statement_type)) = parse_statement_type(statement_str) {
         match statement_type {
+            StatementType::Alter => validate_alter(statement_str),
+            StatementType::Attach => validate_attach(statement_str),
+            StatementType::Begin => validate_begin(statement_str),
+            StatementType::Commit => validate_commit(statement_str),
             StatementType::Create => validate_create(statement_str),
+            StatementType::Delete => validate_delete(statement_str),
+            StatementType::Detach => validate_detach(statement_str),
+            StatementType::Drop => validate_drop(statement_str),
+            StatementType::Explain => validate_explain(statement_str),
             StatementType::Insert => validate_insert(statement_str),
+            StatementType::Pragma => validate_pragma(statement_str),
+            StatementType::Reindex => validate_reindex(statement_str),
+            StatementType::Rollback => validate_rollback(statement_str),
             StatementType::Select => validate_select(statement_str),
+            StatementType::Update => validate_update(statement_str),
         }
     } else {
         Err(ParseError::UnknownStatement)
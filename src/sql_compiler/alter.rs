@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+
+use nom::{
+    branch::alt,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, map, opt},
+    error::VerboseError,
+    sequence::{pair, preceded, separated_pair, tuple},
+    Finish, IResult,
+};
+
+use super::statement::{ParseError, Statement};
+use super::{keyword, parse_identifier, parse_table_name};
+
+// `ALTER TABLE table RENAME TO new_table` or `ALTER TABLE table RENAME [COLUMN] column TO
+// new_column`
+#[derive(Debug, Clone)]
+pub struct AlterTokens<'a> {
+    pub table_name: Cow<'a, str>,
+    pub action: AlterAction<'a>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AlterAction<'a> {
+    // The new name stays in the database of the table, so it is not qualified
+    RenameTable(Cow<'a, str>),
+    RenameColumn(Cow<'a, str>, Cow<'a, str>),
+}
+
+impl AlterTokens<'_> {
+    pub fn into_owned(self) -> AlterTokens<'static> {
+        AlterTokens {
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            action: match self.action {
+                AlterAction::RenameTable(name) => {
+                    AlterAction::RenameTable(Cow::Owned(name.into_owned()))
+                }
+                AlterAction::RenameColumn(from, to) => AlterAction::RenameColumn(
+                    Cow::Owned(from.into_owned()),
+                    Cow::Owned(to.into_owned()),
+                ),
+            },
+        }
+    }
+}
+
+fn parse_rename(input: &str) -> IResult<&str, AlterAction<'_>, VerboseError<&str>> {
+    let to = tuple((multispace1, keyword("to"), multispace1));
+    let rename_table = map(
+        preceded(pair(keyword("to"), multispace1), parse_identifier),
+        |name| AlterAction::RenameTable(Cow::Borrowed(name)),
+    );
+    let rename_column = map(
+        preceded(
+            opt(pair(keyword("column"), multispace1)),
+            separated_pair(parse_identifier, to, parse_identifier),
+        ),
+        |(from, to)| AlterAction::RenameColumn(Cow::Borrowed(from), Cow::Borrowed(to)),
+    );
+    preceded(
+        pair(keyword("rename"), multispace1),
+        alt((rename_table, rename_column)),
+    )(input)
+}
+
+fn parse_alter(input: &str) -> IResult<&str, AlterTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        keyword("alter"),
+        multispace1,
+        keyword("table"),
+        multispace1,
+    ))(input)?;
+    let (input, table_name) = parse_table_name(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, action) = parse_rename(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        AlterTokens {
+            table_name: Cow::Borrowed(table_name),
+            action,
+        },
+    ))
+}
+
+pub(super) fn validate_alter(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_alter(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, alter_tokens)) => Ok(Statement::Alter(alter_tokens)),
+    }
+}
@@ -0,0 +1,46 @@
+use console::style;
+
+use super::token_at;
+
+// Shows the line of a statement holding the byte at `offset`, with the token starting there
+// colored and underlined by carets. A position at the end of the statement gets a single caret
+// past its last character. Colors follow those of stderr, so NO_COLOR turns them off
+pub fn annotate(sql: &str, offset: usize) -> String {
+    let offset = offset.min(sql.len());
+    let line_start = sql[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = sql[offset..]
+        .find('\n')
+        .map_or(sql.len(), |idx| offset + idx);
+    let token = token_at(&sql[offset..line_end]);
+
+    // Tabs are shown as spaces so that the carets line up below the token
+    let before = sql[line_start..offset].replace('\t', " ");
+    let after = sql[offset + token.len()..line_end].replace('\t', " ");
+    let underline = "^".repeat(token.chars().count().max(1));
+    format!(
+        "    {}{}{}\n    {}{}",
+        before,
+        style(token).red().bold().for_stderr(),
+        after,
+        " ".repeat(before.chars().count()),
+        style(underline).red().bold().for_stderr()
+    )
+}
+
+// Byte offset of the first place where `word` appears in the statement on its own, rather than as
+// part of a longer name. Case is ignored
+pub fn find_word(sql: &str, word: &str) -> Option<usize> {
+    if word.is_empty() {
+        return None;
+    }
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let sql_lower = sql.to_ascii_lowercase();
+    sql_lower
+        .match_indices(&word.to_ascii_lowercase())
+        .map(|(idx, _)| idx)
+        .find(|&idx| {
+            let before = sql[..idx].chars().next_back();
+            let after = sql[idx + word.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+}
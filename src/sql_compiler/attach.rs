@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag_no_case,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, opt},
+    error::VerboseError,
+    sequence::{delimited, pair, tuple},
+    Finish, IResult,
+};
+
+use super::statement::{ParseError, Statement};
+use super::{escaped_string_double_quote, escaped_string_single_quote, keyword, parse_identifier};
+
+#[derive(Debug, Clone)]
+pub struct AttachTokens<'a> {
+    pub path: Cow<'a, str>,
+    pub alias: Cow<'a, str>,
+}
+
+impl AttachTokens<'_> {
+    pub fn into_owned(self) -> AttachTokens<'static> {
+        AttachTokens {
+            path: Cow::Owned(self.path.into_owned()),
+            alias: Cow::Owned(self.alias.into_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DetachTokens<'a> {
+    pub alias: Cow<'a, str>,
+}
+
+impl DetachTokens<'_> {
+    pub fn into_owned(self) -> DetachTokens<'static> {
+        DetachTokens {
+            alias: Cow::Owned(self.alias.into_owned()),
+        }
+    }
+}
+
+fn parse_path(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((
+        delimited(char('\''), escaped_string_single_quote, char('\'')),
+        delimited(char('"'), escaped_string_double_quote, char('"')),
+    ))(input)
+}
+
+fn parse_attach(input: &str) -> IResult<&str, AttachTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        tag_no_case("attach"),
+        multispace1,
+        opt(pair(keyword("database"), multispace1)),
+    ))(input)?;
+
+    let (input, path) = parse_path(input)?;
+    let (input, _) = tuple((multispace1, keyword("as"), multispace1))(input)?;
+    let (input, alias) = parse_identifier(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        AttachTokens {
+            path: Cow::Borrowed(path),
+            alias: Cow::Borrowed(alias),
+        },
+    ))
+}
+
+fn parse_detach(input: &str) -> IResult<&str, DetachTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        tag_no_case("detach"),
+        multispace1,
+        opt(pair(keyword("database"), multispace1)),
+    ))(input)?;
+
+    let (input, alias) = parse_identifier(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        DetachTokens {
+            alias: Cow::Borrowed(alias),
+        },
+    ))
+}
+
+pub(super) fn validate_attach(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_attach(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, attach_tokens)) => Ok(Statement::Attach(attach_tokens)),
+    }
+}
+
+pub(super) fn validate_detach(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_detach(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, detach_tokens)) => Ok(Statement::Detach(detach_tokens)),
+    }
+}
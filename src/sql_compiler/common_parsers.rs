@@ -1,24 +1,93 @@
+use std::borrow::Cow;
+
 use nom::{
-    bytes::complete::escaped,
-    character::complete::{alphanumeric1, anychar, char, none_of, one_of},
-    combinator::{opt, recognize, verify},
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_till1},
+    character::complete::{alphanumeric1, anychar, char, none_of},
+    combinator::{all_consuming, not, opt, peek, recognize, verify},
     error::VerboseError,
     multi::many0_count,
-    sequence::{pair, preceded},
+    sequence::{delimited, pair, preceded, terminated},
     IResult,
 };
 
-pub(super) fn parse_identifier(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+fn bare_identifier(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
     recognize(pair(
         verify(anychar, |&c: &char| c.is_alphabetic()),
         many0_count(preceded(opt(char('_')), alphanumeric1)),
     ))(input)
 }
 
+// An identifier written in double quotes, backticks or square brackets, as SQLite does for
+// names that are keywords or hold other characters. The name is returned without its quotes
+fn quoted_identifier(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((
+        delimited(char('"'), take_till1(|c| c == '"'), char('"')),
+        delimited(char('`'), take_till1(|c| c == '`'), char('`')),
+        delimited(char('['), take_till1(|c| c == ']'), char(']')),
+    ))(input)
+}
+
+pub(super) fn parse_identifier(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((bare_identifier, quoted_identifier))(input)
+}
+
+// Whether the name can be written without quotes, being a plain identifier and not a reserved
+// keyword
+pub(super) fn is_bare_identifier(name: &str) -> bool {
+    all_consuming(bare_identifier)(name).is_ok()
+        && !RESERVED_KEYWORDS.contains(&name.to_lowercase().as_str())
+}
+
+// A table name, optionally qualified with the alias of an attached database as `alias.table`.
+// Only unqualified names may be quoted
+pub(super) fn parse_table_name(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((
+        recognize(pair(bare_identifier, opt(pair(char('.'), bare_identifier)))),
+        quoted_identifier,
+    ))(input)
+}
+
+// A backslash escapes any character, so that e.g. regular expressions can be written as they are,
+// and a quote may also be doubled as in standard SQL. The text is returned as written, escapes
+// included
 pub(super) fn escaped_string_single_quote(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
-    escaped(none_of("\\\'"), '\\', one_of(r#""n\'"#))(input)
+    recognize(many0_count(alt((
+        recognize(none_of("\\'")),
+        recognize(pair(char('\\'), anychar)),
+        tag("''"),
+    ))))(input)
 }
 
 pub(super) fn escaped_string_double_quote(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
-    escaped(none_of("\\\""), '\\', one_of(r#""n\'"#))(input)
+    recognize(many0_count(alt((
+        recognize(none_of("\\\"")),
+        recognize(pair(char('\\'), anychar)),
+        tag("\"\""),
+    ))))(input)
+}
+
+// Turns the doubled quotes of a string written between the given quotes back into single ones
+pub(super) fn unescape_quotes(text: &str, quote: char) -> Cow<'_, str> {
+    let doubled = format!("{}{}", quote, quote);
+    match text.contains(&doubled) {
+        true => Cow::Owned(text.replace(&doubled, &quote.to_string())),
+        false => Cow::Borrowed(text),
+    }
+}
+
+pub(super) const RESERVED_KEYWORDS: &[&str] = &[
+    "and", "as", "by", "false", "from", "group", "is", "not", "null", "or", "true", "where",
+];
+
+// Matches a keyword case-insensitively, making sure it is not just the prefix of a longer word
+pub(super) fn keyword<'a>(
+    kw: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, VerboseError<&'a str>> {
+    terminated(
+        tag_no_case(kw),
+        not(peek(verify(anychar, |&c: &char| {
+            c.is_alphanumeric() || c == '_'
+        }))),
+    )
 }
@@ -1,63 +1,304 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
 use nom::{
     branch::alt,
     bytes::complete::tag_no_case,
     character::complete::{char, digit1, multispace0, multispace1},
-    combinator::{all_consuming, cut, map_res},
-    error::{convert_error, VerboseError},
-    multi::separated_list1,
-    sequence::{delimited, pair, separated_pair, tuple},
+    combinator::{all_consuming, consumed, cut, map, map_res, opt, recognize, value, verify},
+    error::VerboseError,
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
     Finish, IResult,
 };
 
-use super::parse_identifier;
+use super::expression::parse_expr;
+use super::insert::parse_value;
 use super::statement::{ParseError, Statement};
-use crate::backend::columns::{ColumnItemType, IntegerType, TextType};
+use super::{escaped_string_single_quote, keyword, parse_identifier, parse_table_name};
+use crate::backend::columns::{
+    ColumnConstraints, ColumnDefault, ColumnItemType, EnumType, IntegerType, TextType,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CreateTokens<'a> {
-    pub table_name: &'a str,
-    pub columns: Vec<(&'a str, ColumnItemType)>,
+    pub table_name: Cow<'a, str>,
+    pub columns: Vec<(Cow<'a, str>, ColumnItemType, ColumnConstraints)>,
+    // Columns declared as PRIMARY KEY
+    pub primary_key: Vec<Cow<'a, str>>,
+    // Columns given a COMMENT, along with it
+    pub column_comments: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    pub comment: Option<Cow<'a, str>>,
+    // CREATE TABLE IF NOT EXISTS does nothing when the table is already there
+    pub if_not_exists: bool,
+}
+
+impl CreateTokens<'_> {
+    pub fn into_owned(self) -> CreateTokens<'static> {
+        CreateTokens {
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            columns: self
+                .columns
+                .into_iter()
+                .map(|(name, column_type, constraints)| {
+                    (Cow::Owned(name.into_owned()), column_type, constraints)
+                })
+                .collect(),
+            primary_key: self
+                .primary_key
+                .into_iter()
+                .map(|name| Cow::Owned(name.into_owned()))
+                .collect(),
+            column_comments: self
+                .column_comments
+                .into_iter()
+                .map(|(name, comment)| {
+                    (
+                        Cow::Owned(name.into_owned()),
+                        Cow::Owned(comment.into_owned()),
+                    )
+                })
+                .collect(),
+            comment: self.comment.map(|comment| Cow::Owned(comment.into_owned())),
+            if_not_exists: self.if_not_exists,
+        }
+    }
 }
 
+// `CREATE INDEX [IF NOT EXISTS] [alias.]index ON table (column, ...)`. The table is the one of
+// that name in the database the index name is qualified with
+#[derive(Debug, Clone)]
+pub struct CreateIndexTokens<'a> {
+    pub index_name: Cow<'a, str>,
+    pub table_name: Cow<'a, str>,
+    pub columns: Vec<Cow<'a, str>>,
+    // CREATE INDEX IF NOT EXISTS does nothing when the index is already there
+    pub if_not_exists: bool,
+}
+
+impl CreateIndexTokens<'_> {
+    pub fn into_owned(self) -> CreateIndexTokens<'static> {
+        CreateIndexTokens {
+            index_name: Cow::Owned(self.index_name.into_owned()),
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            columns: self
+                .columns
+                .into_iter()
+                .map(|column| Cow::Owned(column.into_owned()))
+                .collect(),
+            if_not_exists: self.if_not_exists,
+        }
+    }
+
+    // Name of the table qualified like the index, so that it is looked up in the same database
+    pub fn qualified_table_name(&self) -> String {
+        match self.index_name.split_once('.') {
+            Some((alias, _)) => format!("{}.{}", alias, self.table_name),
+            None => self.table_name.to_string(),
+        }
+    }
+}
+
+// INTEGER is 64-bit, as in SQLite
 fn parse_int_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
-    let (remainder, _) = tag_no_case("int")(input)?;
-    Ok((remainder, ColumnItemType::Integer(IntegerType::Int)))
+    let (remainder, int_type) = alt((
+        value(IntegerType::BigInt, tag_no_case("integer")),
+        value(IntegerType::Int, tag_no_case("int")),
+        value(IntegerType::BigInt, tag_no_case("bigint")),
+        value(IntegerType::SmallInt, tag_no_case("smallint")),
+    ))(input)?;
+    Ok((remainder, ColumnItemType::Integer(int_type)))
 }
 
 fn parse_ubigint_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
-    let (remainder, _) = alt((tag_no_case("unsigned big int"), tag_no_case("primary key")))(input)?;
+    let (remainder, _) = tag_no_case("unsigned big int")(input)?;
     Ok((remainder, ColumnItemType::Integer(IntegerType::UBigInt)))
 }
 
-fn parse_text_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
-    let (remainder, num_characters) = delimited(
-        tag_no_case("varchar("),
-        map_res(digit1, |s: &str| s.parse::<u8>()),
+fn parse_primary_key(input: &str) -> IResult<&str, (), VerboseError<&str>> {
+    value(
+        (),
+        tuple((tag_no_case("primary"), multispace1, tag_no_case("key"))),
+    )(input)
+}
+
+fn parse_length<T: FromStr>(input: &str) -> IResult<&str, T, VerboseError<&str>> {
+    delimited(
+        char('('),
+        map_res(digit1, |s: &str| s.parse::<T>()),
         char(')'),
+    )(input)
+}
+
+fn parse_text_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
+    let (remainder, text_type) = alt((
+        map(
+            preceded(tag_no_case("varchar"), parse_length),
+            TextType::Varchar,
+        ),
+        map(preceded(tag_no_case("char"), parse_length), TextType::Char),
+        value(TextType::Text, tag_no_case("text")),
+    ))(input)?;
+
+    Ok((remainder, ColumnItemType::Text(text_type)))
+}
+
+// ENUM('a', 'b', ...), where every label must be different
+fn parse_enum_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
+    let label = delimited(char('\''), escaped_string_single_quote, char('\''));
+    let labels = delimited(
+        pair(char('('), multispace0),
+        separated_list1(delimited(multispace0, char(','), multispace0), label),
+        pair(multispace0, char(')')),
+    );
+    let (remainder, labels) = preceded(
+        pair(tag_no_case("enum"), multispace0),
+        verify(labels, |labels: &Vec<&str>| {
+            labels
+                .iter()
+                .enumerate()
+                .all(|(idx, label)| !labels[..idx].contains(label))
+        }),
     )(input)?;
 
-    Ok((
-        remainder,
-        ColumnItemType::Text(TextType::Varchar(num_characters)),
-    ))
+    let labels = labels.into_iter().map(str::to_string).collect();
+    Ok((remainder, ColumnItemType::Enum(EnumType { labels })))
+}
+
+fn parse_uuid_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
+    value(ColumnItemType::Uuid, tag_no_case("uuid"))(input)
+}
+
+fn parse_json_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
+    value(ColumnItemType::Json, tag_no_case("json"))(input)
 }
 
 fn parse_column_type(input: &str) -> IResult<&str, ColumnItemType, VerboseError<&str>> {
-    alt((parse_int_type, parse_ubigint_type, parse_text_type))(input)
+    alt((
+        parse_int_type,
+        parse_ubigint_type,
+        parse_text_type,
+        parse_enum_type,
+        parse_uuid_type,
+        parse_json_type,
+    ))(input)
+}
+
+// Clauses that may follow the type of a column
+#[derive(Debug, Clone)]
+enum ColumnConstraint<'a> {
+    PrimaryKey,
+    NotNull,
+    Unique,
+    // DEFAULT NULL is the same as no default
+    Default(Option<ColumnDefault>),
+    Check(&'a str),
+    References(&'a str, &'a str),
+    Comment(&'a str),
+}
+
+fn parse_comment(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    delimited(char('\''), escaped_string_single_quote, char('\''))(input)
+}
+
+// A literal is kept as written, like the values of an INSERT. Any other expression is kept as its
+// SQL text, to be evaluated for every row inserted
+fn parse_default(input: &str) -> IResult<&str, Option<ColumnDefault>, VerboseError<&str>> {
+    alt((
+        map(consumed(parse_expr), |(text, _)| {
+            match all_consuming(parse_value)(text) {
+                _ if text.eq_ignore_ascii_case("null") => None,
+                Ok((_, literal)) => Some(ColumnDefault::Literal(literal.into_owned())),
+                Err(_) => Some(ColumnDefault::Expr(text.to_string())),
+            }
+        }),
+        value(None, keyword("null")),
+        map(parse_value, |literal| {
+            Some(ColumnDefault::Literal(literal.into_owned()))
+        }),
+    ))(input)
+}
+
+fn parse_column_constraint(input: &str) -> IResult<&str, ColumnConstraint<'_>, VerboseError<&str>> {
+    let check_expr = delimited(
+        pair(char('('), multispace0),
+        recognize(parse_expr),
+        pair(multispace0, char(')')),
+    );
+    let referenced_column = delimited(
+        pair(char('('), multispace0),
+        parse_identifier,
+        pair(multispace0, char(')')),
+    );
+
+    alt((
+        value(ColumnConstraint::PrimaryKey, parse_primary_key),
+        value(
+            ColumnConstraint::NotNull,
+            tuple((keyword("not"), multispace1, keyword("null"))),
+        ),
+        value(ColumnConstraint::Unique, keyword("unique")),
+        map(
+            preceded(pair(keyword("default"), multispace1), parse_default),
+            ColumnConstraint::Default,
+        ),
+        map(
+            preceded(pair(keyword("check"), multispace0), check_expr),
+            ColumnConstraint::Check,
+        ),
+        map(
+            preceded(
+                pair(keyword("references"), multispace1),
+                separated_pair(parse_identifier, multispace0, referenced_column),
+            ),
+            |(table, column)| ColumnConstraint::References(table, column),
+        ),
+        map(
+            preceded(pair(keyword("comment"), multispace1), parse_comment),
+            ColumnConstraint::Comment,
+        ),
+    ))(input)
 }
 
-fn parse_columns(input: &str) -> IResult<&str, Vec<(&str, ColumnItemType)>, VerboseError<&str>> {
+// A column type followed by any number of constraints. PRIMARY KEY in place of the type stands
+// for an UNSIGNED BIG INT primary key
+fn parse_column_definition(
+    input: &str,
+) -> IResult<&str, (ColumnItemType, Vec<ColumnConstraint<'_>>), VerboseError<&str>> {
+    let (input, (column_type, first_constraint)) = alt((
+        value(
+            (
+                ColumnItemType::Integer(IntegerType::UBigInt),
+                Some(ColumnConstraint::PrimaryKey),
+            ),
+            parse_primary_key,
+        ),
+        map(parse_column_type, |column_type| (column_type, None)),
+    ))(input)?;
+    let (input, constraints) = many0(preceded(multispace1, parse_column_constraint))(input)?;
+
+    let constraints = first_constraint.into_iter().chain(constraints).collect();
+    Ok((input, (column_type, constraints)))
+}
+
+// Name, type and constraints of a column
+type ColumnDefinition<'a> = (&'a str, ColumnItemType, Vec<ColumnConstraint<'a>>);
+
+fn parse_columns(input: &str) -> IResult<&str, Vec<ColumnDefinition<'_>>, VerboseError<&str>> {
     separated_list1(
         char(','),
         cut(delimited(
             multispace0,
-            separated_pair(parse_identifier, multispace1, parse_column_type),
+            map(
+                separated_pair(parse_identifier, multispace1, parse_column_definition),
+                |(name, (column_type, constraints))| (name, column_type, constraints),
+            ),
             multispace0,
         )),
     )(input)
 }
 
-fn parse_create(input: &str) -> IResult<&str, CreateTokens, VerboseError<&str>> {
+fn parse_create(input: &str) -> IResult<&str, CreateTokens<'_>, VerboseError<&str>> {
     let (input, _) = tuple((
         multispace0,
         tag_no_case("create"),
@@ -65,24 +306,154 @@ fn parse_create(input: &str) -> IResult<&str, CreateTokens, VerboseError<&str>>
         tag_no_case("table"),
         multispace1,
     ))(input)?;
+    let (input, if_not_exists) = opt(tuple((
+        keyword("if"),
+        multispace1,
+        keyword("not"),
+        multispace1,
+        keyword("exists"),
+        multispace1,
+    )))(input)?;
 
-    let (input, table_name) = parse_identifier(input)?;
+    let (input, table_name) = parse_table_name(input)?;
     let (input, _) = multispace0(input)?;
     let (input, columns_vec) = delimited(char('('), parse_columns, char(')'))(input)?;
+    // COMMENT 'text' or COMMENT = 'text' after the columns describes the table
+    let (input, comment) = opt(preceded(
+        tuple((
+            multispace0,
+            keyword("comment"),
+            multispace0,
+            opt(char('=')),
+            multispace0,
+        )),
+        parse_comment,
+    ))(input)?;
     let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
 
     Ok((
         "",
         CreateTokens {
-            table_name,
-            columns: columns_vec,
+            table_name: Cow::Borrowed(table_name),
+            primary_key: columns_vec
+                .iter()
+                .filter(|(_, _, constraints)| {
+                    constraints
+                        .iter()
+                        .any(|constraint| matches!(constraint, ColumnConstraint::PrimaryKey))
+                })
+                .map(|(name, _, _)| Cow::Borrowed(*name))
+                .collect(),
+            column_comments: columns_vec
+                .iter()
+                .flat_map(|(name, _, constraints)| {
+                    constraints
+                        .iter()
+                        .filter_map(|constraint| match constraint {
+                            ColumnConstraint::Comment(comment) => {
+                                Some((Cow::Borrowed(*name), Cow::Borrowed(*comment)))
+                            }
+                            _ => None,
+                        })
+                })
+                .collect(),
+            comment: comment.map(Cow::Borrowed),
+            if_not_exists: if_not_exists.is_some(),
+            columns: columns_vec
+                .into_iter()
+                .map(|(name, column_type, constraints)| {
+                    (
+                        Cow::Borrowed(name),
+                        column_type,
+                        collect_constraints(constraints),
+                    )
+                })
+                .collect(),
+        },
+    ))
+}
+
+// Later clauses of the same kind replace earlier ones
+fn collect_constraints(constraints: Vec<ColumnConstraint>) -> ColumnConstraints {
+    let mut collected = ColumnConstraints::default();
+    for constraint in constraints {
+        match constraint {
+            ColumnConstraint::PrimaryKey | ColumnConstraint::Comment(_) => {}
+            ColumnConstraint::NotNull => collected.not_null = true,
+            ColumnConstraint::Unique => collected.unique = true,
+            ColumnConstraint::Default(default) => collected.default = default,
+            ColumnConstraint::Check(check) => collected.check = Some(check.to_string()),
+            ColumnConstraint::References(table, column) => {
+                collected.references = Some((table.to_string(), column.to_string()))
+            }
+        }
+    }
+    collected
+}
+
+fn parse_create_index(input: &str) -> IResult<&str, CreateIndexTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        keyword("create"),
+        multispace1,
+        keyword("index"),
+        multispace1,
+    ))(input)?;
+    let (input, if_not_exists) = opt(tuple((
+        keyword("if"),
+        multispace1,
+        keyword("not"),
+        multispace1,
+        keyword("exists"),
+        multispace1,
+    )))(input)?;
+    let (input, index_name) = parse_table_name(input)?;
+    let (input, _) = tuple((multispace1, keyword("on"), multispace1))(input)?;
+    let (input, table_name) = parse_identifier(input)?;
+    let (input, columns) = preceded(
+        multispace0,
+        delimited(
+            pair(char('('), multispace0),
+            separated_list1(
+                delimited(multispace0, char(','), multispace0),
+                parse_identifier,
+            ),
+            pair(multispace0, char(')')),
+        ),
+    )(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        CreateIndexTokens {
+            index_name: Cow::Borrowed(index_name),
+            table_name: Cow::Borrowed(table_name),
+            columns: columns.into_iter().map(Cow::Borrowed).collect(),
+            if_not_exists: if_not_exists.is_some(),
         },
     ))
 }
 
-pub(super) fn validate_create(input: &str) -> Result<Statement, ParseError> {
+// Whether the statement creates an index rather than a table
+fn creates_index(input: &str) -> bool {
+    tuple((
+        multispace0::<&str, VerboseError<&str>>,
+        keyword("create"),
+        multispace1,
+        keyword("index"),
+    ))(input)
+    .is_ok()
+}
+
+pub(super) fn validate_create(input: &str) -> Result<Statement<'_>, ParseError> {
+    if creates_index(input) {
+        return match parse_create_index(input).finish() {
+            Err(e) => Err(ParseError::malformed(input, e)),
+            Ok((_, create_index_tokens)) => Ok(Statement::CreateIndex(create_index_tokens)),
+        };
+    }
     match parse_create(input).finish() {
-        Err(e) => Err(ParseError::MalformedStatement(convert_error(input, e))),
+        Err(e) => Err(ParseError::malformed(input, e)),
         Ok((_, create_tokens)) => Ok(Statement::Create(create_tokens)),
     }
 }
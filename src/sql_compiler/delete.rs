@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+
+use nom::{
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, opt},
+    error::VerboseError,
+    sequence::{pair, tuple},
+    Finish, IResult,
+};
+
+use super::expression::Expr;
+use super::ordering::{parse_ordered_limit, OrderingTerm, RowLimit};
+use super::select::parse_where_clause;
+use super::statement::{ParseError, Statement};
+use super::{keyword, parse_table_name};
+
+// `DELETE FROM table [WHERE condition] [[ORDER BY ...] LIMIT n [OFFSET m]]`. Without a WHERE
+// clause every row is deleted
+#[derive(Debug, Clone)]
+pub struct DeleteTokens<'a> {
+    pub table_name: Cow<'a, str>,
+    pub where_clause: Option<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<RowLimit>,
+}
+
+impl DeleteTokens<'_> {
+    pub fn into_owned(self) -> DeleteTokens<'static> {
+        DeleteTokens {
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            where_clause: self.where_clause,
+            order_by: self.order_by,
+            limit: self.limit,
+        }
+    }
+}
+
+fn parse_delete(input: &str) -> IResult<&str, DeleteTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        keyword("delete"),
+        multispace1,
+        keyword("from"),
+        multispace1,
+    ))(input)?;
+    let (input, table_name) = parse_table_name(input)?;
+    let (input, where_clause) = opt(parse_where_clause)(input)?;
+    let (input, (order_by, limit)) = parse_ordered_limit(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        DeleteTokens {
+            table_name: Cow::Borrowed(table_name),
+            where_clause,
+            order_by,
+            limit,
+        },
+    ))
+}
+
+pub(super) fn validate_delete(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_delete(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, delete_tokens)) => Ok(Statement::Delete(delete_tokens)),
+    }
+}
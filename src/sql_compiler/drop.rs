@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+
+use nom::{
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, opt},
+    error::VerboseError,
+    sequence::{pair, tuple},
+    Finish, IResult,
+};
+
+use super::statement::{ParseError, Statement};
+use super::{keyword, parse_table_name};
+
+// `DROP TABLE [IF EXISTS] table`
+#[derive(Debug, Clone)]
+pub struct DropTokens<'a> {
+    pub table_name: Cow<'a, str>,
+    // DROP TABLE IF EXISTS does nothing when the table is not there
+    pub if_exists: bool,
+}
+
+impl DropTokens<'_> {
+    pub fn into_owned(self) -> DropTokens<'static> {
+        DropTokens {
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            if_exists: self.if_exists,
+        }
+    }
+}
+
+// `DROP INDEX [IF EXISTS] [alias.]index`
+#[derive(Debug, Clone)]
+pub struct DropIndexTokens<'a> {
+    pub index_name: Cow<'a, str>,
+    // DROP INDEX IF EXISTS does nothing when the index is not there
+    pub if_exists: bool,
+}
+
+impl DropIndexTokens<'_> {
+    pub fn into_owned(self) -> DropIndexTokens<'static> {
+        DropIndexTokens {
+            index_name: Cow::Owned(self.index_name.into_owned()),
+            if_exists: self.if_exists,
+        }
+    }
+}
+
+fn parse_drop(input: &str) -> IResult<&str, DropTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        keyword("drop"),
+        multispace1,
+        keyword("table"),
+        multispace1,
+    ))(input)?;
+    let (input, if_exists) = opt(tuple((
+        keyword("if"),
+        multispace1,
+        keyword("exists"),
+        multispace1,
+    )))(input)?;
+    let (input, table_name) = parse_table_name(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        DropTokens {
+            table_name: Cow::Borrowed(table_name),
+            if_exists: if_exists.is_some(),
+        },
+    ))
+}
+
+fn parse_drop_index(input: &str) -> IResult<&str, DropIndexTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace0,
+        keyword("drop"),
+        multispace1,
+        keyword("index"),
+        multispace1,
+    ))(input)?;
+    let (input, if_exists) = opt(tuple((
+        keyword("if"),
+        multispace1,
+        keyword("exists"),
+        multispace1,
+    )))(input)?;
+    let (input, index_name) = parse_table_name(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        DropIndexTokens {
+            index_name: Cow::Borrowed(index_name),
+            if_exists: if_exists.is_some(),
+        },
+    ))
+}
+
+// Whether the statement drops an index rather than a table
+fn drops_index(input: &str) -> bool {
+    tuple((
+        multispace0::<&str, VerboseError<&str>>,
+        keyword("drop"),
+        multispace1,
+        keyword("index"),
+    ))(input)
+    .is_ok()
+}
+
+pub(super) fn validate_drop(input: &str) -> Result<Statement<'_>, ParseError> {
+    if drops_index(input) {
+        return match parse_drop_index(input).finish() {
+            Err(e) => Err(ParseError::malformed(input, e)),
+            Ok((_, drop_index_tokens)) => Ok(Statement::DropIndex(drop_index_tokens)),
+        };
+    }
+    match parse_drop(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, drop_tokens)) => Ok(Statement::Drop(drop_tokens)),
+    }
+}
@@ -0,0 +1,28 @@
+use nom::{
+    bytes::complete::tag_no_case,
+    character::complete::{multispace0, multispace1},
+    error::VerboseError,
+    sequence::tuple,
+    Finish,
+};
+
+use super::parse_statement;
+use super::statement::{ParseError, Statement};
+
+pub(super) fn validate_explain(input: &str) -> Result<Statement<'_>, ParseError> {
+    let explain_prefix = tuple((
+        multispace0::<&str, VerboseError<&str>>,
+        tag_no_case("explain"),
+        multispace1,
+    ))(input)
+    .finish();
+
+    match explain_prefix {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((explained_statement, _)) => {
+            let statement = parse_statement(explained_statement)
+                .map_err(|err| err.shifted(input.len() - explained_statement.len()))?;
+            Ok(Statement::Explain(Box::new(statement)))
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::fmt;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, digit1, multispace0, multispace1},
+    combinator::{map, map_res, opt, value, verify},
+    error::VerboseError,
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use super::select::{parse_select_body, SelectTokens};
+use super::{
+    escaped_string_double_quote, escaped_string_single_quote, keyword, parse_identifier,
+    parse_table_name, unescape_quotes, RESERVED_KEYWORDS,
+};
+use crate::backend::row::SQLType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Minus,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Subtract,
+    Concat,
+    Multiply,
+    Divide,
+    Modulo,
+    Glob,
+    Regexp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(SQLType),
+    Column(String),
+    Unary(UnaryOperator, Box<Expr>),
+    Binary(Box<Expr>, BinaryOperator, Box<Expr>),
+    IsNull { expr: Box<Expr>, negated: bool },
+    Function { name: String, args: Vec<Expr> },
+    // Scalar subquery, whose value is the first column of the first row it returns
+    Subquery(Box<SelectTokens<'static>>),
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOperator::Minus => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "NOT "),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Or => "OR",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Concat => "||",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Glob => "GLOB",
+            BinaryOperator::Regexp => "REGEXP",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Literal(SQLType::Text(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            Expr::Literal(literal) => write!(f, "{}", literal),
+            Expr::Column(name) => write!(f, "{}", name),
+            Expr::Unary(operator, operand) => write!(f, "{}{}", operator, operand),
+            Expr::Binary(left, operator, right) => write!(f, "({} {} {})", left, operator, right),
+            Expr::IsNull { expr, negated } => {
+                write!(f, "{} IS {}NULL", expr, if *negated { "NOT " } else { "" })
+            }
+            // count() with no arguments counts every row, like count(*)
+            Expr::Function { name, args } if name == "count" && args.is_empty() => {
+                write!(f, "count(*)")
+            }
+            Expr::Function { name, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Expr::Subquery(select) => write!(f, "({})", select),
+        }
+    }
+}
+
+type ExprResult<'a> = IResult<&'a str, Expr, VerboseError<&'a str>>;
+
+fn parse_integer_literal(input: &str) -> ExprResult<'_> {
+    map_res(digit1, |digits: &str| {
+        digits
+            .parse::<i32>()
+            .map(SQLType::Integer)
+            .or_else(|_| digits.parse::<u64>().map(SQLType::UBigInt))
+            .map(Expr::Literal)
+    })(input)
+}
+
+fn parse_string_literal(input: &str) -> ExprResult<'_> {
+    alt((
+        map(
+            delimited(char('\''), escaped_string_single_quote, char('\'')),
+            |s| Expr::Literal(SQLType::Text(unescape_quotes(s, '\'').into_owned())),
+        ),
+        map(
+            delimited(char('"'), escaped_string_double_quote, char('"')),
+            |s| Expr::Literal(SQLType::Text(unescape_quotes(s, '"').into_owned())),
+        ),
+    ))(input)
+}
+
+fn parse_keyword_literal(input: &str) -> ExprResult<'_> {
+    alt((
+        value(Expr::Literal(SQLType::Null), keyword("null")),
+        value(Expr::Literal(SQLType::Integer(1)), keyword("true")),
+        value(Expr::Literal(SQLType::Integer(0)), keyword("false")),
+    ))(input)
+}
+
+fn parse_function_call(input: &str) -> ExprResult<'_> {
+    map(
+        pair(
+            terminated(parse_identifier, pair(multispace0, char('('))),
+            terminated(
+                alt((
+                    value(Vec::new(), delimited(multispace0, char('*'), multispace0)),
+                    separated_list0(char(','), delimited(multispace0, parse_expr, multispace0)),
+                )),
+                pair(multispace0, char(')')),
+            ),
+        ),
+        |(name, args)| Expr::Function {
+            name: name.to_lowercase(),
+            args,
+        },
+    )(input)
+}
+
+// A column, optionally qualified with the name of its table as `table.column`
+fn parse_column_ref(input: &str) -> ExprResult<'_> {
+    map(
+        verify(parse_table_name, |identifier: &str| {
+            !RESERVED_KEYWORDS.contains(&identifier.to_lowercase().as_str())
+        }),
+        |identifier: &str| Expr::Column(identifier.to_owned()),
+    )(input)
+}
+
+fn parse_subquery(input: &str) -> ExprResult<'_> {
+    map(
+        delimited(
+            pair(char('('), multispace0),
+            parse_select_body,
+            pair(multispace0, char(')')),
+        ),
+        |select| Expr::Subquery(Box::new(select.into_owned())),
+    )(input)
+}
+
+fn parse_primary(input: &str) -> ExprResult<'_> {
+    preceded(
+        multispace0,
+        alt((
+            parse_integer_literal,
+            parse_string_literal,
+            parse_keyword_literal,
+            parse_function_call,
+            parse_column_ref,
+            parse_subquery,
+            delimited(
+                char('('),
+                delimited(multispace0, parse_expr, multispace0),
+                char(')'),
+            ),
+        )),
+    )(input)
+}
+
+fn parse_unary(input: &str) -> ExprResult<'_> {
+    alt((
+        map(
+            preceded(pair(multispace0, char('-')), parse_unary),
+            |operand| Expr::Unary(UnaryOperator::Minus, Box::new(operand)),
+        ),
+        parse_primary,
+    ))(input)
+}
+
+// Folds `first (operator operand)*` into a left-associative chain of binary expressions
+fn fold_binary(first: Expr, rest: Vec<(BinaryOperator, Expr)>) -> Expr {
+    rest.into_iter().fold(first, |left, (operator, right)| {
+        Expr::Binary(Box::new(left), operator, Box::new(right))
+    })
+}
+
+fn parse_multiplicative(input: &str) -> ExprResult<'_> {
+    let operator = alt((
+        value(BinaryOperator::Multiply, char('*')),
+        value(BinaryOperator::Divide, char('/')),
+        value(BinaryOperator::Modulo, char('%')),
+    ));
+    let (input, first) = parse_unary(input)?;
+    let (input, rest) = many0(pair(preceded(multispace0, operator), parse_unary))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
+
+fn parse_additive(input: &str) -> ExprResult<'_> {
+    let operator = alt((
+        value(BinaryOperator::Concat, tag("||")),
+        value(BinaryOperator::Add, char('+')),
+        value(BinaryOperator::Subtract, char('-')),
+    ));
+    let (input, first) = parse_multiplicative(input)?;
+    let (input, rest) = many0(pair(preceded(multispace0, operator), parse_multiplicative))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
+
+fn parse_comparison(input: &str) -> ExprResult<'_> {
+    let operator = alt((
+        value(BinaryOperator::LessOrEqual, tag("<=")),
+        value(BinaryOperator::GreaterOrEqual, tag(">=")),
+        value(BinaryOperator::NotEqual, tag("!=")),
+        value(BinaryOperator::NotEqual, tag("<>")),
+        value(BinaryOperator::Equal, tag("==")),
+        value(BinaryOperator::Equal, char('=')),
+        value(BinaryOperator::Less, char('<')),
+        value(BinaryOperator::Greater, char('>')),
+    ));
+    // `x NOT GLOB y` is `NOT (x GLOB y)`, and likewise for REGEXP
+    let pattern_operator = delimited(
+        multispace1,
+        pair(
+            opt(terminated(keyword("not"), multispace1)),
+            alt((
+                value(BinaryOperator::Glob, keyword("glob")),
+                value(BinaryOperator::Regexp, keyword("regexp")),
+            )),
+        ),
+        multispace0,
+    );
+    let operator = alt((
+        map(preceded(multispace0, operator), |operator| (None, operator)),
+        pattern_operator,
+    ));
+    let (input, first) = parse_additive(input)?;
+    let (input, rest) = many0(pair(operator, parse_additive))(input)?;
+    let comparison = rest
+        .into_iter()
+        .fold(first, |left, ((not, operator), right)| {
+            let comparison = Expr::Binary(Box::new(left), operator, Box::new(right));
+            match not {
+                Some(_) => Expr::Unary(UnaryOperator::Not, Box::new(comparison)),
+                None => comparison,
+            }
+        });
+
+    let (input, is_null) = opt(preceded(
+        tuple((multispace1, keyword("is"), multispace1)),
+        terminated(
+            opt(terminated(keyword("not"), multispace1)),
+            keyword("null"),
+        ),
+    ))(input)?;
+
+    match is_null {
+        Some(negated) => Ok((
+            input,
+            Expr::IsNull {
+                expr: Box::new(comparison),
+                negated: negated.is_some(),
+            },
+        )),
+        None => Ok((input, comparison)),
+    }
+}
+
+fn parse_not(input: &str) -> ExprResult<'_> {
+    alt((
+        map(
+            preceded(tuple((multispace0, keyword("not"), multispace1)), parse_not),
+            |operand| Expr::Unary(UnaryOperator::Not, Box::new(operand)),
+        ),
+        parse_comparison,
+    ))(input)
+}
+
+fn parse_and(input: &str) -> ExprResult<'_> {
+    let (input, first) = parse_not(input)?;
+    let (input, rest) = many0(pair(
+        value(
+            BinaryOperator::And,
+            delimited(multispace1, keyword("and"), multispace1),
+        ),
+        parse_not,
+    ))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
+
+pub fn parse_expr(input: &str) -> ExprResult<'_> {
+    let (input, first) = parse_and(input)?;
+    let (input, rest) = many0(pair(
+        value(
+            BinaryOperator::Or,
+            delimited(multispace1, keyword("or"), multispace1),
+        ),
+        parse_and,
+    ))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
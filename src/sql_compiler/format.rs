@@ -0,0 +1,536 @@
+use std::iter;
+
+use nom::combinator::all_consuming;
+
+use super::expression::{parse_expr, BinaryOperator, Expr, UnaryOperator};
+use super::statement::{ParseError, Statement};
+use super::{is_bare_identifier, parse_statement, split_statements};
+use super::{
+    AlterAction, AlterTokens, CreateTokens, DeleteTokens, InsertTokens, InsertValue, OrderingTerm,
+    OutfileTokens, PragmaTokens, RowLimit, SelectItem, SelectTokens, UpdateTokens,
+};
+use crate::backend::columns::ColumnDefault;
+use crate::backend::row::SQLType;
+
+const INDENT: &str = "    ";
+
+// Formats every statement of a script, separated by blank lines. Metacommands are kept as written
+pub fn format_sql(sql: &str) -> Result<String, ParseError> {
+    let formatted = split_statements(sql)
+        .into_iter()
+        .map(|(_, statement)| match statement.starts_with('.') {
+            true => Ok(statement.to_string()),
+            false => parse_statement(statement).map(|statement| format_statement(&statement)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(formatted.join("\n\n"))
+}
+
+// Writes a statement back as SQL with upper case keywords, one clause per line and only the
+// parentheses its operators need
+pub fn format_statement(statement: &Statement) -> String {
+    format!("{};", format_body(statement))
+}
+
+fn format_body(statement: &Statement) -> String {
+    match statement {
+        Statement::Alter(tokens) => format_alter(tokens),
+        Statement::Attach(tokens) => format!(
+            "ATTACH DATABASE {} AS {}",
+            quote_text(&tokens.path),
+            identifier(&tokens.alias)
+        ),
+        Statement::Begin => "BEGIN".to_string(),
+        Statement::Commit => "COMMIT".to_string(),
+        Statement::Create(tokens) => format_create(tokens),
+        Statement::CreateIndex(tokens) => format!(
+            "CREATE INDEX {}{} ON {} ({})",
+            if tokens.if_not_exists {
+                "IF NOT EXISTS "
+            } else {
+                ""
+            },
+            table_name(&tokens.index_name),
+            identifier(&tokens.table_name),
+            tokens
+                .columns
+                .iter()
+                .map(|column| identifier(column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Statement::Delete(tokens) => format_delete(tokens),
+        Statement::Detach(tokens) => format!("DETACH DATABASE {}", identifier(&tokens.alias)),
+        Statement::Drop(tokens) => format!(
+            "DROP TABLE {}{}",
+            if tokens.if_exists { "IF EXISTS " } else { "" },
+            table_name(&tokens.table_name)
+        ),
+        Statement::DropIndex(tokens) => format!(
+            "DROP INDEX {}{}",
+            if tokens.if_exists { "IF EXISTS " } else { "" },
+            table_name(&tokens.index_name)
+        ),
+        Statement::Explain(statement) => format!("EXPLAIN {}", format_body(statement)),
+        Statement::Select(tokens) => format_select(tokens, false),
+        Statement::Insert(tokens) => format_insert(tokens),
+        Statement::Pragma(tokens) => format_pragma(tokens),
+        Statement::Reindex(tokens) => match &tokens.table_name {
+            Some(table) => format!("REINDEX {}", table_name(table)),
+            None => "REINDEX".to_string(),
+        },
+        Statement::Rollback => "ROLLBACK".to_string(),
+        Statement::Update(tokens) => format_update(tokens),
+    }
+}
+
+// Names that are not plain identifiers, or are reserved keywords, are written in backticks, as
+// double quotes would make them text in expressions
+fn identifier(name: &str) -> String {
+    match is_bare_identifier(name) {
+        true => name.to_string(),
+        false => format!("`{}`", name),
+    }
+}
+
+// Table names may be qualified as `alias.table`, and `csv('file.csv')` is kept as it is
+fn table_name(name: &str) -> String {
+    match name.split_once('.') {
+        _ if name.contains('(') => name.to_string(),
+        Some((alias, table)) if is_bare_identifier(alias) && is_bare_identifier(table) => {
+            name.to_string()
+        }
+        _ => identifier(name),
+    }
+}
+
+fn quote_text(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn format_alter(tokens: &AlterTokens) -> String {
+    let action = match &tokens.action {
+        AlterAction::RenameTable(name) => format!("RENAME TO {}", identifier(name)),
+        AlterAction::RenameColumn(from, to) => {
+            format!("RENAME COLUMN {} TO {}", identifier(from), identifier(to))
+        }
+    };
+    format!("ALTER TABLE {} {}", table_name(&tokens.table_name), action)
+}
+
+fn format_create(tokens: &CreateTokens) -> String {
+    let definitions: Vec<String> = tokens
+        .columns
+        .iter()
+        .map(|(name, column_type, constraints)| {
+            let mut definition = format!("{}{} {}", INDENT, identifier(name), column_type);
+            if tokens.primary_key.contains(name) {
+                definition.push_str(" PRIMARY KEY");
+            }
+            if constraints.not_null {
+                definition.push_str(" NOT NULL");
+            }
+            if constraints.unique {
+                definition.push_str(" UNIQUE");
+            }
+            match &constraints.default {
+                Some(ColumnDefault::Expr(default)) => {
+                    let default = match all_consuming(parse_expr)(default.trim()) {
+                        Ok((_, expr)) => format_expr(&expr),
+                        Err(_) => default.to_string(),
+                    };
+                    definition += &format!(" DEFAULT ({})", default);
+                }
+                Some(default) => definition += &format!(" DEFAULT {}", default.to_sql(column_type)),
+                None => {}
+            }
+            if let Some(check) = &constraints.check {
+                // The check is kept as written, and only reformatted if it still parses
+                let check = match all_consuming(parse_expr)(check.trim()) {
+                    Ok((_, expr)) => format_expr(&expr),
+                    Err(_) => check.to_string(),
+                };
+                definition += &format!(" CHECK ({})", check);
+            }
+            if let Some((table, column)) = &constraints.references {
+                definition += &format!(" REFERENCES {}({})", table_name(table), identifier(column));
+            }
+            if let Some((_, comment)) = tokens
+                .column_comments
+                .iter()
+                .find(|(column, _)| column == name)
+            {
+                definition += &format!(" COMMENT {}", quote_text(comment));
+            }
+            definition
+        })
+        .collect();
+
+    let mut create = format!(
+        "CREATE TABLE {}{} (\n{}\n)",
+        if tokens.if_not_exists {
+            "IF NOT EXISTS "
+        } else {
+            ""
+        },
+        table_name(&tokens.table_name),
+        definitions.join(",\n")
+    );
+    if let Some(comment) = &tokens.comment {
+        create += &format!(" COMMENT {}", quote_text(comment));
+    }
+    create
+}
+
+// Numbers are written bare and other literals quoted, as INSERT accepts both for any column
+fn format_value(value: &Option<InsertValue>) -> String {
+    match value {
+        None => "NULL".to_string(),
+        Some(InsertValue::Expr(expr)) => format_expr(expr),
+        Some(InsertValue::Literal(value)) => {
+            let digits = value.strip_prefix('-').unwrap_or(value);
+            match !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                true => value.to_string(),
+                false => quote_text(value),
+            }
+        }
+    }
+}
+
+fn format_insert(tokens: &InsertTokens) -> String {
+    let table = table_name(&tokens.table_name);
+    if tokens.column_values.is_empty() && tokens.select.is_none() {
+        return format!("INSERT INTO {} DEFAULT VALUES", table);
+    }
+
+    let mut insert = format!("INSERT INTO {}", table);
+    if !tokens.column_names.is_empty() {
+        let names: Vec<String> = tokens
+            .column_names
+            .iter()
+            .map(|name| identifier(name))
+            .collect();
+        insert += &format!(" ({})", names.join(", "));
+    }
+    if let Some(select_tokens) = &tokens.select {
+        return insert + "\n" + &format_select(select_tokens, false);
+    }
+    let values: Vec<String> = tokens.column_values.iter().map(format_value).collect();
+    insert + &format!("\nVALUES ({})", values.join(", "))
+}
+
+// Numbers are assigned with `=` and names passed as an argument, which PRAGMA accepts either way
+fn format_pragma(tokens: &PragmaTokens) -> String {
+    match &tokens.value {
+        None => format!("PRAGMA {}", tokens.name.to_lowercase()),
+        Some(value) if value.parse::<i64>().is_ok() => {
+            format!("PRAGMA {} = {}", tokens.name.to_lowercase(), value)
+        }
+        Some(value) => format!(
+            "PRAGMA {}({})",
+            tokens.name.to_lowercase(),
+            table_name(value)
+        ),
+    }
+}
+
+fn format_select_item(item: &SelectItem) -> String {
+    match item {
+        SelectItem::Wildcard => "*".to_string(),
+        SelectItem::Expr { expr, alias: None } => format_expr(expr),
+        SelectItem::Expr {
+            expr,
+            alias: Some(alias),
+        } => format!("{} AS {}", format_expr(expr), identifier(alias)),
+    }
+}
+
+// A single select item stays on the SELECT line, several go on a line each. The conditions of a
+// WHERE clause joined by AND, or by OR, also go on a line each. Subqueries are written inline,
+// on a single line
+fn format_select(tokens: &SelectTokens, inline: bool) -> String {
+    let items: Vec<String> = tokens.select_items.iter().map(format_select_item).collect();
+    let clause_start = if inline { " " } else { "\n" };
+    let mut select = match items.as_slice() {
+        [item] => format!("SELECT {}", item),
+        items if inline => format!("SELECT {}", items.join(", ")),
+        items => format!(
+            "SELECT\n{}{}",
+            INDENT,
+            items.join(&format!(",\n{}", INDENT))
+        ),
+    };
+    if let Some(table) = &tokens.table_name {
+        let tables: Vec<String> = iter::once(table)
+            .chain(&tokens.joined_tables)
+            .map(|table| table_name(table))
+            .collect();
+        select += &format!("{}FROM {}", clause_start, tables.join(", "));
+    }
+    if let Some(where_clause) = &tokens.where_clause {
+        select += &format!("{}{}", clause_start, format_where(where_clause, inline));
+    }
+    if !tokens.group_by.is_empty() {
+        let keys: Vec<String> = tokens.group_by.iter().map(format_expr).collect();
+        select += &format!("{}GROUP BY {}", clause_start, keys.join(", "));
+    }
+    if let Some(outfile) = &tokens.outfile {
+        select += &format_outfile(outfile);
+    }
+    select
+}
+
+fn format_where(where_clause: &Expr, inline: bool) -> String {
+    match where_clause {
+        Expr::Binary(_, operator @ (BinaryOperator::And | BinaryOperator::Or), _) if !inline => {
+            let mut conditions = Vec::new();
+            flatten_chain(where_clause, *operator, &mut conditions);
+            let conditions: Vec<String> = conditions
+                .into_iter()
+                .map(|condition| format_operand(condition, precedence_of(*operator) + 1))
+                .collect();
+            format!(
+                "WHERE {}",
+                conditions.join(&format!("\n{}{} ", INDENT, operator))
+            )
+        }
+        where_clause => format!("WHERE {}", format_expr(where_clause)),
+    }
+}
+
+fn format_delete(tokens: &DeleteTokens) -> String {
+    let mut delete = format!("DELETE FROM {}", table_name(&tokens.table_name));
+    if let Some(where_clause) = &tokens.where_clause {
+        delete += &format!("\n{}", format_where(where_clause, false));
+    }
+    delete + &format_ordered_limit(&tokens.order_by, &tokens.limit)
+}
+
+// Each assignment after the first goes on a line of its own, lined up below the first
+fn format_update(tokens: &UpdateTokens) -> String {
+    let assignments: Vec<String> = tokens
+        .assignments
+        .iter()
+        .map(|(name, value)| format!("{} = {}", identifier(name), format_expr(value)))
+        .collect();
+    let mut update = format!(
+        "UPDATE {}\nSET {}",
+        table_name(&tokens.table_name),
+        assignments.join(&format!(",\n{}", INDENT))
+    );
+    if let Some(where_clause) = &tokens.where_clause {
+        update += &format!("\n{}", format_where(where_clause, false));
+    }
+    update + &format_ordered_limit(&tokens.order_by, &tokens.limit)
+}
+
+fn format_ordered_limit(order_by: &[OrderingTerm], limit: &Option<RowLimit>) -> String {
+    let mut clauses = String::new();
+    if !order_by.is_empty() {
+        let terms: Vec<String> = order_by
+            .iter()
+            .map(|term| match term.descending {
+                true => format!("{} DESC", format_expr(&term.expr)),
+                false => format_expr(&term.expr),
+            })
+            .collect();
+        clauses += &format!("\nORDER BY {}", terms.join(", "));
+    }
+    if let Some(RowLimit { count, offset }) = limit {
+        clauses += &format!("\nLIMIT {}", count);
+        if *offset > 0 {
+            clauses += &format!(" OFFSET {}", offset);
+        }
+    }
+    clauses
+}
+
+fn format_outfile(outfile: &OutfileTokens) -> String {
+    let mut clause = format!("\nINTO OUTFILE {}", quote_text(&outfile.path));
+    if outfile.delimiter.is_none() && outfile.quote.is_none() {
+        return clause;
+    }
+    clause += " FIELDS";
+    if let Some(delimiter) = outfile.delimiter {
+        clause += &format!(" TERMINATED BY {}", quote_text(&delimiter.to_string()));
+    }
+    if let Some(quote) = outfile.quote {
+        let optionally = if outfile.quote_all { "" } else { "OPTIONALLY " };
+        clause += &format!(
+            " {}ENCLOSED BY {}",
+            optionally,
+            quote_text(&quote.to_string())
+        );
+    }
+    clause
+}
+
+// The operands of a left-associative chain of the same operator, from left to right
+fn flatten_chain<'a>(expr: &'a Expr, operator: BinaryOperator, operands: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Binary(left, chained, right) if *chained == operator => {
+            flatten_chain(left, operator, operands);
+            operands.push(right);
+        }
+        expr => operands.push(expr),
+    }
+}
+
+// How tightly each kind of expression binds, following the levels of the parser
+fn precedence_of(operator: BinaryOperator) -> u8 {
+    match operator {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::Equal
+        | BinaryOperator::NotEqual
+        | BinaryOperator::Less
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterOrEqual
+        | BinaryOperator::Glob
+        | BinaryOperator::Regexp => 5,
+        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Concat => 6,
+        BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 7,
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary(_, operator, _) => precedence_of(*operator),
+        Expr::Unary(UnaryOperator::Not, _) => 3,
+        Expr::IsNull { .. } => 4,
+        Expr::Unary(UnaryOperator::Minus, _) => 8,
+        Expr::Literal(_) | Expr::Column(_) | Expr::Function { .. } | Expr::Subquery(_) => 9,
+    }
+}
+
+// Wraps the expression in parentheses when it binds less tightly than its position requires
+fn format_operand(expr: &Expr, min_precedence: u8) -> String {
+    match precedence(expr) < min_precedence {
+        true => format!("({})", format_expr(expr)),
+        false => format_expr(expr),
+    }
+}
+
+pub fn format_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Literal(SQLType::Text(text)) => quote_text(text),
+        Expr::Literal(literal) => literal.to_string(),
+        Expr::Column(name) => table_name(name),
+        Expr::Unary(UnaryOperator::Not, operand) => format!("NOT {}", format_operand(operand, 3)),
+        Expr::Unary(UnaryOperator::Minus, operand) => {
+            let operand = format_operand(operand, 8);
+            // `--` would start a comment
+            match operand.starts_with('-') {
+                true => format!("-({})", operand),
+                false => format!("-{}", operand),
+            }
+        }
+        // Operators are left-associative, so an operand on the right of the same level needs
+        // parentheses
+        Expr::Binary(left, operator, right) => {
+            let precedence = precedence_of(*operator);
+            format!(
+                "{} {} {}",
+                format_operand(left, precedence),
+                operator,
+                format_operand(right, precedence + 1)
+            )
+        }
+        Expr::IsNull { expr, negated } => format!(
+            "{} IS {}NULL",
+            format_operand(expr, 5),
+            if *negated { "NOT " } else { "" }
+        ),
+        Expr::Function { name, args } if name == "count" && args.is_empty() => {
+            "count(*)".to_string()
+        }
+        Expr::Function { name, args } => {
+            let args: Vec<String> = args.iter().map(format_expr).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        Expr::Subquery(select) => format!("({})", format_select(select, true)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_one(sql: &str) -> String {
+        format_sql(sql).unwrap()
+    }
+
+    #[test]
+    fn clauses_go_on_a_line_each() {
+        assert_eq!(
+            format_one("select id, name from people where id > 1 and (name = 'a' or name = 'b');"),
+            "SELECT\n    id,\n    name\nFROM people\nWHERE id > 1\n    AND (name = 'a' OR name = 'b');"
+        );
+        assert_eq!(
+            format_one("select count(*) from t group by name;"),
+            "SELECT count(*)\nFROM t\nGROUP BY name;"
+        );
+        assert_eq!(
+            format_one("update t set a = 1, b = 'x' where id = 2;"),
+            "UPDATE t\nSET a = 1,\n    b = 'x'\nWHERE id = 2;"
+        );
+        assert_eq!(
+            format_one("insert into t (id, name) values (-1, 'it''s');"),
+            "INSERT INTO t (id, name)\nVALUES (-1, 'it''s');"
+        );
+        assert_eq!(
+            format_one("create table t (id int primary key, name text not null);"),
+            "CREATE TABLE t (\n    id INT PRIMARY KEY,\n    name TEXT NOT NULL\n);"
+        );
+        assert_eq!(
+            format_one("create index if not exists by_name on t (name, id);"),
+            "CREATE INDEX IF NOT EXISTS by_name ON t (name, id);"
+        );
+    }
+
+    #[test]
+    fn only_needed_parentheses_are_kept() {
+        let format = |input| format_expr(&all_consuming(parse_expr)(input).unwrap().1);
+        assert_eq!(format("((a + b)) * c"), "(a + b) * c");
+        assert_eq!(format("a + (b * c)"), "a + b * c");
+        assert_eq!(format("(a - b) - c"), "a - b - c");
+        assert_eq!(format("a - (b - c)"), "a - (b - c)");
+        assert_eq!(format("not (a = 1 and b = 2)"), "NOT (a = 1 AND b = 2)");
+        assert_eq!(format("- (-a)"), "-(-a)");
+        assert_eq!(format("(a is null) = 0"), "(a IS NULL) = 0");
+    }
+
+    #[test]
+    fn names_that_need_it_are_quoted() {
+        assert_eq!(
+            format_one("select `first name` from `my table` where `first name` = 'a';"),
+            "SELECT `first name`\nFROM `my table`\nWHERE `first name` = 'a';"
+        );
+        assert_eq!(
+            format_one("select * from `group`;"),
+            "SELECT *\nFROM `group`;"
+        );
+        assert_eq!(format_one("select * from aux.t;"), "SELECT *\nFROM aux.t;");
+    }
+
+    #[test]
+    fn scripts_keep_metacommands_and_fail_on_bad_statements() {
+        assert_eq!(
+            format_one("begin; .tables\ninsert into t values (1);commit;"),
+            "BEGIN;\n\n.tables\n\nINSERT INTO t\nVALUES (1);\n\nCOMMIT;"
+        );
+        assert!(format_sql("select * from t; selec 1;").is_err());
+    }
+
+    #[test]
+    fn formatted_statements_format_the_same_again() {
+        let script =
+            "create table t (id int primary key, n text default 'x' check (length(n) < 5));\
+            select a, (select max(id) from t) as m from t where not a or b is not null;\
+            delete from t where id > (select max(id) from u);\
+            pragma table_info(t);";
+        let formatted = format_sql(script).unwrap();
+        assert_eq!(format_sql(&formatted).unwrap(), formatted);
+    }
+}
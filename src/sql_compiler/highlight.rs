@@ -0,0 +1,45 @@
+use console::style;
+
+use super::KEYWORDS;
+
+// Colors the keywords, quoted strings and numbers of a line of SQL for display in the terminal.
+// Metacommand lines are returned untouched.
+pub fn highlight(line: &str) -> String {
+    if line.trim_start().starts_with('.') {
+        return line.to_string();
+    }
+
+    let mut highlighted = String::with_capacity(line.len());
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // An unterminated string runs until the end of the line
+                let end = chars
+                    .find(|&(_, next)| next == c)
+                    .map_or(line.len(), |(idx, _)| idx + 1);
+                let string = &line[start..end];
+                highlighted.push_str(&style(string).green().for_stderr().to_string());
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((idx, next)) =
+                    chars.next_if(|&(_, next)| next.is_alphanumeric() || next == '_')
+                {
+                    end = idx + next.len_utf8();
+                }
+                let word = &line[start..end];
+
+                if word.chars().all(|c| c.is_ascii_digit()) {
+                    highlighted.push_str(&style(word).cyan().for_stderr().to_string());
+                } else if KEYWORDS.contains(&word.to_lowercase().as_str()) {
+                    highlighted.push_str(&style(word).blue().bold().for_stderr().to_string());
+                } else {
+                    highlighted.push_str(word);
+                }
+            }
+            c => highlighted.push(c),
+        }
+    }
+    highlighted
+}
@@ -1,22 +1,66 @@
+use std::borrow::Cow;
+
 use nom::{
     branch::alt,
     bytes::complete::tag_no_case,
-    character::complete::{char, digit1, multispace0, multispace1},
-    combinator::{all_consuming, cut},
-    error::{convert_error, VerboseError},
+    character::complete::{char, digit1, multispace0, multispace1, one_of},
+    combinator::{all_consuming, cut, map, map_opt, opt, peek, recognize, value},
+    error::VerboseError,
     multi::separated_list1,
-    sequence::{delimited, pair, tuple},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     Finish, IResult,
 };
 
+use super::expression::{parse_expr, Expr};
+use super::select::{parse_select_body, SelectTokens};
 use super::statement::{ParseError, Statement};
-use super::{escaped_string_double_quote, escaped_string_single_quote, parse_identifier};
+use super::{
+    escaped_string_double_quote, escaped_string_single_quote, keyword, parse_identifier,
+    parse_table_name, unescape_quotes,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InsertTokens<'a> {
-    pub table_name: &'a str,
-    pub column_names: Vec<&'a str>,
-    pub column_values: Vec<&'a str>,
+    pub table_name: Cow<'a, str>,
+    // Left empty when the values are given for every column in the order they were declared
+    pub column_names: Vec<Cow<'a, str>>,
+    // NULL is written as None
+    pub column_values: Vec<Option<InsertValue<'a>>>,
+    // Query whose rows are inserted, with the values left empty
+    pub select: Option<SelectTokens<'a>>,
+}
+
+// A value of VALUES. Literals are kept as written, to be read as a value of the type of their
+// column. Anything else, such as uuid(), is evaluated when the row is inserted
+#[derive(Debug, Clone)]
+pub enum InsertValue<'a> {
+    Literal(Cow<'a, str>),
+    Expr(Expr),
+}
+
+impl InsertValue<'_> {
+    pub fn into_owned(self) -> InsertValue<'static> {
+        match self {
+            InsertValue::Literal(text) => InsertValue::Literal(Cow::Owned(text.into_owned())),
+            InsertValue::Expr(expr) => InsertValue::Expr(expr),
+        }
+    }
+}
+
+impl InsertTokens<'_> {
+    pub fn into_owned(self) -> InsertTokens<'static> {
+        let owned = |item: Cow<str>| Cow::Owned(item.into_owned());
+        InsertTokens {
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            column_names: self.column_names.into_iter().map(owned).collect(),
+            column_values: self
+                .column_values
+                .into_iter()
+                .map(|value| value.map(InsertValue::into_owned))
+                .collect(),
+            select: self.select.map(SelectTokens::into_owned),
+        }
+    }
 }
 
 fn parse_column_names(input: &str) -> IResult<&str, Vec<&str>, VerboseError<&str>> {
@@ -26,22 +70,119 @@ fn parse_column_names(input: &str) -> IResult<&str, Vec<&str>, VerboseError<&str
     )(input)
 }
 
-fn parse_value(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+pub(super) fn parse_value(input: &str) -> IResult<&str, Cow<'_, str>, VerboseError<&str>> {
     alt((
-        digit1,
-        delimited(char('\''), escaped_string_single_quote, char('\'')),
-        delimited(char('"'), escaped_string_double_quote, char('"')),
+        map(recognize(pair(opt(char('-')), digit1)), Cow::Borrowed),
+        map(
+            delimited(char('\''), escaped_string_single_quote, char('\'')),
+            |text| unescape_quotes(text, '\''),
+        ),
+        map(
+            delimited(char('"'), escaped_string_double_quote, char('"')),
+            |text| unescape_quotes(text, '"'),
+        ),
+        parse_unistr,
     ))(input)
 }
 
-fn parse_column_values(input: &str) -> IResult<&str, Vec<&str>, VerboseError<&str>> {
+// unistr('...'), which sqlite3 dumps text with control characters as. `\XXXX`, `\uXXXX`,
+// `\+XXXXXX` and `\UXXXXXXXX` stand for the character with that hex code and `\\` for a backslash
+fn parse_unistr(input: &str) -> IResult<&str, Cow<'_, str>, VerboseError<&str>> {
+    let text = delimited(char('\''), escaped_string_single_quote, char('\''));
+    let argument = delimited(
+        pair(char('('), multispace0),
+        text,
+        pair(multispace0, char(')')),
+    );
+    map_opt(
+        preceded(pair(keyword("unistr"), multispace0), argument),
+        |text| decode_unistr(&unescape_quotes(text, '\'')).map(Cow::Owned),
+    )(input)
+}
+
+fn decode_unistr(text: &str) -> Option<String> {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find('\\') {
+        decoded.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        let (skip, digits) = match rest.chars().next()? {
+            '\\' => {
+                decoded.push('\\');
+                rest = &rest[1..];
+                continue;
+            }
+            'u' => (1, 4),
+            '+' => (1, 6),
+            'U' => (1, 8),
+            _ => (0, 4),
+        };
+        let code = rest.get(skip..skip + digits)?;
+        decoded.push(char::from_u32(u32::from_str_radix(code, 16).ok()?)?);
+        rest = &rest[skip + digits..];
+    }
+    decoded.push_str(rest);
+    Some(decoded)
+}
+
+// Whether a value ends here, so that one starting like a literal, e.g. `1 + 1`, is read as an
+// expression
+fn value_end(input: &str) -> IResult<&str, (), VerboseError<&str>> {
+    value((), peek(pair(multispace0, one_of(",)"))))(input)
+}
+
+fn parse_column_values(
+    input: &str,
+) -> IResult<&str, Vec<Option<InsertValue<'_>>>, VerboseError<&str>> {
+    let value_or_null = alt((
+        value(None, terminated(keyword("null"), value_end)),
+        map(terminated(parse_value, value_end), |literal| {
+            Some(InsertValue::Literal(literal))
+        }),
+        map(parse_expr, |expr| Some(InsertValue::Expr(expr))),
+    ));
     separated_list1(
         char(','),
-        cut(delimited(multispace0, parse_value, multispace0)),
+        cut(delimited(multispace0, value_or_null, multispace0)),
     )(input)
 }
 
-fn parse_insert(input: &str) -> IResult<&str, InsertTokens, VerboseError<&str>> {
+// Names of the columns given values, and the values. NULL is written as None
+type NamesAndValues<'a> = (Vec<&'a str>, Vec<Option<InsertValue<'a>>>);
+
+// Every column takes its default value
+fn parse_default_values(input: &str) -> IResult<&str, NamesAndValues<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((tag_no_case("default"), multispace1, tag_no_case("values")))(input)?;
+    Ok((input, (Vec::new(), Vec::new())))
+}
+
+// The column names may be left out to give a value to every column
+fn parse_names_and_values(input: &str) -> IResult<&str, NamesAndValues<'_>, VerboseError<&str>> {
+    let (input, column_names) = opt(delimited(
+        char('('),
+        parse_column_names,
+        pair(char(')'), multispace0),
+    ))(input)?;
+    let (input, _) = tag_no_case("values")(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, column_values) = delimited(char('('), parse_column_values, char(')'))(input)?;
+    Ok((input, (column_names.unwrap_or_default(), column_values)))
+}
+
+// `INSERT INTO t [(columns)] SELECT ...`, which inserts every row of the query
+fn parse_names_and_select(
+    input: &str,
+) -> IResult<&str, (Vec<&str>, SelectTokens<'_>), VerboseError<&str>> {
+    let (input, column_names) = opt(delimited(
+        char('('),
+        parse_column_names,
+        pair(char(')'), multispace0),
+    ))(input)?;
+    let (input, select_tokens) = parse_select_body(input)?;
+    Ok((input, (column_names.unwrap_or_default(), select_tokens)))
+}
+
+fn parse_insert(input: &str) -> IResult<&str, InsertTokens<'_>, VerboseError<&str>> {
     let (input, _) = tuple((
         multispace0,
         tag_no_case("insert"),
@@ -50,28 +191,33 @@ fn parse_insert(input: &str) -> IResult<&str, InsertTokens, VerboseError<&str>>
         multispace1,
     ))(input)?;
 
-    let (input, table_name) = parse_identifier(input)?;
+    let (input, table_name) = parse_table_name(input)?;
     let (input, _) = multispace0(input)?;
-    let (input, column_names) = delimited(char('('), parse_column_names, char(')'))(input)?;
-    let (input, _) = multispace1(input)?;
-    let (input, _) = tag_no_case("values")(input)?;
-    let (input, _) = multispace0(input)?;
-    let (input, column_values) = delimited(char('('), parse_column_values, char(')'))(input)?;
+    let (input, (column_names, column_values, select)) = alt((
+        map(
+            alt((parse_default_values, parse_names_and_values)),
+            |(column_names, column_values)| (column_names, column_values, None),
+        ),
+        map(parse_names_and_select, |(column_names, select_tokens)| {
+            (column_names, Vec::new(), Some(select_tokens))
+        }),
+    ))(input)?;
     let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
 
     Ok((
         "",
         InsertTokens {
-            table_name,
-            column_names,
+            table_name: Cow::Borrowed(table_name),
+            column_names: column_names.into_iter().map(Cow::Borrowed).collect(),
             column_values,
+            select,
         },
     ))
 }
 
-pub(super) fn validate_insert(input: &str) -> Result<Statement, ParseError> {
+pub(super) fn validate_insert(input: &str) -> Result<Statement<'_>, ParseError> {
     match parse_insert(input).finish() {
-        Err(e) => Err(ParseError::MalformedStatement(convert_error(input, e))),
+        Err(e) => Err(ParseError::malformed(input, e)),
         Ok((_, insert_tokens)) => Ok(Statement::Insert(insert_tokens)),
     }
 }
@@ -0,0 +1,85 @@
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, multispace0, multispace1},
+    combinator::{map, map_res, opt, value},
+    error::VerboseError,
+    multi::separated_list1,
+    sequence::{pair, preceded, tuple},
+    IResult,
+};
+
+use super::expression::{parse_expr, Expr};
+use super::keyword;
+
+// Term of an ORDER BY clause. Rows are sorted in ascending order unless DESC is given
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+// `LIMIT count [OFFSET offset]`, which skips `offset` rows and keeps the `count` after them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowLimit {
+    pub count: usize,
+    pub offset: usize,
+}
+
+fn parse_ordering_term(input: &str) -> IResult<&str, OrderingTerm, VerboseError<&str>> {
+    let direction = alt((value(false, keyword("asc")), value(true, keyword("desc"))));
+    map(
+        pair(parse_expr, opt(preceded(multispace1, direction))),
+        |(expr, descending)| OrderingTerm {
+            expr,
+            descending: descending.unwrap_or(false),
+        },
+    )(input)
+}
+
+fn parse_order_by(input: &str) -> IResult<&str, Vec<OrderingTerm>, VerboseError<&str>> {
+    preceded(
+        tuple((
+            multispace0,
+            keyword("order"),
+            multispace1,
+            keyword("by"),
+            multispace1,
+        )),
+        separated_list1(
+            tuple((multispace0, char(','), multispace0)),
+            parse_ordering_term,
+        ),
+    )(input)
+}
+
+fn parse_limit(input: &str) -> IResult<&str, RowLimit, VerboseError<&str>> {
+    let count = || map_res(digit1, |digits: &str| digits.parse::<usize>());
+    map(
+        pair(
+            preceded(tuple((multispace0, keyword("limit"), multispace1)), count()),
+            opt(preceded(
+                tuple((multispace1, keyword("offset"), multispace1)),
+                count(),
+            )),
+        ),
+        |(count, offset)| RowLimit {
+            count,
+            offset: offset.unwrap_or(0),
+        },
+    )(input)
+}
+
+// `[ORDER BY term, ...] LIMIT count [OFFSET offset]` after the WHERE clause of DELETE and UPDATE,
+// which then only change the rows the limit keeps. ORDER BY is only allowed with a LIMIT, as it
+// decides nothing else
+pub(super) fn parse_ordered_limit(
+    input: &str,
+) -> IResult<&str, (Vec<OrderingTerm>, Option<RowLimit>), VerboseError<&str>> {
+    map(
+        opt(pair(opt(parse_order_by), parse_limit)),
+        |ordered_limit| match ordered_limit {
+            Some((order_by, limit)) => (order_by.unwrap_or_default(), Some(limit)),
+            None => (Vec::new(), None),
+        },
+    )(input)
+}
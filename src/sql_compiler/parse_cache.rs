@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::mem;
+
+use super::parse_statement;
+use super::statement::{ParseError, Statement};
+
+const DEFAULT_CAPACITY: usize = 100;
+
+/// Keeps the parsed form of recently run statements, keyed by their SQL text, so running the same
+/// statement again skips parsing. The least recently used statement is evicted when full.
+#[derive(Debug)]
+pub struct ParseCache {
+    capacity: usize,
+    // Each statement along with the last time it was used
+    entries: HashMap<String, (Statement<'static>, u64)>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for ParseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl ParseCache {
+    /// Creates a cache holding up to `capacity` statements. A capacity of 0 disables caching
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Parses a statement, reusing the cached result if the same SQL was parsed before. Statements
+    /// that fail to parse are not cached.
+    pub fn parse<'a>(&mut self, sql: &'a str) -> Result<Statement<'a>, ParseError> {
+        self.clock += 1;
+        if let Some((statement, last_used)) = self.entries.get_mut(sql) {
+            *last_used = self.clock;
+            self.hits += 1;
+            return Ok(statement.clone());
+        }
+
+        self.misses += 1;
+        let statement = parse_statement(sql)?;
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.evict(self.entries.len() + 1 - self.capacity);
+            }
+            self.entries.insert(
+                sql.to_string(),
+                (statement.clone().into_owned(), self.clock),
+            );
+        }
+        Ok(statement)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes how many statements are kept, evicting the least recently used ones if needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(self.entries.len().saturating_sub(capacity));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of statements found in the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of statements that had to be parsed
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Approximate bytes taken up by the cached statements. Each one counts its SQL text twice, as
+    /// the parsed statement owns copies of the names and values in it
+    pub fn memory_used(&self) -> usize {
+        self.entries
+            .keys()
+            .map(|sql| 2 * sql.len() + mem::size_of::<(Statement, u64)>())
+            .sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn evict(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let mut by_age: Vec<(u64, String)> = self
+            .entries
+            .iter()
+            .map(|(sql, (_, last_used))| (*last_used, sql.clone()))
+            .collect();
+        by_age.sort_unstable();
+        for (_, sql) in by_age.into_iter().take(count) {
+            self.entries.remove(&sql);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Connection, SQLType};
+
+    const SELECT_A: &str = "SELECT * FROM a;";
+    const SELECT_B: &str = "SELECT * FROM b;";
+    const SELECT_C: &str = "SELECT * FROM c;";
+
+    #[test]
+    fn repeated_statements_are_parsed_once() {
+        let mut cache = ParseCache::new(10);
+        let parsed = cache.parse(SELECT_A).unwrap();
+        let cached = cache.parse(SELECT_A).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", cached));
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (1, 1, 1));
+
+        // Statements are keyed by their exact text
+        cache.parse("select * from a;").unwrap();
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (1, 2, 2));
+    }
+
+    #[test]
+    fn least_recently_used_statements_are_evicted() {
+        let mut cache = ParseCache::new(2);
+        cache.parse(SELECT_A).unwrap();
+        cache.parse(SELECT_B).unwrap();
+        cache.parse(SELECT_A).unwrap();
+        cache.parse(SELECT_C).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        let misses = cache.misses();
+        cache.parse(SELECT_A).unwrap();
+        cache.parse(SELECT_C).unwrap();
+        assert_eq!(cache.misses(), misses);
+        cache.parse(SELECT_B).unwrap();
+        assert_eq!(cache.misses(), misses + 1);
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        cache.parse(SELECT_B).unwrap();
+        assert_eq!(cache.misses(), misses + 1);
+    }
+
+    #[test]
+    fn failures_and_zero_capacity_are_not_cached() {
+        let mut cache = ParseCache::new(10);
+        assert!(cache.parse("SELEC * FROM a;").is_err());
+        assert!(cache.parse("SELEC * FROM a;").is_err());
+        assert_eq!((cache.misses(), cache.len()), (2, 0));
+
+        let mut cache = ParseCache::new(0);
+        cache.parse(SELECT_A).unwrap();
+        cache.parse(SELECT_A).unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (0, 2));
+        assert!(cache.is_empty());
+        assert_eq!(cache.memory_used(), 0);
+    }
+
+    #[test]
+    fn cached_statements_see_schema_changes() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let count = |conn: &mut Connection| {
+            conn.query("SELECT COUNT(*) FROM t;")
+                .unwrap()
+                .map(|row| row.into_values())
+                .collect::<Vec<_>>()
+        };
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY);")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (1);").unwrap();
+        assert_eq!(count(&mut conn), [vec![SQLType::Integer(1)]]);
+
+        conn.execute("DROP TABLE t;").unwrap();
+        assert!(conn.query("SELECT COUNT(*) FROM t;").is_err());
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        assert_eq!(count(&mut conn), [vec![SQLType::Integer(0)]]);
+        assert!(conn.parse_cache().hits() >= 2);
+    }
+}
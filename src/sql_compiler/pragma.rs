@@ -0,0 +1,61 @@
+use std::borrow::Cow;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag_no_case,
+    character::complete::{char, digit1, multispace0, multispace1},
+    combinator::{all_consuming, opt, recognize},
+    error::VerboseError,
+    sequence::{delimited, pair, preceded, tuple},
+    Finish, IResult,
+};
+
+use super::statement::{ParseError, Statement};
+use super::{parse_identifier, parse_table_name};
+
+// `PRAGMA name;` reads a setting and `PRAGMA name = value;` changes it. `PRAGMA name(argument);`
+// passes an argument to pragmas that report on something, such as a table
+#[derive(Debug, Clone)]
+pub struct PragmaTokens<'a> {
+    pub name: Cow<'a, str>,
+    pub value: Option<Cow<'a, str>>,
+}
+
+impl PragmaTokens<'_> {
+    pub fn into_owned(self) -> PragmaTokens<'static> {
+        PragmaTokens {
+            name: Cow::Owned(self.name.into_owned()),
+            value: self.value.map(|value| Cow::Owned(value.into_owned())),
+        }
+    }
+}
+
+fn parse_pragma(input: &str) -> IResult<&str, PragmaTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((multispace0, tag_no_case("pragma"), multispace1))(input)?;
+    let (input, name) = parse_identifier(input)?;
+    let value = || alt((parse_table_name, recognize(pair(opt(char('-')), digit1))));
+    let (input, value) = opt(alt((
+        preceded(tuple((multispace0, char('='), multispace0)), value()),
+        delimited(
+            tuple((multispace0, char('('), multispace0)),
+            value(),
+            pair(multispace0, char(')')),
+        ),
+    )))(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        PragmaTokens {
+            name: Cow::Borrowed(name),
+            value: value.map(Cow::Borrowed),
+        },
+    ))
+}
+
+pub(super) fn validate_pragma(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_pragma(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, pragma_tokens)) => Ok(Statement::Pragma(pragma_tokens)),
+    }
+}
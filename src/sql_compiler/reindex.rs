@@ -0,0 +1,49 @@
+use std::borrow::Cow;
+
+use nom::{
+    bytes::complete::tag_no_case,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, opt},
+    error::VerboseError,
+    sequence::{pair, preceded, tuple},
+    Finish, IResult,
+};
+
+use super::parse_table_name;
+use super::statement::{ParseError, Statement};
+
+// `REINDEX [table]`. Without a table every table of the database is rebuilt
+#[derive(Debug, Clone)]
+pub struct ReindexTokens<'a> {
+    pub table_name: Option<Cow<'a, str>>,
+}
+
+impl ReindexTokens<'_> {
+    pub fn into_owned(self) -> ReindexTokens<'static> {
+        ReindexTokens {
+            table_name: self
+                .table_name
+                .map(|table_name| Cow::Owned(table_name.into_owned())),
+        }
+    }
+}
+
+fn parse_reindex(input: &str) -> IResult<&str, ReindexTokens<'_>, VerboseError<&str>> {
+    let (input, _) = pair(multispace0, tag_no_case("reindex"))(input)?;
+    let (input, table_name) = opt(preceded(multispace1, parse_table_name))(input)?;
+    let (_, _) = all_consuming(tuple((multispace0, char(';'))))(input)?;
+
+    Ok((
+        "",
+        ReindexTokens {
+            table_name: table_name.map(Cow::Borrowed),
+        },
+    ))
+}
+
+pub(super) fn validate_reindex(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_reindex(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, reindex_tokens)) => Ok(Statement::Reindex(reindex_tokens)),
+    }
+}
@@ -0,0 +1,58 @@
+// A statement or metacommand along with the line of the script it starts on
+type LocatedStatement<'a> = (usize, &'a str);
+
+// Splits a script into its statements, each paired with the line it starts on. Statements end at
+// a `;` outside of quotes, which is kept as part of the statement. A line starting with `.`
+// between statements is a metacommand and runs until the end of that line.
+pub fn split_statements(script: &str) -> Vec<LocatedStatement<'_>> {
+    let (mut statements, unterminated) = split_terminated(script);
+    // Whatever follows the last `;` is passed on as is, so it gets reported as incomplete
+    statements.extend(unterminated);
+    statements
+}
+
+// Whether the input ends at a statement boundary, with no statement left waiting for its `;`
+pub fn is_complete(script: &str) -> bool {
+    split_terminated(script).1.is_none()
+}
+
+// Returns the terminated statements and metacommands of a script, plus the trailing statement
+// that has not been terminated yet, if any
+fn split_terminated(script: &str) -> (Vec<LocatedStatement<'_>>, Option<LocatedStatement<'_>>) {
+    let mut statements = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    let mut quote: Option<char> = None;
+    let mut line = 1;
+
+    let mut chars = script.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        if start.is_none() && c == '.' {
+            let end = script[idx..]
+                .find('\n')
+                .map_or(script.len(), |len| idx + len);
+            statements.push((line, script[idx..end].trim_end()));
+            while chars.next_if(|&(next_idx, _)| next_idx < end).is_some() {}
+            continue;
+        }
+        if start.is_none() && !c.is_whitespace() {
+            start = Some((idx, line));
+        }
+
+        match (c, quote) {
+            ('\n', _) => line += 1,
+            (c, Some(open)) if c == open => quote = None,
+            (_, Some(_)) => {}
+            ('\'' | '"', None) => quote = Some(c),
+            (';', None) => {
+                let (start_idx, start_line) = start.take().unwrap();
+                statements.push((start_line, &script[start_idx..=idx]));
+            }
+            _ => {}
+        }
+    }
+
+    let unterminated =
+        start.map(|(start_idx, start_line)| (start_line, script[start_idx..].trim_end()));
+
+    (statements, unterminated)
+}
@@ -1,32 +1,291 @@
+use std::borrow::Cow;
+use std::fmt;
+
 use nom::{
-    bytes::complete::tag_no_case,
-    character::complete::{char, multispace0},
-    combinator::all_consuming,
-    error::{convert_error, VerboseError},
-    sequence::{pair, tuple},
+    branch::alt,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, map, map_opt, opt, recognize, value},
+    error::VerboseError,
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     Finish, IResult,
 };
 
-use super::parse_identifier;
+use super::expression::{parse_expr, Expr};
 use super::statement::{ParseError, Statement};
+use super::{
+    escaped_string_double_quote, escaped_string_single_quote, keyword, parse_identifier,
+    parse_table_name,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Wildcard,
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+// `INTO OUTFILE 'path' [FIELDS [TERMINATED BY 'c'] [[OPTIONALLY] ENCLOSED BY 'c']]`. Fields
+// are quoted only when needed if OPTIONALLY is given, and always otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutfileTokens<'a> {
+    pub path: Cow<'a, str>,
+    pub delimiter: Option<char>,
+    pub quote: Option<char>,
+    pub quote_all: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct SelectTokens<'a> {
-    pub table_name: &'a str,
+    // None for SELECT without FROM, which evaluates its expressions once
+    pub table_name: Option<Cow<'a, str>>,
+    // Tables listed after the first one in FROM. Every row of one is combined with every row of
+    // those before it
+    pub joined_tables: Vec<Cow<'a, str>>,
+    pub select_items: Vec<SelectItem>,
+    pub where_clause: Option<Expr>,
+    // Rows with the same values for these expressions are folded into one. Empty without GROUP BY
+    pub group_by: Vec<Expr>,
+    pub outfile: Option<OutfileTokens<'a>>,
+}
+
+impl SelectTokens<'_> {
+    pub fn into_owned(self) -> SelectTokens<'static> {
+        SelectTokens {
+            table_name: self
+                .table_name
+                .map(|table_name| Cow::Owned(table_name.into_owned())),
+            joined_tables: self
+                .joined_tables
+                .into_iter()
+                .map(|table_name| Cow::Owned(table_name.into_owned()))
+                .collect(),
+            select_items: self.select_items,
+            where_clause: self.where_clause,
+            group_by: self.group_by,
+            outfile: self.outfile.map(|outfile| OutfileTokens {
+                path: Cow::Owned(outfile.path.into_owned()),
+                ..outfile
+            }),
+        }
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard => write!(f, "*"),
+            SelectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+            SelectItem::Expr {
+                expr,
+                alias: Some(alias),
+            } => write!(f, "{} AS {}", expr, alias),
+        }
+    }
+}
+
+// Written back as SQL, without the INTO OUTFILE clause
+impl fmt::Display for SelectTokens<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let items: Vec<String> = self
+            .select_items
+            .iter()
+            .map(|item| item.to_string())
+            .collect();
+        write!(f, "SELECT {}", items.join(", "))?;
+        if let Some(table_name) = &self.table_name {
+            write!(f, " FROM {}", table_name)?;
+            for joined_table in &self.joined_tables {
+                write!(f, ", {}", joined_table)?;
+            }
+        }
+        if let Some(where_clause) = &self.where_clause {
+            write!(f, " WHERE {}", where_clause)?;
+        }
+        if !self.group_by.is_empty() {
+            let keys: Vec<String> = self.group_by.iter().map(|key| key.to_string()).collect();
+            write!(f, " GROUP BY {}", keys.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_select_item(input: &str) -> IResult<&str, SelectItem, VerboseError<&str>> {
+    alt((
+        value(SelectItem::Wildcard, char('*')),
+        map(
+            pair(
+                parse_expr,
+                opt(preceded(
+                    tuple((multispace1, keyword("as"), multispace1)),
+                    parse_identifier,
+                )),
+            ),
+            |(expr, alias)| SelectItem::Expr {
+                expr,
+                alias: alias.map(|alias| alias.to_owned()),
+            },
+        ),
+    ))(input)
+}
+
+fn parse_select_items(input: &str) -> IResult<&str, Vec<SelectItem>, VerboseError<&str>> {
+    separated_list1(
+        char(','),
+        delimited(multispace0, parse_select_item, multispace0),
+    )(input)
+}
+
+pub(super) fn parse_where_clause(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+    preceded(
+        tuple((multispace0, keyword("where"), multispace1)),
+        parse_expr,
+    )(input)
+}
+
+fn parse_group_by(input: &str) -> IResult<&str, Vec<Expr>, VerboseError<&str>> {
+    preceded(
+        tuple((
+            multispace0,
+            keyword("group"),
+            multispace1,
+            keyword("by"),
+            multispace1,
+        )),
+        separated_list1(tuple((multispace0, char(','), multispace0)), parse_expr),
+    )(input)
+}
+
+fn parse_quoted(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    alt((
+        delimited(char('\''), escaped_string_single_quote, char('\'')),
+        delimited(char('"'), escaped_string_double_quote, char('"')),
+    ))(input)
+}
+
+fn parse_quoted_char(input: &str) -> IResult<&str, char, VerboseError<&str>> {
+    map_opt(parse_quoted, |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    })(input)
+}
+
+fn parse_outfile(input: &str) -> IResult<&str, OutfileTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((
+        multispace1,
+        keyword("into"),
+        multispace1,
+        keyword("outfile"),
+        multispace1,
+    ))(input)?;
+    let (input, path) = parse_quoted(input)?;
+
+    let terminated_by = preceded(
+        tuple((
+            multispace1,
+            keyword("terminated"),
+            multispace1,
+            keyword("by"),
+            multispace1,
+        )),
+        parse_quoted_char,
+    );
+    let enclosed_by = pair(
+        preceded(
+            multispace1,
+            opt(terminated(keyword("optionally"), multispace1)),
+        ),
+        preceded(
+            tuple((keyword("enclosed"), multispace1, keyword("by"), multispace1)),
+            parse_quoted_char,
+        ),
+    );
+    let (input, fields) = opt(preceded(
+        pair(multispace1, keyword("fields")),
+        pair(opt(terminated_by), opt(enclosed_by)),
+    ))(input)?;
+
+    let (delimiter, enclosed_by) = fields.unwrap_or((None, None));
+    Ok((
+        input,
+        OutfileTokens {
+            path: Cow::Borrowed(path),
+            delimiter,
+            quote: enclosed_by.map(|(_, quote)| quote),
+            quote_all: enclosed_by.is_some_and(|(optionally, _)| optionally.is_none()),
+        },
+    ))
+}
+
+// `csv('file.csv')`, which reads the rows of a CSV file as a table. It is kept as written and
+// serves as the name of the table
+fn parse_table_function(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    recognize(tuple((
+        keyword("csv"),
+        multispace0,
+        char('('),
+        multispace0,
+        delimited(char('\''), escaped_string_single_quote, char('\'')),
+        multispace0,
+        char(')'),
+    )))(input)
+}
+
+// Everything up to the INTO OUTFILE clause, which is also the form of a subquery
+pub(super) fn parse_select_body(
+    input: &str,
+) -> IResult<&str, SelectTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((multispace0, keyword("select"), multispace0))(input)?;
+    let (input, select_items) = parse_select_items(input)?;
+    let table = || alt((parse_table_function, parse_table_name));
+    let (input, tables) = opt(preceded(
+        pair(keyword("from"), multispace0),
+        pair(
+            table(),
+            many0(preceded(
+                tuple((multispace0, char(','), multispace0)),
+                table(),
+            )),
+        ),
+    ))(input)?;
+    let (table_name, joined_tables) = tables.unzip();
+    let (input, where_clause) = opt(parse_where_clause)(input)?;
+    let (input, group_by) = opt(parse_group_by)(input)?;
+    Ok((
+        input,
+        SelectTokens {
+            table_name: table_name.map(Cow::Borrowed),
+            joined_tables: joined_tables
+                .unwrap_or_default()
+                .into_iter()
+                .map(Cow::Borrowed)
+                .collect(),
+            select_items,
+            where_clause,
+            group_by: group_by.unwrap_or_default(),
+            outfile: None,
+        },
+    ))
 }
 
-fn parse_select(input: &str) -> IResult<&str, SelectTokens, VerboseError<&str>> {
-    let (input, _) = tuple((tag_no_case("select"), multispace0))(input)?;
-    let (input, _) = tuple((char('*'), multispace0))(input)?;
-    let (input, _) = tuple((tag_no_case("from"), multispace0))(input)?;
-    let (input, table_name) = parse_identifier(input)?;
+fn parse_select(input: &str) -> IResult<&str, SelectTokens<'_>, VerboseError<&str>> {
+    let (input, select_tokens) = parse_select_body(input)?;
+    let (input, outfile) = opt(parse_outfile)(input)?;
     let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
-    Ok(("", SelectTokens { table_name }))
+    Ok((
+        "",
+        SelectTokens {
+            outfile,
+            ..select_tokens
+        },
+    ))
 }
 
-pub(super) fn validate_select(input: &str) -> Result<Statement, ParseError> {
+pub(super) fn validate_select(input: &str) -> Result<Statement<'_>, ParseError> {
     match parse_select(input).finish() {
-        Err(e) => Err(ParseError::MalformedStatement(convert_error(input, e))),
+        Err(e) => Err(ParseError::malformed(input, e)),
         Ok((_, select_tokens)) => Ok(Statement::Select(select_tokens)),
     }
 }
@@ -1,21 +1,109 @@
 use core::fmt::Display;
 
-use super::create::CreateTokens;
+use nom::error::{VerboseError, VerboseErrorKind};
+use nom::Offset;
+
+use super::alter::AlterTokens;
+use super::attach::{AttachTokens, DetachTokens};
+use super::create::{CreateIndexTokens, CreateTokens};
+use super::delete::DeleteTokens;
+use super::drop::{DropIndexTokens, DropTokens};
 use super::insert::InsertTokens;
+use super::pragma::PragmaTokens;
+use super::reindex::ReindexTokens;
 use super::select::SelectTokens;
+use super::update::UpdateTokens;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement<'a> {
+    Alter(AlterTokens<'a>),
+    Attach(AttachTokens<'a>),
+    Begin,
+    Commit,
     Create(CreateTokens<'a>),
+    CreateIndex(CreateIndexTokens<'a>),
+    Delete(DeleteTokens<'a>),
+    Detach(DetachTokens<'a>),
+    Drop(DropTokens<'a>),
+    DropIndex(DropIndexTokens<'a>),
+    Explain(Box<Statement<'a>>),
     Select(SelectTokens<'a>),
     Insert(InsertTokens<'a>),
+    Pragma(PragmaTokens<'a>),
+    Reindex(ReindexTokens<'a>),
+    Rollback,
+    Update(UpdateTokens<'a>),
+}
+
+impl Statement<'_> {
+    // Whether the statement modifies rows, which is what changes() counts
+    pub fn changes_rows(&self) -> bool {
+        matches!(
+            self,
+            Statement::Insert(_) | Statement::Delete(_) | Statement::Update(_)
+        )
+    }
+
+    // Whether the statement changes the database file, so it needs the write lock and a flush
+    pub fn writes(&self) -> bool {
+        match self {
+            Statement::Alter(_)
+            | Statement::Create(_)
+            | Statement::CreateIndex(_)
+            | Statement::Delete(_)
+            | Statement::Drop(_)
+            | Statement::DropIndex(_)
+            | Statement::Insert(_)
+            | Statement::Reindex(_)
+            | Statement::Update(_) => true,
+            Statement::Pragma(PragmaTokens { name, value }) => {
+                value.is_some() && name.eq_ignore_ascii_case("user_version")
+            }
+            _ => false,
+        }
+    }
+
+    // Copies whatever the statement borrows from the SQL text, so it can outlive it
+    pub fn into_owned(self) -> Statement<'static> {
+        match self {
+            Statement::Alter(tokens) => Statement::Alter(tokens.into_owned()),
+            Statement::Attach(tokens) => Statement::Attach(tokens.into_owned()),
+            Statement::Begin => Statement::Begin,
+            Statement::Commit => Statement::Commit,
+            Statement::Create(tokens) => Statement::Create(tokens.into_owned()),
+            Statement::CreateIndex(tokens) => Statement::CreateIndex(tokens.into_owned()),
+            Statement::Delete(tokens) => Statement::Delete(tokens.into_owned()),
+            Statement::Detach(tokens) => Statement::Detach(tokens.into_owned()),
+            Statement::Drop(tokens) => Statement::Drop(tokens.into_owned()),
+            Statement::DropIndex(tokens) => Statement::DropIndex(tokens.into_owned()),
+            Statement::Explain(statement) => Statement::Explain(Box::new(statement.into_owned())),
+            Statement::Select(tokens) => Statement::Select(tokens.into_owned()),
+            Statement::Insert(tokens) => Statement::Insert(tokens.into_owned()),
+            Statement::Pragma(tokens) => Statement::Pragma(tokens.into_owned()),
+            Statement::Reindex(tokens) => Statement::Reindex(tokens.into_owned()),
+            Statement::Rollback => Statement::Rollback,
+            Statement::Update(tokens) => Statement::Update(tokens.into_owned()),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum StatementType {
+    Alter,
+    Attach,
+    Begin,
+    Commit,
     Create,
+    Delete,
+    Detach,
+    Drop,
+    Explain,
     Insert,
+    Pragma,
+    Reindex,
+    Rollback,
     Select,
+    Update,
 }
 
 impl TryFrom<&str> for StatementType {
@@ -23,9 +111,21 @@ impl TryFrom<&str> for StatementType {
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         match s.to_lowercase().as_str() {
+            "alter" => Ok(StatementType::Alter),
+            "attach" => Ok(StatementType::Attach),
+            "begin" => Ok(StatementType::Begin),
+            "commit" => Ok(StatementType::Commit),
             "create" => Ok(StatementType::Create),
+            "delete" => Ok(StatementType::Delete),
+            "detach" => Ok(StatementType::Detach),
+            "drop" => Ok(StatementType::Drop),
+            "explain" => Ok(StatementType::Explain),
             "insert" => Ok(StatementType::Insert),
+            "pragma" => Ok(StatementType::Pragma),
+            "reindex" => Ok(StatementType::Reindex),
+            "rollback" => Ok(StatementType::Rollback),
             "select" => Ok(StatementType::Select),
+            "update" => Ok(StatementType::Update),
             _ => Err(ParseError::UnknownStatement),
         }
     }
@@ -33,18 +133,61 @@ impl TryFrom<&str> for StatementType {
 
 #[derive(Debug)]
 pub enum ParseError {
-    MalformedStatement(String),
+    // Byte offset in the statement where parsing failed, and what was wrong there
+    MalformedStatement(usize, String),
     UnknownStatement,
 }
 
+impl ParseError {
+    // Describes the first error nom recorded, which is the one of the innermost parser and thus
+    // the furthest into the statement
+    pub(super) fn malformed(input: &str, err: VerboseError<&str>) -> Self {
+        let Some((remaining, kind)) = err.errors.first() else {
+            return ParseError::MalformedStatement(0, "syntax error".to_string());
+        };
+        let remaining = remaining.trim_start();
+        let found = match token_at(remaining) {
+            "" => "end of input".to_string(),
+            token => format!("'{}'", token),
+        };
+        let message = match kind {
+            VerboseErrorKind::Char(c) => format!("expected '{}', found {}", c, found),
+            VerboseErrorKind::Context(context) => format!("expected {}, found {}", context, found),
+            VerboseErrorKind::Nom(_) => format!("unexpected {}", found),
+        };
+        ParseError::MalformedStatement(input.offset(remaining), message)
+    }
+
+    // Moves the position of the error by `offset` bytes, for statements parsed from within another
+    pub(super) fn shifted(self, offset: usize) -> Self {
+        match self {
+            ParseError::MalformedStatement(position, message) => {
+                ParseError::MalformedStatement(position + offset, message)
+            }
+            err => err,
+        }
+    }
+}
+
+// The word, number or symbol that starts the text, or nothing at its end
+pub fn token_at(text: &str) -> &str {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    match text.chars().next() {
+        None => "",
+        Some(c) if is_word(c) => {
+            let end = text.find(|c| !is_word(c)).unwrap_or(text.len());
+            &text[..end]
+        }
+        Some(c) => &text[..c.len_utf8()],
+    }
+}
+
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseError::MalformedStatement(parse_trace) => write!(
-                f,
-                "Error encountered when parsing statement: \n {}",
-                parse_trace
-            ),
+            ParseError::MalformedStatement(_, message) => {
+                write!(f, "Error encountered when parsing statement: {}", message)
+            }
             ParseError::UnknownStatement => write!(f, "Unrecognized statement"),
         }
     }
@@ -0,0 +1,50 @@
+use nom::{
+    bytes::complete::tag_no_case,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, opt},
+    error::VerboseError,
+    sequence::{pair, tuple},
+    Finish, IResult,
+};
+
+use super::keyword;
+use super::statement::{ParseError, Statement};
+
+// BEGIN, COMMIT and ROLLBACK may all be followed by the TRANSACTION keyword
+fn parse_transaction_statement<'a>(
+    input: &'a str,
+    statement_keyword: &'static str,
+) -> IResult<&'a str, (), VerboseError<&'a str>> {
+    let (_, _) = all_consuming(tuple((
+        multispace0,
+        tag_no_case(statement_keyword),
+        opt(pair(multispace1, keyword("transaction"))),
+        multispace0,
+        char(';'),
+    )))(input)?;
+
+    Ok(("", ()))
+}
+
+fn validate_transaction_statement<'a>(
+    input: &'a str,
+    statement_keyword: &'static str,
+    statement: Statement<'a>,
+) -> Result<Statement<'a>, ParseError> {
+    match parse_transaction_statement(input, statement_keyword).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok(_) => Ok(statement),
+    }
+}
+
+pub(super) fn validate_begin(input: &str) -> Result<Statement<'_>, ParseError> {
+    validate_transaction_statement(input, "begin", Statement::Begin)
+}
+
+pub(super) fn validate_commit(input: &str) -> Result<Statement<'_>, ParseError> {
+    validate_transaction_statement(input, "commit", Statement::Commit)
+}
+
+pub(super) fn validate_rollback(input: &str) -> Result<Statement<'_>, ParseError> {
+    validate_transaction_statement(input, "rollback", Statement::Rollback)
+}
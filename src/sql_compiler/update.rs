@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+
+use nom::{
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, cut, opt},
+    error::VerboseError,
+    multi::separated_list1,
+    sequence::{delimited, pair, separated_pair, tuple},
+    Finish, IResult,
+};
+
+use super::expression::{parse_expr, Expr};
+use super::ordering::{parse_ordered_limit, OrderingTerm, RowLimit};
+use super::select::parse_where_clause;
+use super::statement::{ParseError, Statement};
+use super::{keyword, parse_identifier, parse_table_name};
+
+// Names of the columns assigned, each with the value it is given
+type Assignments<'a> = Vec<(Cow<'a, str>, Expr)>;
+
+// `UPDATE table SET column = value, ... [WHERE condition] [[ORDER BY ...] LIMIT n [OFFSET m]]`.
+// Values are expressions evaluated against the row being updated, so they may read its columns
+#[derive(Debug, Clone)]
+pub struct UpdateTokens<'a> {
+    pub table_name: Cow<'a, str>,
+    pub assignments: Assignments<'a>,
+    pub where_clause: Option<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<RowLimit>,
+}
+
+impl UpdateTokens<'_> {
+    pub fn into_owned(self) -> UpdateTokens<'static> {
+        UpdateTokens {
+            table_name: Cow::Owned(self.table_name.into_owned()),
+            assignments: self
+                .assignments
+                .into_iter()
+                .map(|(name, value)| (Cow::Owned(name.into_owned()), value))
+                .collect(),
+            where_clause: self.where_clause,
+            order_by: self.order_by,
+            limit: self.limit,
+        }
+    }
+}
+
+fn parse_assignments(input: &str) -> IResult<&str, Assignments<'_>, VerboseError<&str>> {
+    let assignment = separated_pair(
+        parse_identifier,
+        tuple((multispace0, char('='), multispace0)),
+        parse_expr,
+    );
+    let (input, assignments) = separated_list1(
+        char(','),
+        cut(delimited(multispace0, assignment, multispace0)),
+    )(input)?;
+    Ok((
+        input,
+        assignments
+            .into_iter()
+            .map(|(name, value)| (Cow::Borrowed(name), value))
+            .collect(),
+    ))
+}
+
+fn parse_update(input: &str) -> IResult<&str, UpdateTokens<'_>, VerboseError<&str>> {
+    let (input, _) = tuple((multispace0, keyword("update"), multispace1))(input)?;
+    let (input, table_name) = parse_table_name(input)?;
+    let (input, _) = tuple((multispace1, keyword("set"), multispace1))(input)?;
+    let (input, assignments) = parse_assignments(input)?;
+    let (input, where_clause) = opt(parse_where_clause)(input)?;
+    let (input, (order_by, limit)) = parse_ordered_limit(input)?;
+    let (_, _) = all_consuming(pair(multispace0, char(';')))(input)?;
+
+    Ok((
+        "",
+        UpdateTokens {
+            table_name: Cow::Borrowed(table_name),
+            assignments,
+            where_clause,
+            order_by,
+            limit,
+        },
+    ))
+}
+
+pub(super) fn validate_update(input: &str) -> Result<Statement<'_>, ParseError> {
+    match parse_update(input).finish() {
+        Err(e) => Err(ParseError::malformed(input, e)),
+        Ok((_, update_tokens)) => Ok(Statement::Update(update_tokens)),
+    }
+}
@@ -1,23 +1,210 @@
+use std::io::Write;
+
 use crate::backend::database::Database;
+use crate::backend::row::SQLType;
+use crate::metrics::Stopwatch;
 use crate::sql_compiler::Statement;
 
+mod alter;
+mod attach;
+mod constraints;
 mod create;
+mod delete;
+mod drop;
+mod expert;
+mod explain;
+pub mod expr;
+mod information_schema;
 mod insert;
+mod interpreter;
+pub mod interrupt;
+mod operators;
+mod planner;
+mod pragma;
+mod program;
+pub(crate) mod progress;
+mod reindex;
+pub mod result_writer;
 mod select;
+mod subquery;
+mod transaction;
+mod update;
 mod vm_error;
 
-use create::process_create;
+use alter::process_alter;
+use attach::{process_attach, process_detach};
+use create::{process_create, process_create_index};
+use delete::process_delete;
+use drop::{process_drop, process_drop_index};
+use expert::process_expert;
+use explain::process_explain;
 use insert::process_insert;
-use select::process_select;
-use vm_error::VMError;
+use pragma::process_pragma;
+pub use pragma::PRAGMA_NAMES;
+use reindex::process_reindex;
+use result_writer::{CsvOptions, OutputSettings};
+use select::{collect_select, describe_select, export_outfile, export_select, process_select};
+use transaction::{process_begin, process_commit, process_rollback};
+use update::process_update;
+pub use vm_error::VMError;
 
+// Runs a statement, returning the number of rows it changed
 pub fn execute_statement(
     statement: Statement,
-    db_instance: Option<&mut Database>,
-) -> Result<(), VMError> {
+    mut db_instance: Option<&mut Database>,
+    output_settings: &OutputSettings,
+) -> Result<u64, VMError> {
+    let stopwatch = Stopwatch::start();
+    let result = run_statement(statement, db_instance.as_deref_mut(), output_settings);
+    if let Some(db) = db_instance {
+        db.record_statement(stopwatch.elapsed(), result.is_ok());
+    }
+    result
+}
+
+fn run_statement(
+    statement: Statement,
+    mut db_instance: Option<&mut Database>,
+    output_settings: &OutputSettings,
+) -> Result<u64, VMError> {
+    if let Some(db) = db_instance.as_deref_mut() {
+        db.refresh().map_err(VMError::RefreshError)?;
+    }
+    if output_settings.expert {
+        return process_expert(statement, db_instance, output_settings).map(|_| 0);
+    }
+
+    let writes = statement.writes();
+    let changes_rows = statement.changes_rows();
+    if let Some(db) = db_instance.as_deref_mut().filter(|_| writes) {
+        if db.is_read_only() {
+            return Err(VMError::ReadOnly);
+        }
+        db.begin_write().map_err(VMError::CommitError)?;
+        db.begin_statement();
+    }
+
+    let result = match statement {
+        Statement::Alter(alter_tokens) => {
+            process_alter(alter_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::Attach(attach_tokens) => {
+            process_attach(attach_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::Begin => process_begin(db_instance.as_deref_mut()).map(|_| 0),
+        Statement::Commit => process_commit(db_instance.as_deref_mut()).map(|_| 0),
+        Statement::Create(create_tokens) => {
+            process_create(create_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::CreateIndex(create_index_tokens) => {
+            process_create_index(create_index_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::Delete(delete_tokens) => {
+            process_delete(delete_tokens, db_instance.as_deref_mut())
+        }
+        Statement::Detach(detach_tokens) => {
+            process_detach(detach_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::Drop(drop_tokens) => {
+            process_drop(drop_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::DropIndex(drop_index_tokens) => {
+            process_drop_index(drop_index_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::Explain(statement) => {
+            process_explain(*statement, db_instance.as_deref_mut(), output_settings).map(|_| 0)
+        }
+        Statement::Insert(insert_tokens) => {
+            process_insert(insert_tokens, db_instance.as_deref_mut())
+        }
+        Statement::Pragma(pragma_tokens) => {
+            process_pragma(pragma_tokens, db_instance.as_deref_mut(), output_settings).map(|_| 0)
+        }
+        Statement::Reindex(reindex_tokens) => {
+            process_reindex(reindex_tokens, db_instance.as_deref_mut()).map(|_| 0)
+        }
+        Statement::Rollback => process_rollback(db_instance.as_deref_mut()).map(|_| 0),
+        Statement::Select(select_tokens) => {
+            process_select(select_tokens, db_instance.as_deref_mut(), output_settings).map(|_| 0)
+        }
+        Statement::Update(update_tokens) => {
+            process_update(update_tokens, db_instance.as_deref_mut())
+        }
+    };
+    let changes = match result {
+        Ok(changes) => changes,
+        Err(err) => {
+            if let Some(db) = db_instance.as_deref_mut().filter(|_| writes) {
+                // A statement that fails changes nothing, even halfway through a transaction. Its
+                // error is the one worth reporting, and a transaction keeps its lock until it ends
+                let _ = db.rollback_statement();
+                if !db.in_transaction() {
+                    let _ = db.end_write();
+                }
+            }
+            return Err(err);
+        }
+    };
+
+    if let Some(db) = db_instance {
+        if writes {
+            db.end_statement();
+        }
+        // Changes are written to disk as soon as the statement that made them completes, or at
+        // COMMIT inside a transaction
+        if writes && !db.in_transaction() {
+            db.flush().map_err(VMError::CommitError)?;
+        }
+        if changes_rows {
+            db.record_changes(changes);
+        }
+    }
+
+    Ok(changes)
+}
+
+// Runs a SELECT and returns its result columns and rows instead of printing them. A SELECT
+// ... INTO OUTFILE writes its rows to the file and returns none
+pub fn query_statement(
+    statement: Statement,
+    db: &Database,
+) -> Result<(Vec<String>, Vec<Vec<SQLType>>), VMError> {
+    let stopwatch = Stopwatch::start();
+    let result = match statement {
+        Statement::Select(select_tokens) => match export_outfile(&select_tokens, db) {
+            Ok(Some(_)) => Ok((Vec::new(), Vec::new())),
+            Ok(None) => collect_select(select_tokens, db),
+            Err(err) => Err(err),
+        },
+        _ => Err(VMError::NotAQuery),
+    };
+    db.record_statement(stopwatch.elapsed(), result.is_ok());
+    result
+}
+
+// Result columns of a SELECT along with the table columns they read, if any, without running it
+pub(crate) fn describe_statement(
+    statement: Statement,
+    db: &Database,
+) -> Result<Vec<(String, Option<program::ColumnOrigin>)>, VMError> {
     match statement {
-        Statement::Create(create_tokens) => process_create(create_tokens, db_instance),
-        Statement::Insert(insert_tokens) => process_insert(insert_tokens, db_instance),
-        Statement::Select(select_tokens) => process_select(select_tokens, db_instance),
+        Statement::Select(select_tokens) => describe_select(&select_tokens, db),
+        _ => Err(VMError::NotAQuery),
     }
 }
+
+// Streams the rows of a SELECT to `out` as CSV, returning the number of rows written
+pub fn export_statement(
+    statement: Statement,
+    db: &Database,
+    out: Box<dyn Write>,
+    options: CsvOptions,
+) -> Result<u64, VMError> {
+    let stopwatch = Stopwatch::start();
+    let result = match statement {
+        Statement::Select(select_tokens) => export_select(&select_tokens, db, out, options),
+        _ => Err(VMError::NotAQuery),
+    };
+    db.record_statement(stopwatch.elapsed(), result.is_ok());
+    result
+}
@@ -0,0 +1,176 @@
+use super::delete::matching_rows;
+use super::planner::rename_column;
+use super::vm_error::VMError;
+use crate::backend::catalog::CatalogEntry;
+use crate::backend::database::{Database, DatabaseError};
+use crate::backend::row::Row;
+use crate::sql_compiler::expression::parse_expr;
+use crate::sql_compiler::{format_expr, AlterAction, AlterTokens};
+
+// Renames a table or one of its columns, along with the foreign keys of the tables of the same
+// database that reference it. Every catalog entry changed is written by the same flush, so the
+// rename is never found half done. If a part of it fails, the rest is discarded unless a
+// transaction holds it
+pub(super) fn process_alter(
+    alter_tokens: AlterTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let AlterTokens { table_name, action } = alter_tokens;
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    match action {
+        AlterAction::RenameTable(new_name) => rename_table(open_database, &table_name, &new_name),
+        AlterAction::RenameColumn(from, to) => {
+            rename_table_column(open_database, &table_name, &from, &to)
+        }
+    }
+}
+
+fn rename_table(db: &mut Database, table_name: &str, new_name: &str) -> Result<(), VMError> {
+    let write_error = |err| VMError::TableWriteError(table_name.to_string(), err);
+    let (table_db, unqualified_name) = db.resolve(table_name).map_err(write_error)?;
+    let table = db.table(table_name).map_err(write_error)?;
+    if table_db.table(new_name).is_ok() {
+        return Err(write_error(DatabaseError::DuplicateTable));
+    }
+
+    let mut entry = table.catalog_entry();
+    entry.name = new_name.to_string();
+    let mut redefined = vec![(table_name.to_string(), entry)];
+    redefined.extend(other_tables(table_db, table_name, unqualified_name));
+
+    for (name, mut entry) in redefined {
+        let mut references_table = false;
+        for constraints in entry.constraints.values_mut() {
+            if let Some((referenced_table, _)) = &mut constraints.references {
+                if referenced_table == unqualified_name {
+                    *referenced_table = new_name.to_string();
+                    references_table = true;
+                }
+            }
+        }
+        if references_table || name == table_name {
+            db.redefine_table(&name, entry).map_err(write_error)?;
+        }
+    }
+    Ok(())
+}
+
+fn rename_table_column(
+    db: &mut Database,
+    table_name: &str,
+    from: &str,
+    to: &str,
+) -> Result<(), VMError> {
+    let write_error = |err| VMError::TableWriteError(table_name.to_string(), err);
+    let (table_db, unqualified_name) = db.resolve(table_name).map_err(write_error)?;
+    let table = db.table(table_name).map_err(write_error)?;
+    if !table.columns.contains_key(from) {
+        return Err(VMError::ColumnNotInTable(from.to_string()));
+    }
+    if table.columns.contains_key(to) {
+        return Err(VMError::ColumnAlreadyExists(to.to_string()));
+    }
+
+    // Values of a row are ordered by the names of their columns, which the new name may change
+    let renamed = |name: &String| match name == from {
+        true => to.to_string(),
+        false => name.clone(),
+    };
+    let old_order: Vec<String> = table.columns.keys().map(renamed).collect();
+    let rows = matching_rows(db, table, table_name, None)?;
+
+    let mut entry = table.catalog_entry();
+    if let Some(column_type) = entry.columns.remove(from) {
+        entry.columns.insert(to.to_string(), column_type);
+    }
+    if let Some(constraints) = entry.constraints.remove(from) {
+        entry.constraints.insert(to.to_string(), constraints);
+    }
+    for constraints in entry.constraints.values_mut() {
+        if let Some(check) = &mut constraints.check {
+            *check = rename_in_check(check, from, to);
+        }
+    }
+    if let Some(comment) = entry.comments.columns.remove(from) {
+        entry.comments.columns.insert(to.to_string(), comment);
+    }
+    entry.declared_order = entry.declared_order.iter().map(renamed).collect();
+    for index in &mut entry.indexes {
+        index.columns = index.columns.iter().map(renamed).collect();
+    }
+    entry.primary_key = entry.primary_key.as_ref().map(renamed);
+
+    // Foreign keys referencing the column, which may be in the table itself
+    let mut redefined = vec![(table_name.to_string(), entry)];
+    redefined.extend(other_tables(table_db, table_name, unqualified_name));
+    for (name, mut entry) in redefined {
+        let mut references_column = false;
+        for constraints in entry.constraints.values_mut() {
+            if let Some((referenced_table, referenced_column)) = &mut constraints.references {
+                if referenced_table == unqualified_name && referenced_column == from {
+                    *referenced_column = to.to_string();
+                    references_column = true;
+                }
+            }
+        }
+        if references_column || name == table_name {
+            db.redefine_table(&name, entry).map_err(write_error)?;
+        }
+    }
+
+    let table = db.table(table_name).map_err(write_error)?;
+    let new_order: Vec<&String> = table.columns.keys().collect();
+    if old_order.iter().eq(new_order.iter().copied()) {
+        return Ok(());
+    }
+    let positions: Vec<usize> = new_order
+        .iter()
+        .filter_map(|name| old_order.iter().position(|old_name| old_name == *name))
+        .collect();
+    // Indexes hold the same values as before, in the order of their own columns
+    for row in rows {
+        let values = positions
+            .iter()
+            .map(|&idx| row.attributes()[idx].clone())
+            .collect();
+        table
+            .rewrite(Row::new(row.rowid(), values))
+            .map_err(|err| write_error(err.into()))?;
+    }
+    Ok(())
+}
+
+// Catalog entries of the other tables of the database, under their names qualified the same way
+// as `table_name`
+fn other_tables(
+    table_db: &Database,
+    table_name: &str,
+    unqualified_name: &str,
+) -> Vec<(String, CatalogEntry)> {
+    table_db
+        .table_names()
+        .into_iter()
+        .filter(|name| *name != unqualified_name)
+        .filter_map(|name| {
+            let qualified_name = match table_name.split_once('.') {
+                Some((alias, _)) => format!("{}.{}", alias, name),
+                None => name.to_string(),
+            };
+            let entry = table_db.table(name).ok()?.catalog_entry();
+            Some((qualified_name, entry))
+        })
+        .collect()
+}
+
+// CHECK constraints are kept as SQL text, which is only written anew when it reads the column
+fn rename_in_check(check: &str, from: &str, to: &str) -> String {
+    let Ok((_, expr)) = parse_expr(check) else {
+        return check.to_string();
+    };
+    let renamed = rename_column(expr.clone(), from, to);
+    match renamed == expr {
+        true => check.to_string(),
+        false => format_expr(&renamed),
+    }
+}
@@ -0,0 +1,27 @@
+use super::vm_error::VMError;
+use crate::backend::database::Database;
+use crate::sql_compiler::{AttachTokens, DetachTokens};
+
+pub(super) fn process_attach(
+    attach_tokens: AttachTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let AttachTokens { path, alias } = attach_tokens;
+
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    open_database
+        .attach(&path, &alias)
+        .map_err(|err| VMError::AttachError(path.to_string(), err))
+}
+
+pub(super) fn process_detach(
+    detach_tokens: DetachTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    open_database
+        .detach(&detach_tokens.alias)
+        .map_err(VMError::DetachError)
+}
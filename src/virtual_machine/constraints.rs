@@ -0,0 +1,261 @@
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+use super::expr::{as_integer, compare_values, evaluate, truth_value, Scope};
+use super::vm_error::VMError;
+use crate::backend::columns::ColumnItemType;
+use crate::backend::cursor::DBCursor;
+use crate::backend::database::Database;
+use crate::backend::row::{Row, SQLType};
+use crate::backend::table::Table;
+use crate::sql_compiler::expression::parse_expr;
+
+// Checks a row about to be inserted against the constraints of the table's columns. Values are
+// in the order of the table's columns. A row about to be updated gives the rowid it is stored
+// under as `replaced`, so that its old values are not taken for those of another row
+pub(super) fn check_constraints(
+    db: &Database,
+    table: &Table,
+    rowid: u64,
+    values: &[SQLType],
+    replaced: Option<u64>,
+) -> Result<(), VMError> {
+    let column_names = table.columns.to_printable();
+
+    // The PRIMARY KEY is the rowid, so a row already stored under it holds the same key. It is
+    // found here rather than by the insert, which only knows the rowid the key is encoded as
+    if let Some(primary_key) = &table.primary_key {
+        let taken = replaced != Some(rowid)
+            && table
+                .contains(rowid)
+                .map_err(|err| VMError::TableReadError(table.name.to_string(), err.into()))?;
+        if taken {
+            let value = column_names
+                .iter()
+                .position(|name| name == primary_key)
+                .map_or_else(String::new, |idx| values[idx].to_string());
+            return Err(VMError::PrimaryKeyViolation(
+                format!("{}.{}", table.name, primary_key),
+                value,
+            ));
+        }
+    }
+
+    for (idx, (name, value)) in column_names.iter().zip(values).enumerate() {
+        let Some(constraints) = table.columns.constraints(name) else {
+            continue;
+        };
+        let qualified_name = || format!("{}.{}", table.name, name);
+
+        if constraints.not_null && *value == SQLType::Null {
+            return Err(VMError::NotNullViolation(qualified_name()));
+        }
+
+        if let Some(check) = &constraints.check {
+            let expr = parse_expr(check)
+                .map_err(|_| VMError::CheckViolation(qualified_name()))?
+                .1;
+            let scope = Scope::new(&column_names, values).with_rowid(rowid);
+            if truth_value(&evaluate(&expr, &scope)?) == Some(false) {
+                return Err(VMError::CheckViolation(qualified_name()));
+            }
+        }
+
+        // NULL is neither a duplicate of anything nor has to be found in the referenced table
+        if *value == SQLType::Null {
+            continue;
+        }
+
+        if constraints.unique && contains_value(table, idx, value, None, replaced)? {
+            return Err(VMError::UniqueViolation(qualified_name()));
+        }
+
+        let references = constraints
+            .references
+            .as_ref()
+            .filter(|_| db.foreign_keys());
+        if let Some((referenced_table, referenced_column)) = references {
+            let found = if referenced_table == &table.name {
+                // A row may reference itself
+                let in_row = column_names
+                    .iter()
+                    .position(|column| column == referenced_column)
+                    .is_some_and(|referenced_idx| {
+                        compare_values(value, &values[referenced_idx]) == Ordering::Equal
+                    });
+                in_row || references_value(table, referenced_column, value, replaced)?
+            } else {
+                let referenced_table = db
+                    .table(referenced_table)
+                    .map_err(|err| VMError::TableReadError(referenced_table.to_string(), err))?;
+                references_value(referenced_table, referenced_column, value, None)?
+            };
+            if !found {
+                return Err(VMError::ForeignKeyViolation(qualified_name()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Checks that no row of the tables of `db` references a value of the deleted rows that `table`
+// no longer holds. Rows are checked once every one of them is deleted, so a row referencing
+// another deleted along with it does not hold the delete back. The old versions of updated rows
+// count as deleted
+pub(super) fn check_deleted_references(
+    db: &Database,
+    table: &Table,
+    deleted: &[Row],
+) -> Result<(), VMError> {
+    if !db.foreign_keys() || deleted.is_empty() {
+        return Ok(());
+    }
+
+    for referencing_name in db.table_names() {
+        let referencing = db
+            .table(referencing_name)
+            .map_err(|err| VMError::TableReadError(referencing_name.to_string(), err))?;
+        for (idx, column) in referencing.columns.keys().enumerate() {
+            let references = referencing
+                .columns
+                .constraints(column)
+                .and_then(|constraints| constraints.references.as_ref());
+            let Some((_, referenced_column)) =
+                references.filter(|(referenced_table, _)| *referenced_table == table.name)
+            else {
+                continue;
+            };
+            let Some(referenced_idx) = table
+                .columns
+                .keys()
+                .position(|name| name == referenced_column)
+            else {
+                continue;
+            };
+
+            for row in deleted {
+                let Some(value) = row.attributes().get(referenced_idx) else {
+                    continue;
+                };
+                if *value == SQLType::Null
+                    || references_value(table, referenced_column, value, None)?
+                {
+                    continue;
+                }
+                if contains_value(referencing, idx, value, None, None)? {
+                    return Err(VMError::ForeignKeyViolation(format!(
+                        "{}.{}",
+                        referencing.name, column
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Checks that no row of another table of `db` references the table about to be dropped. Rows of
+// the table itself go along with it
+pub(super) fn check_dropped_references(db: &Database, table: &Table) -> Result<(), VMError> {
+    if !db.foreign_keys() {
+        return Ok(());
+    }
+
+    for referencing_name in db.table_names() {
+        if referencing_name == table.name {
+            continue;
+        }
+        let referencing = db
+            .table(referencing_name)
+            .map_err(|err| VMError::TableReadError(referencing_name.to_string(), err))?;
+        for (idx, column) in referencing.columns.keys().enumerate() {
+            let references = referencing
+                .columns
+                .constraints(column)
+                .and_then(|constraints| constraints.references.as_ref());
+            if references.is_none_or(|(referenced_table, _)| *referenced_table != table.name) {
+                continue;
+            }
+
+            let mut cursor = DBCursor::new(referencing.root_page_num());
+            while let Some(row) = referencing
+                .next_row(&mut cursor)
+                .map_err(|err| VMError::TableReadError(referencing_name.to_string(), err.into()))?
+            {
+                if row
+                    .attributes()
+                    .get(idx)
+                    .is_some_and(|value| *value != SQLType::Null)
+                {
+                    return Err(VMError::ForeignKeyViolation(format!(
+                        "{}.{}",
+                        referencing.name, column
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Whether some row of the table holds the value in the given column. The rows visited can be
+// limited to a single rowid, and one rowid can be skipped
+fn contains_value(
+    table: &Table,
+    column_idx: usize,
+    value: &SQLType,
+    rowid: Option<u64>,
+    skipped: Option<u64>,
+) -> Result<bool, VMError> {
+    let mut cursor = match rowid {
+        Some(rowid) => DBCursor::with_bounds(
+            table.root_page_num(),
+            Bound::Included(rowid),
+            Bound::Included(rowid),
+        ),
+        None => DBCursor::new(table.root_page_num()),
+    };
+    while let Some(row) = table
+        .next_row(&mut cursor)
+        .map_err(|err| VMError::TableReadError(table.name.to_string(), err.into()))?
+    {
+        if Some(row.rowid()) == skipped {
+            continue;
+        }
+        let existing = row.attributes().get(column_idx);
+        if existing.is_some_and(|existing| compare_values(existing, value) == Ordering::Equal) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Looks the value up in the referenced column, seeking straight to it when that column is the
+// primary key of its table
+fn references_value(
+    table: &Table,
+    column: &str,
+    value: &SQLType,
+    skipped: Option<u64>,
+) -> Result<bool, VMError> {
+    let Some(column_idx) = table.columns.keys().position(|name| name == column) else {
+        return Ok(false);
+    };
+    let rowid = match (table.primary_key.as_deref(), table.columns.get(column)) {
+        (Some(primary_key), Some(ColumnItemType::Integer(int_type))) if primary_key == column => {
+            let Some(value) = as_integer(value) else {
+                return Ok(false);
+            };
+            let (min, max) = int_type.range();
+            if !(min..=max).contains(&value) {
+                return Ok(false);
+            }
+            Some(int_type.to_key(value))
+        }
+        _ => None,
+    };
+    contains_value(table, column_idx, value, rowid, skipped)
+}
@@ -1,7 +1,11 @@
+use std::borrow::Cow;
+
+use super::insert::default_value;
 use super::vm_error::VMError;
-use crate::backend::columns::Columns;
+use crate::backend::catalog::Comments;
+use crate::backend::columns::{ColumnItemType, Columns, ROWID_COLUMN};
 use crate::backend::database::{Database, DatabaseError};
-use crate::sql_compiler::CreateTokens;
+use crate::sql_compiler::{CreateIndexTokens, CreateTokens};
 
 pub(super) fn process_create(
     create_tokens: CreateTokens,
@@ -10,27 +14,270 @@ pub(super) fn process_create(
     let CreateTokens {
         table_name,
         columns: columns_to_insert,
+        primary_key,
+        column_comments,
+        comment,
+        if_not_exists,
     } = create_tokens;
 
     let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    if if_not_exists && open_database.table(&table_name).is_ok() {
+        return Ok(());
+    }
 
     let mut columns = Columns::new();
 
-    for (column_name, column_type) in columns_to_insert.into_iter() {
+    for (column_name, column_type, constraints) in columns_to_insert.into_iter() {
+        // Expressions are tried once, so one that cannot be evaluated fails here rather than on
+        // every INSERT
+        if let Some(default) = &constraints.default {
+            default_value(&column_name, default, &column_type, open_database)?;
+        }
+        if !constraints.is_empty() {
+            columns.1.insert(column_name.to_string(), constraints);
+        }
         if columns
             .insert(column_name.to_string(), column_type)
             .is_some()
         {
             return Err(VMError::DuplicatedColumnName(column_name.to_string()));
         }
+        columns.2.push(column_name.to_string());
+    }
+
+    // Foreign keys may point to another column of the table being created, or to a table of the
+    // database it is created in
+    let (table_db, unqualified_name) = open_database
+        .resolve(&table_name)
+        .map_err(|err| VMError::TableWriteError(table_name.to_string(), err))?;
+    for constraints in columns.1.values() {
+        let Some((referenced_table, referenced_column)) = &constraints.references else {
+            continue;
+        };
+        let found = match referenced_table == unqualified_name {
+            true => columns.contains_key(referenced_column),
+            false => table_db
+                .table(referenced_table)
+                .is_ok_and(|table| table.columns.contains_key(referenced_column)),
+        };
+        if !found {
+            return Err(VMError::UnknownReferencedColumn(format!(
+                "{}.{}",
+                referenced_table, referenced_column
+            )));
+        }
+    }
+
+    // Tables with neither get their rowids assigned on insert
+    let primary_key = match primary_key.as_slice() {
+        [primary_key] => Some(primary_key.to_string()),
+        [] if matches!(columns.get(ROWID_COLUMN), Some(ColumnItemType::Integer(_))) => {
+            Some(ROWID_COLUMN.to_string())
+        }
+        [] => None,
+        _ => return Err(VMError::MultiplePrimaryKeys(table_name.to_string())),
+    };
+    if let Some(primary_key) = &primary_key {
+        if !matches!(columns.get(primary_key), Some(ColumnItemType::Integer(_))) {
+            return Err(VMError::NonIntegerPrimaryKey(primary_key.clone()));
+        }
     }
 
+    let comments = Comments {
+        table: comment.map(Cow::into_owned),
+        columns: column_comments
+            .into_iter()
+            .map(|(column, comment)| (column.into_owned(), comment.into_owned()))
+            .collect(),
+    };
+
     open_database
-        .add_table(table_name, columns)
+        .add_table(&table_name, columns, primary_key, comments)
         .map_err(|err| match err {
             DatabaseError::DuplicateTable => VMError::DuplicatedTableName(table_name.to_string()),
-            _ => unreachable!(),
+            err => VMError::TableWriteError(table_name.to_string(), err),
         })?;
 
     Ok(())
 }
+
+pub(super) fn process_create_index(
+    create_index_tokens: CreateIndexTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    let index_name = &create_index_tokens.index_name;
+    let table_name = create_index_tokens.qualified_table_name();
+    let write_error = |err| VMError::TableWriteError(table_name.clone(), err);
+
+    let (index_db, unqualified_name) = open_database.resolve(index_name).map_err(write_error)?;
+    if create_index_tokens.if_not_exists && index_db.index_table(unqualified_name).is_some() {
+        return Ok(());
+    }
+    let table = open_database.table(&table_name).map_err(write_error)?;
+    let mut columns = Vec::new();
+    for column in &create_index_tokens.columns {
+        if !table.columns.contains_key(column.as_ref()) {
+            return Err(VMError::ColumnNotInTable(column.to_string()));
+        }
+        columns.push(column.to_string());
+    }
+
+    open_database
+        .add_index(index_name, &create_index_tokens.table_name, columns)
+        .map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql_compiler::{parse_statement, Statement};
+    use crate::virtual_machine::select::compile_select;
+    use crate::{Connection, SQLType};
+
+    // Table of people with an index on their city, whose rows reach a few pages
+    fn indexed_table() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE people (id INT PRIMARY KEY, name TEXT, city TEXT);")
+            .unwrap();
+        for id in 0..200 {
+            let city = ["Lima", "Oslo", "Rome"][id % 3];
+            conn.execute(&format!(
+                "INSERT INTO people VALUES ({}, 'person {}', '{}');",
+                id, id, city
+            ))
+            .unwrap();
+        }
+        conn.execute("CREATE INDEX by_city ON people (city, name);")
+            .unwrap();
+        conn
+    }
+
+    fn ids(conn: &mut Connection, sql: &str) -> Vec<i32> {
+        conn.query(sql)
+            .unwrap()
+            .map(|row| match row.into_values().remove(0) {
+                SQLType::Integer(id) => id,
+                value => panic!("unexpected id {:?}", value),
+            })
+            .collect()
+    }
+
+    fn plan(conn: &Connection, sql: &str) -> String {
+        let Statement::Select(select_tokens) = parse_statement(sql).unwrap() else {
+            panic!("not a SELECT");
+        };
+        compile_select(&select_tokens, &conn.database())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn equality_on_the_first_columns_reads_the_index() {
+        let mut conn = indexed_table();
+        let sql = "SELECT id FROM people WHERE city = 'Oslo' AND name < 'person 11';";
+        assert!(plan(&conn, sql).contains("USING INDEX by_city (city)"));
+        assert_eq!(ids(&mut conn, sql), [1, 10, 100, 103, 106, 109]);
+
+        let sql = "SELECT id FROM people WHERE 'person 7' = name AND city = 'Oslo';";
+        assert!(plan(&conn, sql).contains("USING INDEX by_city (city, name)"));
+        assert_eq!(ids(&mut conn, sql), [7]);
+
+        // The index cannot help without its first column, or with a rowid to seek to
+        for sql in [
+            "SELECT id FROM people WHERE name = 'person 7';",
+            "SELECT id FROM people WHERE city = 'Oslo' AND id = 7;",
+            "SELECT id FROM people WHERE city > 'Oslo';",
+        ] {
+            assert!(!plan(&conn, sql).contains("USING INDEX"), "{}", sql);
+        }
+        assert_eq!(
+            ids(&mut conn, "SELECT id FROM people WHERE city = 'Paris';"),
+            []
+        );
+    }
+
+    #[test]
+    fn writes_keep_the_index_up_to_date() {
+        let mut conn = indexed_table();
+        conn.execute("UPDATE people SET city = 'Oslo' WHERE id < 6;")
+            .unwrap();
+        conn.execute("DELETE FROM people WHERE id = 4 OR id = 7;")
+            .unwrap();
+        conn.execute("INSERT INTO people VALUES (500, 'newcomer', 'Oslo');")
+            .unwrap();
+        assert!(
+            conn.integrity_check().is_empty(),
+            "{:?}",
+            conn.integrity_check()
+        );
+
+        let sql = "SELECT id FROM people WHERE city = 'Oslo' AND (id < 15 OR id > 400);";
+        let mut found = ids(&mut conn, sql);
+        found.sort_unstable();
+        assert_eq!(found, [0, 1, 2, 3, 5, 10, 13, 500]);
+
+        // A statement that fails halfway leaves the index as it was. Ids from 300 down to 200 are
+        // new, and 199 is taken
+        assert!(conn
+            .execute("INSERT INTO people SELECT 300 - id, name, city FROM people;")
+            .is_err());
+        assert!(
+            conn.integrity_check().is_empty(),
+            "{:?}",
+            conn.integrity_check()
+        );
+        assert_eq!(ids(&mut conn, sql).len(), 8);
+    }
+
+    #[test]
+    fn renamed_columns_stay_indexed() {
+        let mut conn = indexed_table();
+        conn.execute("ALTER TABLE people RENAME COLUMN city TO town;")
+            .unwrap();
+        assert!(
+            conn.integrity_check().is_empty(),
+            "{:?}",
+            conn.integrity_check()
+        );
+        let sql = "SELECT id FROM people WHERE town = 'Rome' AND name < 'person 11';";
+        assert!(plan(&conn, sql).contains("USING INDEX by_city (town)"));
+        assert_eq!(ids(&mut conn, sql), [101, 104, 107]);
+    }
+
+    #[test]
+    fn create_and_drop_index() {
+        let mut conn = indexed_table();
+        assert!(conn
+            .execute("CREATE INDEX by_city ON people (name);")
+            .is_err());
+        conn.execute("CREATE INDEX IF NOT EXISTS by_city ON people (name);")
+            .unwrap();
+        assert!(conn
+            .execute("CREATE INDEX by_age ON people (age);")
+            .is_err());
+        assert!(conn.execute("CREATE INDEX i ON nobody (city);").is_err());
+
+        let page_count = conn.database().page_count();
+        conn.execute("DROP INDEX by_city;").unwrap();
+        assert!(conn.database().index_table("by_city").is_none());
+        assert!(conn.database().page_count() <= page_count);
+        assert!(
+            conn.integrity_check().is_empty(),
+            "{:?}",
+            conn.integrity_check()
+        );
+        assert!(conn.execute("DROP INDEX by_city;").is_err());
+        conn.execute("DROP INDEX IF EXISTS by_city;").unwrap();
+
+        // Indexes go with their table
+        conn.execute("CREATE INDEX by_name ON people (name);")
+            .unwrap();
+        conn.execute("DROP TABLE people;").unwrap();
+        assert!(conn.database().index_table("by_name").is_none());
+        assert!(
+            conn.integrity_check().is_empty(),
+            "{:?}",
+            conn.integrity_check()
+        );
+    }
+}
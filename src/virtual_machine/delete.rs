@@ -0,0 +1,218 @@
+use std::cmp::Ordering;
+
+use super::constraints::check_deleted_references;
+use super::expr::{compare_values, evaluate, evaluate_predicate, Scope};
+use super::interrupt::is_interrupted;
+use super::operators::{BoxedOperator, Limit, Operator, Sort, Values};
+use super::planner::{plan_table_scan, resolve_table_columns};
+use super::subquery::{contains_subquery, resolve_subqueries};
+use super::vm_error::VMError;
+use crate::backend::cursor::DBCursor;
+use crate::backend::database::Database;
+use crate::backend::row::Row;
+use crate::backend::table::Table;
+use crate::sql_compiler::expression::Expr;
+use crate::sql_compiler::{DeleteTokens, OrderingTerm, RowLimit};
+
+// Deletes the rows of a table the WHERE clause holds for, returning how many were deleted
+pub(super) fn process_delete(
+    delete_tokens: DeleteTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<u64, VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    delete_rows(delete_tokens, open_database)
+}
+
+fn delete_rows(delete_tokens: DeleteTokens, db: &Database) -> Result<u64, VMError> {
+    let DeleteTokens {
+        table_name,
+        where_clause,
+        order_by,
+        limit,
+    } = delete_tokens;
+    let write_error = |err| VMError::TableWriteError(table_name.to_string(), err);
+    let (table_db, _) = db.resolve(&table_name).map_err(write_error)?;
+    let table = db.table(&table_name).map_err(write_error)?;
+
+    let deleted = matching_rows(db, table, &table_name, where_clause)?;
+    let deleted = limit_rows(db, table, &table_name, deleted, order_by, limit)?;
+    for row in &deleted {
+        table
+            .delete(row.rowid())
+            .map_err(|err| write_error(err.into()))?;
+    }
+    check_deleted_references(table_db, table, &deleted)?;
+
+    Ok(deleted.len() as u64)
+}
+
+// Rows of the table the WHERE clause holds for, as they are stored. Every one is found before the
+// caller changes any, as changing a cell may move the others within its page under the cursor
+pub(super) fn matching_rows(
+    db: &Database,
+    table: &Table,
+    table_name: &str,
+    where_clause: Option<Expr>,
+) -> Result<Vec<Row>, VMError> {
+    let Some(((lower_bound, upper_bound), filter)) =
+        plan_table_scan(table, table_name, where_clause)
+    else {
+        return Ok(Vec::new());
+    };
+
+    let columns = table.columns.to_printable();
+    let mut cursor = DBCursor::with_bounds(table.root_page_num(), lower_bound, upper_bound);
+    let mut rows = Vec::new();
+    while let Some(row) = table
+        .next_row(&mut cursor)
+        .map_err(|err| VMError::TableReadError(table_name.to_string(), err.into()))?
+    {
+        if is_interrupted() {
+            return Err(VMError::Interrupted);
+        }
+        if let Some(filter) = &filter {
+            let decoded = table.columns.decode_row(row.clone());
+            let scope = Scope::new(&columns, decoded.attributes()).with_rowid(row.rowid());
+            let holds = match contains_subquery(filter) {
+                true => {
+                    evaluate_predicate(&resolve_subqueries(filter.clone(), db, &scope)?, &scope)?
+                }
+                false => evaluate_predicate(filter, &scope)?,
+            };
+            if !holds {
+                continue;
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+// Rows a LIMIT keeps out of those matched, after sorting them by the ORDER BY terms. Without
+// terms they are taken in rowid order, the one they are stored in
+pub(super) fn limit_rows(
+    db: &Database,
+    table: &Table,
+    table_name: &str,
+    rows: Vec<Row>,
+    order_by: Vec<OrderingTerm>,
+    limit: Option<RowLimit>,
+) -> Result<Vec<Row>, VMError> {
+    let Some(RowLimit { count, offset }) = limit else {
+        return Ok(rows);
+    };
+
+    // Each row is stood in for by its sort keys, under its position among the matched rows
+    let columns = table.columns.to_printable();
+    let terms: Vec<_> = order_by
+        .into_iter()
+        .map(|term| {
+            (
+                resolve_table_columns(term.expr, table, table_name),
+                term.descending,
+            )
+        })
+        .collect();
+    let mut keyed = Vec::with_capacity(rows.len());
+    for (idx, row) in rows.iter().enumerate() {
+        let decoded = table.columns.decode_row(row.clone());
+        let scope = Scope::new(&columns, decoded.attributes()).with_rowid(row.rowid());
+        let mut keys = Vec::with_capacity(terms.len());
+        for (expr, _) in &terms {
+            keys.push(match contains_subquery(expr) {
+                true => evaluate(&resolve_subqueries(expr.clone(), db, &scope)?, &scope)?,
+                false => evaluate(expr, &scope)?,
+            });
+        }
+        keyed.push(Row::new(idx as u64, keys));
+    }
+
+    let mut operator: BoxedOperator = Box::new(Values::new(Vec::new(), keyed));
+    if !terms.is_empty() {
+        let descending: Vec<bool> = terms.iter().map(|(_, descending)| *descending).collect();
+        let comparator = move |a: &Row, b: &Row| {
+            let keys = a.attributes().iter().zip(b.attributes());
+            for ((a, b), descending) in keys.zip(&descending) {
+                let ordering = match descending {
+                    true => compare_values(b, a),
+                    false => compare_values(a, b),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        };
+        operator = Box::new(Sort::new(operator, Box::new(comparator)));
+    }
+    let mut operator = Limit::new(operator, count, offset);
+
+    let mut rows: Vec<Option<Row>> = rows.into_iter().map(Some).collect();
+    let mut kept = Vec::new();
+    while let Some(keyed) = operator.next_row()? {
+        kept.extend(rows[keyed.rowid() as usize].take());
+    }
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Connection, SQLType};
+
+    // Table of (id, score) rows, with scores in another order than the ids
+    fn scored_table() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, score INT);")
+            .unwrap();
+        for (id, score) in [(1, 30), (2, 10), (3, 20), (4, 40)] {
+            conn.execute(&format!("INSERT INTO t VALUES ({}, {});", id, score))
+                .unwrap();
+        }
+        conn
+    }
+
+    fn ids(conn: &mut Connection) -> Vec<SQLType> {
+        conn.query("SELECT id FROM t;")
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect()
+    }
+
+    #[test]
+    fn delete_with_limit_takes_rows_in_rowid_order() {
+        let mut conn = scored_table();
+        conn.execute("DELETE FROM t WHERE id > 0 LIMIT 1;").unwrap();
+        assert_eq!(conn.changes(), 1);
+        assert_eq!(ids(&mut conn), [2, 3, 4].map(SQLType::Integer));
+    }
+
+    #[test]
+    fn delete_with_order_by_and_offset() {
+        let mut conn = scored_table();
+        conn.execute("DELETE FROM t ORDER BY score DESC LIMIT 2 OFFSET 1;")
+            .unwrap();
+        assert_eq!(conn.changes(), 2);
+        assert_eq!(ids(&mut conn), [2, 4].map(SQLType::Integer));
+    }
+
+    #[test]
+    fn update_with_order_by_and_limit() {
+        let mut conn = scored_table();
+        conn.execute("UPDATE t SET score = 0 WHERE id < 4 ORDER BY score LIMIT 2;")
+            .unwrap();
+        assert_eq!(conn.changes(), 2);
+        let zeroed: Vec<SQLType> = conn
+            .query("SELECT id FROM t WHERE score = 0;")
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect();
+        assert_eq!(zeroed, [2, 3].map(SQLType::Integer));
+    }
+
+    #[test]
+    fn order_by_requires_limit() {
+        let mut conn = scored_table();
+        assert!(conn.execute("DELETE FROM t ORDER BY id;").is_err());
+        assert_eq!(ids(&mut conn).len(), 4);
+    }
+}
@@ -0,0 +1,43 @@
+use super::constraints::check_dropped_references;
+use super::vm_error::VMError;
+use crate::backend::database::Database;
+use crate::sql_compiler::{DropIndexTokens, DropTokens};
+
+pub(super) fn process_drop(
+    drop_tokens: DropTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let DropTokens {
+        table_name,
+        if_exists,
+    } = drop_tokens;
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    let write_error = |err| VMError::TableWriteError(table_name.to_string(), err);
+    if if_exists && open_database.table(&table_name).is_err() {
+        return Ok(());
+    }
+
+    let (table_db, _) = open_database.resolve(&table_name).map_err(write_error)?;
+    let table = open_database.table(&table_name).map_err(write_error)?;
+    check_dropped_references(table_db, table)?;
+
+    open_database.drop_table(&table_name).map_err(write_error)
+}
+
+pub(super) fn process_drop_index(
+    drop_index_tokens: DropIndexTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let DropIndexTokens {
+        index_name,
+        if_exists,
+    } = drop_index_tokens;
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    let write_error = |err| VMError::IndexWriteError(index_name.to_string(), err);
+
+    let (index_db, unqualified_name) = open_database.resolve(&index_name).map_err(write_error)?;
+    if if_exists && index_db.index_table(unqualified_name).is_none() {
+        return Ok(());
+    }
+    open_database.drop_index(&index_name).map_err(write_error)
+}
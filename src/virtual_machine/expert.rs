@@ -0,0 +1,167 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use super::planner::{choose_index, constrained_columns};
+use super::program::{Instruction, ScanPlan};
+use super::result_writer::OutputSettings;
+use super::select::compile_select;
+use super::vm_error::VMError;
+use crate::backend::catalog::IndexEntry;
+use crate::backend::database::Database;
+use crate::sql_compiler::{format_statement, CreateIndexTokens, Statement};
+
+// Suggests an index for a SELECT based on the filter the planner could not turn into a seek,
+// followed by the plan the statement currently runs with
+pub(super) fn process_expert(
+    statement: Statement,
+    db_instance: Option<&mut Database>,
+    output_settings: &OutputSettings,
+) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    let Statement::Select(select_tokens) = statement else {
+        return Err(VMError::NotAnalyzable);
+    };
+    let program = compile_select(&select_tokens, open_database)?;
+
+    let scan = program
+        .instructions
+        .iter()
+        .find_map(|instruction| match instruction {
+            Instruction::OpenRead { scan, .. } => Some(scan),
+            _ => None,
+        });
+
+    let table_name = select_tokens.table_name.as_deref().unwrap_or_default();
+    let suggestion = scan.and_then(|scan| suggest_index(scan, table_name, open_database));
+
+    let mut out = output_settings
+        .destination()
+        .map_err(VMError::OutputError)?;
+    let mut write = |line: String| writeln!(out, "{}", line).map_err(VMError::OutputError);
+
+    write(suggestion.unwrap_or_else(|| "(no new indexes)".to_string()))?;
+    write(String::new())?;
+    match scan {
+        Some(scan) => write(format!("SCAN {}", scan)),
+        None => write("No rows can match, the table is never read".to_string()),
+    }
+}
+
+// CREATE INDEX statement for an index the scan could seek on instead of filtering every row.
+// Indexes are only sought by equality, so only the columns the filter compares for equality with
+// values they can hold go in it. None when the scan already reads an index
+fn suggest_index(scan: &ScanPlan, table_name: &str, db: &Database) -> Option<String> {
+    if scan.index_seek.is_some() {
+        return None;
+    }
+    let filter = scan.filter.as_ref()?;
+    let columns = &db.table(table_name).ok()?.columns;
+    let (equality_columns, _) = constrained_columns(filter.clone());
+    let index_columns: Vec<String> = equality_columns
+        .into_iter()
+        .filter(|column| {
+            let index = IndexEntry {
+                name: String::new(),
+                columns: vec![column.clone()],
+                root_page: 0,
+            };
+            choose_index(filter, columns, &[index]).is_some()
+        })
+        .collect();
+    if index_columns.is_empty() {
+        return None;
+    }
+
+    let (schema, table) = match table_name.split_once('.') {
+        Some((alias, table)) => (format!("{}.", alias), table),
+        None => (String::new(), table_name),
+    };
+    let statement = Statement::CreateIndex(CreateIndexTokens {
+        index_name: Cow::Owned(format!(
+            "{}{}_idx_{}",
+            schema,
+            table,
+            index_columns.join("_")
+        )),
+        table_name: Cow::Borrowed(table),
+        columns: index_columns.into_iter().map(Cow::Owned).collect(),
+        if_not_exists: false,
+    });
+    Some(format_statement(&statement))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::virtual_machine::result_writer::OutputSettings;
+    use crate::Connection;
+
+    // What .expert prints for a statement
+    fn expert(conn: &mut Connection, sql: &str) -> String {
+        let path = env::temp_dir().join(format!("sql_rs_expert_{}.txt", process::id()));
+        let output_settings = OutputSettings {
+            expert: true,
+            output_file: Some(fs::File::create(&path).unwrap()),
+            ..Default::default()
+        };
+        conn.execute_with_output(sql, &output_settings).unwrap();
+        let output = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        output
+    }
+
+    fn people() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE people (id INT PRIMARY KEY, name TEXT, `home city` TEXT, age INT);",
+        )
+        .unwrap();
+        for id in 0..50 {
+            conn.execute(&format!(
+                "INSERT INTO people VALUES ({id}, 'person {id}', '{}', {});",
+                ["Lima", "Oslo"][id % 2],
+                20 + id
+            ))
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn suggested_indexes_can_be_created_and_are_used() {
+        let mut conn = people();
+        let sql = "SELECT id FROM people WHERE `home city` = 'Oslo' AND age = 45 AND name = 5;";
+        let output = expert(&mut conn, sql);
+        let suggestion = output.lines().next().unwrap();
+        // A text column never equals a number, so an index on it would not help
+        assert_eq!(
+            suggestion,
+            "CREATE INDEX `people_idx_home city_age` ON people (`home city`, age);"
+        );
+        let rows = conn.query(sql).unwrap().count();
+
+        conn.execute(suggestion).unwrap();
+        let output = expert(&mut conn, sql);
+        assert!(output.starts_with("(no new indexes)\n"), "{output}");
+        assert!(
+            output.contains("SCAN people USING INDEX people_idx_home city_age (home city, age)"),
+            "{output}"
+        );
+        assert_eq!(conn.query(sql).unwrap().count(), rows);
+    }
+
+    #[test]
+    fn filters_an_index_cannot_seek_on_get_no_suggestion() {
+        let mut conn = people();
+        for sql in [
+            "SELECT id FROM people WHERE age > 30;",
+            "SELECT id FROM people WHERE id = 3 OR age = 30;",
+            "SELECT id FROM people;",
+        ] {
+            let output = expert(&mut conn, sql);
+            assert!(output.starts_with("(no new indexes)\n"), "{sql}: {output}");
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::io::Write;
+
+use super::insert::compile_insert;
+use super::result_writer::OutputSettings;
+use super::select::compile_select;
+use super::vm_error::VMError;
+use crate::backend::database::Database;
+use crate::sql_compiler::Statement;
+
+pub(super) fn process_explain(
+    statement: Statement,
+    db_instance: Option<&mut Database>,
+    output_settings: &OutputSettings,
+) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    let program = match statement {
+        Statement::Select(select_tokens) => compile_select(&select_tokens, open_database)?,
+        Statement::Insert(insert_tokens) => compile_insert(insert_tokens, open_database)?,
+        _ => return Err(VMError::NotExplainable),
+    };
+
+    let mut out = output_settings
+        .destination()
+        .map_err(VMError::OutputError)?;
+    writeln!(out, "{}", program).map_err(VMError::OutputError)?;
+
+    Ok(())
+}
@@ -0,0 +1,570 @@
+use std::cmp::Ordering;
+
+use thiserror::Error;
+
+use crate::backend::columns::ROWID;
+use crate::backend::row::SQLType;
+use crate::sql_compiler::expression::{BinaryOperator, Expr, UnaryOperator};
+
+mod aggregate;
+mod functions;
+mod pattern;
+
+pub use aggregate::Aggregate;
+pub(crate) use functions::is_deterministic;
+use pattern::{glob_matches, Regex};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ExprError {
+    #[error("Column {0} not in table")]
+    UnknownColumn(String),
+    #[error("Unknown function: {0}")]
+    UnknownFunction(String),
+    #[error("Wrong number of arguments to function {0}(): expected {1}, got {2}")]
+    WrongNumberOfArguments(String, String, usize),
+    #[error("Cannot apply operator {0} to values {1} and {2}")]
+    TypeMismatch(String, String, String),
+    #[error("Integer overflow when evaluating expression")]
+    IntegerOverflow,
+    #[error("Malformed JSON: {0}")]
+    MalformedJson(String),
+    #[error("Invalid JSON path: {0}")]
+    InvalidJsonPath(String),
+    #[error("Invalid regular expression {0}: {1}")]
+    InvalidRegex(String, String),
+    #[error("Aggregate function {0}() can only be used on its own in the select list")]
+    MisusedAggregate(String),
+    #[error("Subqueries are only supported in the select list")]
+    UnsupportedSubquery,
+}
+
+// Column values visible to an expression, addressed by column name
+pub struct Scope<'a> {
+    pub columns: &'a [String],
+    pub values: &'a [SQLType],
+    // Key of the row, readable through the hidden rowid column
+    pub rowid: Option<u64>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(columns: &'a [String], values: &'a [SQLType]) -> Self {
+        Self {
+            columns,
+            values,
+            rowid: None,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(&[], &[])
+    }
+
+    pub fn with_rowid(self, rowid: u64) -> Self {
+        Self {
+            rowid: Some(rowid),
+            ..self
+        }
+    }
+
+    pub(super) fn lookup(&self, name: &str) -> Result<SQLType, ExprError> {
+        let value = self
+            .columns
+            .iter()
+            .position(|column| column == name)
+            .and_then(|idx| self.values.get(idx))
+            .cloned();
+        match (value, self.rowid) {
+            (Some(value), _) => Ok(value),
+            (None, Some(rowid)) if name == ROWID => Ok(SQLType::UBigInt(rowid)),
+            _ => Err(ExprError::UnknownColumn(name.to_string())),
+        }
+    }
+}
+
+pub(super) fn as_integer(value: &SQLType) -> Option<i128> {
+    match value {
+        SQLType::Integer(num) => Some(*num as i128),
+        SQLType::UBigInt(num) => Some(*num as i128),
+        SQLType::BigInt(num) => Some(*num as i128),
+        SQLType::SmallInt(num) => Some(*num as i128),
+        _ => None,
+    }
+}
+
+// Integer results take the narrowest integer representation that can hold them
+pub(super) fn from_integer(num: i128) -> Result<SQLType, ExprError> {
+    if let Ok(num) = i32::try_from(num) {
+        Ok(SQLType::Integer(num))
+    } else if let Ok(num) = u64::try_from(num) {
+        Ok(SQLType::UBigInt(num))
+    } else if let Ok(num) = i64::try_from(num) {
+        Ok(SQLType::BigInt(num))
+    } else {
+        Err(ExprError::IntegerOverflow)
+    }
+}
+
+pub(super) fn from_bool(value: bool) -> SQLType {
+    SQLType::Integer(value as i32)
+}
+
+// SQL truthiness: NULL is neither true nor false, so it is reported as None
+pub fn truth_value(value: &SQLType) -> Option<bool> {
+    match value {
+        SQLType::Null => None,
+        SQLType::Text(s) => Some(s.trim().parse::<i128>().is_ok_and(|num| num != 0)),
+        other => Some(as_integer(other) != Some(0)),
+    }
+}
+
+// Orders values as NULL < numbers < text, comparing numbers by value regardless of width
+pub fn compare_values(left: &SQLType, right: &SQLType) -> Ordering {
+    fn type_rank(value: &SQLType) -> u8 {
+        match value {
+            SQLType::Null => 0,
+            SQLType::Integer(_)
+            | SQLType::UBigInt(_)
+            | SQLType::BigInt(_)
+            | SQLType::SmallInt(_) => 1,
+            SQLType::Text(_) | SQLType::Uuid(_) => 2,
+        }
+    }
+
+    match (left, right) {
+        (SQLType::Text(left), SQLType::Text(right)) => left.cmp(right),
+        (SQLType::Uuid(left), SQLType::Uuid(right)) => left.cmp(right),
+        // The hyphenated form sorts like the bytes it encodes, so UUIDs compare with text by it
+        (SQLType::Uuid(left), SQLType::Text(right)) => left.to_string().cmp(&right.to_lowercase()),
+        (SQLType::Text(left), SQLType::Uuid(right)) => left.to_lowercase().cmp(&right.to_string()),
+        _ => match (as_integer(left), as_integer(right)) {
+            (Some(left), Some(right)) => left.cmp(&right),
+            _ => type_rank(left).cmp(&type_rank(right)),
+        },
+    }
+}
+
+fn evaluate_unary(operator: UnaryOperator, operand: SQLType) -> Result<SQLType, ExprError> {
+    match (operator, operand) {
+        (_, SQLType::Null) => Ok(SQLType::Null),
+        (UnaryOperator::Not, operand) => Ok(truth_value(&operand)
+            .map(|truth| from_bool(!truth))
+            .unwrap_or(SQLType::Null)),
+        (UnaryOperator::Minus, operand) => match as_integer(&operand) {
+            Some(num) => from_integer(-num),
+            None => Err(ExprError::TypeMismatch(
+                "-".to_string(),
+                operand.to_string(),
+                String::new(),
+            )),
+        },
+    }
+}
+
+fn evaluate_logical(
+    operator: BinaryOperator,
+    left: &SQLType,
+    right: &SQLType,
+) -> Result<SQLType, ExprError> {
+    let (left, right) = (truth_value(left), truth_value(right));
+    let result = match operator {
+        BinaryOperator::And => match (left, right) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        },
+        _ => match (left, right) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        },
+    };
+    Ok(result.map(from_bool).unwrap_or(SQLType::Null))
+}
+
+fn evaluate_binary(
+    operator: BinaryOperator,
+    left: SQLType,
+    right: SQLType,
+) -> Result<SQLType, ExprError> {
+    if matches!(operator, BinaryOperator::And | BinaryOperator::Or) {
+        return evaluate_logical(operator, &left, &right);
+    }
+    if left == SQLType::Null || right == SQLType::Null {
+        return Ok(SQLType::Null);
+    }
+
+    let ordering = || compare_values(&left, &right);
+    match operator {
+        BinaryOperator::Equal => return Ok(from_bool(ordering().is_eq())),
+        BinaryOperator::NotEqual => return Ok(from_bool(ordering().is_ne())),
+        BinaryOperator::Less => return Ok(from_bool(ordering().is_lt())),
+        BinaryOperator::LessOrEqual => return Ok(from_bool(ordering().is_le())),
+        BinaryOperator::Greater => return Ok(from_bool(ordering().is_gt())),
+        BinaryOperator::GreaterOrEqual => return Ok(from_bool(ordering().is_ge())),
+        BinaryOperator::Concat => return Ok(SQLType::Text(format!("{}{}", left, right))),
+        // The pattern is the right operand
+        BinaryOperator::Glob => {
+            return Ok(from_bool(glob_matches(
+                &right.to_string(),
+                &left.to_string(),
+            )))
+        }
+        BinaryOperator::Regexp => {
+            let pattern = right.to_string();
+            let regex = Regex::new(&pattern)
+                .map_err(|reason| ExprError::InvalidRegex(pattern.clone(), reason))?;
+            return Ok(from_bool(regex.is_match(&left.to_string())));
+        }
+        _ => {}
+    }
+
+    let (Some(left_num), Some(right_num)) = (as_integer(&left), as_integer(&right)) else {
+        return Err(ExprError::TypeMismatch(
+            operator.to_string(),
+            left.to_string(),
+            right.to_string(),
+        ));
+    };
+
+    match operator {
+        BinaryOperator::Add => from_integer(left_num + right_num),
+        BinaryOperator::Subtract => from_integer(left_num - right_num),
+        BinaryOperator::Multiply => from_integer(
+            left_num
+                .checked_mul(right_num)
+                .ok_or(ExprError::IntegerOverflow)?,
+        ),
+        // Like SQLite, division by zero yields NULL instead of an error
+        BinaryOperator::Divide if right_num == 0 => Ok(SQLType::Null),
+        BinaryOperator::Modulo if right_num == 0 => Ok(SQLType::Null),
+        BinaryOperator::Divide => from_integer(left_num / right_num),
+        BinaryOperator::Modulo => from_integer(left_num % right_num),
+        _ => unreachable!(),
+    }
+}
+
+pub fn evaluate(expr: &Expr, scope: &Scope) -> Result<SQLType, ExprError> {
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Column(name) => scope.lookup(name),
+        Expr::Unary(operator, operand) => evaluate_unary(*operator, evaluate(operand, scope)?),
+        Expr::Binary(left, operator, right) => {
+            evaluate_binary(*operator, evaluate(left, scope)?, evaluate(right, scope)?)
+        }
+        Expr::IsNull { expr, negated } => {
+            let is_null = evaluate(expr, scope)? == SQLType::Null;
+            Ok(from_bool(is_null != *negated))
+        }
+        Expr::Function { name, args } => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            functions::call(name, args)
+        }
+        // The interpreter runs subqueries and replaces them by their value before evaluating
+        Expr::Subquery(_) => Err(ExprError::UnsupportedSubquery),
+    }
+}
+
+// Evaluates a predicate, treating NULL as false as WHERE clauses do
+pub fn evaluate_predicate(expr: &Expr, scope: &Scope) -> Result<bool, ExprError> {
+    Ok(truth_value(&evaluate(expr, scope)?).unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(num: i32) -> Expr {
+        Expr::Literal(SQLType::Integer(num))
+    }
+
+    fn text(s: &str) -> Expr {
+        Expr::Literal(SQLType::Text(s.to_string()))
+    }
+
+    fn null() -> Expr {
+        Expr::Literal(SQLType::Null)
+    }
+
+    fn binary(left: Expr, operator: BinaryOperator, right: Expr) -> Expr {
+        Expr::Binary(Box::new(left), operator, Box::new(right))
+    }
+
+    fn eval(expr: &Expr) -> Result<SQLType, ExprError> {
+        evaluate(expr, &Scope::empty())
+    }
+
+    #[test]
+    fn arithmetic() {
+        let expr = binary(
+            int(2),
+            BinaryOperator::Add,
+            binary(int(3), BinaryOperator::Multiply, int(4)),
+        );
+        assert_eq!(eval(&expr), Ok(SQLType::Integer(14)));
+        assert_eq!(
+            eval(&binary(int(7), BinaryOperator::Modulo, int(4))),
+            Ok(SQLType::Integer(3))
+        );
+        assert_eq!(
+            eval(&Expr::Unary(UnaryOperator::Minus, Box::new(int(5)))),
+            Ok(SQLType::Integer(-5))
+        );
+    }
+
+    #[test]
+    fn arithmetic_widens_to_ubigint() {
+        let expr = binary(int(i32::MAX), BinaryOperator::Add, int(1));
+        assert_eq!(eval(&expr), Ok(SQLType::UBigInt(i32::MAX as u64 + 1)));
+    }
+
+    #[test]
+    fn arithmetic_overflow() {
+        let expr = binary(
+            Expr::Literal(SQLType::UBigInt(u64::MAX)),
+            BinaryOperator::Add,
+            int(1),
+        );
+        assert_eq!(eval(&expr), Err(ExprError::IntegerOverflow));
+    }
+
+    #[test]
+    fn division_by_zero_is_null() {
+        assert_eq!(
+            eval(&binary(int(1), BinaryOperator::Divide, int(0))),
+            Ok(SQLType::Null)
+        );
+    }
+
+    #[test]
+    fn arithmetic_on_text_is_an_error() {
+        let result = eval(&binary(text("a"), BinaryOperator::Add, int(1)));
+        assert!(matches!(result, Err(ExprError::TypeMismatch(..))));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            eval(&binary(int(1), BinaryOperator::Less, int(2))),
+            Ok(SQLType::Integer(1))
+        );
+        assert_eq!(
+            eval(&binary(
+                Expr::Literal(SQLType::UBigInt(3)),
+                BinaryOperator::Equal,
+                int(3)
+            )),
+            Ok(SQLType::Integer(1))
+        );
+        assert_eq!(
+            eval(&binary(text("abc"), BinaryOperator::Greater, text("abd"))),
+            Ok(SQLType::Integer(0))
+        );
+        assert_eq!(
+            eval(&binary(int(10), BinaryOperator::Less, text("1"))),
+            Ok(SQLType::Integer(1))
+        );
+    }
+
+    #[test]
+    fn null_propagation() {
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::Add, int(1))),
+            Ok(SQLType::Null)
+        );
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::Equal, null())),
+            Ok(SQLType::Null)
+        );
+        assert_eq!(
+            eval(&Expr::Unary(UnaryOperator::Not, Box::new(null()))),
+            Ok(SQLType::Null)
+        );
+    }
+
+    #[test]
+    fn three_valued_logic() {
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::And, int(0))),
+            Ok(SQLType::Integer(0))
+        );
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::And, int(1))),
+            Ok(SQLType::Null)
+        );
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::Or, int(1))),
+            Ok(SQLType::Integer(1))
+        );
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::Or, int(0))),
+            Ok(SQLType::Null)
+        );
+    }
+
+    #[test]
+    fn is_null() {
+        let expr = Expr::IsNull {
+            expr: Box::new(null()),
+            negated: false,
+        };
+        assert_eq!(eval(&expr), Ok(SQLType::Integer(1)));
+        let expr = Expr::IsNull {
+            expr: Box::new(int(1)),
+            negated: true,
+        };
+        assert_eq!(eval(&expr), Ok(SQLType::Integer(1)));
+    }
+
+    #[test]
+    fn column_references() {
+        let columns = vec!["age".to_string(), "name".to_string()];
+        let values = vec![SQLType::Integer(30), SQLType::Text("bob".to_string())];
+        let scope = Scope::new(&columns, &values);
+
+        let expr = binary(
+            Expr::Column("age".to_string()),
+            BinaryOperator::GreaterOrEqual,
+            int(18),
+        );
+        assert_eq!(evaluate_predicate(&expr, &scope), Ok(true));
+        assert_eq!(
+            evaluate(&Expr::Column("email".to_string()), &scope),
+            Err(ExprError::UnknownColumn("email".to_string()))
+        );
+    }
+
+    #[test]
+    fn predicate_treats_null_as_false() {
+        assert_eq!(evaluate_predicate(&null(), &Scope::empty()), Ok(false));
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(
+            eval(&binary(text("a"), BinaryOperator::Concat, int(1))),
+            Ok(SQLType::Text("a1".to_string()))
+        );
+    }
+
+    #[test]
+    fn functions() {
+        let call = |name: &str, args: Vec<Expr>| {
+            eval(&Expr::Function {
+                name: name.to_string(),
+                args,
+            })
+        };
+        assert_eq!(
+            call("upper", vec![text("abc")]),
+            Ok(SQLType::Text("ABC".to_string()))
+        );
+        assert_eq!(call("length", vec![text("abc")]), Ok(SQLType::Integer(3)));
+        assert_eq!(
+            call("coalesce", vec![null(), int(2), int(3)]),
+            Ok(SQLType::Integer(2))
+        );
+        assert_eq!(call("upper", vec![null()]), Ok(SQLType::Null));
+        assert_eq!(
+            call("nope", vec![]),
+            Err(ExprError::UnknownFunction("nope".to_string()))
+        );
+        assert!(matches!(
+            call("lower", vec![]),
+            Err(ExprError::WrongNumberOfArguments(..))
+        ));
+        assert_eq!(call("abs", vec![int(-4)]), Ok(SQLType::Integer(4)));
+        assert_eq!(call("round", vec![text("2.5")]), Ok(SQLType::Integer(3)));
+        assert_eq!(
+            call("round", vec![text("3.14159"), int(2)]),
+            Ok(SQLType::Text("3.14".to_string()))
+        );
+        assert_eq!(
+            call("max", vec![int(1), int(3), int(2)]),
+            Ok(SQLType::Integer(3))
+        );
+        assert_eq!(call("min", vec![int(1), null()]), Ok(SQLType::Null));
+        assert_eq!(
+            call("min", vec![int(1)]),
+            Err(ExprError::MisusedAggregate("min".to_string()))
+        );
+    }
+
+    #[test]
+    fn pattern_matching() {
+        let glob =
+            |s: &str, pattern: &str| eval(&binary(text(s), BinaryOperator::Glob, text(pattern)));
+        assert_eq!(glob("abc", "a*"), Ok(SQLType::Integer(1)));
+        assert_eq!(glob("abc", "A*"), Ok(SQLType::Integer(0)));
+        assert_eq!(glob("abc", "?[a-c]c"), Ok(SQLType::Integer(1)));
+        assert_eq!(glob("abc", "[^a]*"), Ok(SQLType::Integer(0)));
+
+        let regexp =
+            |s: &str, pattern: &str| eval(&binary(text(s), BinaryOperator::Regexp, text(pattern)));
+        assert_eq!(regexp("hello world", "o w"), Ok(SQLType::Integer(1)));
+        assert_eq!(regexp("hello", "^h(e|a)l+o$"), Ok(SQLType::Integer(1)));
+        assert_eq!(regexp("a1b2", r"^(\w\d){2}$"), Ok(SQLType::Integer(1)));
+        assert_eq!(regexp("abc", "^[0-9]+$"), Ok(SQLType::Integer(0)));
+        assert_eq!(regexp("ab", "^(a|b)*c?$"), Ok(SQLType::Integer(1)));
+        assert_eq!(regexp("aaa", "^a{1,2}$"), Ok(SQLType::Integer(0)));
+        assert_eq!(regexp("", "(a*)*"), Ok(SQLType::Integer(1)));
+        assert!(matches!(
+            regexp("abc", "(a"),
+            Err(ExprError::InvalidRegex(..))
+        ));
+        assert!(matches!(
+            regexp("abc", "a{2,1}"),
+            Err(ExprError::InvalidRegex(..))
+        ));
+        assert!(matches!(
+            regexp("abc", "(a{1000}){1000}"),
+            Err(ExprError::InvalidRegex(..))
+        ));
+
+        // Patterns that make backtracking matchers take exponential time
+        let long = format!("{}b", "a".repeat(5000));
+        assert_eq!(regexp(&long, "(a*)*c"), Ok(SQLType::Integer(0)));
+        assert_eq!(regexp(&long, "^(a|aa)+$"), Ok(SQLType::Integer(0)));
+        assert_eq!(glob(&long, &"*a".repeat(20)), Ok(SQLType::Integer(0)));
+        assert_eq!(
+            eval(&binary(null(), BinaryOperator::Regexp, text("a"))),
+            Ok(SQLType::Null)
+        );
+    }
+
+    #[test]
+    fn aggregates() {
+        let fold = |aggregate: Aggregate, values: Vec<Option<SQLType>>| {
+            values
+                .into_iter()
+                .try_fold(aggregate.initial(), |accumulator, value| {
+                    aggregate.step(accumulator, value)
+                })
+        };
+        let values = || {
+            vec![
+                Some(SQLType::Integer(3)),
+                Some(SQLType::Null),
+                Some(SQLType::Integer(1)),
+            ]
+        };
+        assert_eq!(fold(Aggregate::Count, values()), Ok(SQLType::Integer(2)));
+        assert_eq!(
+            fold(Aggregate::Count, vec![None, None]),
+            Ok(SQLType::Integer(2))
+        );
+        assert_eq!(fold(Aggregate::Sum, values()), Ok(SQLType::Integer(4)));
+        assert_eq!(fold(Aggregate::Min, values()), Ok(SQLType::Integer(1)));
+        assert_eq!(fold(Aggregate::Max, values()), Ok(SQLType::Integer(3)));
+        assert_eq!(fold(Aggregate::Sum, vec![]), Ok(SQLType::Null));
+        assert_eq!(
+            eval(&Expr::Function {
+                name: "count".to_string(),
+                args: vec![],
+            }),
+            Err(ExprError::MisusedAggregate("count".to_string()))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::cmp::Ordering;
+
+use super::{as_integer, compare_values, from_integer, ExprError};
+use crate::backend::row::SQLType;
+
+// Function folding the values of a column over every row into a single value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Aggregate::Count),
+            "sum" => Some(Aggregate::Sum),
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            _ => None,
+        }
+    }
+
+    // min() and max() with several arguments are the scalar functions of the same name instead
+    pub fn from_call(name: &str, num_args: usize) -> Option<Self> {
+        match Self::from_name(name)? {
+            Aggregate::Min | Aggregate::Max if num_args > 1 => None,
+            aggregate => Some(aggregate),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+
+    // Number of arguments taken, from which count() also allows none to count every row
+    pub fn check_arity(&self, num_args: usize) -> Result<(), ExprError> {
+        match (self, num_args) {
+            (_, 1) | (Aggregate::Count, 0) => Ok(()),
+            (Aggregate::Count, _) => Err(ExprError::WrongNumberOfArguments(
+                self.name().to_string(),
+                "0 or 1".to_string(),
+                num_args,
+            )),
+            _ => Err(ExprError::WrongNumberOfArguments(
+                self.name().to_string(),
+                "1".to_string(),
+                num_args,
+            )),
+        }
+    }
+
+    // Value over no rows
+    pub fn initial(&self) -> SQLType {
+        match self {
+            Aggregate::Count => SQLType::Integer(0),
+            _ => SQLType::Null,
+        }
+    }
+
+    // Folds the value of the next row into the accumulated one. NULL values are skipped, while
+    // count() without arguments receives no value and counts every row
+    pub fn step(&self, accumulator: SQLType, value: Option<SQLType>) -> Result<SQLType, ExprError> {
+        let value = match value {
+            Some(SQLType::Null) => return Ok(accumulator),
+            Some(value) => value,
+            None => SQLType::Null,
+        };
+        match (self, accumulator) {
+            (Aggregate::Count, count) => from_integer(as_integer(&count).unwrap_or(0) + 1),
+            (_, SQLType::Null) if *self != Aggregate::Sum => Ok(value),
+            (Aggregate::Sum, sum) => {
+                let Some(num) = as_integer(&value) else {
+                    return Err(ExprError::TypeMismatch(
+                        self.name().to_string(),
+                        sum.to_string(),
+                        value.to_string(),
+                    ));
+                };
+                from_integer(as_integer(&sum).unwrap_or(0) + num)
+            }
+            (Aggregate::Min, min) if compare_values(&value, &min) == Ordering::Less => Ok(value),
+            (Aggregate::Max, max) if compare_values(&value, &max) == Ordering::Greater => Ok(value),
+            (_, accumulator) => Ok(accumulator),
+        }
+    }
+}
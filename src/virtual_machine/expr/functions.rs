@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use super::{as_integer, compare_values, from_integer, Aggregate, ExprError};
+use crate::backend::row::SQLType;
+use crate::backend::uuid::Uuid;
+use crate::json::JsonValue;
+
+fn check_arity(name: &str, args: &[SQLType], expected: usize) -> Result<(), ExprError> {
+    if args.len() != expected {
+        return Err(ExprError::WrongNumberOfArguments(
+            name.to_string(),
+            expected.to_string(),
+            args.len(),
+        ));
+    }
+    Ok(())
+}
+
+fn map_text(
+    name: &str,
+    args: Vec<SQLType>,
+    f: impl Fn(&str) -> SQLType,
+) -> Result<SQLType, ExprError> {
+    check_arity(name, &args, 1)?;
+    match &args[0] {
+        SQLType::Null => Ok(SQLType::Null),
+        SQLType::Text(s) => Ok(f(s)),
+        other => Ok(f(&other.to_string())),
+    }
+}
+
+// Scalars become the matching SQL value, while arrays and objects are returned as JSON text
+fn from_json(value: &JsonValue) -> SQLType {
+    match value {
+        JsonValue::Null => SQLType::Null,
+        JsonValue::Bool(value) => SQLType::Integer(*value as i32),
+        JsonValue::Number(num) => {
+            from_integer(*num).unwrap_or_else(|_| SQLType::Text(num.to_string()))
+        }
+        JsonValue::String(s) => SQLType::Text(s.clone()),
+        other => SQLType::Text(other.to_string()),
+    }
+}
+
+fn json_extract(document: &SQLType, path: &SQLType) -> Result<SQLType, ExprError> {
+    if *document == SQLType::Null || *path == SQLType::Null {
+        return Ok(SQLType::Null);
+    }
+    let (document, path) = (document.to_string(), path.to_string());
+    let document = document
+        .parse::<JsonValue>()
+        .map_err(|_| ExprError::MalformedJson(document))?;
+    let value = document
+        .extract(&path)
+        .map_err(|_| ExprError::InvalidJsonPath(path))?;
+    Ok(value.map_or(SQLType::Null, from_json))
+}
+
+fn abs(value: &SQLType) -> Result<SQLType, ExprError> {
+    match (value, as_integer(value)) {
+        (SQLType::Null, _) => Ok(SQLType::Null),
+        (_, Some(num)) => from_integer(num.abs()),
+        _ => Err(ExprError::TypeMismatch(
+            "abs".to_string(),
+            value.to_string(),
+            String::new(),
+        )),
+    }
+}
+
+// Integers have no fractional part to round. As there is no floating point type, decimals can
+// only come as text: they round to an integer, or to text with the given number of digits
+fn round(value: &SQLType, digits: Option<&SQLType>) -> Result<SQLType, ExprError> {
+    let digits = match digits {
+        None => 0,
+        Some(SQLType::Null) => return Ok(SQLType::Null),
+        Some(digits) => as_integer(digits).ok_or_else(|| {
+            ExprError::TypeMismatch("round".to_string(), value.to_string(), digits.to_string())
+        })?,
+    };
+    let mismatch =
+        || ExprError::TypeMismatch("round".to_string(), value.to_string(), digits.to_string());
+    match value {
+        SQLType::Null => Ok(SQLType::Null),
+        SQLType::Text(s) => {
+            let num = s.trim().parse::<f64>().map_err(|_| mismatch())?;
+            if digits <= 0 {
+                return from_integer(num.round() as i128);
+            }
+            let precision = digits.min(f64::DIGITS as i128) as usize;
+            Ok(SQLType::Text(format!("{:.*}", precision, num)))
+        }
+        other if as_integer(other).is_some() => Ok(other.clone()),
+        _ => Err(mismatch()),
+    }
+}
+
+// Scalar min() and max() with two or more arguments. Any NULL argument makes the result NULL
+fn extreme(name: &str, args: Vec<SQLType>, keep: Ordering) -> Result<SQLType, ExprError> {
+    if args.len() < 2 {
+        return Err(ExprError::WrongNumberOfArguments(
+            name.to_string(),
+            "at least 2".to_string(),
+            args.len(),
+        ));
+    }
+    if args.contains(&SQLType::Null) {
+        return Ok(SQLType::Null);
+    }
+    Ok(args
+        .into_iter()
+        .reduce(|best, arg| {
+            if compare_values(&arg, &best) == keep {
+                arg
+            } else {
+                best
+            }
+        })
+        .unwrap_or(SQLType::Null))
+}
+
+// Functions whose result only depends on their arguments, so calls with constant arguments can be
+// evaluated once at compile time
+pub(crate) fn is_deterministic(name: &str) -> bool {
+    !matches!(name, "uuid" | "random")
+}
+
+pub(super) fn call(name: &str, args: Vec<SQLType>) -> Result<SQLType, ExprError> {
+    match name {
+        "upper" => map_text(name, args, |s| SQLType::Text(s.to_uppercase())),
+        "lower" => map_text(name, args, |s| SQLType::Text(s.to_lowercase())),
+        "length" => map_text(name, args, |s| {
+            SQLType::Integer(s.chars().count().try_into().unwrap_or(i32::MAX))
+        }),
+        "abs" => {
+            check_arity(name, &args, 1)?;
+            abs(&args[0])
+        }
+        "round" => match args.len() {
+            1 | 2 => round(&args[0], args.get(1)),
+            num_args => Err(ExprError::WrongNumberOfArguments(
+                name.to_string(),
+                "1 or 2".to_string(),
+                num_args,
+            )),
+        },
+        // Any 64-bit integer. Every hasher built by RandomState has new keys, so hashing nothing
+        // gives a new value each time
+        "random" => {
+            check_arity(name, &args, 0)?;
+            let num = RandomState::new().build_hasher().finish() as i64;
+            from_integer(num as i128)
+        }
+        "min" | "max" if Aggregate::from_call(name, args.len()).is_none() => {
+            let keep = match name {
+                "min" => Ordering::Less,
+                _ => Ordering::Greater,
+            };
+            extreme(name, args, keep)
+        }
+        "uuid" => {
+            check_arity(name, &args, 0)?;
+            Ok(SQLType::Uuid(Uuid::new_v4()))
+        }
+        "json_extract" => {
+            check_arity(name, &args, 2)?;
+            json_extract(&args[0], &args[1])
+        }
+        "coalesce" => {
+            if args.len() < 2 {
+                return Err(ExprError::WrongNumberOfArguments(
+                    name.to_string(),
+                    "at least 2".to_string(),
+                    args.len(),
+                ));
+            }
+            Ok(args
+                .into_iter()
+                .find(|arg| *arg != SQLType::Null)
+                .unwrap_or(SQLType::Null))
+        }
+        name if Aggregate::from_name(name).is_some() => {
+            Err(ExprError::MisusedAggregate(name.to_string()))
+        }
+        _ => Err(ExprError::UnknownFunction(name.to_string())),
+    }
+}
@@ -0,0 +1,423 @@
+use std::mem;
+
+// Set of characters matched by a bracket expression, e.g. `[a-z_]` or `[^0-9]`
+#[derive(Debug, Clone, PartialEq)]
+struct CharClass {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        let in_ranges = self
+            .ranges
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&c));
+        in_ranges != self.negated
+    }
+
+    // Parses the part after the opening bracket up to the closing one, which is taken literally
+    // when it comes first. Returns the class and the number of characters consumed
+    fn parse(chars: &[char], escapes: bool) -> Option<(Self, usize)> {
+        let negated = chars.first() == Some(&'^');
+        let mut idx = negated as usize;
+        let mut ranges = Vec::new();
+        loop {
+            let c = match chars.get(idx)? {
+                ']' if idx > negated as usize => return Some((Self { ranges, negated }, idx + 1)),
+                '\\' if escapes => {
+                    idx += 1;
+                    let escaped = *chars.get(idx)?;
+                    if let Some(class) = shorthand_class(escaped).filter(|class| !class.negated) {
+                        ranges.extend(class.ranges);
+                        idx += 1;
+                        continue;
+                    }
+                    escaped
+                }
+                c => *c,
+            };
+            idx += 1;
+            match (chars.get(idx), chars.get(idx + 1)) {
+                (Some('-'), Some(last)) if *last != ']' => {
+                    ranges.push((c, *last));
+                    idx += 2;
+                }
+                _ => ranges.push((c, c)),
+            }
+        }
+    }
+}
+
+// Classes written as `\d`, `\w` and `\s`, and their negations in upper case
+fn shorthand_class(c: char) -> Option<CharClass> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\r')],
+        _ => return None,
+    };
+    Some(CharClass {
+        ranges,
+        negated: c.is_ascii_uppercase(),
+    })
+}
+
+// Case-sensitive match of the whole text against a GLOB pattern, where `*` matches any sequence
+// of characters, `?` any single character and `[...]` any character of a class
+pub(super) fn glob_matches(pattern: &str, text: &str) -> bool {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut nodes = vec![Node::Start];
+    let mut idx = 0;
+    while let Some(&c) = chars.get(idx) {
+        idx += 1;
+        nodes.push(match c {
+            '*' => Node::Repeat {
+                node: Box::new(Node::Any),
+                min: 0,
+                max: None,
+            },
+            '?' => Node::Any,
+            '[' => match CharClass::parse(&chars[idx..], false) {
+                Some((class, len)) => {
+                    idx += len;
+                    Node::Class(class)
+                }
+                // An unterminated bracket is an ordinary character
+                None => Node::Char(c),
+            },
+            c => Node::Char(c),
+        });
+    }
+    nodes.push(Node::End);
+
+    // The program grows with the pattern alone, as globs have no bounded repetitions
+    let mut compiler = Compiler::new(usize::MAX);
+    match compiler.compile_alternatives(&[nodes]) {
+        Ok(()) => compiler.finish().is_match(text),
+        Err(_) => unreachable!("glob patterns have no size limit"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Char(char),
+    Any,
+    Class(CharClass),
+    Start,
+    End,
+    // Alternatives separated by `|`
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+// Regular expression supporting literals, `.`, classes, anchors, groups, alternation and the
+// quantifiers `*`, `+`, `?` and `{m,n}`. It is compiled to a Thompson NFA, whose threads all
+// advance one character at a time, so matching takes time linear in the length of the text
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Regex {
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut idx = 0;
+        let alternatives = parse_alternatives(&chars, &mut idx)?;
+        if let Some(c) = chars.get(idx) {
+            return Err(format!("unexpected {} at position {}", c, idx));
+        }
+
+        let mut compiler = Compiler::new(MAX_PROGRAM_LEN);
+        compiler.compile_alternatives(&alternatives)?;
+        Ok(compiler.finish())
+    }
+
+    // Whether the pattern matches some part of the text. Anchor it with `^` and `$` to match
+    // the whole text
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        for pos in 0..=text.len() {
+            // A match may start at any position
+            if self.add_thread(&mut current, 0, pos, text.len()) {
+                return true;
+            }
+            let Some(&c) = text.get(pos) else {
+                break;
+            };
+            next.clear();
+            for &pc in &current.pcs {
+                let matched = match &self.program[pc] {
+                    Inst::Char(expected) => *expected == c,
+                    Inst::Any => true,
+                    Inst::Class(class) => class.contains(c),
+                    _ => false,
+                };
+                if matched && self.add_thread(&mut next, pc + 1, pos + 1, text.len()) {
+                    return true;
+                }
+            }
+            mem::swap(&mut current, &mut next);
+        }
+        false
+    }
+
+    // Adds the thread at `pc` to the set, following jumps, splits and the anchors that hold at
+    // `pos`, so only threads waiting for a character are left to advance. Every instruction is
+    // added once per position, which keeps loops that match nothing such as `(a*)*` finite.
+    // Returns whether a thread reached the end of the pattern
+    fn add_thread(&self, threads: &mut Threads, pc: usize, pos: usize, len: usize) -> bool {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if !threads.insert(pc) {
+                continue;
+            }
+            match &self.program[pc] {
+                Inst::Jump(target) => stack.push(*target),
+                Inst::Split(first, second) => stack.extend([*second, *first]),
+                Inst::Start if pos == 0 => stack.push(pc + 1),
+                Inst::End if pos == len => stack.push(pc + 1),
+                Inst::Match => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+// Instructions of a compiled pattern
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(CharClass),
+    Start,
+    End,
+    // Continues at both addresses
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+// Bounded repetitions copy what they repeat, so nesting them as in `(a{1000}){1000}` could make
+// programs of any size
+const MAX_PROGRAM_LEN: usize = 10_000;
+
+struct Compiler {
+    program: Vec<Inst>,
+    max_len: usize,
+}
+
+impl Compiler {
+    fn new(max_len: usize) -> Self {
+        Self {
+            program: Vec::new(),
+            max_len,
+        }
+    }
+
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= self.max_len {
+            return Err("pattern is too large".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(c) => self.emit(Inst::Char(*c))?,
+            Node::Any => self.emit(Inst::Any)?,
+            Node::Class(class) => self.emit(Inst::Class(class.clone()))?,
+            Node::Start => self.emit(Inst::Start)?,
+            Node::End => self.emit(Inst::End)?,
+            Node::Group(alternatives) => return self.compile_alternatives(alternatives),
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.emit(Inst::Jump(split))?;
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    }
+                    // Each optional repetition may be skipped, which skips the ones after it
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        for split in splits {
+                            self.program[split] = Inst::Split(split + 1, self.program.len());
+                        }
+                    }
+                }
+                return Ok(());
+            }
+        };
+        Ok(())
+    }
+
+    fn compile_alternatives(&mut self, alternatives: &[Vec<Node>]) -> Result<(), String> {
+        let mut jumps = Vec::new();
+        for (idx, alternative) in alternatives.iter().enumerate() {
+            let is_last = idx + 1 == alternatives.len();
+            let split = match is_last {
+                true => None,
+                false => Some(self.emit(Inst::Split(0, 0))?),
+            };
+            for node in alternative {
+                self.compile(node)?;
+            }
+            if let Some(split) = split {
+                jumps.push(self.emit(Inst::Jump(0))?);
+                self.program[split] = Inst::Split(split + 1, self.program.len());
+            }
+        }
+        for jump in jumps {
+            self.program[jump] = Inst::Jump(self.program.len());
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Regex {
+        self.program.push(Inst::Match);
+        Regex {
+            program: self.program,
+        }
+    }
+}
+
+// Threads of the automaton at one position of the text, as the addresses they are at
+struct Threads {
+    pcs: Vec<usize>,
+    added: Vec<bool>,
+}
+
+impl Threads {
+    fn new(program_len: usize) -> Self {
+        Self {
+            pcs: Vec::new(),
+            added: vec![false; program_len],
+        }
+    }
+
+    fn insert(&mut self, pc: usize) -> bool {
+        if self.added[pc] {
+            return false;
+        }
+        self.added[pc] = true;
+        self.pcs.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for pc in self.pcs.drain(..) {
+            self.added[pc] = false;
+        }
+    }
+}
+
+fn parse_alternatives(chars: &[char], idx: &mut usize) -> Result<Vec<Vec<Node>>, String> {
+    let mut alternatives = vec![parse_sequence(chars, idx)?];
+    while chars.get(*idx) == Some(&'|') {
+        *idx += 1;
+        alternatives.push(parse_sequence(chars, idx)?);
+    }
+    Ok(alternatives)
+}
+
+fn parse_sequence(chars: &[char], idx: &mut usize) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    while let Some(&c) = chars.get(*idx) {
+        let start = *idx;
+        *idx += 1;
+        let node = match c {
+            '|' | ')' => {
+                *idx = start;
+                break;
+            }
+            '(' => {
+                // Groups do not capture, so `(?:...)` is the same as `(...)`
+                if chars[*idx..].starts_with(&['?', ':']) {
+                    *idx += 2;
+                }
+                let alternatives = parse_alternatives(chars, idx)?;
+                if chars.get(*idx) != Some(&')') {
+                    return Err(format!("unclosed group at position {}", start));
+                }
+                *idx += 1;
+                Node::Group(alternatives)
+            }
+            '[' => {
+                let (class, len) = CharClass::parse(&chars[*idx..], true)
+                    .ok_or_else(|| format!("unclosed bracket at position {}", start))?;
+                *idx += len;
+                Node::Class(class)
+            }
+            '\\' => {
+                let escaped = *chars
+                    .get(*idx)
+                    .ok_or_else(|| "trailing backslash".to_string())?;
+                *idx += 1;
+                shorthand_class(escaped).map_or(Node::Char(escaped), Node::Class)
+            }
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '*' | '+' | '?' | '{' => {
+                let Some(node) = nodes.pop() else {
+                    return Err(format!("nothing to repeat at position {}", start));
+                };
+                let (min, max) = match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    '?' => (0, Some(1)),
+                    _ => parse_bounds(chars, idx)?,
+                };
+                Node::Repeat {
+                    node: Box::new(node),
+                    min,
+                    max,
+                }
+            }
+            c => Node::Char(c),
+        };
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+// `{n}`, `{n,}` or `{n,m}`, after the opening brace
+fn parse_bounds(chars: &[char], idx: &mut usize) -> Result<(usize, Option<usize>), String> {
+    let end = chars[*idx..]
+        .iter()
+        .position(|c| *c == '}')
+        .ok_or_else(|| "unclosed repetition bounds".to_string())?;
+    let bounds: String = chars[*idx..*idx + end].iter().collect();
+    *idx += end + 1;
+
+    let parse = |bound: &str| {
+        bound
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid repetition bounds {{{}}}", bounds))
+    };
+    match bounds.split_once(',') {
+        None => parse(&bounds).map(|count| (count, Some(count))),
+        Some((min, max)) if max.trim().is_empty() => Ok((parse(min)?, None)),
+        Some((min, max)) => match (parse(min)?, parse(max)?) {
+            (min, max) if min > max => Err(format!("invalid repetition bounds {{{}}}", bounds)),
+            (min, max) => Ok((min, Some(max))),
+        },
+    }
+}
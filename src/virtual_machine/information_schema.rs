@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use crate::backend::columns::{ColumnDefault, ColumnItemType, Columns, IntegerType, TextType};
+use crate::backend::database::Database;
+use crate::backend::row::{Row, SQLType};
+
+// Schema of the read-only tables describing the tables of every open database. Their rows are
+// generated from the catalog each time they are read
+const SCHEMA: &str = "information_schema";
+
+// Columns and rows of the table if it is one of the information_schema tables. Rows are numbered
+// from 1 in the order they are generated
+pub(super) fn virtual_table(table_name: &str, db: &Database) -> Option<(Columns, Vec<Row>)> {
+    let (schema, name) = table_name.split_once('.')?;
+    if schema != SCHEMA {
+        return None;
+    }
+
+    let text = || ColumnItemType::Text(TextType::Text);
+    let (columns, rows) = match name {
+        "tables" => (
+            Columns::from(vec![
+                ("table_schema", text()),
+                ("table_name", text()),
+                ("table_type", text()),
+                ("table_comment", text()),
+            ]),
+            tables(db),
+        ),
+        "columns" => (
+            Columns::from(vec![
+                ("table_schema", text()),
+                ("table_name", text()),
+                ("column_name", text()),
+                (
+                    "ordinal_position",
+                    ColumnItemType::Integer(IntegerType::Int),
+                ),
+                ("data_type", text()),
+                ("is_nullable", text()),
+                ("column_default", text()),
+                ("column_key", text()),
+                ("column_comment", text()),
+            ]),
+            columns(db),
+        ),
+        _ => return None,
+    };
+
+    let rows = (1..)
+        .zip(rows)
+        .map(|(rowid, fields)| Row::new(rowid, fields.into_values().collect()))
+        .collect();
+    Some((columns, rows))
+}
+
+// Fields of a row by column name, which puts them in the same order as the columns
+type Fields = BTreeMap<&'static str, SQLType>;
+
+fn text(value: &str) -> SQLType {
+    SQLType::Text(value.to_string())
+}
+
+fn optional_text(value: Option<&str>) -> SQLType {
+    value.map_or(SQLType::Null, text)
+}
+
+fn tables(db: &Database) -> Vec<Fields> {
+    let mut rows = Vec::new();
+    for (schema, _) in db.databases() {
+        for table_name in db.table_names_in(schema).unwrap_or_default() {
+            let Ok(table) = db.table(&format!("{}.{}", schema, table_name)) else {
+                continue;
+            };
+            rows.push(Fields::from([
+                ("table_schema", text(schema)),
+                ("table_name", text(table_name)),
+                ("table_type", text("BASE TABLE")),
+                (
+                    "table_comment",
+                    optional_text(table.comments.table.as_deref()),
+                ),
+            ]));
+        }
+    }
+    rows
+}
+
+// Columns are numbered in the order SELECT * returns them
+fn columns(db: &Database) -> Vec<Fields> {
+    let mut rows = Vec::new();
+    for (schema, _) in db.databases() {
+        for table_name in db.table_names_in(schema).unwrap_or_default() {
+            let Ok(table) = db.table(&format!("{}.{}", schema, table_name)) else {
+                continue;
+            };
+            for (column_name, position) in table.columns.declared_order().into_iter().zip(1..) {
+                let Some(column_type) = table.columns.get(column_name) else {
+                    continue;
+                };
+                let constraints = table.columns.constraints(column_name);
+                let is_primary_key = table.primary_key.as_deref() == Some(column_name);
+                let not_null = is_primary_key || constraints.is_some_and(|c| c.not_null);
+                let column_key = match constraints {
+                    _ if is_primary_key => text("PRI"),
+                    Some(constraints) if constraints.unique => text("UNI"),
+                    _ => SQLType::Null,
+                };
+                rows.push(Fields::from([
+                    ("table_schema", text(schema)),
+                    ("table_name", text(table_name)),
+                    ("column_name", text(column_name)),
+                    ("ordinal_position", SQLType::Integer(position)),
+                    ("data_type", text(&column_type.to_string())),
+                    ("is_nullable", text(if not_null { "NO" } else { "YES" })),
+                    (
+                        "column_default",
+                        optional_text(
+                            constraints
+                                .and_then(|constraints| constraints.default.as_ref())
+                                .map(ColumnDefault::as_str),
+                        ),
+                    ),
+                    ("column_key", column_key),
+                    (
+                        "column_comment",
+                        optional_text(table.comments.columns.get(column_name).map(String::as_str)),
+                    ),
+                ]));
+            }
+        }
+    }
+    rows
+}
@@ -1,50 +1,140 @@
+use std::borrow::Cow;
+use std::ops::ControlFlow;
+
+use super::expr::{as_integer, evaluate, Scope};
+use super::interpreter;
+use super::program::{Instruction, Program};
+use super::select::collect_select;
+use super::subquery::{contains_subquery, resolve_subqueries};
 use super::vm_error::VMError;
-use crate::backend::columns::{ColumnItemType, ColumnType, Columns};
+use crate::backend::columns::{
+    ColumnDefault, ColumnItemType, ColumnType, Columns, IntegerParseError,
+};
 use crate::backend::database::Database;
-use crate::backend::row::{Row, SQLType};
-use crate::sql_compiler::InsertTokens;
+use crate::backend::row::SQLType;
+use crate::backend::uuid::Uuid;
+use crate::json::JsonValue;
+use crate::sql_compiler::expression::{parse_expr, Expr};
+use crate::sql_compiler::{InsertTokens, InsertValue, SelectTokens};
 
-fn parse_value(input: &str, column_type: &ColumnItemType) -> Option<SQLType> {
-    match column_type {
-        ColumnItemType::Integer(int_type) => int_type.validate(input),
+pub(super) fn parse_value(
+    name: &str,
+    input: &str,
+    column_type: &ColumnItemType,
+) -> Result<SQLType, VMError> {
+    let parsed_value = match column_type {
+        ColumnItemType::Integer(int_type) => {
+            return int_type.parse(input).map_err(|err| {
+                let (name, input) = (name.to_string(), input.to_string());
+                match err {
+                    IntegerParseError::OutOfRange => {
+                        VMError::ValueOutOfRange(name, int_type.clone(), input)
+                    }
+                    IntegerParseError::Malformed => {
+                        VMError::MalformedInteger(name, int_type.clone(), input)
+                    }
+                }
+            })
+        }
+
+        ColumnItemType::Text(text_type) => {
+            return text_type.validate(input).ok_or_else(|| {
+                VMError::ValueTooLong(name.to_string(), text_type.clone(), input.to_string())
+            })
+        }
+
+        ColumnItemType::Enum(enum_type) => enum_type.validate(input),
+
+        ColumnItemType::Uuid => input.parse::<Uuid>().ok().map(SQLType::Uuid),
+
+        ColumnItemType::Json => input
+            .parse::<JsonValue>()
+            .ok()
+            .map(|_| SQLType::Text(input.to_string())),
+    };
+    parsed_value.ok_or_else(|| VMError::ItemParsingError(input.to_string()))
+}
 
-        ColumnItemType::Text(text_type) => text_type.validate(input),
+// Value a column left out of an INSERT takes. Expressions are evaluated anew for every row, and
+// their value is read like a literal
+pub(super) fn default_value(
+    name: &str,
+    default: &ColumnDefault,
+    column_type: &ColumnItemType,
+    db: &Database,
+) -> Result<SQLType, VMError> {
+    let value = match default {
+        ColumnDefault::Literal(literal) => Some(Cow::Borrowed(literal.as_str())),
+        ColumnDefault::Expr(text) => {
+            let (_, expr) =
+                parse_expr(text).map_err(|_| VMError::ItemParsingError(text.to_string()))?;
+            evaluate_value(&expr, db)?
+        }
+    };
+    match value {
+        Some(value) => parse_value(name, &value, column_type),
+        None => Ok(SQLType::Null),
     }
 }
 
+// Parses the values of a row, along with its rowid when the table has a PRIMARY KEY and the row
+// gives it a value. Columns left out take their default value, or NULL if they have none
 fn parse_values(
     columns: &Columns,
-    items_to_add: &mut Vec<(&str, &str)>,
-) -> Result<(u64, Vec<SQLType>), VMError> {
+    primary_key: Option<&str>,
+    items_to_add: &[(&str, Option<&str>)],
+    db: &Database,
+) -> Result<(Option<u64>, Vec<SQLType>), VMError> {
+    if let Some((name, _)) = items_to_add
+        .iter()
+        .find(|(name, _)| !columns.contains_key(*name))
+    {
+        return Err(VMError::ColumnNotInTable(name.to_string()));
+    }
+
     let mut parsed_values = Vec::<SQLType>::new();
 
     let mut id_optn = None;
 
-    for (name, value) in items_to_add {
-        let column_item_type = columns
-            .get(*name)
-            .ok_or(VMError::ColumnNotInTable(name.to_string()))?;
-        if let Some(parsed_value) = parse_value(value, column_item_type) {
-            // NOTE: Harcoding ID-related stuff. This should change
-            if *name == "id" {
-                if let SQLType::UBigInt(val) = parsed_value {
-                    id_optn = Some(val);
-                }
+    for (name, column_item_type) in columns.iter() {
+        let value = items_to_add
+            .iter()
+            .find(|(item_name, _)| item_name == name)
+            .map(|(_, value)| *value);
+        let default = || columns.constraints(name)?.default.as_ref();
+        let parsed_value = match (value, default()) {
+            (Some(Some(value)), _) => parse_value(name, value, column_item_type)?,
+            (None, Some(default)) => default_value(name, default, column_item_type, db)?,
+            _ => SQLType::Null,
+        };
+        if Some(name.as_str()) == primary_key {
+            if let (ColumnItemType::Integer(int_type), Some(val)) =
+                (column_item_type, as_integer(&parsed_value))
+            {
+                id_optn = Some(int_type.to_key(val));
             }
-            parsed_values.push(parsed_value);
-        } else {
-            return Err(VMError::ItemParsingError(value.to_string()));
         }
+        parsed_values.push(parsed_value);
     }
 
-    if let Some(id) = id_optn {
-        Ok((id, parsed_values))
-    } else {
-        Err(VMError::NoIdParsed)
-    }
+    Ok((id_optn, parsed_values))
+}
+
+// Value of an expression given in VALUES, written as a literal so that it is read like one. It
+// cannot refer to any column, as the row does not exist yet
+fn evaluate_value(expr: &Expr, db: &Database) -> Result<Option<Cow<'static, str>>, VMError> {
+    let scope = Scope::empty();
+    let value = match contains_subquery(expr) {
+        true => evaluate(&resolve_subqueries(expr.clone(), db, &scope)?, &scope)?,
+        false => evaluate(expr, &scope)?,
+    };
+    Ok(match value {
+        SQLType::Null => None,
+        value => Some(Cow::Owned(value.to_string())),
+    })
 }
 
-fn order_and_check_dup(items_to_add: &mut Vec<(&str, &str)>) -> Result<(), VMError> {
+fn order_and_check_dup(items_to_add: &mut Vec<(&str, Option<&str>)>) -> Result<(), VMError> {
     // Sort elements to be added in a predictable way
     items_to_add.sort_unstable_by_key(|item| item.0);
     // If there are duplicate keys, return error immediately
@@ -57,41 +147,324 @@ fn order_and_check_dup(items_to_add: &mut Vec<(&str, &str)>) -> Result<(), VMErr
     }
 }
 
-pub(super) fn process_insert(
+pub(super) fn compile_insert(
     insert_tokens: InsertTokens,
-    db_instance: Option<&mut Database>,
-) -> Result<(), VMError> {
-    let open_database = db_instance.ok_or(VMError::DBClosed)?;
-
+    db: &Database,
+) -> Result<Program, VMError> {
     let InsertTokens {
         table_name,
         column_names,
         column_values,
+        select,
     } = insert_tokens;
+    if select.is_some() {
+        return Err(VMError::NotExplainable);
+    }
 
+    let table = db
+        .table(&table_name)
+        .map_err(|err| VMError::TableWriteError(table_name.to_string(), err))?;
+
+    // Values without column names go to every column, in the order they were declared
+    let column_names: Vec<&str> = match column_names.is_empty() && !column_values.is_empty() {
+        true => table.columns.declared_order(),
+        false => column_names.iter().map(AsRef::as_ref).collect(),
+    };
     let (names_len, values_len) = (column_names.len(), column_values.len());
 
     if names_len != values_len {
         return Err(VMError::ColumnNamesValuesMismatch(names_len, values_len));
     }
 
-    let mut items_to_add: Vec<(&str, &str)> = column_names.into_iter().zip(column_values).collect();
+    let column_values = column_values
+        .into_iter()
+        .map(|value| match value {
+            Some(InsertValue::Expr(expr)) => evaluate_value(&expr, db),
+            Some(InsertValue::Literal(literal)) => Ok(Some(literal)),
+            None => Ok(None),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut items_to_add: Vec<(&str, Option<&str>)> = column_names
+        .into_iter()
+        .zip(column_values.iter().map(Option::as_deref))
+        .collect();
 
     order_and_check_dup(&mut items_to_add)?;
 
-    let table = open_database
-        .get_table(table_name)
-        .map_err(|err| VMError::TableWriteError(table_name.to_string(), err.to_string()))?;
+    let (id, values) = parse_values(
+        &table.columns,
+        table.primary_key.as_deref(),
+        &items_to_add,
+        db,
+    )?;
+
+    let mut program = Program::new();
+    let rowid = program.alloc_register();
+    let start = program.num_registers;
+    for value in values {
+        let target = program.alloc_register();
+        program.emit(Instruction::Value { value, target });
+    }
+    // A PRIMARY KEY left out or NULL is given the next value, as a rowid would be
+    if let Some(id) = id {
+        program.emit(Instruction::Value {
+            value: SQLType::UBigInt(id),
+            target: rowid,
+        });
+    } else {
+        let key_idx = table
+            .primary_key
+            .as_ref()
+            .and_then(|primary_key| table.columns.keys().position(|name| name == primary_key));
+        program.emit(Instruction::NewRowid {
+            table: table_name.to_string(),
+            target: rowid,
+            key: key_idx.map(|idx| start + idx),
+        });
+    }
+    program.emit(Instruction::Insert {
+        table: table_name.to_string(),
+        rowid,
+        start,
+        count: program.num_registers - start,
+    });
+    program.emit(Instruction::Halt);
+
+    Ok(program)
+}
+
+pub(super) fn process_insert(
+    insert_tokens: InsertTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<u64, VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    if let Some(select_tokens) = insert_tokens.select.clone() {
+        return insert_select(insert_tokens, select_tokens, open_database);
+    }
+
+    let program = compile_insert(insert_tokens, open_database)?;
+
+    interpreter::run(&program, open_database, &mut |_| {
+        Ok(ControlFlow::Continue(()))
+    })
+}
+
+// Inserts the rows of a query one at a time, as if each was given with VALUES. They are all read
+// before the first one is inserted, so a query reading the table does not see the new rows
+fn insert_select(
+    insert_tokens: InsertTokens,
+    select_tokens: SelectTokens,
+    db: &Database,
+) -> Result<u64, VMError> {
+    let table = db
+        .table(&insert_tokens.table_name)
+        .map_err(|err| VMError::TableWriteError(insert_tokens.table_name.to_string(), err))?;
+    let names_len = match insert_tokens.column_names.len() {
+        0 => table.columns.declared_order().len(),
+        names_len => names_len,
+    };
+    let (result_columns, rows) = collect_select(select_tokens, db)?;
+    if names_len != result_columns.len() {
+        return Err(VMError::ColumnNamesValuesMismatch(
+            names_len,
+            result_columns.len(),
+        ));
+    }
+
+    let mut changes = 0;
+    for row in rows {
+        let column_values = row
+            .into_iter()
+            .map(|value| match value {
+                SQLType::Null => None,
+                value => Some(InsertValue::Literal(Cow::Owned(value.to_string()))),
+            })
+            .collect();
+        let row_tokens = InsertTokens {
+            table_name: insert_tokens.table_name.clone(),
+            column_names: insert_tokens.column_names.clone(),
+            column_values,
+            select: None,
+        };
+        let program = compile_insert(row_tokens, db)?;
+        changes += interpreter::run(&program, db, &mut |_| Ok(ControlFlow::Continue(())))?;
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Connection, ErrorCode, SQLType};
+
+    fn copy_table(create: &str, insert: &str) -> Vec<SQLType> {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(&format!("CREATE TABLE t {};", create))
+            .unwrap();
+        conn.execute(&format!("CREATE TABLE copy {};", create))
+            .unwrap();
+        conn.execute(&format!("INSERT INTO t VALUES {};", insert))
+            .unwrap();
+        conn.execute("INSERT INTO copy SELECT * FROM t;").unwrap();
+
+        let original = conn.query("SELECT * FROM t;").unwrap().next().unwrap();
+        let copy = conn.query("SELECT * FROM copy;").unwrap().next().unwrap();
+        assert_eq!(original.columns(), copy.columns());
+        assert_eq!(original.values(), copy.values());
+        copy.into_values()
+    }
+
+    #[test]
+    fn select_star_copies_columns_in_declared_order() {
+        let values = copy_table("(id INT PRIMARY KEY, zeta INT, alpha INT)", "(1, 100, 2)");
+        assert_eq!(
+            values,
+            vec![
+                SQLType::Integer(1),
+                SQLType::Integer(100),
+                SQLType::Integer(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn select_star_copies_columns_of_different_types() {
+        let values = copy_table(
+            "(id INT PRIMARY KEY, zname TEXT, age INT)",
+            "(1, 'alice', 30)",
+        );
+        assert_eq!(values[1], SQLType::Text("alice".to_string()));
+    }
 
-    let columns = &table.columns;
+    fn keys(conn: &mut Connection, table: &str) -> Vec<SQLType> {
+        conn.query(&format!("SELECT id FROM {};", table))
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect()
+    }
 
-    let (id, values) = parse_values(columns, &mut items_to_add)?;
-    let row_to_insert = Row::new(id, values);
+    #[test]
+    fn default_values_assign_the_next_primary_key() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE a (id INT PRIMARY KEY, name TEXT DEFAULT 'x');")
+            .unwrap();
+        conn.execute("INSERT INTO a DEFAULT VALUES;").unwrap();
+        conn.execute("INSERT INTO a DEFAULT VALUES;").unwrap();
+        conn.execute("INSERT INTO a VALUES (10, 'y');").unwrap();
+        conn.execute("INSERT INTO a (name) VALUES ('z');").unwrap();
+        conn.execute("INSERT INTO a VALUES (NULL, 'w');").unwrap();
+        assert_eq!(
+            keys(&mut conn, "a"),
+            [1, 2, 10, 11, 12].map(SQLType::Integer)
+        );
+    }
 
-    table.insert(row_to_insert)?;
+    #[test]
+    fn assigned_primary_keys_follow_negative_ones_and_stop_at_the_type_limit() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE s (id SMALLINT PRIMARY KEY);")
+            .unwrap();
+        conn.execute("INSERT INTO s VALUES (-5);").unwrap();
+        conn.execute("INSERT INTO s DEFAULT VALUES;").unwrap();
+        assert_eq!(keys(&mut conn, "s"), [-5, -4].map(SQLType::SmallInt));
 
-    // println!("{:?}", table);
-    // println!("{:?}", table.deserialize_rows());
+        conn.execute("INSERT INTO s VALUES (32767);").unwrap();
+        assert!(conn.execute("INSERT INTO s DEFAULT VALUES;").is_err());
+    }
+
+    #[test]
+    fn values_may_be_expressions() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, u UUID, n INT);")
+            .unwrap();
+        conn.execute("INSERT INTO t (id, u, n) VALUES (1, uuid(), 1 + 1);")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES ((SELECT max(id) FROM t) + 1, uuid(), -3);")
+            .unwrap();
+
+        let rows: Vec<Vec<SQLType>> = conn
+            .query("SELECT id, u, n FROM t;")
+            .unwrap()
+            .map(|row| row.into_values())
+            .collect();
+        assert_eq!(rows[0][0], SQLType::Integer(1));
+        assert_eq!(rows[0][2], SQLType::Integer(2));
+        assert_eq!(
+            (&rows[1][0], &rows[1][2]),
+            (&SQLType::Integer(2), &SQLType::Integer(-3))
+        );
+        assert!(matches!(rows[0][1], SQLType::Uuid(_)));
+        assert_ne!(rows[0][1], rows[1][1]);
+
+        // Values are checked against the type of their column like literals
+        assert!(conn
+            .execute("INSERT INTO t VALUES (3, 'x' || 'y', 0);")
+            .is_err());
+    }
 
-    Ok(())
+    #[test]
+    fn default_expressions_are_evaluated_for_every_row() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE t (id INT PRIMARY KEY, r BIGINT DEFAULT random(), u UUID DEFAULT uuid(), \
+             n INT DEFAULT (1 + 2) NOT NULL, s TEXT DEFAULT 'x');",
+        )
+        .unwrap();
+        for id in 1..=3 {
+            conn.execute(&format!("INSERT INTO t (id) VALUES ({});", id))
+                .unwrap();
+        }
+
+        let rows: Vec<Vec<SQLType>> = conn
+            .query("SELECT r, u, n, s FROM t;")
+            .unwrap()
+            .map(|row| row.into_values())
+            .collect();
+        assert!(rows.iter().all(|row| row[2] == SQLType::Integer(3)));
+        assert!(rows
+            .iter()
+            .all(|row| row[3] == SQLType::Text("x".to_string())));
+        assert!(rows.iter().all(|row| matches!(row[1], SQLType::Uuid(_))));
+        // Three random 64-bit values are all different but for a vanishing chance
+        assert!(rows[0][0] != rows[1][0] || rows[1][0] != rows[2][0]);
+        assert_ne!(rows[0][1], rows[1][1]);
+        assert_ne!(rows[1][1], rows[2][1]);
+    }
+
+    #[test]
+    fn default_expressions_are_checked_when_the_table_is_created() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(conn
+            .execute("CREATE TABLE t (id INT PRIMARY KEY, n INT DEFAULT (id + 1));")
+            .is_err());
+        assert!(conn
+            .execute("CREATE TABLE t (id INT PRIMARY KEY, n INT DEFAULT upper('a'));")
+            .is_err());
+    }
+
+    #[test]
+    fn text_too_long_for_its_column_names_the_limit() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, code CHAR(3), name VARCHAR(4));")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (1, 'ab   ', 'abcd');")
+            .unwrap();
+
+        let err = conn
+            .execute("INSERT INTO t VALUES (2, 'abcd', 'x');")
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::TypeMismatch);
+        assert_eq!(
+            err.to_string(),
+            "Value abcd too long for column code of type CHAR(3), which holds at most 3 \
+             characters, not counting trailing spaces"
+        );
+        let err = conn
+            .execute("UPDATE t SET name = 'abcde' WHERE id = 1;")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Value abcde too long for column name of type VARCHAR(4), which holds at most 4 bytes"
+        );
+    }
 }
@@ -0,0 +1,278 @@
+use std::ops::{ControlFlow, RangeBounds};
+
+use super::constraints::check_constraints;
+use super::expr::{evaluate, truth_value, Scope};
+use super::information_schema::virtual_table;
+use super::operators::{
+    csv_path, BoxedOperator, CsvScan, Filter, Group, IndexScan, Join, ParallelScan, Qualify, Scan,
+    Values, PARALLEL_SCAN_MIN_ROWS,
+};
+use super::planner::table_qualifier;
+use super::program::{Instruction, Program, Register, ScanPlan};
+use super::progress;
+use super::subquery::{contains_subquery, resolve_subqueries};
+use super::vm_error::VMError;
+use crate::backend::columns::ColumnItemType;
+use crate::backend::database::Database;
+use crate::backend::row::{Row, SQLType};
+use crate::sql_compiler::expression::Expr;
+
+struct VMCursor<'a> {
+    operator: BoxedOperator<'a>,
+    columns: Vec<String>,
+    current_row: Option<Row>,
+}
+
+impl<'a> VMCursor<'a> {
+    fn open(scan: &ScanPlan, db: &'a Database) -> Result<Self, VMError> {
+        let mut operator = Self::scan_operator(scan, db)?;
+        if !scan.joins.is_empty() {
+            operator = Box::new(Qualify::new(operator, table_qualifier(&scan.table)));
+            for join in &scan.joins {
+                let qualifier = table_qualifier(&join.table);
+                let right = Box::new(Qualify::new(Self::scan_operator(join, db)?, qualifier));
+                operator = Box::new(Join::new(operator, right, None));
+            }
+            if let Some(join_filter) = &scan.join_filter {
+                operator = Box::new(Filter::from_expr(operator, join_filter.clone()));
+            }
+        }
+        if let Some(group) = &scan.group {
+            operator = Box::new(Group::new(operator, group.clone(), db));
+        }
+        Ok(Self {
+            columns: operator.columns(),
+            operator,
+            current_row: None,
+        })
+    }
+
+    fn scan_operator(scan: &ScanPlan, db: &'a Database) -> Result<BoxedOperator<'a>, VMError> {
+        if let Some((columns, rows)) = virtual_table(&scan.table, db) {
+            let rows = rows
+                .into_iter()
+                .filter(|row| scan.rowid_range.contains(&row.rowid()))
+                .collect();
+            let mut operator: BoxedOperator = Box::new(Values::new(columns.to_printable(), rows));
+            if let Some(filter) = &scan.filter {
+                operator = Box::new(Filter::from_expr(operator, filter.clone()));
+            }
+            return Ok(operator);
+        }
+
+        if let Some(path) = csv_path(&scan.table) {
+            let mut operator: BoxedOperator =
+                Box::new(CsvScan::open(&path)?.with_bounds(scan.rowid_range));
+            if let Some(filter) = &scan.filter {
+                operator = Box::new(Filter::from_expr(operator, filter.clone()));
+            }
+            return Ok(operator);
+        }
+
+        let table = db
+            .table(&scan.table)
+            .map_err(|err| VMError::TableReadError(scan.table.to_string(), err))?;
+
+        let index = scan
+            .index_seek
+            .as_ref()
+            .and_then(|index_seek| Some((table.index(&index_seek.index)?, index_seek)));
+        if let Some((index, index_seek)) = index {
+            let mut operator: BoxedOperator =
+                Box::new(IndexScan::new(table, index, &index_seek.values));
+            if let Some(filter) = &scan.filter {
+                operator = Box::new(Filter::from_expr(operator, filter.clone()));
+            }
+            return Ok(operator);
+        }
+
+        // Large scans are split across threads when enabled with PRAGMA threads
+        let snapshot_path = db
+            .snapshot_path(&scan.table)
+            .filter(|_| db.threads() > 1 && scan.estimated_rows >= PARALLEL_SCAN_MIN_ROWS);
+
+        Ok(match snapshot_path {
+            Some(path) => Box::new(ParallelScan::new(
+                table,
+                path,
+                scan,
+                db.threads(),
+                db.read_uncommitted(),
+                db.paranoid(),
+            )?),
+            None => {
+                let (lower_bound, upper_bound) = scan.rowid_range;
+                let mut operator: BoxedOperator =
+                    Box::new(Scan::with_bounds(table, lower_bound, upper_bound));
+                if let Some(filter) = &scan.filter {
+                    operator = Box::new(Filter::from_expr(operator, filter.clone()));
+                }
+                operator
+            }
+        })
+    }
+
+    fn advance(&mut self) -> Result<bool, VMError> {
+        self.current_row = self.operator.next_row()?;
+        Ok(self.current_row.is_some())
+    }
+
+    fn row(&self) -> Result<&Row, VMError> {
+        self.current_row
+            .as_ref()
+            .ok_or(VMError::CursorNotPositioned)
+    }
+}
+
+fn read_register(registers: &[Option<SQLType>], register: Register) -> Result<&SQLType, VMError> {
+    registers[register]
+        .as_ref()
+        .ok_or(VMError::UninitializedRegister(register))
+}
+
+fn read_registers(
+    registers: &[Option<SQLType>],
+    start: Register,
+    count: usize,
+) -> Result<Vec<SQLType>, VMError> {
+    (start..start + count)
+        .map(|register| read_register(registers, register).cloned())
+        .collect()
+}
+
+// Evaluates an expression against the cursor's current row, or on its own without a cursor.
+// Subqueries in it are run first
+fn evaluate_at(expr: &Expr, cursor: Option<&VMCursor>, db: &Database) -> Result<SQLType, VMError> {
+    let scope = match cursor {
+        Some(vm_cursor) => {
+            let row = vm_cursor.row()?;
+            Scope::new(&vm_cursor.columns, row.attributes()).with_rowid(row.rowid())
+        }
+        None => Scope::empty(),
+    };
+    if !contains_subquery(expr) {
+        return Ok(evaluate(expr, &scope)?);
+    }
+    let expr = resolve_subqueries(expr.clone(), db, &scope)?;
+    Ok(evaluate(&expr, &scope)?)
+}
+
+// Runs a program to completion, returning the number of rows it inserted
+pub(super) fn run(
+    program: &Program,
+    db: &Database,
+    on_row: &mut dyn FnMut(Vec<SQLType>) -> Result<ControlFlow<()>, VMError>,
+) -> Result<u64, VMError> {
+    let mut changes = 0;
+    let mut registers: Vec<Option<SQLType>> = vec![None; program.num_registers];
+    let mut cursors: Vec<Option<VMCursor>> = (0..program.num_cursors).map(|_| None).collect();
+    let mut pc = 0;
+
+    loop {
+        let instruction = program
+            .instructions
+            .get(pc)
+            .ok_or(VMError::ProgramCounterOutOfRange(pc))?;
+        pc += 1;
+        progress::step()?;
+
+        match instruction {
+            Instruction::OpenRead { cursor, scan } => {
+                cursors[*cursor] = Some(VMCursor::open(scan, db)?);
+            }
+            Instruction::Rewind { cursor, if_empty } => {
+                let vm_cursor = cursors[*cursor].as_mut().ok_or(VMError::CursorNotOpen)?;
+                if !vm_cursor.advance()? {
+                    pc = *if_empty;
+                }
+            }
+            Instruction::Column {
+                cursor,
+                column,
+                target,
+            } => {
+                let row = cursors[*cursor]
+                    .as_ref()
+                    .ok_or(VMError::CursorNotOpen)?
+                    .row()?;
+                registers[*target] = row.attributes().get(*column).cloned();
+            }
+            Instruction::Eval {
+                cursor,
+                expr,
+                target,
+            } => {
+                let vm_cursor = cursor
+                    .map(|cursor| cursors[cursor].as_ref().ok_or(VMError::CursorNotOpen))
+                    .transpose()?;
+                registers[*target] = Some(evaluate_at(expr, vm_cursor, db)?);
+            }
+            Instruction::AggStep {
+                cursor,
+                aggregate,
+                arg,
+                accumulator,
+            } => {
+                let vm_cursor = cursors[*cursor].as_ref().ok_or(VMError::CursorNotOpen)?;
+                let value = arg
+                    .as_ref()
+                    .map(|arg| evaluate_at(arg, Some(vm_cursor), db))
+                    .transpose()?;
+                let accumulated = read_register(&registers, *accumulator)?.clone();
+                registers[*accumulator] = Some(aggregate.step(accumulated, value)?);
+            }
+            Instruction::ResultRow { start, count } => {
+                // The consumer can stop the program early, e.g. when the user quits the pager
+                if on_row(read_registers(&registers, *start, *count)?)?.is_break() {
+                    return Ok(changes);
+                }
+            }
+            Instruction::Next { cursor, if_more } => {
+                let vm_cursor = cursors[*cursor].as_mut().ok_or(VMError::CursorNotOpen)?;
+                if vm_cursor.advance()? {
+                    pc = *if_more;
+                }
+            }
+            Instruction::IfNot { condition, target } => {
+                if truth_value(read_register(&registers, *condition)?) != Some(true) {
+                    pc = *target;
+                }
+            }
+            Instruction::Value { value, target } => {
+                registers[*target] = Some(value.clone());
+            }
+            Instruction::NewRowid { table, target, key } => {
+                let table = db
+                    .table(table)
+                    .map_err(|err| VMError::TableWriteError(table.to_string(), err))?;
+                let rowid = table.next_rowid()?;
+                registers[*target] = Some(SQLType::UBigInt(rowid));
+                if let (Some(key), Some(ColumnItemType::Integer(int_type))) =
+                    (key, table.primary_key_type())
+                {
+                    registers[*key] = Some(int_type.to_value(int_type.from_key(rowid)));
+                }
+            }
+            Instruction::Insert {
+                table,
+                rowid,
+                start,
+                count,
+            } => {
+                let SQLType::UBigInt(rowid) = read_register(&registers, *rowid)? else {
+                    return Err(VMError::NoIdParsed);
+                };
+                let values = read_registers(&registers, *start, *count)?;
+                // Tables named by constraints are looked up in the database holding the table
+                let (table_db, table) = db
+                    .resolve(table)
+                    .and_then(|(table_db, name)| Ok((table_db, table_db.table(name)?)))
+                    .map_err(|err| VMError::TableWriteError(table.to_string(), err))?;
+                check_constraints(table_db, table, *rowid, &values, None)?;
+                table.insert(Row::new(*rowid, values))?;
+                changes += 1;
+            }
+            Instruction::Halt => return Ok(changes),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Set from outside the VM, e.g. by a SIGINT handler, to stop the statement that is running
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Handle of the connection whose statement runs on this thread
+    static WATCHED: RefCell<Option<InterruptHandle>> = const { RefCell::new(None) };
+}
+
+// Only stores to an atomic, so it is safe to call from a signal handler
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+        || WATCHED.with(|watched| {
+            watched
+                .borrow()
+                .as_ref()
+                .is_some_and(|handle| handle.0.load(Ordering::SeqCst))
+        })
+}
+
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Cancels the statements of a single connection. It can be sent to other threads, unlike the
+/// connection itself
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Makes the statement running on the connection stop at the next row it reads and fail
+    /// with an Interrupted error. Does nothing when no statement is running
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    // Runs statements that the handle can interrupt, dropping interrupts made before they start
+    pub(crate) fn run<T>(&self, statements: impl FnOnce() -> T) -> T {
+        self.0.store(false, Ordering::SeqCst);
+        let result = self.watch(statements);
+        self.0.store(false, Ordering::SeqCst);
+        result
+    }
+
+    // Makes `is_interrupted` check the handle on the current thread while `f` runs
+    pub(crate) fn watch<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = WATCHED.with(|watched| watched.replace(Some(self.clone())));
+        let result = f();
+        WATCHED.with(|watched| *watched.borrow_mut() = previous);
+        result
+    }
+
+    // The handle checked on the current thread, for work handed to other threads
+    pub(crate) fn watched() -> Option<Self> {
+        WATCHED.with(|watched| watched.borrow().clone())
+    }
+}
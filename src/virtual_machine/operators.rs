@@ -0,0 +1,35 @@
+use super::vm_error::VMError;
+use crate::backend::row::Row;
+
+mod csv_scan;
+mod filter;
+mod group;
+mod join;
+mod limit;
+mod parallel_scan;
+mod qualify;
+mod scan;
+mod sort;
+mod values;
+
+pub use csv_scan::{csv_path, CsvScan};
+pub use filter::Filter;
+pub use group::Group;
+pub use join::Join;
+pub use limit::Limit;
+pub use parallel_scan::{ParallelScan, PARALLEL_SCAN_MIN_ROWS};
+pub use qualify::Qualify;
+pub use scan::{IndexScan, Scan};
+pub use sort::Sort;
+pub use values::Values;
+
+/// A pull-based (Volcano-style) query operator. Each call to `next_row` yields the next row of
+/// the operator's output, or `None` once it is exhausted. Operators own their inputs, so a query
+/// plan is simply a tree of boxed operators rooted at the one the VM pulls from.
+pub trait Operator {
+    fn columns(&self) -> Vec<String>;
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError>;
+}
+
+pub type BoxedOperator<'a> = Box<dyn Operator + 'a>;
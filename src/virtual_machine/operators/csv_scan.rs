@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::{Bound, RangeBounds};
+
+use super::Operator;
+use crate::backend::columns::{ColumnItemType, Columns, TextType};
+use crate::backend::row::{Row, SQLType};
+use crate::virtual_machine::interrupt::is_interrupted;
+use crate::virtual_machine::vm_error::VMError;
+
+// Path of the file read by a table written as `csv('file.csv')` in the FROM clause
+pub fn csv_path(table_name: &str) -> Option<String> {
+    let (function, argument) = table_name.split_once('(')?;
+    if !function.trim_end().eq_ignore_ascii_case("csv") {
+        return None;
+    }
+    let path = argument
+        .strip_suffix(')')?
+        .trim()
+        .strip_prefix('\'')?
+        .strip_suffix('\'')?;
+    Some(path.replace("''", "'"))
+}
+
+// Reads the rows of a CSV file as it goes, so queries that stop early never parse the rest of it.
+// The first record names the columns, which all hold text, and the following ones are numbered
+// from 1. Missing fields are null and extra ones are ignored
+pub struct CsvScan {
+    path: String,
+    reader: BufReader<File>,
+    columns: Columns,
+    // Position in each record of the field of every column, in the order the columns are returned
+    fields: Vec<usize>,
+    rowid: u64,
+    rowid_range: (Bound<u64>, Bound<u64>),
+}
+
+impl CsvScan {
+    pub fn open(path: &str) -> Result<Self, VMError> {
+        let read_error = |err: String| VMError::CsvReadError(path.to_string(), err);
+        let file = File::open(path).map_err(|err| read_error(err.to_string()))?;
+        let mut reader = BufReader::new(file);
+
+        let header = read_record(&mut reader)
+            .map_err(read_error)?
+            .ok_or_else(|| read_error("no header".to_string()))?;
+        let header: Vec<&str> = header.iter().map(|name| name.trim()).collect();
+        let mut columns = Columns::new();
+        for name in &header {
+            let text = ColumnItemType::Text(TextType::Text);
+            if name.is_empty() || columns.insert(name.to_string(), text).is_some() {
+                return Err(read_error(format!("invalid column name '{}'", name)));
+            }
+            columns.2.push(name.to_string());
+        }
+        let fields = columns
+            .keys()
+            .filter_map(|name| header.iter().position(|field| field == name))
+            .collect();
+
+        Ok(Self {
+            path: path.to_string(),
+            reader,
+            columns,
+            fields,
+            rowid: 0,
+            rowid_range: (Bound::Unbounded, Bound::Unbounded),
+        })
+    }
+
+    // Only returns rows whose number lies within the bounds, and stops reading past the last one
+    pub fn with_bounds(self, rowid_range: (Bound<u64>, Bound<u64>)) -> Self {
+        Self {
+            rowid_range,
+            ..self
+        }
+    }
+
+    pub fn table_columns(&self) -> &Columns {
+        &self.columns
+    }
+}
+
+impl Operator for CsvScan {
+    fn columns(&self) -> Vec<String> {
+        self.columns.to_printable()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        loop {
+            if is_interrupted() {
+                return Err(VMError::Interrupted);
+            }
+            let past_end = match self.rowid_range.1 {
+                Bound::Included(upper) => self.rowid >= upper,
+                Bound::Excluded(upper) => self.rowid + 1 >= upper,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                return Ok(None);
+            }
+            let Some(mut record) = read_record(&mut self.reader)
+                .map_err(|err| VMError::CsvReadError(self.path.clone(), err))?
+            else {
+                return Ok(None);
+            };
+            self.rowid += 1;
+            if !self.rowid_range.contains(&self.rowid) {
+                continue;
+            }
+
+            let values = self
+                .fields
+                .iter()
+                .map(|idx| match record.get_mut(*idx) {
+                    Some(field) => SQLType::Text(std::mem::take(field)),
+                    None => SQLType::Null,
+                })
+                .collect();
+            return Ok(Some(Row::new(self.rowid, values)));
+        }
+    }
+}
+
+// Reads the next record, taking in further lines while a quoted field is left open. Blank lines
+// are skipped
+fn read_record(reader: &mut impl BufRead) -> Result<Option<Vec<String>>, String> {
+    let mut text = String::new();
+    loop {
+        let read = reader.read_line(&mut text).map_err(|err| err.to_string())?;
+        let record = text.trim_end_matches(['\n', '\r']);
+        if read == 0 && record.is_empty() {
+            return Ok(None);
+        }
+        if record.is_empty() {
+            text.clear();
+            continue;
+        }
+        match split_fields(record) {
+            Some(fields) => return Ok(Some(fields)),
+            None if read == 0 => return Err("unterminated quoted field".to_string()),
+            None => {}
+        }
+    }
+}
+
+// Splits a record following RFC 4180: fields may be quoted and quotes inside a quoted field are
+// doubled. Returns None when a quoted field is still open at the end of the text
+fn split_fields(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next()? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => break,
+                    c => field.push(c),
+                }
+            },
+            ',' => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    Some(fields)
+}
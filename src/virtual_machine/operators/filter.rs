@@ -0,0 +1,44 @@
+use super::{BoxedOperator, Operator};
+use crate::backend::row::Row;
+use crate::sql_compiler::expression::Expr;
+use crate::virtual_machine::expr::{evaluate_predicate, Scope};
+use crate::virtual_machine::vm_error::VMError;
+
+pub type Predicate<'a> = Box<dyn Fn(&Row) -> Result<bool, VMError> + 'a>;
+
+pub struct Filter<'a> {
+    input: BoxedOperator<'a>,
+    predicate: Predicate<'a>,
+}
+
+impl<'a> Filter<'a> {
+    pub fn new(input: BoxedOperator<'a>, predicate: Predicate<'a>) -> Self {
+        Self { input, predicate }
+    }
+
+    pub fn from_expr(input: BoxedOperator<'a>, expr: Expr) -> Self {
+        let columns = input.columns();
+        let predicate = move |row: &Row| {
+            Ok(evaluate_predicate(
+                &expr,
+                &Scope::new(&columns, row.attributes()).with_rowid(row.rowid()),
+            )?)
+        };
+        Self::new(input, Box::new(predicate))
+    }
+}
+
+impl Operator for Filter<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.input.columns()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        while let Some(row) = self.input.next_row()? {
+            if (self.predicate)(&row)? {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::btree_map::{self, Entry};
+use std::collections::BTreeMap;
+use std::mem;
+
+use super::{BoxedOperator, Operator};
+use crate::backend::database::Database;
+use crate::backend::key::encode_key;
+use crate::backend::row::{Row, SQLType};
+use crate::sql_compiler::expression::Expr;
+use crate::virtual_machine::expr::{evaluate, Scope};
+use crate::virtual_machine::program::{GroupOutput, GroupPlan};
+use crate::virtual_machine::subquery::{contains_subquery, resolve_subqueries};
+use crate::virtual_machine::vm_error::VMError;
+
+// Grouping is a blocking operator: every row of the input is folded into its group on the first
+// pull. Groups are keyed by the encoded values of their keys, so they come out in the order of
+// those values, and rows whose keys are NULL make up a group of their own
+pub struct Group<'a> {
+    input: BoxedOperator<'a>,
+    plan: GroupPlan,
+    // Subqueries in the outputs read the tables of the database
+    db: &'a Database,
+    groups: Option<btree_map::IntoValues<Box<[u8]>, Vec<SQLType>>>,
+    next_rowid: u64,
+}
+
+impl<'a> Group<'a> {
+    pub fn new(input: BoxedOperator<'a>, plan: GroupPlan, db: &'a Database) -> Self {
+        Self {
+            input,
+            plan,
+            db,
+            groups: None,
+            next_rowid: 0,
+        }
+    }
+
+    fn collect_groups(&mut self) -> Result<BTreeMap<Box<[u8]>, Vec<SQLType>>, VMError> {
+        let columns = self.input.columns();
+        let mut groups = BTreeMap::new();
+        while let Some(row) = self.input.next_row()? {
+            let scope = Scope::new(&columns, row.attributes()).with_rowid(row.rowid());
+            let key = self
+                .plan
+                .keys
+                .iter()
+                .map(|key| evaluate_at(key, &scope, self.db))
+                .collect::<Result<Vec<SQLType>, _>>()?;
+
+            let values = match groups.entry(encode_key(&key)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let values = self
+                        .plan
+                        .outputs
+                        .iter()
+                        .map(|output| match output {
+                            GroupOutput::Expr(expr) => evaluate_at(expr, &scope, self.db),
+                            GroupOutput::Aggregate(aggregate, _) => Ok(aggregate.initial()),
+                        })
+                        .collect::<Result<Vec<SQLType>, _>>()?;
+                    entry.insert(values)
+                }
+            };
+            for (value, output) in values.iter_mut().zip(&self.plan.outputs) {
+                if let GroupOutput::Aggregate(aggregate, arg) = output {
+                    let arg = arg
+                        .as_ref()
+                        .map(|arg| evaluate_at(arg, &scope, self.db))
+                        .transpose()?;
+                    *value = aggregate.step(mem::replace(value, SQLType::Null), arg)?;
+                }
+            }
+        }
+        Ok(groups)
+    }
+}
+
+// Evaluates an expression against a row of the input, running the subqueries in it first
+fn evaluate_at(expr: &Expr, scope: &Scope, db: &Database) -> Result<SQLType, VMError> {
+    match contains_subquery(expr) {
+        true => Ok(evaluate(
+            &resolve_subqueries(expr.clone(), db, scope)?,
+            scope,
+        )?),
+        false => Ok(evaluate(expr, scope)?),
+    }
+}
+
+impl Operator for Group<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.plan
+            .outputs
+            .iter()
+            .map(|output| output.to_string())
+            .collect()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        if self.groups.is_none() {
+            self.groups = Some(self.collect_groups()?.into_values());
+        }
+
+        let Some(values) = self.groups.as_mut().and_then(|groups| groups.next()) else {
+            return Ok(None);
+        };
+        // Groups are numbered in the order they are returned
+        self.next_rowid += 1;
+        Ok(Some(Row::new(self.next_rowid, values)))
+    }
+}
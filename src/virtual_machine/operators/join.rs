@@ -0,0 +1,77 @@
+use super::filter::Predicate;
+use super::{BoxedOperator, Operator};
+use crate::backend::row::Row;
+use crate::virtual_machine::vm_error::VMError;
+
+// Nested-loop join. The right input is materialized once so it can be replayed for every row of
+// the left input; joined rows keep the rowid of the left side.
+pub struct Join<'a> {
+    left: BoxedOperator<'a>,
+    right: BoxedOperator<'a>,
+    predicate: Option<Predicate<'a>>,
+    right_rows: Option<Vec<Row>>,
+    current_left: Option<Row>,
+    right_idx: usize,
+}
+
+impl<'a> Join<'a> {
+    pub fn new(
+        left: BoxedOperator<'a>,
+        right: BoxedOperator<'a>,
+        predicate: Option<Predicate<'a>>,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            predicate,
+            right_rows: None,
+            current_left: None,
+            right_idx: 0,
+        }
+    }
+}
+
+impl Operator for Join<'_> {
+    fn columns(&self) -> Vec<String> {
+        let mut columns = self.left.columns();
+        columns.extend(self.right.columns());
+        columns
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        if self.right_rows.is_none() {
+            let mut rows = Vec::new();
+            while let Some(row) = self.right.next_row()? {
+                rows.push(row);
+            }
+            self.right_rows = Some(rows);
+        }
+        let right_rows = self.right_rows.as_ref().unwrap();
+
+        loop {
+            if self.current_left.is_none() || self.right_idx >= right_rows.len() {
+                self.current_left = self.left.next_row()?;
+                self.right_idx = 0;
+                if self.current_left.is_none() || right_rows.is_empty() {
+                    return Ok(None);
+                }
+            }
+            let left_row = self.current_left.as_ref().unwrap();
+
+            let right_row = &right_rows[self.right_idx];
+            self.right_idx += 1;
+
+            let mut attributes = left_row.attributes().to_vec();
+            attributes.extend_from_slice(right_row.attributes());
+            let joined_row = Row::new(left_row.rowid(), attributes);
+
+            let matches = match &self.predicate {
+                Some(predicate) => predicate(&joined_row)?,
+                None => true,
+            };
+            if matches {
+                return Ok(Some(joined_row));
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use super::{BoxedOperator, Operator};
+use crate::backend::row::Row;
+use crate::virtual_machine::vm_error::VMError;
+
+pub struct Limit<'a> {
+    input: BoxedOperator<'a>,
+    limit: usize,
+    offset: usize,
+    emitted: usize,
+}
+
+impl<'a> Limit<'a> {
+    pub fn new(input: BoxedOperator<'a>, limit: usize, offset: usize) -> Self {
+        Self {
+            input,
+            limit,
+            offset,
+            emitted: 0,
+        }
+    }
+}
+
+impl Operator for Limit<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.input.columns()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        while self.offset > 0 {
+            if self.input.next_row()?.is_none() {
+                return Ok(None);
+            }
+            self.offset -= 1;
+        }
+
+        if self.emitted >= self.limit {
+            return Ok(None);
+        }
+
+        let row = self.input.next_row()?;
+        if row.is_some() {
+            self.emitted += 1;
+        }
+        Ok(row)
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use super::Operator;
+use crate::backend::columns::Columns;
+use crate::backend::row::Row;
+use crate::backend::table::{RowidRange, Table, TableError, TableReader};
+use crate::sql_compiler::expression::Expr;
+use crate::virtual_machine::expr::{evaluate_predicate, Scope};
+use crate::virtual_machine::interrupt::{is_interrupted, InterruptHandle};
+use crate::virtual_machine::program::ScanPlan;
+use crate::virtual_machine::vm_error::VMError;
+
+// Smaller tables are scanned on the calling thread, where starting the workers would cost more
+// than it saves
+pub const PARALLEL_SCAN_MIN_ROWS: u64 = 10_000;
+
+// Rows a worker gets ahead of the reader before waiting for it, which bounds the memory a scan
+// takes however large its table is
+const WORKER_QUEUE_ROWS: usize = 1024;
+
+// Splits a scan across worker threads, each reading part of the rowid range through its own handle
+// on the database file and filtering its rows. Workers send their rows through bounded queues,
+// which are read in rowid order, the next worker's once the previous one is done
+pub struct ParallelScan<'a> {
+    table: &'a Table,
+    columns: Vec<String>,
+    // Workers whose rows are yet to be read, in rowid order
+    workers: VecDeque<Worker>,
+    // Set when the scan is dropped before its end, so the workers stop reading
+    stopped: Arc<AtomicBool>,
+}
+
+struct Worker {
+    rows: Receiver<Result<Row, VMError>>,
+    // Gives the number of rows the worker read
+    handle: JoinHandle<u64>,
+}
+
+impl<'a> ParallelScan<'a> {
+    pub fn new(
+        table: &'a Table,
+        path: &Path,
+        scan: &ScanPlan,
+        threads: usize,
+        read_uncommitted: bool,
+        paranoid: bool,
+    ) -> Result<Self, VMError> {
+        let columns = table.columns.to_printable();
+        let ranges = table
+            .split_rowid_range(scan.rowid_range, threads)
+            .map_err(|err| VMError::TableReadError(table.name.to_string(), err.into()))?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker = ScanWorker {
+            path: path.to_path_buf(),
+            table_name: table.name.to_string(),
+            root_page_num: table.root_page_num(),
+            read_uncommitted,
+            paranoid,
+            columns: columns.clone(),
+            column_types: table.columns.clone(),
+            filter: scan.filter.clone(),
+            stopped: Arc::clone(&stopped),
+        };
+        // Workers stop along with the statement when its connection is interrupted
+        let interrupt_handle = InterruptHandle::watched().unwrap_or_default();
+        let workers = ranges
+            .into_iter()
+            .map(|range| {
+                let (sender, rows) = mpsc::sync_channel(WORKER_QUEUE_ROWS);
+                let worker = worker.clone();
+                let interrupt_handle = interrupt_handle.clone();
+                let handle =
+                    thread::spawn(move || interrupt_handle.watch(|| worker.scan(range, sender)));
+                Worker { rows, handle }
+            })
+            .collect();
+
+        Ok(Self {
+            table,
+            columns,
+            workers,
+            stopped,
+        })
+    }
+}
+
+impl Operator for ParallelScan<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        while let Some(worker) = self.workers.front() {
+            if let Ok(row) = worker.rows.recv() {
+                return row.map(Some);
+            }
+            // The worker is done once it drops its end of the queue
+            let worker = self.workers.pop_front().unwrap();
+            let rows_read = worker
+                .handle
+                .join()
+                .unwrap_or_else(|err| panic::resume_unwind(err));
+            self.table.record_rows_read(rows_read);
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for ParallelScan<'_> {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        for worker in self.workers.drain(..) {
+            drop(worker.rows);
+            if let Ok(rows_read) = worker.handle.join() {
+                self.table.record_rows_read(rows_read);
+            }
+        }
+    }
+}
+
+// What every worker needs to scan its part of the table
+#[derive(Clone)]
+struct ScanWorker {
+    path: PathBuf,
+    table_name: String,
+    root_page_num: u32,
+    read_uncommitted: bool,
+    paranoid: bool,
+    columns: Vec<String>,
+    column_types: Columns,
+    filter: Option<Expr>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ScanWorker {
+    // Sends the rows of the range that pass the filter, or the error that stopped the scan.
+    // Returns the number of rows read
+    fn scan(self, range: RowidRange, rows: SyncSender<Result<Row, VMError>>) -> u64 {
+        let mut rows_read = 0;
+        if let Err(err) = self.send_rows(range, &rows, &mut rows_read) {
+            let _ = rows.send(Err(err));
+        }
+        rows_read
+    }
+
+    fn send_rows(
+        &self,
+        range: RowidRange,
+        rows: &SyncSender<Result<Row, VMError>>,
+        rows_read: &mut u64,
+    ) -> Result<(), VMError> {
+        let table_error =
+            |err: TableError| VMError::TableReadError(self.table_name.to_string(), err.into());
+        let mut reader = TableReader::open(
+            &self.path,
+            self.root_page_num,
+            range,
+            self.read_uncommitted,
+            self.paranoid,
+        )
+        .map_err(table_error)?;
+
+        while let Some(row) = reader.next_row().map_err(table_error)? {
+            *rows_read += 1;
+            if is_interrupted() {
+                return Err(VMError::Interrupted);
+            }
+            if self.stopped.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let row = self.column_types.decode_row(row);
+            let keep = match &self.filter {
+                Some(filter) => {
+                    let scope = Scope::new(&self.columns, row.attributes()).with_rowid(row.rowid());
+                    evaluate_predicate(filter, &scope)?
+                }
+                None => true,
+            };
+            // Nobody reads the rows anymore once the scan is dropped
+            if keep && rows.send(Ok(row)).is_err() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::PARALLEL_SCAN_MIN_ROWS;
+    use crate::{Connection, SQLType};
+
+    fn ids(conn: &mut Connection, sql: &str) -> Vec<SQLType> {
+        conn.query(sql)
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect()
+    }
+
+    #[test]
+    fn workers_stream_their_rows_in_rowid_order() {
+        let path = env::temp_dir().join(format!("sql_rs_parallel_scan_{}.db", process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut conn = Connection::open(path).unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, n INT); INSERT INTO t VALUES (0, 0);")
+            .unwrap();
+        // Doubles the table until it is large enough to be scanned in parallel
+        let mut num_rows = 1;
+        while num_rows < PARALLEL_SCAN_MIN_ROWS {
+            conn.execute(&format!(
+                "INSERT INTO t SELECT id + {}, (id * 7) % 100 FROM t;",
+                num_rows
+            ))
+            .unwrap();
+            num_rows *= 2;
+        }
+
+        let queries = [
+            "SELECT id FROM t WHERE n < 3;",
+            "SELECT n FROM t WHERE id >= 10000;",
+            "SELECT COUNT(*) FROM t;",
+        ];
+        conn.execute("PRAGMA threads = 1;").unwrap();
+        let serial: Vec<Vec<SQLType>> = queries.iter().map(|sql| ids(&mut conn, sql)).collect();
+        conn.execute("PRAGMA threads = 4;").unwrap();
+        for (sql, expected) in queries.iter().zip(&serial) {
+            assert_eq!(&ids(&mut conn, sql), expected, "{}", sql);
+        }
+        assert_eq!(serial[1].len() as u64, num_rows - 10000);
+        assert_eq!(serial[2], [SQLType::Integer(num_rows as i32)]);
+
+        drop(conn);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,31 @@
+use super::{BoxedOperator, Operator};
+use crate::backend::row::Row;
+use crate::virtual_machine::vm_error::VMError;
+
+// Names the columns of its input `table.column`, so that those of different tables stay apart
+// once their rows are joined
+pub struct Qualify<'a> {
+    input: BoxedOperator<'a>,
+    columns: Vec<String>,
+}
+
+impl<'a> Qualify<'a> {
+    pub fn new(input: BoxedOperator<'a>, qualifier: &str) -> Self {
+        let columns = input
+            .columns()
+            .iter()
+            .map(|column| format!("{}.{}", qualifier, column))
+            .collect();
+        Self { input, columns }
+    }
+}
+
+impl Operator for Qualify<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        self.input.next_row()
+    }
+}
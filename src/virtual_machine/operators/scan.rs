@@ -0,0 +1,95 @@
+use std::ops::Bound;
+
+use super::Operator;
+use crate::backend::catalog::IndexEntry;
+use crate::backend::cursor::DBCursor;
+use crate::backend::row::{Row, SQLType};
+use crate::backend::table::{IndexRowids, Table, TableError};
+use crate::virtual_machine::interrupt::is_interrupted;
+use crate::virtual_machine::vm_error::VMError;
+
+pub struct Scan<'a> {
+    table: &'a Table,
+    cursor: DBCursor,
+    // Rowid of a point lookup, checked against the table's bloom filter before reading the tree
+    point_lookup: Option<u64>,
+    exhausted: bool,
+}
+
+impl<'a> Scan<'a> {
+    // Only visits rows whose rowid lies within the bounds, seeking directly to the first one
+    pub fn with_bounds(table: &'a Table, lower_bound: Bound<u64>, upper_bound: Bound<u64>) -> Self {
+        Self {
+            table,
+            cursor: DBCursor::with_bounds(table.root_page_num(), lower_bound, upper_bound),
+            point_lookup: match (lower_bound, upper_bound) {
+                (Bound::Included(lower), Bound::Included(upper)) if lower == upper => Some(lower),
+                _ => None,
+            },
+            exhausted: false,
+        }
+    }
+}
+
+impl Operator for Scan<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.table.columns.to_printable()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        // Every query reads its rows through a scan, so this is where a long one gets cancelled
+        if is_interrupted() {
+            return Err(VMError::Interrupted);
+        }
+        if let Some(rowid) = self.point_lookup.take() {
+            self.exhausted = !self
+                .table
+                .may_contain(rowid)
+                .map_err(|err| VMError::TableReadError(self.table.name.to_string(), err.into()))?;
+        }
+        if self.exhausted {
+            return Ok(None);
+        }
+        let row = self
+            .table
+            .next_row(&mut self.cursor)
+            .map_err(|err| VMError::TableReadError(self.table.name.to_string(), err.into()))?;
+        Ok(row.map(|row| self.table.columns.decode_row(row)))
+    }
+}
+
+// Reads the rows of a table whose indexed values start with the given ones, in the order of the
+// index
+pub struct IndexScan<'a> {
+    table: &'a Table,
+    rowids: IndexRowids<'a>,
+}
+
+impl<'a> IndexScan<'a> {
+    pub fn new(table: &'a Table, index: &IndexEntry, values: &[SQLType]) -> Self {
+        Self {
+            table,
+            rowids: table.index_rowids(index, values),
+        }
+    }
+}
+
+impl Operator for IndexScan<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.table.columns.to_printable()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        if is_interrupted() {
+            return Err(VMError::Interrupted);
+        }
+        let read_error =
+            |err: TableError| VMError::TableReadError(self.table.name.to_string(), err.into());
+        while let Some(rowid) = self.rowids.next_rowid().map_err(read_error)? {
+            if let Some(row) = self.table.row(rowid).map_err(read_error)? {
+                return Ok(Some(self.table.columns.decode_row(row)));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,44 @@
+use std::cmp::Ordering;
+use std::vec;
+
+use super::{BoxedOperator, Operator};
+use crate::backend::row::Row;
+use crate::virtual_machine::vm_error::VMError;
+
+pub type Comparator<'a> = Box<dyn Fn(&Row, &Row) -> Ordering + 'a>;
+
+// Sorting is a blocking operator: the whole input is materialized on the first pull.
+pub struct Sort<'a> {
+    input: BoxedOperator<'a>,
+    comparator: Comparator<'a>,
+    sorted_rows: Option<vec::IntoIter<Row>>,
+}
+
+impl<'a> Sort<'a> {
+    pub fn new(input: BoxedOperator<'a>, comparator: Comparator<'a>) -> Self {
+        Self {
+            input,
+            comparator,
+            sorted_rows: None,
+        }
+    }
+}
+
+impl Operator for Sort<'_> {
+    fn columns(&self) -> Vec<String> {
+        self.input.columns()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        if self.sorted_rows.is_none() {
+            let mut rows = Vec::new();
+            while let Some(row) = self.input.next_row()? {
+                rows.push(row);
+            }
+            rows.sort_by(|a, b| (self.comparator)(a, b));
+            self.sorted_rows = Some(rows.into_iter());
+        }
+
+        Ok(self.sorted_rows.as_mut().and_then(|rows| rows.next()))
+    }
+}
@@ -0,0 +1,28 @@
+use super::Operator;
+use crate::backend::row::Row;
+use crate::virtual_machine::vm_error::VMError;
+
+// Yields rows that are already in memory, such as those of tables generated on the fly
+pub struct Values {
+    columns: Vec<String>,
+    rows: std::vec::IntoIter<Row>,
+}
+
+impl Values {
+    pub fn new(columns: Vec<String>, rows: Vec<Row>) -> Self {
+        Self {
+            columns,
+            rows: rows.into_iter(),
+        }
+    }
+}
+
+impl Operator for Values {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row>, VMError> {
+        Ok(self.rows.next())
+    }
+}
@@ -0,0 +1,748 @@
+use std::ops::Bound;
+
+use super::expr::{as_integer, evaluate, is_deterministic, truth_value, Scope};
+use super::program::IndexSeek;
+use crate::backend::catalog::{IndexEntry, TableStats};
+use crate::backend::columns::{ColumnItemType, ColumnType, Columns, IntegerType, ROWID};
+use crate::backend::row::SQLType;
+use crate::backend::table::{self, Table};
+use crate::sql_compiler::expression::{BinaryOperator, Expr};
+
+#[derive(Debug, Clone)]
+pub enum PredicatePlan {
+    AlwaysTrue,
+    AlwaysFalse,
+    Filter(Expr),
+}
+
+fn literal_truth(expr: &Expr) -> Option<Option<bool>> {
+    match expr {
+        Expr::Literal(value) => Some(truth_value(value)),
+        _ => None,
+    }
+}
+
+fn has_only_literal_operands(expr: &Expr) -> bool {
+    let is_literal = |expr: &Expr| matches!(expr, Expr::Literal(_));
+    match expr {
+        Expr::Unary(_, operand) => is_literal(operand),
+        Expr::Binary(left, _, right) => is_literal(left) && is_literal(right),
+        Expr::IsNull { expr, .. } => is_literal(expr),
+        Expr::Function { name, args } => is_deterministic(name) && args.iter().all(is_literal),
+        Expr::Literal(_) | Expr::Column(_) | Expr::Subquery(_) => false,
+    }
+}
+
+// Replaces an expression whose operands are all literals by its value. Expressions that fail to
+// evaluate are kept as they are so the error surfaces when the statement runs.
+fn evaluate_if_constant(expr: Expr) -> Expr {
+    if !has_only_literal_operands(&expr) {
+        return expr;
+    }
+    match evaluate(&expr, &Scope::empty()) {
+        Ok(value) => Expr::Literal(value),
+        Err(_) => expr,
+    }
+}
+
+// Value-preserving simplification: constant subexpressions are evaluated at compile time, as
+// well as `x AND FALSE` and `x OR TRUE`, whose result does not depend on x.
+pub fn fold_constants(expr: Expr) -> Expr {
+    match expr {
+        Expr::Unary(operator, operand) => {
+            evaluate_if_constant(Expr::Unary(operator, Box::new(fold_constants(*operand))))
+        }
+        Expr::Binary(left, operator, right) => {
+            let (left, right) = (fold_constants(*left), fold_constants(*right));
+            let (left_truth, right_truth) = (literal_truth(&left), literal_truth(&right));
+
+            match operator {
+                BinaryOperator::And
+                    if left_truth == Some(Some(false)) || right_truth == Some(Some(false)) =>
+                {
+                    Expr::Literal(SQLType::Integer(0))
+                }
+                BinaryOperator::Or
+                    if left_truth == Some(Some(true)) || right_truth == Some(Some(true)) =>
+                {
+                    Expr::Literal(SQLType::Integer(1))
+                }
+                _ => evaluate_if_constant(Expr::Binary(Box::new(left), operator, Box::new(right))),
+            }
+        }
+        Expr::IsNull { expr, negated } => evaluate_if_constant(Expr::IsNull {
+            expr: Box::new(fold_constants(*expr)),
+            negated,
+        }),
+        Expr::Function { name, args } => evaluate_if_constant(Expr::Function {
+            name,
+            args: args.into_iter().map(fold_constants).collect(),
+        }),
+        other => other,
+    }
+}
+
+// Replaces every column reference by the expression made from its name. Columns inside
+// subqueries are left alone, since they may refer to the subquery's own table
+pub fn map_columns(expr: Expr, f: &impl Fn(String) -> Expr) -> Expr {
+    let map = |expr: Box<Expr>| Box::new(map_columns(*expr, f));
+    match expr {
+        Expr::Column(name) => f(name),
+        Expr::Unary(operator, operand) => Expr::Unary(operator, map(operand)),
+        Expr::Binary(left, operator, right) => Expr::Binary(map(left), operator, map(right)),
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: map(expr),
+            negated,
+        },
+        Expr::Function { name, args } => Expr::Function {
+            name,
+            args: args.into_iter().map(|arg| map_columns(arg, f)).collect(),
+        },
+        other => other,
+    }
+}
+
+// Makes every reference to column `from` refer to column `to` instead
+pub fn rename_column(expr: Expr, from: &str, to: &str) -> Expr {
+    map_columns(expr, &|name| {
+        Expr::Column(if name == from { to.to_string() } else { name })
+    })
+}
+
+// Name columns of a table are qualified with, which leaves out the alias of an attached database.
+// Table functions such as `csv('file.csv')` are kept whole
+pub fn table_qualifier(table_name: &str) -> &str {
+    match table_name.contains('(') {
+        true => table_name,
+        false => table_name.rsplit('.').next().unwrap_or(table_name),
+    }
+}
+
+// Drops the qualifier from columns qualified with the name of the table, so `t.a` reads column
+// a of table t
+pub fn unqualify_columns(expr: Expr, table_name: &str) -> Expr {
+    let table = table_qualifier(table_name);
+    map_columns(expr, &|name| match name.rsplit_once('.') {
+        Some((qualifier, column)) if qualifier == table => Expr::Column(column.to_string()),
+        _ => Expr::Column(name),
+    })
+}
+
+// Truthiness-preserving simplification of a WHERE clause, where NULL behaves like FALSE:
+// `x AND TRUE` and `x OR FALSE` reduce to x.
+fn eliminate_trivial_terms(expr: Expr) -> Expr {
+    let is_true = |expr: &Expr| literal_truth(expr) == Some(Some(true));
+    let is_not_true = |expr: &Expr| literal_truth(expr).is_some_and(|truth| truth != Some(true));
+
+    match expr {
+        Expr::Binary(left, BinaryOperator::And, right) => {
+            let (left, right) = (
+                eliminate_trivial_terms(*left),
+                eliminate_trivial_terms(*right),
+            );
+            if is_not_true(&left) || is_not_true(&right) {
+                Expr::Literal(SQLType::Integer(0))
+            } else if is_true(&left) {
+                right
+            } else if is_true(&right) {
+                left
+            } else {
+                Expr::Binary(Box::new(left), BinaryOperator::And, Box::new(right))
+            }
+        }
+        Expr::Binary(left, BinaryOperator::Or, right) => {
+            let (left, right) = (
+                eliminate_trivial_terms(*left),
+                eliminate_trivial_terms(*right),
+            );
+            if is_true(&left) || is_true(&right) {
+                Expr::Literal(SQLType::Integer(1))
+            } else if is_not_true(&left) {
+                right
+            } else if is_not_true(&right) {
+                left
+            } else {
+                Expr::Binary(Box::new(left), BinaryOperator::Or, Box::new(right))
+            }
+        }
+        other => other,
+    }
+}
+
+pub fn plan_predicate(predicate: Option<Expr>) -> PredicatePlan {
+    let Some(predicate) = predicate else {
+        return PredicatePlan::AlwaysTrue;
+    };
+
+    match eliminate_trivial_terms(fold_constants(predicate)) {
+        Expr::Literal(value) if truth_value(&value) == Some(true) => PredicatePlan::AlwaysTrue,
+        Expr::Literal(_) => PredicatePlan::AlwaysFalse,
+        filter => PredicatePlan::Filter(filter),
+    }
+}
+
+fn split_conjuncts(expr: Expr, conjuncts: &mut Vec<Expr>) {
+    match expr {
+        Expr::Binary(left, BinaryOperator::And, right) => {
+            split_conjuncts(*left, conjuncts);
+            split_conjuncts(*right, conjuncts);
+        }
+        other => conjuncts.push(other),
+    }
+}
+
+fn join_conjuncts(conjuncts: Vec<Expr>) -> Option<Expr> {
+    conjuncts
+        .into_iter()
+        .reduce(|left, right| Expr::Binary(Box::new(left), BinaryOperator::And, Box::new(right)))
+}
+
+// Collects the columns an expression reads. Returns false if it has a subquery, since the
+// columns those read are only known once they run
+fn collect_columns<'e>(expr: &'e Expr, columns: &mut Vec<&'e str>) -> bool {
+    match expr {
+        Expr::Column(name) => {
+            columns.push(name);
+            true
+        }
+        Expr::Unary(_, operand) | Expr::IsNull { expr: operand, .. } => {
+            collect_columns(operand, columns)
+        }
+        Expr::Binary(left, _, right) => {
+            collect_columns(left, columns) && collect_columns(right, columns)
+        }
+        Expr::Function { args, .. } => args.iter().all(|arg| collect_columns(arg, columns)),
+        Expr::Literal(_) => true,
+        Expr::Subquery(_) => false,
+    }
+}
+
+// Splits a filter over rows joined from several tables, whose columns are qualified with the
+// names of their tables. Conjuncts that only read the columns of one table are handed to the scan
+// of that table, unqualified, so its rows are filtered before they are joined. The rest is left to
+// check on the joined rows
+pub fn split_join_filter(filter: Expr, tables: &[&str]) -> (Vec<Option<Expr>>, Option<Expr>) {
+    let mut conjuncts = Vec::new();
+    split_conjuncts(filter, &mut conjuncts);
+
+    let mut table_conjuncts: Vec<Vec<Expr>> = vec![Vec::new(); tables.len()];
+    let mut rest = Vec::new();
+    for conjunct in conjuncts {
+        let mut columns = Vec::new();
+        let mut qualifiers = Vec::new();
+        if collect_columns(&conjunct, &mut columns) {
+            qualifiers = columns
+                .iter()
+                .map(|column| column.rsplit_once('.').map(|(qualifier, _)| qualifier))
+                .collect();
+        }
+        qualifiers.dedup();
+        let table = match qualifiers.as_slice() {
+            [Some(qualifier)] => tables
+                .iter()
+                .position(|table| table_qualifier(table) == *qualifier),
+            _ => None,
+        };
+        match table {
+            Some(idx) => table_conjuncts[idx].push(unqualify_columns(conjunct, tables[idx])),
+            None => rest.push(conjunct),
+        }
+    }
+
+    let table_filters = table_conjuncts.into_iter().map(join_conjuncts).collect();
+    (table_filters, join_conjuncts(rest))
+}
+
+// Rowid range being built, kept as i128 so that out-of-range literals can be represented
+struct RowidRange {
+    lower: Bound<i128>,
+    upper: Bound<i128>,
+}
+
+impl RowidRange {
+    fn tighten_lower(&mut self, bound: Bound<i128>) {
+        let tighter = match (&self.lower, &bound) {
+            (Bound::Unbounded, _) => true,
+            (Bound::Included(current), Bound::Included(new) | Bound::Excluded(new)) => {
+                new >= current
+            }
+            (Bound::Excluded(current), Bound::Included(new)) => new > current,
+            (Bound::Excluded(current), Bound::Excluded(new)) => new >= current,
+            (_, Bound::Unbounded) => false,
+        };
+        if tighter {
+            self.lower = bound;
+        }
+    }
+
+    fn tighten_upper(&mut self, bound: Bound<i128>) {
+        let tighter = match (&self.upper, &bound) {
+            (Bound::Unbounded, _) => true,
+            (Bound::Included(current), Bound::Included(new) | Bound::Excluded(new)) => {
+                new <= current
+            }
+            (Bound::Excluded(current), Bound::Included(new)) => new < current,
+            (Bound::Excluded(current), Bound::Excluded(new)) => new <= current,
+            (_, Bound::Unbounded) => false,
+        };
+        if tighter {
+            self.upper = bound;
+        }
+    }
+
+    // Converts to key bounds over keys of `key_type`; an empty range is expressed as a lower
+    // bound past every key
+    fn into_key_bounds(self, key_type: &IntegerType) -> (Bound<u64>, Bound<u64>) {
+        const EMPTY: (Bound<u64>, Bound<u64>) = (Bound::Excluded(u64::MAX), Bound::Unbounded);
+        let (min, max) = key_type.range();
+
+        let lower = match self.lower {
+            Bound::Included(key) | Bound::Excluded(key) if key < min => Bound::Unbounded,
+            Bound::Included(key) if key > max => return EMPTY,
+            Bound::Excluded(key) if key >= max => return EMPTY,
+            Bound::Included(key) => Bound::Included(key_type.to_key(key)),
+            Bound::Excluded(key) => Bound::Excluded(key_type.to_key(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let upper = match self.upper {
+            Bound::Included(key) if key < min => return EMPTY,
+            Bound::Excluded(key) if key <= min => return EMPTY,
+            Bound::Included(key) | Bound::Excluded(key) if key > max => Bound::Unbounded,
+            Bound::Included(key) => Bound::Included(key_type.to_key(key)),
+            Bound::Excluded(key) => Bound::Excluded(key_type.to_key(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        (lower, upper)
+    }
+}
+
+fn flip_comparison(operator: BinaryOperator) -> BinaryOperator {
+    match operator {
+        BinaryOperator::Less => BinaryOperator::Greater,
+        BinaryOperator::LessOrEqual => BinaryOperator::GreaterOrEqual,
+        BinaryOperator::Greater => BinaryOperator::Less,
+        BinaryOperator::GreaterOrEqual => BinaryOperator::LessOrEqual,
+        other => other,
+    }
+}
+
+// Recognizes `rowid_column <op> integer` (in either operand order)
+fn as_rowid_constraint(expr: &Expr, rowid_column: &str) -> Option<(BinaryOperator, i128)> {
+    let Expr::Binary(left, operator, right) = expr else {
+        return None;
+    };
+    let (operator, literal) = match (left.as_ref(), right.as_ref()) {
+        (Expr::Column(name), Expr::Literal(literal)) if name == rowid_column => {
+            (*operator, literal)
+        }
+        (Expr::Literal(literal), Expr::Column(name)) if name == rowid_column => {
+            (flip_comparison(*operator), literal)
+        }
+        _ => return None,
+    };
+    let key = as_integer(literal)?;
+
+    match operator {
+        BinaryOperator::Equal
+        | BinaryOperator::Less
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterOrEqual => Some((operator, key)),
+        _ => None,
+    }
+}
+
+// Moves comparisons between the rowid column and integer constants out of the filter and into
+// seek bounds for the B-tree cursor. Returns the bounds and whatever is left of the filter.
+pub fn extract_rowid_bounds(
+    filter: Expr,
+    rowid_column: &str,
+    key_type: &IntegerType,
+) -> ((Bound<u64>, Bound<u64>), Option<Expr>) {
+    let mut conjuncts = Vec::new();
+    split_conjuncts(filter, &mut conjuncts);
+
+    let mut range = RowidRange {
+        lower: Bound::Unbounded,
+        upper: Bound::Unbounded,
+    };
+    conjuncts.retain(|conjunct| {
+        let Some((operator, key)) = as_rowid_constraint(conjunct, rowid_column) else {
+            return true;
+        };
+        match operator {
+            BinaryOperator::Equal => {
+                range.tighten_lower(Bound::Included(key));
+                range.tighten_upper(Bound::Included(key));
+            }
+            BinaryOperator::Greater => range.tighten_lower(Bound::Excluded(key)),
+            BinaryOperator::GreaterOrEqual => range.tighten_lower(Bound::Included(key)),
+            BinaryOperator::Less => range.tighten_upper(Bound::Excluded(key)),
+            BinaryOperator::LessOrEqual => range.tighten_upper(Bound::Included(key)),
+            _ => unreachable!(),
+        }
+        false
+    });
+
+    (range.into_key_bounds(key_type), join_conjuncts(conjuncts))
+}
+
+// Recognizes `column <op> constant` (in either operand order) for the comparisons a B-tree can
+// seek on
+fn as_column_constraint(expr: &Expr) -> Option<(BinaryOperator, &str)> {
+    let Expr::Binary(left, operator, right) = expr else {
+        return None;
+    };
+    let (operator, column) = match (left.as_ref(), right.as_ref()) {
+        (Expr::Column(name), Expr::Literal(_)) => (*operator, name),
+        (Expr::Literal(_), Expr::Column(name)) => (flip_comparison(*operator), name),
+        _ => return None,
+    };
+
+    match operator {
+        BinaryOperator::Equal
+        | BinaryOperator::Less
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterOrEqual => Some((operator, column.as_str())),
+        _ => None,
+    }
+}
+
+// Value stored by a column for a constant it is compared with for equality, when the rows the
+// comparison holds for are exactly those storing it. Integers only equal integers by value, and
+// text only equals the same text, which is stored as it is, padded or as the position of a label
+fn stored_value(column_type: &ColumnItemType, literal: &SQLType) -> Option<SQLType> {
+    match (column_type, literal) {
+        (ColumnItemType::Integer(int_type), literal) => {
+            let value = as_integer(literal)?;
+            let (min, max) = int_type.range();
+            (min..=max)
+                .contains(&value)
+                .then(|| int_type.to_value(value))
+        }
+        (ColumnItemType::Text(text_type), SQLType::Text(text)) => text_type.validate(text),
+        (ColumnItemType::Enum(enum_type), SQLType::Text(text)) => enum_type.validate(text),
+        _ => None,
+    }
+}
+
+// Picks the index whose first columns the filter compares for equality with constants, preferring
+// the one with most such columns. The comparisons are left in the filter
+pub fn choose_index(filter: &Expr, columns: &Columns, indexes: &[IndexEntry]) -> Option<IndexSeek> {
+    let mut conjuncts = Vec::new();
+    split_conjuncts(filter.clone(), &mut conjuncts);
+    let equalities: Vec<(&str, SQLType)> = conjuncts
+        .iter()
+        .filter_map(|conjunct| {
+            let Expr::Binary(left, BinaryOperator::Equal, right) = conjunct else {
+                return None;
+            };
+            let (column, literal) = match (left.as_ref(), right.as_ref()) {
+                (Expr::Column(column), Expr::Literal(literal))
+                | (Expr::Literal(literal), Expr::Column(column)) => (column, literal),
+                _ => return None,
+            };
+            let value = stored_value(columns.get(column)?, literal)?;
+            Some((column.as_str(), value))
+        })
+        .collect();
+
+    indexes
+        .iter()
+        .map(|index| {
+            let values: Vec<SQLType> = index
+                .columns
+                .iter()
+                .map_while(|column| {
+                    equalities
+                        .iter()
+                        .find(|(name, _)| name == column)
+                        .map(|(_, value)| value.clone())
+                })
+                .collect();
+            (index, values)
+        })
+        .filter(|(_, values)| !values.is_empty())
+        .max_by_key(|(_, values)| values.len())
+        .map(|(index, values)| IndexSeek {
+            index: index.name.clone(),
+            columns: index.columns[..values.len()].to_vec(),
+            values,
+        })
+}
+
+// Makes an expression read the columns of a table as SELECT does. They may be qualified with the
+// table name, and the hidden rowid column reads the PRIMARY KEY of tables that have one
+pub fn resolve_table_columns(expr: Expr, table: &Table, table_name: &str) -> Expr {
+    let expr = unqualify_columns(expr, table_name);
+    match &table.primary_key {
+        Some(primary_key) if !table.columns.contains_key(ROWID) => {
+            rename_column(expr, ROWID, primary_key)
+        }
+        _ => expr,
+    }
+}
+
+// Plans the scan of a table for the rows a WHERE clause holds for. Returns the rowid bounds to
+// seek to and what is left of the clause to check on each row, or None when no row can match
+pub fn plan_table_scan(
+    table: &Table,
+    table_name: &str,
+    where_clause: Option<Expr>,
+) -> Option<(table::RowidRange, Option<Expr>)> {
+    let has_rowid_column = table.columns.contains_key(ROWID);
+    let where_clause = where_clause.map(|expr| resolve_table_columns(expr, table, table_name));
+    let filter = match plan_predicate(where_clause) {
+        PredicatePlan::AlwaysTrue => None,
+        PredicatePlan::Filter(filter) => Some(filter),
+        PredicatePlan::AlwaysFalse => return None,
+    };
+
+    let key_column = table
+        .primary_key
+        .clone()
+        .unwrap_or_else(|| ROWID.to_string());
+    let key_type = match table.columns.get(&key_column) {
+        Some(ColumnItemType::Integer(int_type)) => int_type.clone(),
+        _ => IntegerType::UBigInt,
+    };
+    Some(match filter {
+        Some(filter) if table.primary_key.is_some() || !has_rowid_column => {
+            extract_rowid_bounds(filter, &key_column, &key_type)
+        }
+        filter => ((Bound::Unbounded, Bound::Unbounded), filter),
+    })
+}
+
+// Columns of a filter that are compared against constants, split into those compared for
+// equality and those compared by range. An index on them would let a scan seek instead of
+// filtering every row.
+pub fn constrained_columns(filter: Expr) -> (Vec<String>, Vec<String>) {
+    let mut conjuncts = Vec::new();
+    split_conjuncts(filter, &mut conjuncts);
+
+    let (mut equality, mut range): (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+    for conjunct in &conjuncts {
+        let Some((operator, column)) = as_column_constraint(conjunct) else {
+            continue;
+        };
+        let columns = match operator {
+            BinaryOperator::Equal => &mut equality,
+            _ => &mut range,
+        };
+        if !columns.iter().any(|name| name == column) {
+            columns.push(column.to_string());
+        }
+    }
+    range.retain(|column| !equality.contains(column));
+
+    (equality, range)
+}
+
+// Upper bound on the rows a scan over `rowid_range` can visit. Rowids are unique, so a bounded
+// range can never hold more rows than it has keys.
+pub fn estimate_rows(stats: &TableStats, rowid_range: &(Bound<u64>, Bound<u64>)) -> u64 {
+    let lower = match rowid_range.0 {
+        Bound::Unbounded => 0,
+        Bound::Included(key) => key as u128,
+        Bound::Excluded(key) => key as u128 + 1,
+    };
+    let upper = match rowid_range.1 {
+        Bound::Unbounded => return stats.num_rows,
+        Bound::Included(key) => key as u128 + 1,
+        Bound::Excluded(key) => key as u128,
+    };
+    let keys_in_range = upper.saturating_sub(lower);
+
+    stats
+        .num_rows
+        .min(keys_in_range.try_into().unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_compiler::expression::parse_expr;
+    use crate::sql_compiler::{parse_statement, Statement};
+    use crate::virtual_machine::select::compile_select;
+    use crate::Connection;
+
+    fn expr(input: &str) -> Expr {
+        let (rest, expr) = parse_expr(input).unwrap();
+        assert!(rest.is_empty(), "unparsed input: {rest}");
+        expr
+    }
+
+    #[test]
+    fn constant_subexpressions_are_folded() {
+        assert_eq!(fold_constants(expr("a = 2 * 3 + 1")), expr("a = 7"));
+        assert_eq!(
+            fold_constants(expr("upper('x') = name")),
+            expr("'X' = name")
+        );
+        assert_eq!(fold_constants(expr("a > 1 and 0")), expr("0"));
+        assert_eq!(fold_constants(expr("1 or a > 1")), expr("1"));
+        // Only FALSE decides an AND, so a NULL operand is kept
+        assert_eq!(
+            fold_constants(expr("a > 1 and null")),
+            expr("a > 1 and null")
+        );
+    }
+
+    #[test]
+    fn constants_that_fail_to_evaluate_are_kept() {
+        assert_eq!(fold_constants(expr("a = 'x' + 1")), expr("a = 'x' + 1"));
+    }
+
+    #[test]
+    fn trivial_terms_are_eliminated() {
+        let filter = |input| match plan_predicate(Some(expr(input))) {
+            PredicatePlan::Filter(filter) => Some(filter),
+            _ => None,
+        };
+        assert_eq!(filter("1 = 1 and a > 5"), Some(expr("a > 5")));
+        assert_eq!(filter("a > 5 or 1 > 2"), Some(expr("a > 5")));
+        assert_eq!(
+            filter("a > 5 and (b < 2 or 0)"),
+            Some(expr("a > 5 and b < 2"))
+        );
+
+        assert!(matches!(plan_predicate(None), PredicatePlan::AlwaysTrue));
+        assert!(matches!(
+            plan_predicate(Some(expr("a > 5 or 2 > 1"))),
+            PredicatePlan::AlwaysTrue
+        ));
+        assert!(matches!(
+            plan_predicate(Some(expr("1 = 0"))),
+            PredicatePlan::AlwaysFalse
+        ));
+        // In a WHERE clause NULL rejects the row just like FALSE
+        assert!(matches!(
+            plan_predicate(Some(expr("a > 5 and null"))),
+            PredicatePlan::AlwaysFalse
+        ));
+    }
+
+    #[test]
+    fn folded_where_clauses_select_the_same_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        for id in 1..=5 {
+            conn.execute(&format!("INSERT INTO t VALUES ({id}, 'n{id}');"))
+                .unwrap();
+        }
+        let ids = |conn: &mut Connection, sql: &str| -> Vec<SQLType> {
+            conn.query(sql)
+                .unwrap()
+                .map(|row| row.into_values().remove(0))
+                .collect()
+        };
+
+        assert_eq!(
+            ids(&mut conn, "SELECT id FROM t WHERE 1 = 1 AND id > 1 + 2;"),
+            vec![SQLType::Integer(4), SQLType::Integer(5)]
+        );
+        assert!(ids(&mut conn, "SELECT id FROM t WHERE name = 'n1' AND 2 < 1;").is_empty());
+        assert_eq!(ids(&mut conn, "SELECT id FROM t WHERE NULL OR 1;").len(), 5);
+        // A constant that cannot be evaluated still fails once the statement runs
+        assert!(conn.query("SELECT id FROM t WHERE 'x' + 1 = 2;").is_err());
+    }
+
+    #[test]
+    fn rowid_comparisons_become_seek_bounds() {
+        let key = |value| IntegerType::Int.to_key(value);
+        let bounds = |input| extract_rowid_bounds(expr(input), "id", &IntegerType::Int);
+
+        assert_eq!(
+            bounds("id > 3 and name = 'x' and 7 >= id"),
+            (
+                (Bound::Excluded(key(3)), Bound::Included(key(7))),
+                Some(expr("name = 'x'"))
+            )
+        );
+        assert_eq!(
+            bounds("id = 5 and id < 9"),
+            ((Bound::Included(key(5)), Bound::Included(key(5))), None)
+        );
+        // The tightest of several bounds on the same side wins
+        assert_eq!(
+            bounds("id >= 2 and id > 4 and id >= 3").0,
+            (Bound::Excluded(key(4)), Bound::Unbounded)
+        );
+        // Only comparisons with integers on the rowid column can be sought
+        assert_eq!(
+            bounds("id = 'x' or id = 2"),
+            (
+                (Bound::Unbounded, Bound::Unbounded),
+                Some(expr("id = 'x' or id = 2"))
+            )
+        );
+        assert_eq!(
+            bounds("other > 3"),
+            (
+                (Bound::Unbounded, Bound::Unbounded),
+                Some(expr("other > 3"))
+            )
+        );
+    }
+
+    #[test]
+    fn bounds_outside_the_key_type_are_clamped() {
+        // Negative literals are negations until constants are folded
+        let bounds = |input| {
+            extract_rowid_bounds(fold_constants(expr(input)), "id", &IntegerType::SmallInt).0
+        };
+        let key = |value| IntegerType::SmallInt.to_key(value);
+
+        assert_eq!(
+            bounds("id > -100000 and id < 100000"),
+            (Bound::Unbounded, Bound::Unbounded)
+        );
+        assert_eq!(
+            bounds("id >= -5 and id < 100000"),
+            (Bound::Included(key(-5)), Bound::Unbounded)
+        );
+        // A range no key falls in seeks past every key
+        assert_eq!(
+            bounds("id > 40000"),
+            (Bound::Excluded(u64::MAX), Bound::Unbounded)
+        );
+        assert_eq!(
+            bounds("id < -40000"),
+            (Bound::Excluded(u64::MAX), Bound::Unbounded)
+        );
+    }
+
+    #[test]
+    fn scans_seek_to_the_rows_in_range() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        for id in -500..500 {
+            conn.execute(&format!("INSERT INTO t VALUES ({id}, 'n{id}');"))
+                .unwrap();
+        }
+        let sql = "SELECT id FROM t WHERE id >= -2 AND id < 3 AND name <> 'n0';";
+        let Statement::Select(select_tokens) = parse_statement(sql).unwrap() else {
+            panic!("not a SELECT");
+        };
+        let plan = compile_select(&select_tokens, &conn.database())
+            .unwrap()
+            .to_string();
+        assert!(
+            plan.contains("SEEK id >= -2 AND id < 3 WHERE (name != 'n0')"),
+            "{plan}"
+        );
+
+        let rows_read = conn.database().pager_metrics().rows_read;
+        let ids: Vec<SQLType> = conn
+            .query(sql)
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect();
+        assert_eq!(ids, [-2, -1, 1, 2].map(SQLType::Integer).to_vec());
+        assert_eq!(conn.database().pager_metrics().rows_read - rows_read, 5);
+    }
+}
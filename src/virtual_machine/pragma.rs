@@ -0,0 +1,276 @@
+use std::str::FromStr;
+
+use super::result_writer::OutputSettings;
+use super::vm_error::VMError;
+use crate::backend::database::Database;
+use crate::backend::row::SQLType;
+use crate::backend::{CacheSize, WriteLock};
+use crate::sql_compiler::PragmaTokens;
+
+// Settings that can be read and changed with PRAGMA, plus the checks it can run
+pub const PRAGMA_NAMES: [&str; 18] = [
+    "bloom_filter",
+    "cache_size",
+    "foreign_keys",
+    "freelist_count",
+    "integrity_check",
+    "max_file_size",
+    "max_row_size",
+    "max_table_pages",
+    "memory_used",
+    "page_count",
+    "paranoid",
+    "read_uncommitted",
+    "schema_version",
+    "soft_heap_limit",
+    "table_info",
+    "threads",
+    "user_version",
+    "write_lock",
+];
+
+// Changes the setting named by the pragma when given a value, otherwise prints its current value.
+// integrity_check prints each problem found in the database, or "ok", and table_info describes
+// the columns of the table given as argument. The max_ limits are in bytes, or in pages for
+// max_table_pages, and 0 means no limit, as it does for soft_heap_limit
+pub(super) fn process_pragma(
+    pragma_tokens: PragmaTokens,
+    db_instance: Option<&mut Database>,
+    output_settings: &OutputSettings,
+) -> Result<(), VMError> {
+    let PragmaTokens { name, value } = pragma_tokens;
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    let invalid_value =
+        |value: &str| VMError::InvalidPragmaValue(name.to_string(), value.to_string());
+
+    let rows = match (name.to_lowercase().as_str(), value.as_deref()) {
+        ("write_lock", Some(value)) => {
+            let write_lock = value
+                .parse::<WriteLock>()
+                .map_err(|_| invalid_value(value))?;
+            open_database.set_write_lock(write_lock);
+            return Ok(());
+        }
+        ("write_lock", None) => vec![open_database.write_lock().to_string()],
+        ("read_uncommitted", Some(value)) => {
+            let read_uncommitted = parse_bool(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_read_uncommitted(read_uncommitted);
+            return Ok(());
+        }
+        ("read_uncommitted", None) => vec![on_off(open_database.read_uncommitted())],
+        ("paranoid", Some(value)) => {
+            let paranoid = parse_bool(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_paranoid(paranoid);
+            return Ok(());
+        }
+        ("paranoid", None) => vec![on_off(open_database.paranoid())],
+        ("bloom_filter", Some(value)) => {
+            let bloom_filter = parse_bool(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_bloom_filter(bloom_filter);
+            return Ok(());
+        }
+        ("bloom_filter", None) => vec![on_off(open_database.bloom_filter())],
+        ("foreign_keys", Some(value)) => {
+            let foreign_keys = parse_bool(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_foreign_keys(foreign_keys);
+            return Ok(());
+        }
+        ("foreign_keys", None) => vec![on_off(open_database.foreign_keys())],
+        ("cache_size", Some(value)) => {
+            let cache_size = value
+                .parse::<CacheSize>()
+                .map_err(|_| invalid_value(value))?;
+            open_database.set_cache_size(cache_size);
+            return Ok(());
+        }
+        ("cache_size", None) => vec![open_database.cache_size().to_string()],
+        ("max_file_size", Some(value)) => {
+            let mut quotas = open_database.quotas();
+            quotas.max_file_size = parse_limit(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_quotas(quotas);
+            return Ok(());
+        }
+        ("max_file_size", None) => vec![limit(open_database.quotas().max_file_size)],
+        ("max_table_pages", Some(value)) => {
+            let mut quotas = open_database.quotas();
+            quotas.max_table_pages = parse_limit(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_quotas(quotas);
+            return Ok(());
+        }
+        ("max_table_pages", None) => vec![limit(open_database.quotas().max_table_pages)],
+        ("max_row_size", Some(value)) => {
+            let mut quotas = open_database.quotas();
+            quotas.max_row_size = parse_limit(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_quotas(quotas);
+            return Ok(());
+        }
+        ("max_row_size", None) => vec![limit(open_database.quotas().max_row_size)],
+        ("soft_heap_limit", Some(value)) => {
+            let soft_heap_limit = parse_limit(value).ok_or_else(|| invalid_value(value))?;
+            open_database.set_soft_heap_limit(soft_heap_limit);
+            return Ok(());
+        }
+        ("soft_heap_limit", None) => vec![limit(open_database.soft_heap_limit())],
+        ("memory_used", None) => vec![open_database.memory_used().to_string()],
+        ("threads", Some(value)) => {
+            let threads = value.parse().map_err(|_| invalid_value(value))?;
+            open_database.set_threads(threads);
+            return Ok(());
+        }
+        ("threads", None) => vec![open_database.threads().to_string()],
+        ("user_version", Some(value)) => {
+            let user_version = value.parse().map_err(|_| invalid_value(value))?;
+            open_database
+                .set_user_version(user_version)
+                .map_err(VMError::CommitError)?;
+            return Ok(());
+        }
+        ("user_version", None) => vec![open_database.user_version().to_string()],
+        ("schema_version", None) => vec![open_database.schema_version().to_string()],
+        ("page_count", None) => vec![open_database.page_count().to_string()],
+        ("freelist_count", None) => {
+            let stats = open_database.stats().map_err(VMError::StatsError)?;
+            vec![stats.freelist_count.to_string()]
+        }
+        ("table_info", Some(table_name)) => {
+            let table = open_database
+                .table(table_name)
+                .map_err(|err| VMError::TableReadError(table_name.to_string(), err))?;
+            let flag = |set: bool| SQLType::Integer(set.into());
+            let columns = [
+                "cid",
+                "name",
+                "type",
+                "notnull",
+                "dflt_value",
+                "pk",
+                "comment",
+            ];
+            // Columns are numbered in the order they were declared, as in information_schema
+            let rows = table
+                .columns
+                .declared_order()
+                .into_iter()
+                .zip(0..)
+                .map(|(name, cid)| {
+                    let column_type = &table.columns[name];
+                    let constraints = table.columns.constraints(name);
+                    let is_primary_key = table.primary_key.as_deref() == Some(name);
+                    let default = constraints.and_then(|constraints| constraints.default.as_ref());
+                    let comment = table.comments.columns.get(name);
+                    vec![
+                        SQLType::Integer(cid),
+                        SQLType::Text(name.to_string()),
+                        SQLType::Text(column_type.to_string()),
+                        flag(constraints.is_some_and(|constraints| constraints.not_null)),
+                        default.map_or(SQLType::Null, |default| {
+                            SQLType::Text(default.to_sql(column_type))
+                        }),
+                        flag(is_primary_key),
+                        comment.map_or(SQLType::Null, |comment| SQLType::Text(comment.clone())),
+                    ]
+                })
+                .collect();
+            return write_rows(output_settings, columns.map(str::to_string).to_vec(), rows);
+        }
+        ("integrity_check", None) => {
+            let problems = open_database.integrity_check();
+            match problems.is_empty() {
+                true => vec!["ok".to_string()],
+                false => problems,
+            }
+        }
+        _ => return Err(VMError::UnknownPragma(name.to_string())),
+    };
+
+    let rows = rows
+        .into_iter()
+        .map(|row| vec![SQLType::Text(row)])
+        .collect();
+    write_rows(output_settings, vec![name.to_lowercase()], rows)
+}
+
+fn write_rows(
+    output_settings: &OutputSettings,
+    columns: Vec<String>,
+    rows: Vec<Vec<SQLType>>,
+) -> Result<(), VMError> {
+    let mut writer = output_settings
+        .writer(columns)
+        .map_err(VMError::OutputError)?;
+    for row in rows {
+        let flow = writer.write_row(row).map_err(VMError::OutputError)?;
+        if flow.is_break() {
+            break;
+        }
+    }
+    writer.finish().map_err(VMError::OutputError)
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+fn limit<T: ToString>(value: Option<T>) -> String {
+    value.map_or("0".to_string(), |value| value.to_string())
+}
+
+fn parse_limit<T: FromStr + PartialEq + Default>(value: &str) -> Option<Option<T>> {
+    let value = value.parse::<T>().ok()?;
+    Some(Some(value).filter(|value| *value != T::default()))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "1" => Some(true),
+        "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::{env, process};
+
+    use crate::backend::database::{Database, MEMORY_PATH};
+    use crate::sql_compiler::parse_statement;
+    use crate::virtual_machine::execute_statement;
+    use crate::virtual_machine::result_writer::{OutputMode, OutputSettings};
+
+    #[test]
+    fn table_info_numbers_columns_in_declared_order() {
+        let path = env::temp_dir().join(format!("sql_rs_table_info_{}.csv", process::id()));
+        let mut db = Database::open(MEMORY_PATH).unwrap();
+        let create = "CREATE TABLE t (id INT PRIMARY KEY, name TEXT, age INT);";
+        execute_statement(
+            parse_statement(create).unwrap(),
+            Some(&mut db),
+            &OutputSettings::default(),
+        )
+        .unwrap();
+
+        let output_settings = OutputSettings {
+            mode: OutputMode::Csv,
+            output_file: Some(File::create(&path).unwrap()),
+            ..OutputSettings::default()
+        };
+        execute_statement(
+            parse_statement("PRAGMA table_info(t);").unwrap(),
+            Some(&mut db),
+            &output_settings,
+        )
+        .unwrap();
+        drop(output_settings);
+
+        let output = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let columns: Vec<(&str, &str)> = output
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split(',').take(2).collect::<Vec<_>>().try_into().ok())
+            .map(|[cid, name]: [&str; 2]| (cid, name))
+            .collect();
+        assert_eq!(columns, [("0", "id"), ("1", "name"), ("2", "age")]);
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt;
+use std::ops::Bound;
+
+use tabled::{builder::Builder, settings::style::Style};
+
+use super::expr::Aggregate;
+use crate::backend::columns::{ColumnItemType, IntegerType};
+use crate::backend::row::SQLType;
+use crate::sql_compiler::expression::Expr;
+
+pub type Register = usize;
+pub type CursorId = usize;
+pub type Address = usize;
+
+// Describes the operator pipeline a read cursor iterates over
+#[derive(Debug, Clone)]
+pub struct ScanPlan {
+    pub table: String,
+    pub rowid_range: (Bound<u64>, Bound<u64>),
+    // Column holding the rowids, along with its type, which determines how its values map to keys
+    pub key_column: String,
+    pub key_type: IntegerType,
+    pub filter: Option<Expr>,
+    // Index to read the rows from instead of the whole table, when the filter requires values
+    // for its first columns
+    pub index_seek: Option<IndexSeek>,
+    // Scans of the other tables of a FROM list naming several. Every row of one is combined with
+    // every row of those before it, and the columns of the combined rows are named `table.column`
+    pub joins: Vec<ScanPlan>,
+    // Condition on the combined rows, made of what the filters of single tables cannot check
+    pub join_filter: Option<Expr>,
+    // Folds the rows the scan yields into one per group, for queries with GROUP BY
+    pub group: Option<GroupPlan>,
+    // Planner estimate of the rows the scan visits, from the table statistics
+    pub estimated_rows: u64,
+}
+
+// Rows of an index whose first columns hold the given values, which are stored values of those
+// columns. The filter still checks every row read
+#[derive(Debug, Clone)]
+pub struct IndexSeek {
+    pub index: String,
+    pub columns: Vec<String>,
+    pub values: Vec<SQLType>,
+}
+
+impl fmt::Display for ScanPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.table)?;
+
+        let value = |key| self.key_type.from_key(key);
+        let lower = match self.rowid_range.0 {
+            Bound::Included(key) => Some(format!("{} >= {}", self.key_column, value(key))),
+            Bound::Excluded(key) => Some(format!("{} > {}", self.key_column, value(key))),
+            Bound::Unbounded => None,
+        };
+        let upper = match self.rowid_range.1 {
+            Bound::Included(key) => Some(format!("{} <= {}", self.key_column, value(key))),
+            Bound::Excluded(key) => Some(format!("{} < {}", self.key_column, value(key))),
+            Bound::Unbounded => None,
+        };
+        let seek: Vec<String> = [lower, upper].into_iter().flatten().collect();
+        if !seek.is_empty() {
+            write!(f, " SEEK {}", seek.join(" AND "))?;
+        }
+        if let Some(index_seek) = &self.index_seek {
+            write!(
+                f,
+                " USING INDEX {} ({})",
+                index_seek.index,
+                index_seek.columns.join(", ")
+            )?;
+        }
+
+        if let Some(filter) = &self.filter {
+            write!(f, " WHERE {}", filter)?;
+        }
+        write!(f, " (~{} rows)", self.estimated_rows)?;
+
+        for join in &self.joins {
+            write!(f, " CROSS JOIN {}", join)?;
+        }
+        if let Some(join_filter) = &self.join_filter {
+            write!(f, " FILTER {}", join_filter)?;
+        }
+        if let Some(group) = &self.group {
+            let keys: Vec<String> = group.keys.iter().map(|key| key.to_string()).collect();
+            write!(f, " GROUP BY {}", keys.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Rows with the same values for the keys make up a group, which yields a single row with a value
+// for each output
+#[derive(Debug, Clone)]
+pub struct GroupPlan {
+    pub keys: Vec<Expr>,
+    pub outputs: Vec<GroupOutput>,
+}
+
+#[derive(Debug, Clone)]
+pub enum GroupOutput {
+    // Evaluated against the first row of the group
+    Expr(Expr),
+    // Folds the argument over every row of the group
+    Aggregate(Aggregate, Option<Expr>),
+}
+
+impl fmt::Display for GroupOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupOutput::Expr(expr) => write!(f, "{}", expr),
+            GroupOutput::Aggregate(aggregate, arg) => write!(
+                f,
+                "{}",
+                Expr::Function {
+                    name: aggregate.name().to_string(),
+                    args: arg.iter().cloned().collect(),
+                }
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    // Open a read cursor over the rows produced by a scan plan
+    OpenRead {
+        cursor: CursorId,
+        scan: Box<ScanPlan>,
+    },
+    // Position the cursor on its first row, jumping to `if_empty` when there is none
+    Rewind {
+        cursor: CursorId,
+        if_empty: Address,
+    },
+    // Copy a column of the cursor's current row into a register
+    Column {
+        cursor: CursorId,
+        column: usize,
+        target: Register,
+    },
+    // Evaluate an expression against the cursor's current row (if any) into a register
+    Eval {
+        cursor: Option<CursorId>,
+        expr: Expr,
+        target: Register,
+    },
+    // Fold the argument, evaluated against the cursor's current row, into an aggregate's value
+    AggStep {
+        cursor: CursorId,
+        aggregate: Aggregate,
+        arg: Option<Expr>,
+        accumulator: Register,
+    },
+    // Emit registers [start, start + count) as a result row
+    ResultRow {
+        start: Register,
+        count: usize,
+    },
+    // Advance the cursor, jumping to `if_more` when there are rows left
+    Next {
+        cursor: CursorId,
+        if_more: Address,
+    },
+    // Jump to `target` unless the register holds a true value
+    IfNot {
+        condition: Register,
+        target: Address,
+    },
+    // Load a constant into a register
+    Value {
+        value: SQLType,
+        target: Register,
+    },
+    // Load the rowid for a new row into a register. For a table with a PRIMARY KEY, the value
+    // the rowid stands for is also loaded into `key`
+    NewRowid {
+        table: String,
+        target: Register,
+        key: Option<Register>,
+    },
+    // Insert registers [start, start + count) into a table, keyed by the rowid register
+    Insert {
+        table: String,
+        rowid: Register,
+        start: Register,
+        count: usize,
+    },
+    Halt,
+}
+
+impl Instruction {
+    fn opcode(&self) -> &'static str {
+        match self {
+            Instruction::OpenRead { .. } => "OpenRead",
+            Instruction::Rewind { .. } => "Rewind",
+            Instruction::Column { .. } => "Column",
+            Instruction::Eval { .. } => "Eval",
+            Instruction::AggStep { .. } => "AggStep",
+            Instruction::ResultRow { .. } => "ResultRow",
+            Instruction::Next { .. } => "Next",
+            Instruction::IfNot { .. } => "IfNot",
+            Instruction::Value { .. } => "Value",
+            Instruction::NewRowid { .. } => "NewRowid",
+            Instruction::Insert { .. } => "Insert",
+            Instruction::Halt => "Halt",
+        }
+    }
+
+    // Operands in the p1, p2, p3, p4 layout used by EXPLAIN
+    fn operands(&self) -> [String; 4] {
+        let empty = String::new;
+        match self {
+            Instruction::OpenRead { cursor, scan } => {
+                [cursor.to_string(), empty(), empty(), scan.to_string()]
+            }
+            Instruction::Rewind { cursor, if_empty } => {
+                [cursor.to_string(), if_empty.to_string(), empty(), empty()]
+            }
+            Instruction::Column {
+                cursor,
+                column,
+                target,
+            } => [
+                cursor.to_string(),
+                column.to_string(),
+                target.to_string(),
+                empty(),
+            ],
+            Instruction::Eval {
+                cursor,
+                expr,
+                target,
+            } => [
+                cursor.map(|cursor| cursor.to_string()).unwrap_or_default(),
+                target.to_string(),
+                empty(),
+                expr.to_string(),
+            ],
+            Instruction::AggStep {
+                cursor,
+                aggregate,
+                arg,
+                accumulator,
+            } => [
+                cursor.to_string(),
+                accumulator.to_string(),
+                empty(),
+                Expr::Function {
+                    name: aggregate.name().to_string(),
+                    args: arg.iter().cloned().collect(),
+                }
+                .to_string(),
+            ],
+            Instruction::ResultRow { start, count } => {
+                [start.to_string(), count.to_string(), empty(), empty()]
+            }
+            Instruction::Next { cursor, if_more } => {
+                [cursor.to_string(), if_more.to_string(), empty(), empty()]
+            }
+            Instruction::IfNot { condition, target } => {
+                [condition.to_string(), target.to_string(), empty(), empty()]
+            }
+            Instruction::Value { value, target } => {
+                [target.to_string(), empty(), empty(), value.to_string()]
+            }
+            Instruction::NewRowid { table, target, key } => [
+                target.to_string(),
+                key.map_or_else(empty, |key| key.to_string()),
+                empty(),
+                table.to_owned(),
+            ],
+            Instruction::Insert {
+                table,
+                rowid,
+                start,
+                count,
+            } => [
+                rowid.to_string(),
+                start.to_string(),
+                count.to_string(),
+                table.to_owned(),
+            ],
+            Instruction::Halt => [empty(), empty(), empty(), empty()],
+        }
+    }
+}
+
+// Table column that a result column returns as it is, rather than computing it
+#[derive(Debug, Clone)]
+pub struct ColumnOrigin {
+    pub table: String,
+    pub column: String,
+    pub column_type: ColumnItemType,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub result_columns: Vec<String>,
+    // Origin of every result column, for those that have one
+    pub result_origins: Vec<Option<ColumnOrigin>>,
+    pub num_registers: usize,
+    pub num_cursors: usize,
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn alloc_register(&mut self) -> Register {
+        self.num_registers += 1;
+        self.num_registers - 1
+    }
+
+    pub fn alloc_cursor(&mut self) -> CursorId {
+        self.num_cursors += 1;
+        self.num_cursors - 1
+    }
+
+    pub fn emit(&mut self, instruction: Instruction) -> Address {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+    pub fn next_address(&self) -> Address {
+        self.instructions.len()
+    }
+
+    pub fn patch_jump(&mut self, address: Address, target: Address) {
+        match &mut self.instructions[address] {
+            Instruction::Rewind { if_empty: jump, .. }
+            | Instruction::Next { if_more: jump, .. }
+            | Instruction::IfNot { target: jump, .. } => *jump = target,
+            other => panic!("Cannot patch jump target of {} instruction", other.opcode()),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pretty_table_builder = Builder::default();
+        pretty_table_builder.push_record(["addr", "opcode", "p1", "p2", "p3", "p4"]);
+
+        for (address, instruction) in self.instructions.iter().enumerate() {
+            let [p1, p2, p3, p4] = instruction.operands();
+            pretty_table_builder.push_record([
+                address.to_string(),
+                instruction.opcode().to_string(),
+                p1,
+                p2,
+                p3,
+                p4,
+            ]);
+        }
+
+        let mut pretty_table = pretty_table_builder.build();
+        pretty_table.with(Style::psql());
+
+        write!(f, "{}", pretty_table)
+    }
+}
@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::vm_error::VMError;
+
+// Callback that a connection calls every `n_ops` VM steps while it runs statements. It returns
+// true to interrupt the statement
+pub(crate) struct ProgressHandler {
+    n_ops: u64,
+    steps: u64,
+    callback: Box<dyn FnMut() -> bool>,
+}
+
+pub(crate) type SharedProgressHandler = Rc<RefCell<ProgressHandler>>;
+
+thread_local! {
+    // Handler of the connection whose statement runs on this thread
+    static WATCHED: RefCell<Option<SharedProgressHandler>> = const { RefCell::new(None) };
+}
+
+impl ProgressHandler {
+    pub(crate) fn new(n_ops: u64, callback: Box<dyn FnMut() -> bool>) -> Self {
+        Self {
+            n_ops,
+            steps: 0,
+            callback,
+        }
+    }
+}
+
+// Makes `step` report to the handler on the current thread while `f` runs. Steps keep being
+// counted across statements, so scripts of many short ones still reach the handler
+pub(crate) fn watch<T>(handler: Option<&SharedProgressHandler>, f: impl FnOnce() -> T) -> T {
+    let previous = WATCHED.with(|watched| watched.replace(handler.cloned()));
+    let result = f();
+    WATCHED.with(|watched| *watched.borrow_mut() = previous);
+    result
+}
+
+// Counts a VM step, calling the handler once enough of them have run
+pub(super) fn step() -> Result<(), VMError> {
+    // The callback may run statements on another connection, which watch their own handler
+    let Some(handler) = WATCHED.with(|watched| watched.borrow().clone()) else {
+        return Ok(());
+    };
+    let mut handler = handler.borrow_mut();
+    handler.steps += 1;
+    if handler.steps % handler.n_ops == 0 && (handler.callback)() {
+        return Err(VMError::Interrupted);
+    }
+    Ok(())
+}
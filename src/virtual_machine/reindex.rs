@@ -0,0 +1,27 @@
+use super::vm_error::VMError;
+use crate::backend::database::Database;
+use crate::sql_compiler::ReindexTokens;
+
+// Rebuilds what is derived from the rows of the table named by the statement, or of every table
+// of the database when it names none: the statistics kept in the catalog, the indexes and the
+// bloom filter
+pub(super) fn process_reindex(
+    reindex_tokens: ReindexTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    let table_names = match &reindex_tokens.table_name {
+        Some(table_name) => vec![table_name.as_ref()],
+        None => open_database.table_names(),
+    };
+    for table_name in table_names {
+        let table = open_database
+            .table(table_name)
+            .map_err(|err| VMError::TableReadError(table_name.to_string(), err))?;
+        table
+            .reindex()
+            .map_err(|err| VMError::TableWriteError(table_name.to_string(), err.into()))?;
+    }
+    Ok(())
+}
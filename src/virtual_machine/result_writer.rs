@@ -0,0 +1,262 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::ops::ControlFlow;
+use std::str::FromStr;
+
+use crate::backend::row::SQLType;
+
+#[cfg(any(feature = "parquet", feature = "arrow"))]
+pub(crate) mod column_kind;
+mod csv;
+mod json;
+mod line;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod table;
+
+pub use csv::{CsvOptions, CsvWriter};
+use json::JsonWriter;
+use line::LineWriter;
+#[cfg(feature = "parquet")]
+use parquet::ParquetWriter;
+use table::TableWriter;
+
+// Renders the rows produced by a query. `write_row` may stop the query early by returning
+// `ControlFlow::Break`
+pub trait ResultWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    #[default]
+    Table,
+    Csv,
+    Json,
+    Line,
+}
+
+impl OutputMode {
+    pub const NAMES: [&'static str; 4] = ["table", "csv", "json", "line"];
+}
+
+impl FromStr for OutputMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputMode::Table),
+            "csv" => Ok(OutputMode::Csv),
+            "json" => Ok(OutputMode::Json),
+            "line" => Ok(OutputMode::Line),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OutputMode::Table => "table",
+            OutputMode::Csv => "csv",
+            OutputMode::Json => "json",
+            OutputMode::Line => "line",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Shell settings that control how query results are displayed
+#[derive(Debug, Default)]
+pub struct OutputSettings {
+    pub mode: OutputMode,
+    // Print the time taken and pages read after each statement
+    pub timer: bool,
+    // Print the number of rows changed after each statement that modifies rows
+    pub changes: bool,
+    // Escape control characters in text also when results do not go to the terminal. CSV is left
+    // as it is there, as its quoting already keeps line breaks inside values
+    pub escape_redirected: bool,
+    // Stop running a script at its first failing statement
+    pub bail: bool,
+    // Maximum width of each column in table mode, by position. 0 leaves a column unrestricted
+    pub column_widths: Vec<usize>,
+    // Results go to this file instead of stdout when set
+    pub output_file: Option<File>,
+    // Whether the output file only applies to the next statement
+    pub once: bool,
+    // Write the results to the output file as Parquet instead of in the output mode
+    pub parquet: bool,
+    // Suggest indexes for the next statement instead of running it
+    pub expert: bool,
+    // Replace the default prompts, which show the name of the open database, when set
+    pub prompt: Option<String>,
+    pub continuation_prompt: Option<String>,
+}
+
+impl OutputSettings {
+    pub fn destination(&self) -> io::Result<Box<dyn Write>> {
+        match &self.output_file {
+            Some(file) => Ok(Box::new(file.try_clone()?)),
+            None => Ok(Box::new(io::stdout())),
+        }
+    }
+
+    pub fn writer(&self, columns: Vec<String>) -> io::Result<Box<dyn ResultWriter>> {
+        let out = self.destination()?;
+        #[cfg(feature = "parquet")]
+        if self.parquet {
+            return Ok(Box::new(ParquetWriter::new(out, columns)));
+        }
+        // JSON escapes control characters itself
+        let to_terminal = self.output_file.is_none() && io::stdout().is_terminal();
+        let escape = match self.mode {
+            OutputMode::Json => false,
+            OutputMode::Csv => to_terminal,
+            OutputMode::Table | OutputMode::Line => to_terminal || self.escape_redirected,
+        };
+        let columns = match escape {
+            true => columns
+                .iter()
+                .map(|column| escape_control(column).into_owned())
+                .collect(),
+            false => columns,
+        };
+
+        let writer: Box<dyn ResultWriter> = match self.mode {
+            OutputMode::Table => Box::new(TableWriter::new(
+                out,
+                self.output_file.is_none(),
+                columns,
+                self.column_widths.clone(),
+            )),
+            OutputMode::Csv => Box::new(CsvWriter::new(out, columns, CsvOptions::default())),
+            OutputMode::Json => Box::new(JsonWriter::new(out, columns)),
+            OutputMode::Line => Box::new(LineWriter::new(out, columns)),
+        };
+        Ok(match escape {
+            true => Box::new(EscapingWriter(writer)),
+            false => writer,
+        })
+    }
+
+    // Sends results back to stdout
+    pub fn reset_output(&mut self) {
+        self.output_file = None;
+        self.once = false;
+        self.parquet = false;
+    }
+}
+
+// Shows the control characters of a text as escapes, so that values cannot move the cursor, clear
+// the screen or otherwise garble the terminal. Line breaks and tabs are written as `\n`, `\r` and
+// `\t`, and everything else as its code in hex. Backslashes are doubled, so that escapes can be
+// told apart from the same characters in the text
+pub fn escape_control(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| c.is_control() || c == '\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+// Escapes the control characters of text values before passing rows on to another writer
+struct EscapingWriter(Box<dyn ResultWriter>);
+
+impl ResultWriter for EscapingWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>> {
+        let row = row
+            .into_iter()
+            .map(|value| match value {
+                SQLType::Text(text) => match escape_control(&text) {
+                    Cow::Borrowed(_) => SQLType::Text(text),
+                    Cow::Owned(escaped) => SQLType::Text(escaped),
+                },
+                value => value,
+            })
+            .collect();
+        self.0.write_row(row)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.0.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::{env, process};
+
+    use super::{escape_control, OutputMode, OutputSettings};
+    use crate::backend::database::{Database, MEMORY_PATH};
+    use crate::sql_compiler::parse_statement;
+    use crate::virtual_machine::execute_statement;
+
+    fn write_to_file(mode: OutputMode, escape_redirected: bool) -> String {
+        let path = env::temp_dir().join(format!("sql_rs_result_writer_{}_{mode}", process::id()));
+        let mut db = Database::open(MEMORY_PATH).unwrap();
+        for sql in [
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT);",
+            "INSERT INTO t VALUES (1, 'a\nb\\c');",
+        ] {
+            execute_statement(
+                parse_statement(sql).unwrap(),
+                Some(&mut db),
+                &OutputSettings::default(),
+            )
+            .unwrap();
+        }
+
+        let output_settings = OutputSettings {
+            mode,
+            escape_redirected,
+            output_file: Some(File::create(&path).unwrap()),
+            ..OutputSettings::default()
+        };
+        execute_statement(
+            parse_statement("SELECT name FROM t;").unwrap(),
+            Some(&mut db),
+            &output_settings,
+        )
+        .unwrap();
+        drop(output_settings);
+
+        let output = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        output
+    }
+
+    #[test]
+    fn csv_in_files_keeps_values_as_they_are() {
+        assert_eq!(write_to_file(OutputMode::Csv, false), "name\n\"a\nb\\c\"\n");
+        assert_eq!(write_to_file(OutputMode::Csv, true), "name\n\"a\nb\\c\"\n");
+    }
+
+    #[test]
+    fn redirected_output_is_raw_unless_asked_to_escape() {
+        assert!(write_to_file(OutputMode::Line, false).contains("a\nb\\c"));
+        assert!(write_to_file(OutputMode::Line, true).contains("a\\nb\\\\c"));
+    }
+
+    #[test]
+    fn escapes_can_be_told_apart_from_backslashes() {
+        assert_eq!(escape_control("plain"), "plain");
+        assert_eq!(escape_control("a\nb"), "a\\nb");
+        assert_eq!(escape_control("a\\nb"), "a\\\\nb");
+        assert_eq!(escape_control("\x1b[2J"), "\\x1b[2J");
+    }
+}
@@ -0,0 +1,73 @@
+use crate::backend::row::SQLType;
+
+// Type of a column, chosen from the values it holds once every row is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ColumnKind {
+    // Signed integers of the given width in bits
+    Int(u8),
+    UInt64,
+    Uuid,
+    Text,
+}
+
+impl ColumnKind {
+    fn of_value(value: &SQLType) -> Option<Self> {
+        match value {
+            SQLType::Null => None,
+            SQLType::SmallInt(_) => Some(ColumnKind::Int(16)),
+            SQLType::Integer(_) => Some(ColumnKind::Int(32)),
+            SQLType::BigInt(_) => Some(ColumnKind::Int(64)),
+            SQLType::UBigInt(_) => Some(ColumnKind::UInt64),
+            SQLType::Uuid(_) => Some(ColumnKind::Uuid),
+            SQLType::Text(_) => Some(ColumnKind::Text),
+        }
+    }
+
+    // Narrowest kind holding every value. Columns mixing other types, integers that no single
+    // kind holds and columns of NULLs only are written as text
+    pub(crate) fn of_column(values: &[&SQLType]) -> Self {
+        let kinds: Vec<ColumnKind> = values
+            .iter()
+            .filter_map(|value| Self::of_value(value))
+            .collect();
+        let Some(&first) = kinds.first() else {
+            return ColumnKind::Text;
+        };
+        if kinds.iter().all(|kind| *kind == first) {
+            return first;
+        }
+
+        let integers: Option<Vec<i128>> = values
+            .iter()
+            .filter(|value| ***value != SQLType::Null)
+            .map(|value| integer(value))
+            .collect();
+        let Some(integers) = integers else {
+            return ColumnKind::Text;
+        };
+        if !kinds.contains(&ColumnKind::UInt64) {
+            let bits = kinds.iter().map(|kind| match kind {
+                ColumnKind::Int(bits) => *bits,
+                _ => 64,
+            });
+            return ColumnKind::Int(bits.max().unwrap_or(64));
+        }
+        if integers.iter().all(|integer| *integer >= 0) {
+            ColumnKind::UInt64
+        } else if integers.iter().all(|integer| *integer <= i64::MAX.into()) {
+            ColumnKind::Int(64)
+        } else {
+            ColumnKind::Text
+        }
+    }
+}
+
+pub(crate) fn integer(value: &SQLType) -> Option<i128> {
+    match value {
+        SQLType::SmallInt(integer) => Some((*integer).into()),
+        SQLType::Integer(integer) => Some((*integer).into()),
+        SQLType::BigInt(integer) => Some((*integer).into()),
+        SQLType::UBigInt(integer) => Some((*integer).into()),
+        _ => None,
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use super::ResultWriter;
+use crate::backend::row::SQLType;
+
+/// How CSV output separates and quotes fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub quote: char,
+    /// Quote every field instead of only those that need it
+    pub quote_all: bool,
+    /// Start with a line holding the column names
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            quote_all: false,
+            header: true,
+        }
+    }
+}
+
+// Prints a header line followed by one delimited line per row. NULL is written as an empty
+// field.
+pub struct CsvWriter {
+    out: Box<dyn Write>,
+    columns: Vec<String>,
+    options: CsvOptions,
+    header_written: bool,
+}
+
+impl CsvWriter {
+    pub fn new(out: Box<dyn Write>, columns: Vec<String>, options: CsvOptions) -> Self {
+        Self {
+            out,
+            columns,
+            header_written: !options.header,
+            options,
+        }
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            let header = self.format_record(self.columns.iter().map(String::as_str));
+            writeln!(self.out, "{}", header)?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    fn format_record<'a>(&self, fields: impl Iterator<Item = &'a str>) -> String {
+        fields
+            .map(|field| self.escape(field))
+            .collect::<Vec<_>>()
+            .join(&self.options.delimiter.to_string())
+    }
+
+    // Fields holding delimiters, quotes, line breaks or surrounding spaces are quoted, with inner
+    // quotes doubled
+    fn escape(&self, field: &str) -> String {
+        let CsvOptions {
+            delimiter, quote, ..
+        } = self.options;
+        let needs_quotes = self.options.quote_all
+            || field.contains([delimiter, quote, '\n', '\r'])
+            || field.starts_with(' ')
+            || field.ends_with(' ');
+        if needs_quotes {
+            let doubled_quote = format!("{}{}", quote, quote);
+            format!("{}{}{}", quote, field.replace(quote, &doubled_quote), quote)
+        } else {
+            field.to_string()
+        }
+    }
+}
+
+impl ResultWriter for CsvWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>> {
+        self.write_header()?;
+        let fields: Vec<String> = row
+            .into_iter()
+            .map(|value| match value {
+                SQLType::Null => String::new(),
+                value => value.to_string(),
+            })
+            .collect();
+        let record = self.format_record(fields.iter().map(String::as_str));
+        writeln!(self.out, "{}", record)?;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.write_header()?;
+        self.out.flush()
+    }
+}
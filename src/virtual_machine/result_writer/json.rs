@@ -0,0 +1,42 @@
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use super::ResultWriter;
+use crate::backend::row::SQLType;
+use crate::json::JsonValue;
+
+// Prints the result as a JSON array holding one object per row, keyed by column name
+pub struct JsonWriter {
+    out: Box<dyn Write>,
+    columns: Vec<String>,
+    rows_written: usize,
+}
+
+impl JsonWriter {
+    pub fn new(out: Box<dyn Write>, columns: Vec<String>) -> Self {
+        Self {
+            out,
+            columns,
+            rows_written: 0,
+        }
+    }
+}
+
+impl ResultWriter for JsonWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>> {
+        let separator = if self.rows_written == 0 { "[" } else { ",\n" };
+        let object = JsonValue::from_row(&self.columns, row);
+        write!(self.out, "{}{}", separator, object)?;
+        self.rows_written += 1;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        if self.rows_written == 0 {
+            writeln!(self.out, "[]")?;
+        } else {
+            writeln!(self.out, "]")?;
+        }
+        self.out.flush()
+    }
+}
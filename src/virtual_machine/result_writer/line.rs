@@ -0,0 +1,53 @@
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use super::ResultWriter;
+use crate::backend::row::SQLType;
+
+// Prints every value on its own "column = value" line, with a blank line between rows
+pub struct LineWriter {
+    out: Box<dyn Write>,
+    columns: Vec<String>,
+    name_width: usize,
+    rows_written: usize,
+}
+
+impl LineWriter {
+    pub fn new(out: Box<dyn Write>, columns: Vec<String>) -> Self {
+        let name_width = columns
+            .iter()
+            .map(|column| column.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        Self {
+            out,
+            columns,
+            name_width,
+            rows_written: 0,
+        }
+    }
+}
+
+impl ResultWriter for LineWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>> {
+        if self.rows_written > 0 {
+            writeln!(self.out)?;
+        }
+        for (column, value) in self.columns.iter().zip(&row) {
+            writeln!(
+                self.out,
+                "{:>width$} = {}",
+                column,
+                value,
+                width = self.name_width
+            )?;
+        }
+        self.rows_written += 1;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,718 @@
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use super::column_kind::{integer, ColumnKind};
+use super::ResultWriter;
+use crate::backend::row::SQLType;
+
+const MAGIC: &[u8] = b"PAR1";
+
+// Physical types, repetition types, encodings and converted types, numbered as in the Parquet
+// format
+const INT32: i32 = 1;
+const INT64: i32 = 2;
+const BYTE_ARRAY: i32 = 6;
+const FIXED_LEN_BYTE_ARRAY: i32 = 7;
+const OPTIONAL: i32 = 1;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const UTF8: i32 = 0;
+const UINT_64: i32 = 14;
+const INT_16: i32 = 16;
+const INT_32: i32 = 17;
+const INT_64: i32 = 18;
+
+impl ColumnKind {
+    fn physical_type(&self) -> i32 {
+        match self {
+            ColumnKind::Int(16 | 32) => INT32,
+            ColumnKind::Int(_) | ColumnKind::UInt64 => INT64,
+            ColumnKind::Uuid => FIXED_LEN_BYTE_ARRAY,
+            ColumnKind::Text => BYTE_ARRAY,
+        }
+    }
+
+    // Values are written in PLAIN encoding: little-endian integers, UUIDs as their 16 bytes and
+    // text preceded by its length
+    fn encode(&self, value: &SQLType, out: &mut Vec<u8>) {
+        match (self, value) {
+            (ColumnKind::Int(16 | 32), value) => {
+                let integer = integer(value).unwrap_or_default() as i32;
+                out.extend(integer.to_le_bytes());
+            }
+            (ColumnKind::Int(_), value) => {
+                let integer = integer(value).unwrap_or_default() as i64;
+                out.extend(integer.to_le_bytes());
+            }
+            (ColumnKind::UInt64, value) => {
+                let integer = integer(value).unwrap_or_default() as u64;
+                out.extend(integer.to_le_bytes());
+            }
+            (ColumnKind::Uuid, SQLType::Uuid(uuid)) => out.extend(uuid.as_bytes()),
+            (_, value) => {
+                let text = value.to_string();
+                out.extend((text.len() as u32).to_le_bytes());
+                out.extend(text.as_bytes());
+            }
+        }
+    }
+
+    // SchemaElement describing a column with values of this kind
+    fn write_schema_element(&self, name: &str, thrift: &mut Thrift) {
+        thrift.enter();
+        thrift.i32(1, self.physical_type());
+        if *self == ColumnKind::Uuid {
+            thrift.i32(2, 16);
+        }
+        thrift.i32(3, OPTIONAL);
+        thrift.binary(4, name.as_bytes());
+        match self {
+            ColumnKind::Int(16) => thrift.i32(6, INT_16),
+            ColumnKind::Int(32) => thrift.i32(6, INT_32),
+            ColumnKind::Int(_) => thrift.i32(6, INT_64),
+            ColumnKind::UInt64 => thrift.i32(6, UINT_64),
+            ColumnKind::Text => thrift.i32(6, UTF8),
+            ColumnKind::Uuid => {}
+        }
+        // The logical type, a union of structs of which only integers have fields
+        thrift.begin(10);
+        match self {
+            ColumnKind::Int(bits) => {
+                thrift.begin(10);
+                thrift.byte(1, *bits as i8);
+                thrift.bool(2, true);
+                thrift.end();
+            }
+            ColumnKind::UInt64 => {
+                thrift.begin(10);
+                thrift.byte(1, 64);
+                thrift.bool(2, false);
+                thrift.end();
+            }
+            ColumnKind::Uuid => {
+                thrift.begin(14);
+                thrift.end();
+            }
+            ColumnKind::Text => {
+                thrift.begin(1);
+                thrift.end();
+            }
+        }
+        thrift.end();
+        thrift.end();
+    }
+}
+
+// Unsigned LEB128, seven bits per byte starting from the lowest
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// Encoder for the Thrift compact protocol, which Parquet writes its metadata in. Fields are
+// written in increasing order of id, and nested structs are opened with `begin` or `enter` and
+// closed with `end`
+struct Thrift {
+    bytes: Vec<u8>,
+    last_field_id: i16,
+    enclosing_field_ids: Vec<i16>,
+}
+
+impl Thrift {
+    const BOOL_TRUE: u8 = 1;
+    const BOOL_FALSE: u8 = 2;
+    const BYTE: u8 = 3;
+    const I32: u8 = 5;
+    const I64: u8 = 6;
+    const BINARY: u8 = 8;
+    const LIST: u8 = 9;
+    const STRUCT: u8 = 12;
+
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            last_field_id: 0,
+            enclosing_field_ids: Vec::new(),
+        }
+    }
+
+    // Ends the outermost struct
+    fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0);
+        self.bytes
+    }
+
+    fn varint(&mut self, value: u64) {
+        write_varint(&mut self.bytes, value);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    // Fields close enough to the previous one store the difference of their ids with the type
+    fn field(&mut self, id: i16, field_type: u8) {
+        match id - self.last_field_id {
+            delta @ 1..=15 => self.bytes.push(((delta as u8) << 4) | field_type),
+            _ => {
+                self.bytes.push(field_type);
+                self.zigzag(id.into());
+            }
+        }
+        self.last_field_id = id;
+    }
+
+    fn bool(&mut self, id: i16, value: bool) {
+        let field_type = if value {
+            Self::BOOL_TRUE
+        } else {
+            Self::BOOL_FALSE
+        };
+        self.field(id, field_type);
+    }
+
+    fn byte(&mut self, id: i16, value: i8) {
+        self.field(id, Self::BYTE);
+        self.bytes.push(value as u8);
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, Self::I32);
+        self.zigzag(value.into());
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, Self::I64);
+        self.zigzag(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, Self::BINARY);
+        self.binary_element(value);
+    }
+
+    fn binary_element(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.bytes.extend(value);
+    }
+
+    fn i32_element(&mut self, value: i32) {
+        self.zigzag(value.into());
+    }
+
+    // The elements follow, each struct element between `enter` and `end`
+    fn list(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field(id, Self::LIST);
+        match len {
+            0..15 => self.bytes.push(((len as u8) << 4) | element_type),
+            _ => {
+                self.bytes.push(0xf0 | element_type);
+                self.varint(len as u64);
+            }
+        }
+    }
+
+    fn begin(&mut self, id: i16) {
+        self.field(id, Self::STRUCT);
+        self.enter();
+    }
+
+    fn enter(&mut self) {
+        self.enclosing_field_ids.push(self.last_field_id);
+        self.last_field_id = 0;
+    }
+
+    fn end(&mut self) {
+        self.bytes.push(0);
+        self.last_field_id = self.enclosing_field_ids.pop().unwrap_or_default();
+    }
+}
+
+// Definition levels of an optional column, 1 for values and 0 for NULLs, as runs of the
+// RLE/bit-packed hybrid encoding preceded by their length
+fn definition_levels(values: &[&SQLType]) -> Vec<u8> {
+    let mut runs = Vec::new();
+    let mut rest = values;
+    while let Some(first) = rest.first() {
+        let defined = **first != SQLType::Null;
+        let len = rest
+            .iter()
+            .take_while(|value| (***value != SQLType::Null) == defined)
+            .count();
+        write_varint(&mut runs, (len as u64) << 1);
+        runs.push(defined.into());
+        rest = &rest[len..];
+    }
+
+    let mut levels = (runs.len() as u32).to_le_bytes().to_vec();
+    levels.extend(runs);
+    levels
+}
+
+// Where a column's single data page was written
+struct ColumnChunk {
+    offset: usize,
+    size: usize,
+}
+
+// Writes the result as a Parquet file once all rows are in, with every column optional, a
+// single row group and a single uncompressed data page per column
+pub struct ParquetWriter {
+    out: Box<dyn Write>,
+    columns: Vec<String>,
+    rows: Vec<Vec<SQLType>>,
+}
+
+impl ParquetWriter {
+    pub fn new(out: Box<dyn Write>, columns: Vec<String>) -> Self {
+        Self {
+            out,
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    fn values(&self, idx: usize) -> Vec<&SQLType> {
+        self.rows
+            .iter()
+            .map(|row| row.get(idx).unwrap_or(&SQLType::Null))
+            .collect()
+    }
+
+    fn write_column(&self, idx: usize, kind: ColumnKind, file: &mut Vec<u8>) -> ColumnChunk {
+        let values = self.values(idx);
+        let mut page = definition_levels(&values);
+        for value in values.iter().filter(|value| ***value != SQLType::Null) {
+            kind.encode(value, &mut page);
+        }
+
+        let mut header = Thrift::new();
+        header.i32(1, 0);
+        header.i32(2, page.len() as i32);
+        header.i32(3, page.len() as i32);
+        header.begin(5);
+        header.i32(1, values.len() as i32);
+        header.i32(2, PLAIN);
+        header.i32(3, RLE);
+        header.i32(4, RLE);
+        header.end();
+        let header = header.finish();
+
+        let offset = file.len();
+        file.extend(header);
+        file.extend(page);
+        ColumnChunk {
+            offset,
+            size: file.len() - offset,
+        }
+    }
+
+    // Chunks are left out when there are no rows, as the file then has no row groups
+    fn file_metadata(&self, kinds: &[ColumnKind], chunks: &[ColumnChunk]) -> Vec<u8> {
+        let mut thrift = Thrift::new();
+        thrift.i32(1, 1);
+
+        thrift.list(2, Thrift::STRUCT, self.columns.len() + 1);
+        thrift.enter();
+        thrift.binary(4, b"schema");
+        thrift.i32(5, self.columns.len() as i32);
+        thrift.end();
+        for (name, kind) in self.columns.iter().zip(kinds) {
+            kind.write_schema_element(name, &mut thrift);
+        }
+
+        thrift.i64(3, self.rows.len() as i64);
+
+        let row_groups = if chunks.is_empty() { 0 } else { 1 };
+        thrift.list(4, Thrift::STRUCT, row_groups);
+        if row_groups > 0 {
+            thrift.enter();
+            thrift.list(1, Thrift::STRUCT, chunks.len());
+            for ((name, kind), chunk) in self.columns.iter().zip(kinds).zip(chunks) {
+                thrift.enter();
+                thrift.i64(2, chunk.offset as i64);
+                thrift.begin(3);
+                thrift.i32(1, kind.physical_type());
+                thrift.list(2, Thrift::I32, 2);
+                thrift.i32_element(PLAIN);
+                thrift.i32_element(RLE);
+                thrift.list(3, Thrift::BINARY, 1);
+                thrift.binary_element(name.as_bytes());
+                thrift.i32(4, 0);
+                thrift.i64(5, self.rows.len() as i64);
+                thrift.i64(6, chunk.size as i64);
+                thrift.i64(7, chunk.size as i64);
+                thrift.i64(9, chunk.offset as i64);
+                thrift.end();
+                thrift.end();
+            }
+            let total_size: usize = chunks.iter().map(|chunk| chunk.size).sum();
+            thrift.i64(2, total_size as i64);
+            thrift.i64(3, self.rows.len() as i64);
+            thrift.end();
+        }
+
+        thrift.binary(6, b"sql_rs");
+        thrift.finish()
+    }
+}
+
+impl ResultWriter for ParquetWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>> {
+        self.rows.push(row);
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let kinds: Vec<ColumnKind> = (0..self.columns.len())
+            .map(|idx| ColumnKind::of_column(&self.values(idx)))
+            .collect();
+
+        let mut file = MAGIC.to_vec();
+        let mut chunks = Vec::new();
+        if !self.rows.is_empty() {
+            for (idx, kind) in kinds.iter().enumerate() {
+                chunks.push(self.write_column(idx, *kind, &mut file));
+            }
+        }
+        let metadata = self.file_metadata(&kinds, &chunks);
+        file.extend(&metadata);
+        file.extend((metadata.len() as u32).to_le_bytes());
+        file.extend(MAGIC);
+
+        self.out.write_all(&file)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::process;
+
+    use super::{ParquetWriter, ResultWriter};
+    use crate::backend::row::SQLType;
+    use crate::backend::uuid::Uuid;
+
+    // A value of the Thrift compact protocol, decoded without knowing the struct it belongs to
+    #[derive(Debug, Clone, PartialEq)]
+    enum Value {
+        Bool(bool),
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Value>),
+        Struct(Vec<(i16, Value)>),
+    }
+
+    impl Value {
+        fn field(&self, id: i16) -> &Value {
+            let Value::Struct(fields) = self else {
+                panic!("{:?} is not a struct", self);
+            };
+            let found = fields.iter().find(|(field_id, _)| *field_id == id);
+            &found
+                .unwrap_or_else(|| panic!("no field {} in {:?}", id, self))
+                .1
+        }
+
+        fn has_field(&self, id: i16) -> bool {
+            let Value::Struct(fields) = self else {
+                return false;
+            };
+            fields.iter().any(|(field_id, _)| *field_id == id)
+        }
+
+        fn int(&self) -> i64 {
+            match self {
+                Value::Int(int) => *int,
+                value => panic!("{:?} is not an integer", value),
+            }
+        }
+
+        fn text(&self) -> String {
+            match self {
+                Value::Binary(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+                value => panic!("{:?} is not binary", value),
+            }
+        }
+
+        fn list(&self) -> &[Value] {
+            match self {
+                Value::List(elements) => elements,
+                value => panic!("{:?} is not a list", value),
+            }
+        }
+    }
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn new(bytes: &'a [u8], pos: usize) -> Self {
+            Self { bytes, pos }
+        }
+
+        fn take(&mut self, len: usize) -> &'a [u8] {
+            let taken = &self.bytes[self.pos..self.pos + len];
+            self.pos += len;
+            taken
+        }
+
+        fn byte(&mut self) -> u8 {
+            self.take(1)[0]
+        }
+
+        fn varint(&mut self) -> u64 {
+            let mut value = 0;
+            for shift in (0..64).step_by(7) {
+                let byte = self.byte();
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            value
+        }
+
+        fn zigzag(&mut self) -> i64 {
+            let value = self.varint();
+            (value >> 1) as i64 ^ -((value & 1) as i64)
+        }
+
+        fn value(&mut self, value_type: u8) -> Value {
+            match value_type {
+                1 => Value::Bool(true),
+                2 => Value::Bool(false),
+                3 => Value::Int(self.byte() as i8 as i64),
+                4..=6 => Value::Int(self.zigzag()),
+                8 => {
+                    let len = self.varint() as usize;
+                    Value::Binary(self.take(len).to_vec())
+                }
+                9 => {
+                    let header = self.byte();
+                    let len = match header >> 4 {
+                        15 => self.varint() as usize,
+                        len => len as usize,
+                    };
+                    Value::List((0..len).map(|_| self.value(header & 0x0f)).collect())
+                }
+                12 => Value::Struct(self.fields()),
+                value_type => panic!("unexpected type {}", value_type),
+            }
+        }
+
+        fn fields(&mut self) -> Vec<(i16, Value)> {
+            let mut fields = Vec::new();
+            let mut last_id = 0;
+            loop {
+                let header = self.byte();
+                if header == 0 {
+                    return fields;
+                }
+                let id = match header >> 4 {
+                    0 => self.zigzag() as i16,
+                    delta => last_id + delta as i16,
+                };
+                last_id = id;
+                fields.push((id, self.value(header & 0x0f)));
+            }
+        }
+    }
+
+    // Decodes a file as the writer lays it out, returning its column names and rows
+    fn read_parquet(file: &[u8]) -> (Vec<String>, Vec<Vec<SQLType>>) {
+        assert_eq!(&file[..4], b"PAR1");
+        assert_eq!(&file[file.len() - 4..], b"PAR1");
+        let footer_start = file.len() - 8;
+        let metadata_len = u32::from_le_bytes(file[footer_start..][..4].try_into().unwrap());
+        let mut reader = Reader::new(file, footer_start - metadata_len as usize);
+        let metadata = Value::Struct(reader.fields());
+        assert_eq!(reader.pos, footer_start);
+
+        let schema = metadata.field(2).list();
+        assert_eq!(schema[0].field(5).int() as usize, schema.len() - 1);
+        let columns = &schema[1..];
+        let num_rows = metadata.field(3).int() as usize;
+        let mut rows = vec![Vec::new(); num_rows];
+        let row_groups = metadata.field(4).list();
+        assert_eq!(row_groups.len(), usize::from(num_rows > 0));
+        for row_group in row_groups {
+            assert_eq!(row_group.field(3).int() as usize, num_rows);
+            let chunks = row_group.field(1).list();
+            assert_eq!(chunks.len(), columns.len());
+            for (column, chunk) in columns.iter().zip(chunks) {
+                let chunk_metadata = chunk.field(3);
+                let offset = chunk_metadata.field(9).int() as usize;
+                assert_eq!(chunk.field(2).int() as usize, offset);
+                assert_eq!(chunk_metadata.field(1), column.field(1));
+                assert_eq!(chunk_metadata.field(3).list()[0], *column.field(4));
+                let (values, end) = read_page(file, offset, column);
+                assert_eq!(end - offset, chunk_metadata.field(6).int() as usize);
+                for (row, value) in rows.iter_mut().zip(values) {
+                    row.push(value);
+                }
+            }
+        }
+
+        let names = columns
+            .iter()
+            .map(|column| column.field(4).text())
+            .collect();
+        (names, rows)
+    }
+
+    // Values of the single data page of a column, decoded by the column's schema element, and
+    // the offset right past the page
+    fn read_page(file: &[u8], offset: usize, column: &Value) -> (Vec<SQLType>, usize) {
+        let mut reader = Reader::new(file, offset);
+        let header = Value::Struct(reader.fields());
+        assert_eq!(header.field(1).int(), 0);
+        assert_eq!(header.field(2).int(), header.field(3).int());
+        let page_end = reader.pos + header.field(3).int() as usize;
+        let data_page = header.field(5);
+        let num_values = data_page.field(1).int() as usize;
+
+        // Definition levels come as RLE runs of a one-bit width, one byte per run value
+        let levels_len = u32::from_le_bytes(reader.take(4).try_into().unwrap()) as usize;
+        let levels_end = reader.pos + levels_len;
+        let mut defined = Vec::new();
+        while reader.pos < levels_end {
+            let run = reader.varint();
+            assert_eq!(run & 1, 0, "bit-packed runs are not written");
+            let value = reader.byte();
+            defined.extend(std::iter::repeat_n(value == 1, (run >> 1) as usize));
+        }
+        assert_eq!(defined.len(), num_values);
+
+        let converted_type = column.has_field(6).then(|| column.field(6).int());
+        let mut values = Vec::new();
+        for defined in defined {
+            if !defined {
+                values.push(SQLType::Null);
+                continue;
+            }
+            let value = match (column.field(1).int(), converted_type) {
+                (1, Some(16)) => {
+                    let bytes = reader.take(4).try_into().unwrap();
+                    SQLType::SmallInt(i32::from_le_bytes(bytes) as i16)
+                }
+                (1, Some(17)) => {
+                    SQLType::Integer(i32::from_le_bytes(reader.take(4).try_into().unwrap()))
+                }
+                (2, Some(18)) => {
+                    SQLType::BigInt(i64::from_le_bytes(reader.take(8).try_into().unwrap()))
+                }
+                (2, Some(14)) => {
+                    SQLType::UBigInt(u64::from_le_bytes(reader.take(8).try_into().unwrap()))
+                }
+                (6, Some(0)) => {
+                    let len = u32::from_le_bytes(reader.take(4).try_into().unwrap()) as usize;
+                    SQLType::Text(String::from_utf8(reader.take(len).to_vec()).unwrap())
+                }
+                (7, None) => {
+                    assert_eq!(column.field(2).int(), 16);
+                    SQLType::Uuid(Uuid::from_bytes(reader.take(16).try_into().unwrap()))
+                }
+                types => panic!("unexpected column types {:?}", types),
+            };
+            values.push(value);
+        }
+        assert_eq!(reader.pos, page_end);
+        (values, page_end)
+    }
+
+    fn write_parquet(name: &str, columns: &[&str], rows: &[Vec<SQLType>]) -> Vec<u8> {
+        let path = env::temp_dir().join(format!("sql_rs_{}_{}.parquet", name, process::id()));
+        let columns = columns.iter().map(|column| column.to_string()).collect();
+        let mut writer = Box::new(ParquetWriter::new(
+            Box::new(File::create(&path).unwrap()),
+            columns,
+        ));
+        for row in rows {
+            assert!(writer.write_row(row.clone()).unwrap().is_continue());
+        }
+        writer.finish().unwrap();
+        let file = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        file
+    }
+
+    #[test]
+    fn round_trips_every_column_kind() {
+        let uuid = Uuid::from_bytes(*b"0123456789abcdef");
+        let columns = ["small", "int", "big", "unsigned", "id", "name"];
+        let rows = vec![
+            vec![
+                SQLType::SmallInt(-1),
+                SQLType::Integer(1),
+                SQLType::BigInt(i64::MIN),
+                SQLType::UBigInt(u64::MAX),
+                SQLType::Uuid(uuid),
+                SQLType::Text("one".to_string()),
+            ],
+            vec![
+                SQLType::Null,
+                SQLType::Integer(-2),
+                SQLType::Null,
+                SQLType::UBigInt(2),
+                SQLType::Null,
+                SQLType::Text(String::new()),
+            ],
+            vec![
+                SQLType::SmallInt(3),
+                SQLType::Null,
+                SQLType::BigInt(3),
+                SQLType::Null,
+                SQLType::Uuid(uuid),
+                SQLType::Null,
+            ],
+        ];
+
+        let (names, read) = read_parquet(&write_parquet("round_trip", &columns, &rows));
+        assert_eq!(names, columns);
+        assert_eq!(read, rows);
+    }
+
+    #[test]
+    fn round_trips_long_runs_and_mixed_columns() {
+        // Over fifteen schema elements and runs longer than a byte exercise the long forms of
+        // list headers and varints
+        let columns: Vec<String> = (0..20).map(|idx| format!("c{}", idx)).collect();
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        let mut rows: Vec<Vec<SQLType>> = (0..300)
+            .map(|idx| {
+                (0..20)
+                    .map(|column| match idx % 150 < 140 {
+                        true => SQLType::Integer(idx * column),
+                        false => SQLType::Null,
+                    })
+                    .collect()
+            })
+            .collect();
+        rows[7][0] = SQLType::Text("seven".to_string());
+
+        let (names, read) = read_parquet(&write_parquet("long_runs", &columns, &rows));
+        assert_eq!(names, columns);
+        // A column mixing integers and text is written as text
+        for row in &mut rows {
+            if row[0] != SQLType::Null {
+                row[0] = SQLType::Text(row[0].to_string());
+            }
+        }
+        assert_eq!(read, rows);
+    }
+
+    #[test]
+    fn writes_no_row_groups_without_rows() {
+        let (names, rows) = read_parquet(&write_parquet("empty", &["id", "name"], &[]));
+        assert_eq!(names, ["id", "name"]);
+        assert!(rows.is_empty());
+    }
+}
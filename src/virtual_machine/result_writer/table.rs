@@ -0,0 +1,121 @@
+use std::io::{self, IsTerminal, Write};
+use std::ops::ControlFlow;
+
+use console::{Key, Term};
+use tabled::{
+    builder::Builder,
+    settings::{object::Columns, peaker::PriorityMax, style::Style, Modify, Width},
+};
+
+use super::ResultWriter;
+use crate::backend::row::SQLType;
+
+// Lines taken by the table header, its separator and the "more" prompt
+const PAGER_RESERVED_LINES: usize = 3;
+
+// Prints result rows as tables in screen-sized chunks, waiting for the user between chunks.
+// When the output is not a terminal all rows are printed as a single table.
+pub struct TableWriter {
+    out: Box<dyn Write>,
+    columns: Vec<String>,
+    // Columns with a non-zero width are truncated to it
+    column_widths: Vec<usize>,
+    chunk: Vec<Vec<String>>,
+    page_size: Option<usize>,
+    // Tables wider than the terminal get their widest columns wrapped to fit
+    term_width: Option<usize>,
+    printed_chunks: usize,
+    term: Term,
+}
+
+impl TableWriter {
+    pub fn new(
+        out: Box<dyn Write>,
+        interactive: bool,
+        columns: Vec<String>,
+        column_widths: Vec<usize>,
+    ) -> Self {
+        let term = Term::stdout();
+        let (page_size, term_width) = match interactive && term.is_term() {
+            true => {
+                let (rows, cols) = term.size();
+                let page_size = (rows as usize).saturating_sub(PAGER_RESERVED_LINES).max(1);
+                // Paging needs someone at the keyboard to ask for the next chunk
+                let page_size = io::stdin().is_terminal().then_some(page_size);
+                (page_size, Some(cols as usize))
+            }
+            false => (None, None),
+        };
+
+        Self {
+            out,
+            columns,
+            column_widths,
+            chunk: Vec::new(),
+            page_size,
+            term_width,
+            printed_chunks: 0,
+            term,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut pretty_table_builder = Builder::from(std::mem::take(&mut self.chunk));
+        pretty_table_builder.insert_record(0, self.columns.clone());
+
+        let mut pretty_table = pretty_table_builder.build();
+        pretty_table.with(Style::psql());
+        for (column, &width) in self.column_widths.iter().enumerate() {
+            if width > 0 {
+                pretty_table.with(
+                    Modify::new(Columns::single(column)).with(Width::truncate(width).suffix("...")),
+                );
+            }
+        }
+        if let Some(term_width) = self.term_width {
+            pretty_table.with(Width::wrap(term_width).priority(PriorityMax));
+        }
+
+        writeln!(self.out, "{}", pretty_table)?;
+        self.printed_chunks += 1;
+        Ok(())
+    }
+
+    fn prompt_more(&mut self) -> ControlFlow<()> {
+        let _ = write!(self.term, "-- press enter for more, q to quit --");
+        let _ = self.term.flush();
+
+        let flow = loop {
+            match self.term.read_key() {
+                Ok(Key::Enter) | Ok(Key::Char(' ')) => break ControlFlow::Continue(()),
+                Ok(Key::Char('q')) | Ok(Key::Escape) | Err(_) => break ControlFlow::Break(()),
+                _ => continue,
+            }
+        };
+
+        let _ = self.term.clear_line();
+        flow
+    }
+}
+
+impl ResultWriter for TableWriter {
+    fn write_row(&mut self, row: Vec<SQLType>) -> io::Result<ControlFlow<()>> {
+        self.chunk
+            .push(row.iter().map(|value| value.to_string()).collect());
+
+        match self.page_size {
+            Some(page_size) if self.chunk.len() >= page_size => {
+                self.flush()?;
+                Ok(self.prompt_more())
+            }
+            _ => Ok(ControlFlow::Continue(())),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        if !self.chunk.is_empty() || self.printed_chunks == 0 {
+            self.flush()?;
+        }
+        self.out.flush()
+    }
+}
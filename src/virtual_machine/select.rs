@@ -1,20 +1,613 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::iter;
+use std::ops::{Bound, ControlFlow};
+
+use super::expr::Aggregate;
+use super::information_schema::virtual_table;
+use super::interpreter;
+use super::operators::{csv_path, CsvScan};
+use super::planner::{
+    choose_index, estimate_rows, extract_rowid_bounds, fold_constants, map_columns, plan_predicate,
+    rename_column, split_join_filter, table_qualifier, unqualify_columns, PredicatePlan,
+};
+use super::program::{ColumnOrigin, GroupOutput, GroupPlan, Instruction, Program, ScanPlan};
+use super::result_writer::{CsvOptions, CsvWriter, OutputSettings, ResultWriter};
 use super::vm_error::VMError;
+use crate::backend::catalog::{IndexEntry, TableStats};
+use crate::backend::columns::{ColumnItemType, Columns, IntegerType, ROWID};
 use crate::backend::database::Database;
-use crate::sql_compiler::SelectTokens;
+use crate::backend::row::SQLType;
+use crate::sql_compiler::expression::Expr;
+use crate::sql_compiler::{SelectItem, SelectTokens};
+
+// A table of the FROM list, along with what its scan is planned from
+struct Source<'a> {
+    name: &'a str,
+    columns: Cow<'a, Columns>,
+    primary_key: Option<String>,
+    stats: TableStats,
+    indexes: Vec<IndexEntry>,
+}
+
+impl<'a> Source<'a> {
+    fn open(name: &'a str, db: &'a Database) -> Result<Self, VMError> {
+        // information_schema tables have no PRIMARY KEY, and their stats are counted on the spot
+        let (columns, primary_key, stats, indexes) = match virtual_table(name, db) {
+            Some((columns, rows)) => {
+                let stats = TableStats {
+                    num_rows: rows.len() as u64,
+                    ..TableStats::new()
+                };
+                (Cow::Owned(columns), None, stats, Vec::new())
+            }
+            // CSV files are read for their header only, so their rows are not counted
+            None => match csv_path(name) {
+                Some(path) => {
+                    let columns = CsvScan::open(&path)?.table_columns().clone();
+                    (Cow::Owned(columns), None, TableStats::new(), Vec::new())
+                }
+                None => {
+                    let table = db
+                        .table(name)
+                        .map_err(|err| VMError::TableReadError(name.to_string(), err))?;
+                    (
+                        Cow::Borrowed(&table.columns),
+                        table.primary_key.clone(),
+                        table.stats(),
+                        table.indexes.clone(),
+                    )
+                }
+            },
+        };
+        Ok(Self {
+            name,
+            columns,
+            primary_key,
+            stats,
+            indexes,
+        })
+    }
+
+    // The hidden rowid column reads the PRIMARY KEY of tables that have one, unless a column
+    // named rowid hides it
+    fn rowid_alias(&self) -> Option<&str> {
+        match &self.primary_key {
+            Some(primary_key) if !self.columns.contains_key(ROWID) => Some(primary_key),
+            _ => None,
+        }
+    }
+
+    // Columns may be qualified with the table name
+    fn resolve_columns(&self, expr: Expr) -> Expr {
+        let expr = unqualify_columns(expr, self.name);
+        match self.rowid_alias() {
+            Some(primary_key) => rename_column(expr, ROWID, primary_key),
+            None => expr,
+        }
+    }
+
+    // Name a column of the table has among the columns of joined rows
+    fn qualified(&self, column: &str) -> String {
+        let column = match self.rowid_alias() {
+            Some(primary_key) if column == ROWID => primary_key,
+            _ => column,
+        };
+        format!("{}.{}", table_qualifier(self.name), column)
+    }
+
+    fn scan_plan(&self, filter: Option<Expr>) -> ScanPlan {
+        let key_column = self
+            .primary_key
+            .clone()
+            .unwrap_or_else(|| ROWID.to_string());
+        let key_type = match self.columns.get(&key_column) {
+            Some(ColumnItemType::Integer(int_type)) => int_type.clone(),
+            _ => IntegerType::UBigInt,
+        };
+        let (rowid_range, filter) = match filter {
+            // A column named rowid hides the rowids of a table without a PRIMARY KEY
+            Some(filter) if self.primary_key.is_some() || !self.columns.contains_key(ROWID) => {
+                extract_rowid_bounds(filter, &key_column, &key_type)
+            }
+            filter => ((Bound::Unbounded, Bound::Unbounded), filter),
+        };
+        // Seeking on the rowids is preferred, as it reads the rows directly
+        let index_seek = match (&rowid_range, &filter) {
+            ((Bound::Unbounded, Bound::Unbounded), Some(filter)) => {
+                choose_index(filter, &self.columns, &self.indexes)
+            }
+            _ => None,
+        };
+        ScanPlan {
+            table: self.name.to_string(),
+            estimated_rows: estimate_rows(&self.stats, &rowid_range),
+            rowid_range,
+            key_column,
+            key_type,
+            filter,
+            index_seek,
+            joins: Vec::new(),
+            join_filter: None,
+            group: None,
+        }
+    }
+}
+
+// Qualifies the columns of an expression over rows joined from several tables with the table
+// they are in. Columns already qualified are kept in their table, and the others must be in a
+// single one
+fn qualify_columns(expr: Expr, sources: &[Source]) -> Result<Expr, VMError> {
+    let ambiguous = Cell::new(None);
+    let has_column =
+        |source: &Source, column: &str| column == ROWID || source.columns.contains_key(column);
+    let expr = map_columns(expr, &|name| {
+        if let Some((qualifier, column)) = name.rsplit_once('.') {
+            return match sources
+                .iter()
+                .find(|source| table_qualifier(source.name) == qualifier)
+            {
+                Some(source) => Expr::Column(source.qualified(column)),
+                None => Expr::Column(name),
+            };
+        }
+        let mut matching = sources.iter().filter(|source| has_column(source, &name));
+        match (matching.next(), matching.next()) {
+            (Some(source), None) => Expr::Column(source.qualified(&name)),
+            (Some(_), Some(_)) => {
+                ambiguous.set(Some(name.clone()));
+                Expr::Column(name)
+            }
+            (None, _) => Expr::Column(name),
+        }
+    });
+    match ambiguous.into_inner() {
+        Some(name) => Err(VMError::AmbiguousColumn(name)),
+        None => Ok(expr),
+    }
+}
+
+pub(super) fn compile_select(
+    select_tokens: &SelectTokens,
+    db: &Database,
+) -> Result<Program, VMError> {
+    let SelectTokens {
+        table_name,
+        joined_tables,
+        select_items,
+        where_clause,
+        group_by,
+        ..
+    } = select_tokens;
+    let Some(table_name) = table_name else {
+        return compile_select_without_table(select_items, where_clause);
+    };
+
+    let sources = iter::once(table_name)
+        .chain(joined_tables)
+        .map(|name| Source::open(name, db))
+        .collect::<Result<Vec<Source>, _>>()?;
+    let joined = sources.len() > 1;
+    let resolve_columns = |expr: Expr| match joined {
+        true => qualify_columns(expr, &sources),
+        false => Ok(sources[0].resolve_columns(expr)),
+    };
+
+    // Rows joined from several tables name their columns `table.column`. Each column is kept
+    // with the table it comes from and its name there
+    let mut table_columns = Vec::new();
+    let mut column_sources = Vec::new();
+    for (source_idx, source) in sources.iter().enumerate() {
+        for column in source.columns.to_printable() {
+            table_columns.push(match joined {
+                true => source.qualified(&column),
+                false => column.clone(),
+            });
+            column_sources.push((source_idx, column));
+        }
+    }
+    let has_rowid_column = table_columns.iter().any(|column| column == ROWID);
+
+    let origin = |column: usize| {
+        let (source_idx, name) = &column_sources[column];
+        let source = &sources[*source_idx];
+        Some(ColumnOrigin {
+            table: source.name.to_string(),
+            column: name.to_string(),
+            column_type: source.columns.get(name)?.clone(),
+        })
+    };
+
+    let mut program = Program::new();
+    let cursor = program.alloc_cursor();
+
+    // Each output column is either a plain column read, an expression to evaluate or an aggregate
+    // folding every row into its register
+    let mut outputs: Vec<Instruction> = Vec::new();
+    let mut aggregate_values: Vec<Instruction> = Vec::new();
+    for item in select_items {
+        match item {
+            // Columns come in the order their table declared them, which is the order an INSERT
+            // without column names takes its values in, rather than the order rows store them in
+            SelectItem::Wildcard => {
+                for (source_idx, source) in sources.iter().enumerate() {
+                    for name in source.columns.declared_order() {
+                        let column = column_sources
+                            .iter()
+                            .position(|(idx, column)| *idx == source_idx && column == name)
+                            .ok_or_else(|| VMError::ColumnNotInTable(name.to_string()))?;
+                        program.result_columns.push(name.to_owned());
+                        program.result_origins.push(origin(column));
+                        outputs.push(Instruction::Column {
+                            cursor,
+                            column,
+                            target: program.alloc_register(),
+                        });
+                    }
+                }
+            }
+            SelectItem::Expr { expr, alias } => {
+                program
+                    .result_columns
+                    .push(alias.clone().unwrap_or_else(|| expr.to_string()));
+                let target = program.alloc_register();
+                let expr = fold_constants(resolve_columns(expr.clone())?);
+                if let Expr::Function { name, args } = &expr {
+                    if let Some(aggregate) = Aggregate::from_call(name, args.len()) {
+                        aggregate.check_arity(args.len())?;
+                        program.result_origins.push(None);
+                        aggregate_values.push(Instruction::Value {
+                            value: aggregate.initial(),
+                            target,
+                        });
+                        outputs.push(Instruction::AggStep {
+                            cursor,
+                            aggregate,
+                            arg: args.first().cloned(),
+                            accumulator: target,
+                        });
+                        continue;
+                    }
+                }
+                let column_idx = match &expr {
+                    Expr::Column(name) if name == ROWID && !has_rowid_column => None,
+                    Expr::Column(name) => Some(
+                        table_columns
+                            .iter()
+                            .position(|column| column == name)
+                            .ok_or(VMError::ColumnNotInTable(name.to_string()))?,
+                    ),
+                    _ => None,
+                };
+                program.result_origins.push(column_idx.and_then(origin));
+                outputs.push(match column_idx {
+                    Some(column) => Instruction::Column {
+                        cursor,
+                        column,
+                        target,
+                    },
+                    None => Instruction::Eval {
+                        cursor: Some(cursor),
+                        expr,
+                        target,
+                    },
+                });
+            }
+        }
+    }
+
+    // A query with aggregates and no GROUP BY returns a single row once every row has been read.
+    // With GROUP BY, the scan computes the outputs of every group, which are then read as the
+    // columns of its rows
+    let aggregated = !aggregate_values.is_empty() && group_by.is_empty();
+    if aggregated && aggregate_values.len() != outputs.len() {
+        return Err(VMError::MixedAggregate);
+    }
+    let group = match group_by.is_empty() {
+        true => {
+            for aggregate_value in aggregate_values {
+                program.emit(aggregate_value);
+            }
+            None
+        }
+        false => {
+            let group = GroupPlan {
+                keys: group_by
+                    .iter()
+                    .map(|key| Ok(fold_constants(resolve_columns(key.clone())?)))
+                    .collect::<Result<_, VMError>>()?,
+                outputs: outputs
+                    .iter()
+                    .map(|output| group_output(output, &table_columns))
+                    .collect(),
+            };
+            outputs = (0..outputs.len())
+                .map(|column| Instruction::Column {
+                    cursor,
+                    column,
+                    target: column,
+                })
+                .collect();
+            Some(group)
+        }
+    };
+    let num_outputs = outputs.len();
+
+    let where_clause = where_clause.clone().map(resolve_columns).transpose()?;
+    let filter = match plan_predicate(where_clause) {
+        PredicatePlan::AlwaysTrue => None,
+        PredicatePlan::Filter(filter) => Some(filter),
+        PredicatePlan::AlwaysFalse => {
+            // No row can match, so the table is never opened
+            if aggregated {
+                program.emit(Instruction::ResultRow {
+                    start: 0,
+                    count: num_outputs,
+                });
+            }
+            program.emit(Instruction::Halt);
+            return Ok(program);
+        }
+    };
+
+    // Conditions reading a single table of a join filter its rows before they are joined
+    let (filters, join_filter) = match (joined, filter) {
+        (true, Some(filter)) => {
+            let names: Vec<&str> = sources.iter().map(|source| source.name).collect();
+            split_join_filter(filter, &names)
+        }
+        (_, filter) => {
+            let mut filters = vec![None; sources.len()];
+            filters[0] = filter;
+            (filters, None)
+        }
+    };
+    let mut scans = sources
+        .iter()
+        .zip(filters)
+        .map(|(source, filter)| source.scan_plan(filter));
+    let first_scan = scans.next().unwrap();
+    let scan = ScanPlan {
+        joins: scans.collect(),
+        join_filter,
+        group,
+        ..first_scan
+    };
+    program.emit(Instruction::OpenRead {
+        cursor,
+        scan: Box::new(scan),
+    });
+    let rewind = program.emit(Instruction::Rewind {
+        cursor,
+        if_empty: 0,
+    });
+    let loop_start = program.next_address();
+    for output in outputs {
+        program.emit(output);
+    }
+    let result_row = Instruction::ResultRow {
+        start: 0,
+        count: num_outputs,
+    };
+    if !aggregated {
+        program.emit(result_row.clone());
+    }
+    program.emit(Instruction::Next {
+        cursor,
+        if_more: loop_start,
+    });
+    let loop_end = program.next_address();
+    if aggregated {
+        program.emit(result_row);
+    }
+    program.emit(Instruction::Halt);
+    program.patch_jump(rewind, loop_end);
+
+    Ok(program)
+}
+
+// What an instruction computing a result column from the current row computes for a group
+fn group_output(output: &Instruction, table_columns: &[String]) -> GroupOutput {
+    match output {
+        Instruction::Column { column, .. } => {
+            GroupOutput::Expr(Expr::Column(table_columns[*column].clone()))
+        }
+        Instruction::AggStep { aggregate, arg, .. } => {
+            GroupOutput::Aggregate(*aggregate, arg.clone())
+        }
+        Instruction::Eval { expr, .. } => GroupOutput::Expr(expr.clone()),
+        other => unreachable!("{:?} does not compute a result column", other),
+    }
+}
+
+// SELECT without FROM yields a single row, or none when its WHERE clause does not hold
+fn compile_select_without_table(
+    select_items: &[SelectItem],
+    where_clause: &Option<Expr>,
+) -> Result<Program, VMError> {
+    let mut program = Program::new();
+
+    let mut outputs = Vec::new();
+    for item in select_items {
+        let SelectItem::Expr { expr, alias } = item else {
+            return Err(VMError::NoTableForWildcard);
+        };
+        program
+            .result_columns
+            .push(alias.clone().unwrap_or_else(|| expr.to_string()));
+        program.result_origins.push(None);
+        outputs.push(Instruction::Eval {
+            cursor: None,
+            expr: fold_constants(expr.clone()),
+            target: program.alloc_register(),
+        });
+    }
+
+    let skip_row = match plan_predicate(where_clause.clone()) {
+        PredicatePlan::AlwaysTrue => None,
+        PredicatePlan::Filter(filter) => {
+            let condition = program.alloc_register();
+            program.emit(Instruction::Eval {
+                cursor: None,
+                expr: filter,
+                target: condition,
+            });
+            Some(program.emit(Instruction::IfNot {
+                condition,
+                target: 0,
+            }))
+        }
+        PredicatePlan::AlwaysFalse => {
+            program.emit(Instruction::Halt);
+            return Ok(program);
+        }
+    };
+
+    let num_outputs = outputs.len();
+    for output in outputs {
+        program.emit(output);
+    }
+    program.emit(Instruction::ResultRow {
+        start: 0,
+        count: num_outputs,
+    });
+    let halt = program.emit(Instruction::Halt);
+    if let Some(skip_row) = skip_row {
+        program.patch_jump(skip_row, halt);
+    }
+
+    Ok(program)
+}
+
+// Runs a SELECT and collects its result columns and rows
+pub(super) fn collect_select(
+    select_tokens: SelectTokens,
+    db: &Database,
+) -> Result<(Vec<String>, Vec<Vec<SQLType>>), VMError> {
+    let program = compile_select(&select_tokens, db)?;
+
+    let mut rows = Vec::new();
+    interpreter::run(&program, db, &mut |row| {
+        rows.push(row);
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    Ok((program.result_columns, rows))
+}
+
+// Result columns of a SELECT along with their origins, without running it
+pub(super) fn describe_select(
+    select_tokens: &SelectTokens,
+    db: &Database,
+) -> Result<Vec<(String, Option<ColumnOrigin>)>, VMError> {
+    let program = compile_select(select_tokens, db)?;
+    Ok(program
+        .result_columns
+        .into_iter()
+        .zip(program.result_origins)
+        .collect())
+}
+
+// Streams the rows of a SELECT into the writer built for its result columns, returning the
+// number of rows written
+fn write_select(
+    select_tokens: &SelectTokens,
+    db: &Database,
+    make_writer: impl FnOnce(Vec<String>) -> io::Result<Box<dyn ResultWriter>>,
+) -> Result<u64, VMError> {
+    let program = compile_select(select_tokens, db)?;
+
+    let mut writer = make_writer(program.result_columns.clone()).map_err(VMError::OutputError)?;
+    let mut rows_written = 0;
+    interpreter::run(&program, db, &mut |row| {
+        rows_written += 1;
+        writer.write_row(row).map_err(VMError::OutputError)
+    })?;
+    writer.finish().map_err(VMError::OutputError)?;
+
+    Ok(rows_written)
+}
+
+// Writes the rows of a SELECT as CSV, returning the number of rows written
+pub(super) fn export_select(
+    select_tokens: &SelectTokens,
+    db: &Database,
+    out: Box<dyn Write>,
+    options: CsvOptions,
+) -> Result<u64, VMError> {
+    write_select(select_tokens, db, |columns| {
+        Ok(Box::new(CsvWriter::new(out, columns, options)))
+    })
+}
+
+// SELECT ... INTO OUTFILE writes the rows to a new file instead of returning them. Returns the
+// number of rows written, or None if the SELECT has no OUTFILE clause
+pub(super) fn export_outfile(
+    select_tokens: &SelectTokens,
+    db: &Database,
+) -> Result<Option<u64>, VMError> {
+    let Some(outfile) = &select_tokens.outfile else {
+        return Ok(None);
+    };
+    let file = File::create_new(outfile.path.as_ref())
+        .map_err(|err| VMError::OutfileError(outfile.path.to_string(), err))?;
+    let defaults = CsvOptions::default();
+    let options = CsvOptions {
+        delimiter: outfile.delimiter.unwrap_or(defaults.delimiter),
+        quote: outfile.quote.unwrap_or(defaults.quote),
+        quote_all: outfile.quote_all,
+        ..defaults
+    };
+    export_select(select_tokens, db, Box::new(BufWriter::new(file)), options).map(Some)
+}
 
 pub(super) fn process_select(
     select_tokens: SelectTokens,
     db_instance: Option<&mut Database>,
+    output_settings: &OutputSettings,
 ) -> Result<(), VMError> {
-    let SelectTokens { table_name } = select_tokens;
-
     let open_database = db_instance.ok_or(VMError::DBClosed)?;
 
-    let table = open_database
-        .get_table(table_name)
-        .map_err(|err| VMError::TableReadError(table_name.to_string(), err.to_string()))?;
+    if export_outfile(&select_tokens, open_database)?.is_some() {
+        return Ok(());
+    }
 
-    println!("{}", table);
+    write_select(&select_tokens, open_database, |columns| {
+        output_settings.writer(columns)
+    })?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::Connection;
+
+    #[test]
+    fn select_into_outfile_through_the_library() {
+        let path = env::temp_dir().join(format!("sql_rs_outfile_{}.csv", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (1, 'a,b'); INSERT INTO t VALUES (2, 'c');")
+            .unwrap();
+        let sql = format!("SELECT * FROM t INTO OUTFILE '{}';", path.display());
+        assert_eq!(conn.execute(&sql).unwrap(), 0);
+
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, "id,name\n1,\"a,b\"\n2,c\n");
+
+        // The file is not replaced by running it again
+        fs::write(&path, "kept").unwrap();
+        assert!(conn.execute(&sql).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+use std::ops::ControlFlow;
+
+use super::expr::Scope;
+use super::information_schema::virtual_table;
+use super::interpreter;
+use super::operators::{csv_path, CsvScan, Operator};
+use super::planner::{map_columns, table_qualifier};
+use super::select::compile_select;
+use super::vm_error::VMError;
+use crate::backend::columns::ROWID;
+use crate::backend::database::Database;
+use crate::backend::row::SQLType;
+use crate::sql_compiler::expression::Expr;
+use crate::sql_compiler::{SelectItem, SelectTokens};
+
+pub(super) fn contains_subquery(expr: &Expr) -> bool {
+    match expr {
+        Expr::Subquery(_) => true,
+        Expr::Unary(_, operand) => contains_subquery(operand),
+        Expr::Binary(left, _, right) => contains_subquery(left) || contains_subquery(right),
+        Expr::IsNull { expr, .. } => contains_subquery(expr),
+        Expr::Function { args, .. } => args.iter().any(contains_subquery),
+        Expr::Literal(_) | Expr::Column(_) => false,
+    }
+}
+
+// Replaces every subquery in the expression by its value for the row in scope
+pub(super) fn resolve_subqueries(
+    expr: Expr,
+    db: &Database,
+    scope: &Scope,
+) -> Result<Expr, VMError> {
+    let resolve = |expr: Box<Expr>| resolve_subqueries(*expr, db, scope).map(Box::new);
+    Ok(match expr {
+        Expr::Subquery(select) => Expr::Literal(run_scalar(&select, db, scope)?),
+        Expr::Unary(operator, operand) => Expr::Unary(operator, resolve(operand)?),
+        Expr::Binary(left, operator, right) => {
+            Expr::Binary(resolve(left)?, operator, resolve(right)?)
+        }
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: resolve(expr)?,
+            negated,
+        },
+        Expr::Function { name, args } => Expr::Function {
+            name,
+            args: args
+                .into_iter()
+                .map(|arg| resolve_subqueries(arg, db, scope))
+                .collect::<Result<_, _>>()?,
+        },
+        other => other,
+    })
+}
+
+// The first column of the first row the subquery returns, or NULL when it returns none. It is
+// compiled anew for every row it is evaluated for, since the values of the enclosing row it
+// refers to become constants in it
+fn run_scalar(
+    select: &SelectTokens<'static>,
+    db: &Database,
+    scope: &Scope,
+) -> Result<SQLType, VMError> {
+    let select = bind_outer_columns(select, db, scope)?;
+    let program = compile_select(&select, db)?;
+    if program.result_columns.len() != 1 {
+        return Err(VMError::SubqueryColumns(program.result_columns.len()));
+    }
+
+    let mut value = SQLType::Null;
+    interpreter::run(&program, db, &mut |row| {
+        value = row.into_iter().next().unwrap_or(SQLType::Null);
+        Ok(ControlFlow::Break(()))
+    })?;
+    Ok(value)
+}
+
+// Columns that are not in the subquery's tables, or that are qualified with the name of another
+// table, refer to the enclosing row
+fn bind_outer_columns(
+    select: &SelectTokens<'static>,
+    db: &Database,
+    scope: &Scope,
+) -> Result<SelectTokens<'static>, VMError> {
+    let tables: Vec<&str> = select
+        .table_name
+        .iter()
+        .chain(&select.joined_tables)
+        .map(|table_name| table_name.as_ref())
+        .collect();
+    let mut columns = Vec::new();
+    for table_name in &tables {
+        columns.extend(match virtual_table(table_name, db) {
+            Some((columns, _)) => columns.to_printable(),
+            None => match csv_path(table_name) {
+                Some(path) => CsvScan::open(&path)?.columns(),
+                None => db
+                    .table(table_name)
+                    .map_err(|err| VMError::TableReadError(table_name.to_string(), err))?
+                    .columns
+                    .to_printable(),
+            },
+        });
+    }
+    let qualifiers: Vec<&str> = tables.iter().map(|table| table_qualifier(table)).collect();
+
+    // Rows of a FROM list naming several tables hold their columns qualified
+    let bind = |expr: Expr| {
+        map_columns(expr, &|name| {
+            let (is_inner, column) = match name.split_once('.') {
+                Some((qualifier, column)) => (qualifiers.contains(&qualifier), column),
+                None => (name == ROWID || columns.contains(&name), name.as_str()),
+            };
+            match scope.lookup(column).or_else(|_| scope.lookup(&name)) {
+                Ok(value) if !is_inner => Expr::Literal(value),
+                _ => Expr::Column(name),
+            }
+        })
+    };
+
+    Ok(SelectTokens {
+        table_name: select.table_name.clone(),
+        joined_tables: select.joined_tables.clone(),
+        select_items: select
+            .select_items
+            .iter()
+            .cloned()
+            .map(|item| match item {
+                SelectItem::Expr { expr, alias } => SelectItem::Expr {
+                    expr: bind(expr),
+                    alias,
+                },
+                wildcard => wildcard,
+            })
+            .collect(),
+        where_clause: select.where_clause.clone().map(bind),
+        group_by: select.group_by.iter().cloned().map(bind).collect(),
+        outfile: None,
+    })
+}
@@ -0,0 +1,101 @@
+use super::vm_error::VMError;
+use crate::backend::database::Database;
+
+pub(super) fn process_begin(db_instance: Option<&mut Database>) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    open_database.begin().map_err(VMError::TransactionError)
+}
+
+pub(super) fn process_commit(db_instance: Option<&mut Database>) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    open_database.commit().map_err(VMError::TransactionError)
+}
+
+pub(super) fn process_rollback(db_instance: Option<&mut Database>) -> Result<(), VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+
+    open_database.rollback().map_err(VMError::TransactionError)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::{Connection, SQLType};
+
+    fn ids(conn: &mut Connection, table: &str) -> Vec<SQLType> {
+        conn.query(&format!("SELECT id FROM {};", table))
+            .unwrap()
+            .map(|row| row.into_values().remove(0))
+            .collect()
+    }
+
+    #[test]
+    fn failed_statement_is_undone_alone_inside_a_transaction() {
+        let path = env::temp_dir().join(format!("sql_rs_savepoint_{}.db", process::id()));
+        let _ = fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let mut conn = Connection::open(path).unwrap();
+        conn.execute(
+            "CREATE TABLE t (id INT PRIMARY KEY, n INT); CREATE TABLE s (id INT PRIMARY KEY, n INT);",
+        )
+        .unwrap();
+        conn.execute("INSERT INTO s VALUES (1, 10); INSERT INTO s VALUES (3, 30);")
+            .unwrap();
+
+        conn.execute("BEGIN; INSERT INTO t VALUES (3, 0);").unwrap();
+        // Row 1 is inserted before row 3 clashes with the row inserted by the statement before
+        assert!(conn.execute("INSERT INTO t SELECT * FROM s;").is_err());
+        assert!(conn.database().in_transaction());
+        assert_eq!(ids(&mut conn, "t"), [SQLType::Integer(3)]);
+
+        // Changes to pages an earlier statement of the transaction changed are undone too
+        assert!(conn
+            .execute(
+                "UPDATE t SET n = 1; CREATE TABLE u (id INT PRIMARY KEY); UPDATE t SET id = 'x';"
+            )
+            .is_err());
+        conn.execute("COMMIT;").unwrap();
+        drop(conn);
+
+        let mut conn = Connection::open(path).unwrap();
+        let rows: Vec<Vec<SQLType>> = conn
+            .query("SELECT id, n FROM t;")
+            .unwrap()
+            .map(|row| row.into_values())
+            .collect();
+        assert_eq!(rows, [[SQLType::Integer(3), SQLType::Integer(1)]]);
+        assert_eq!(ids(&mut conn, "u"), []);
+        assert_eq!(conn.integrity_check(), Vec::<String>::new());
+        drop(conn);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failed_statement_that_split_pages_is_undone() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, pad TEXT); CREATE TABLE s (id INT PRIMARY KEY, pad TEXT);")
+            .unwrap();
+        for id in 1..=100 {
+            conn.execute(&format!(
+                "INSERT INTO s VALUES ({}, '{}');",
+                id,
+                "x".repeat(200)
+            ))
+            .unwrap();
+        }
+        conn.execute("BEGIN; INSERT INTO t VALUES (100, 'last');")
+            .unwrap();
+        let page_count = conn.database().stats().unwrap().page_count;
+
+        // The rows before the last one fill new pages before it clashes
+        assert!(conn.execute("INSERT INTO t SELECT * FROM s;").is_err());
+        assert_eq!(conn.database().stats().unwrap().page_count, page_count);
+        conn.execute("COMMIT;").unwrap();
+        assert_eq!(ids(&mut conn, "t"), [SQLType::Integer(100)]);
+        assert_eq!(conn.integrity_check(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,148 @@
+use super::constraints::{check_constraints, check_deleted_references};
+use super::delete::{limit_rows, matching_rows};
+use super::expr::{as_integer, evaluate, Scope};
+use super::insert::parse_value;
+use super::planner::resolve_table_columns;
+use super::subquery::{contains_subquery, resolve_subqueries};
+use super::vm_error::VMError;
+use crate::backend::columns::ColumnItemType;
+use crate::backend::database::Database;
+use crate::backend::row::{Row, SQLType};
+use crate::backend::table::Table;
+use crate::sql_compiler::expression::Expr;
+use crate::sql_compiler::UpdateTokens;
+
+// Assigns new values to the rows of a table the WHERE clause holds for, returning how many were
+// updated
+pub(super) fn process_update(
+    update_tokens: UpdateTokens,
+    db_instance: Option<&mut Database>,
+) -> Result<u64, VMError> {
+    let open_database = db_instance.ok_or(VMError::DBClosed)?;
+    update_rows(update_tokens, open_database)
+}
+
+fn update_rows(update_tokens: UpdateTokens, db: &Database) -> Result<u64, VMError> {
+    let UpdateTokens {
+        table_name,
+        assignments,
+        where_clause,
+        order_by,
+        limit,
+    } = update_tokens;
+    let write_error = |err| VMError::TableWriteError(table_name.to_string(), err);
+    let (table_db, _) = db.resolve(&table_name).map_err(write_error)?;
+    let table = db.table(&table_name).map_err(write_error)?;
+
+    // Assignments are paired with the position of their column among the values of a row
+    let columns = table.columns.to_printable();
+    let mut assigned: Vec<(usize, Expr)> = Vec::new();
+    for (name, value) in assignments {
+        let column_idx = columns
+            .iter()
+            .position(|column| *column == name)
+            .ok_or_else(|| VMError::ColumnNotInTable(name.to_string()))?;
+        if assigned.iter().any(|(idx, _)| *idx == column_idx) {
+            return Err(VMError::DuplicateColumns);
+        }
+        assigned.push((column_idx, resolve_table_columns(value, table, &table_name)));
+    }
+
+    let old_rows = matching_rows(db, table, &table_name, where_clause)?;
+    let old_rows = limit_rows(db, table, &table_name, old_rows, order_by, limit)?;
+    for old_row in &old_rows {
+        let decoded = table.columns.decode_row(old_row.clone());
+        let scope = Scope::new(&columns, decoded.attributes()).with_rowid(old_row.rowid());
+        let mut values = old_row.attributes().to_vec();
+        for (column_idx, value) in &assigned {
+            let value = match contains_subquery(value) {
+                true => evaluate(&resolve_subqueries(value.clone(), db, &scope)?, &scope)?,
+                false => evaluate(value, &scope)?,
+            };
+            // Values are read back as if they were written in an INSERT, which checks them
+            // against the type of the column
+            let name = &columns[*column_idx];
+            values[*column_idx] = match (value, table.columns.get(name)) {
+                (SQLType::Null, _) => SQLType::Null,
+                (value, Some(column_type)) => parse_value(name, &value.to_string(), column_type)?,
+                (_, None) => return Err(VMError::ColumnNotInTable(name.to_string())),
+            };
+        }
+
+        let rowid = new_rowid(table, &values, old_row.rowid())?;
+        check_constraints(table_db, table, rowid, &values, Some(old_row.rowid()))?;
+        let new_row = Row::new(rowid, values);
+        // A row moving to another rowid is inserted first, so that one already there fails the
+        // update before the old row is gone
+        let written = match rowid == old_row.rowid() {
+            true => table.update(new_row),
+            false => table
+                .insert(new_row)
+                .and_then(|_| table.delete(old_row.rowid()).map(|_| ())),
+        };
+        written.map_err(|err| write_error(err.into()))?;
+    }
+    check_deleted_references(table_db, table, &old_rows)?;
+
+    Ok(old_rows.len() as u64)
+}
+
+// Rowid the row is stored under once given its new values, which only changes when its PRIMARY
+// KEY does
+fn new_rowid(table: &Table, values: &[SQLType], old_rowid: u64) -> Result<u64, VMError> {
+    let Some(primary_key) = &table.primary_key else {
+        return Ok(old_rowid);
+    };
+    let key_value = table
+        .columns
+        .keys()
+        .position(|name| name == primary_key)
+        .and_then(|idx| as_integer(&values[idx]));
+    match (table.columns.get(primary_key), key_value) {
+        (Some(ColumnItemType::Integer(int_type)), Some(value)) => Ok(int_type.to_key(value)),
+        _ => Err(VMError::MissingPrimaryKey(primary_key.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Connection, ErrorCode};
+
+    fn keyed_table() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (id INT PRIMARY KEY, v INT);")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES (1, 1);").unwrap();
+        conn.execute("INSERT INTO t VALUES (-3, 3);").unwrap();
+        conn
+    }
+
+    #[test]
+    fn update_to_taken_primary_key_names_column_and_value() {
+        let mut conn = keyed_table();
+        let err = conn
+            .execute("UPDATE t SET id = -3 WHERE id = 1;")
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Constraint);
+        assert_eq!(
+            err.to_string(),
+            "PRIMARY KEY constraint failed: t.id = -3 already exists"
+        );
+    }
+
+    #[test]
+    fn insert_of_taken_primary_key_names_column_and_value() {
+        let mut conn = keyed_table();
+        let err = conn.execute("INSERT INTO t VALUES (1, 2);").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Constraint);
+        assert!(err.to_string().contains("t.id = 1"), "{}", err);
+    }
+
+    #[test]
+    fn update_keeping_primary_key_is_not_a_violation() {
+        let mut conn = keyed_table();
+        conn.execute("UPDATE t SET id = 1, v = 5 WHERE id = 1;")
+            .unwrap();
+        assert_eq!(conn.changes(), 1);
+    }
+}
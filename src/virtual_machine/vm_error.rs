@@ -1,5 +1,10 @@
+use std::io;
+
 use thiserror::Error;
 
+use super::expr::ExprError;
+use crate::backend::columns::{IntegerType, TextType};
+use crate::backend::database::DatabaseError;
 use crate::backend::table::TableError;
 
 #[derive(Error, Debug)]
@@ -10,20 +15,127 @@ pub enum VMError {
     DuplicatedTableName(String),
     #[error("Cannot create table. Two columns have the same name: {0}")]
     DuplicatedColumnName(String),
+    #[error("Cannot rename column to {0}. Another column with the same name already exists")]
+    ColumnAlreadyExists(String),
+    #[error("Cannot create table {0}. Only one column can be the PRIMARY KEY")]
+    MultiplePrimaryKeys(String),
+    #[error("Cannot create table. PRIMARY KEY column {0} must have an integer type")]
+    NonIntegerPrimaryKey(String),
     #[error("Error while writing to table {0}: {1}")]
-    TableWriteError(String, String),
+    TableWriteError(String, DatabaseError),
+    #[error("Error while writing to index {0}: {1}")]
+    IndexWriteError(String, DatabaseError),
     #[error("Error while reading table {0}: {1}")]
-    TableReadError(String, String),
+    TableReadError(String, DatabaseError),
     #[error("Mismatch between number of column names ({0}) and number of values passed ({1})")]
     ColumnNamesValuesMismatch(usize, usize),
     #[error("Column {0} not in table")]
     ColumnNotInTable(String),
+    #[error("Column {0} is in more than one table of the FROM list")]
+    AmbiguousColumn(String),
     #[error("Duplcate columns in insert statement")]
     DuplicateColumns,
     #[error("Error when parsing value {0}")]
     ItemParsingError(String),
+    #[error(
+        "Value {2} out of range for column {0} of type {1}, allowed range is {min} to {max}",
+        min = .1.range().0,
+        max = .1.range().1
+    )]
+    ValueOutOfRange(String, IntegerType, String),
+    #[error("Value {2} for column {0} of type {1} is not an integer")]
+    MalformedInteger(String, IntegerType, String),
+    #[error(
+        "Value {2} too long for column {0} of type {1}, which holds at most {limit}",
+        limit = .1.limit()
+    )]
+    ValueTooLong(String, TextType, String),
     #[error("Error when inserting row into table: {0}")]
     ItemInsertingError(#[from] TableError),
     #[error("Cannot insert row without ID in table")]
     NoIdParsed,
+    #[error("Cannot insert row without a value for PRIMARY KEY column {0}")]
+    MissingPrimaryKey(String),
+    #[error("Cannot create table. Column {0} referenced by a foreign key does not exist")]
+    UnknownReferencedColumn(String),
+    #[error("NOT NULL constraint failed: {0}")]
+    NotNullViolation(String),
+    #[error("UNIQUE constraint failed: {0}")]
+    UniqueViolation(String),
+    #[error("PRIMARY KEY constraint failed: {0} = {1} already exists")]
+    PrimaryKeyViolation(String, String),
+    #[error("CHECK constraint failed: {0}")]
+    CheckViolation(String),
+    #[error("FOREIGN KEY constraint failed: {0}")]
+    ForeignKeyViolation(String),
+    #[error("Error when evaluating expression: {0}")]
+    ExpressionError(#[from] ExprError),
+    #[error("Cannot use cursor before it is opened")]
+    CursorNotOpen,
+    #[error("Cannot read from cursor that does not point to a row")]
+    CursorNotPositioned,
+    #[error("Register {0} read before being written")]
+    UninitializedRegister(usize),
+    #[error("SELECT * needs a table to read from")]
+    NoTableForWildcard,
+    #[error("Aggregate functions cannot be mixed with other columns in the select list")]
+    MixedAggregate,
+    #[error("Scalar subquery returns {0} columns, expected 1")]
+    SubqueryColumns(usize),
+    #[error("Only SELECT and INSERT ... VALUES statements can be explained")]
+    NotExplainable,
+    #[error("Only SELECT statements return rows")]
+    NotAQuery,
+    #[error("Only SELECT statements can be analyzed by .expert")]
+    NotAnalyzable,
+    #[error("Program counter out of range: {0}")]
+    ProgramCounterOutOfRange(usize),
+    #[error("Cannot attach database {0}: {1}")]
+    AttachError(String, DatabaseError),
+    #[error("Cannot detach database: {0}")]
+    DetachError(DatabaseError),
+    #[error("Interrupted")]
+    Interrupted,
+    #[error("Cannot compute database statistics: {0}")]
+    StatsError(DatabaseError),
+    #[error("Cannot read CSV file {0}: {1}")]
+    CsvReadError(String, String),
+    #[error("Unknown pragma {0}")]
+    UnknownPragma(String),
+    #[error("Invalid value for pragma {0}: {1}")]
+    InvalidPragmaValue(String, String),
+    #[error("Cannot write to a database opened read-only")]
+    ReadOnly,
+    #[error("Cannot check the database for changes made by other processes: {0}")]
+    RefreshError(DatabaseError),
+    #[error("Transaction error: {0}")]
+    TransactionError(DatabaseError),
+    #[error("Error when writing changes to disk: {0}")]
+    CommitError(DatabaseError),
+    #[error("Cannot create output file {0}: {1}")]
+    OutfileError(String, io::Error),
+    #[error("Cannot write query output: {0}")]
+    OutputError(io::Error),
+}
+
+impl VMError {
+    // Name or value written in the statement that the error is about, if there is one
+    pub fn subject(&self) -> Option<&str> {
+        match self {
+            VMError::DuplicatedTableName(name)
+            | VMError::DuplicatedColumnName(name)
+            | VMError::ColumnAlreadyExists(name)
+            | VMError::NonIntegerPrimaryKey(name)
+            | VMError::ColumnNotInTable(name)
+            | VMError::AmbiguousColumn(name)
+            | VMError::ItemParsingError(name)
+            | VMError::UnknownPragma(name)
+            | VMError::ValueOutOfRange(_, _, name)
+            | VMError::MalformedInteger(_, _, name)
+            | VMError::ValueTooLong(_, _, name)
+            | VMError::ExpressionError(ExprError::UnknownColumn(name))
+            | VMError::ExpressionError(ExprError::UnknownFunction(name)) => Some(name),
+            _ => None,
+        }
+    }
 }